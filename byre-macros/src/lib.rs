@@ -0,0 +1,134 @@
+//! Procedural macros for [byre](https://docs.rs/byre).
+//!
+//! Use the macros through their re-exports in `byre`, ie: `#[byre::timed]`, the generated code
+//! refers to items in the `byre` crate.
+#![deny(
+    future_incompatible,
+    rust_2018_compatibility,
+    rust_2018_idioms,
+    rust_2021_compatibility,
+    rust_2024_compatibility
+)]
+#![deny(missing_docs)]
+
+use proc_macro::TokenStream;
+use proc_macro2::{TokenStream as TokenStream2, TokenTree};
+use quote::{quote, ToTokens as _};
+use syn::{parse_macro_input, ItemFn, LitStr, ReturnType, Type};
+
+/// Records the number of calls, the number of errors and the duration of a function.
+///
+/// The metrics are named after the function, or after the `name` argument when given:
+///
+/// - `<name>.calls` - a counter of calls
+/// - `<name>.errors` - a counter of calls that returned `Err` or panicked
+/// - `<name>.duration` - a histogram of the call duration in seconds
+///
+/// Errors are only detected for functions whose return type is named `Result`. Async functions
+/// are timed until their future completes or is dropped.
+///
+/// The instruments come from the `byre::telemetry::metrics` registry, so the function should
+/// only be called after `byre::telemetry::init`. The macro can be combined with
+/// `#[tracing::instrument]` in either order.
+#[proc_macro_attribute]
+pub fn timed(args: TokenStream, item: TokenStream) -> TokenStream {
+    let mut name: Option<LitStr> = None;
+    let parser = syn::meta::parser(|meta| {
+        if meta.path.is_ident("name") {
+            name = Some(meta.value()?.parse()?);
+            Ok(())
+        } else {
+            Err(meta.error("unsupported timed argument, expected `name`"))
+        }
+    });
+    parse_macro_input!(args with parser);
+    let function = parse_macro_input!(item as ItemFn);
+
+    expand_timed(function, name.map(|name| name.value())).into()
+}
+
+fn expand_timed(function: ItemFn, name: Option<String>) -> TokenStream2 {
+    let ItemFn {
+        attrs,
+        vis,
+        sig,
+        block,
+    } = function;
+
+    let name = name.unwrap_or_else(|| sig.ident.to_string());
+    let calls = format!("{name}.calls");
+    let calls_description = format!("Number of calls to {name}");
+    let errors = format!("{name}.errors");
+    let errors_description = format!("Number of calls to {name} that failed");
+    let duration = format!("{name}.duration");
+    let duration_description = format!("Duration of calls to {name}");
+
+    let timer = quote! {
+        ::byre::telemetry::metrics::CallTimer::new(
+            ::byre::telemetry::metrics::counter(#calls, #calls_description, ""),
+            ::byre::telemetry::metrics::counter(#errors, #errors_description, ""),
+            ::byre::telemetry::metrics::histogram(#duration, #duration_description, "s"),
+        )
+    };
+
+    let result_type = match &sig.output {
+        ReturnType::Type(_, ty) if is_result(ty) && !contains_impl(ty.to_token_stream()) => {
+            Some(ty)
+        }
+        _ => None,
+    };
+
+    let body = match (result_type, sig.asyncness.is_some()) {
+        // The timer is dropped after the body, early returns and panics included.
+        (None, _) => quote! {
+            let __byre_timer = #timer;
+            #block
+        },
+        (Some(ty), false) => quote! {
+            let mut __byre_timer = #timer;
+            let __byre_result: #ty = (move || -> #ty #block)();
+            __byre_timer.record_result(&__byre_result);
+            __byre_result
+        },
+        (Some(ty), true) => quote! {
+            let mut __byre_timer = #timer;
+            let __byre_result: #ty = async move {
+                let __byre_result: #ty = #block;
+                __byre_result
+            }
+            .await;
+            __byre_timer.record_result(&__byre_result);
+            __byre_result
+        },
+    };
+
+    quote! {
+        #(#attrs)*
+        #vis #sig {
+            #body
+        }
+    }
+}
+
+/// Whether the type is named `Result`, ie: `Result<T, E>` or `io::Result<T>`.
+fn is_result(ty: &Type) -> bool {
+    match ty {
+        Type::Path(path) => path
+            .path
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == "Result"),
+        Type::Group(group) => is_result(&group.elem),
+        Type::Paren(paren) => is_result(&paren.elem),
+        _ => false,
+    }
+}
+
+/// `impl Trait` types cannot be written in the annotations the expansion needs.
+fn contains_impl(tokens: TokenStream2) -> bool {
+    tokens.into_iter().any(|token| match token {
+        TokenTree::Ident(ident) => ident == "impl",
+        TokenTree::Group(group) => contains_impl(group.stream()),
+        _ => false,
+    })
+}
@@ -3,8 +3,7 @@ use std::time::Duration;
 
 use clap::Parser;
 use doku::Document;
-use opentelemetry::metrics::{Counter, Histogram, UpDownCounter};
-use opentelemetry::{global, KeyValue};
+use opentelemetry::KeyValue;
 use serde::{Deserialize, Serialize};
 use tracing::{debug, error, info, info_span, instrument, warn, Instrument};
 
@@ -49,36 +48,6 @@ pub struct Arguments {
     pub error_every: u32,
 }
 
-/// Metrics for the example application
-struct AppMetrics {
-    request_counter: Counter<u64>,
-    active_operations: UpDownCounter<i64>,
-    operation_duration: Histogram<f64>,
-}
-
-impl AppMetrics {
-    fn new() -> Self {
-        let meter = global::meter("example_app");
-
-        Self {
-            request_counter: meter
-                .u64_counter("example.requests")
-                .with_description("Total number of requests processed")
-                .with_unit("requests")
-                .build(),
-            active_operations: meter
-                .i64_up_down_counter("example.active_operations")
-                .with_description("Number of currently active operations")
-                .build(),
-            operation_duration: meter
-                .f64_histogram("example.operation.duration")
-                .with_description("Duration of operations in seconds")
-                .with_unit("s")
-                .build(),
-        }
-    }
-}
-
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let service_info = byre::service_info!();
@@ -92,16 +61,14 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         "Starting example application"
     );
 
-    let metrics = AppMetrics::new();
-
     // Run the main loop
-    run_telemetry_demo(&cli.args, &metrics).await;
+    run_telemetry_demo(&cli.args).await;
 
     info!("Example application completed");
     Ok(())
 }
 
-async fn run_telemetry_demo(args: &Arguments, metrics: &AppMetrics) {
+async fn run_telemetry_demo(args: &Arguments) {
     let iterations = if args.iterations == 0 {
         u32::MAX
     } else {
@@ -114,7 +81,11 @@ async fn run_telemetry_demo(args: &Arguments, metrics: &AppMetrics) {
             info!(iteration = i, "Starting iteration");
 
             // Track active operations
-            metrics.active_operations.add(1, &[]);
+            byre::up_down_counter!(
+                "example.active_operations",
+                "Number of currently active operations"
+            )
+            .add(1, &[]);
 
             let start = std::time::Instant::now();
 
@@ -123,7 +94,12 @@ async fn run_telemetry_demo(args: &Arguments, metrics: &AppMetrics) {
 
             if should_error {
                 simulate_failed_operation(i).await;
-                metrics.request_counter.add(
+                byre::counter!(
+                    "example.requests",
+                    "Total number of requests processed",
+                    "requests"
+                )
+                .add(
                     1,
                     &[
                         KeyValue::new("status", "error"),
@@ -132,7 +108,12 @@ async fn run_telemetry_demo(args: &Arguments, metrics: &AppMetrics) {
                 );
             } else {
                 simulate_successful_operation(i).await;
-                metrics.request_counter.add(
+                byre::counter!(
+                    "example.requests",
+                    "Total number of requests processed",
+                    "requests"
+                )
+                .add(
                     1,
                     &[
                         KeyValue::new("status", "success"),
@@ -142,7 +123,12 @@ async fn run_telemetry_demo(args: &Arguments, metrics: &AppMetrics) {
             }
 
             let duration = start.elapsed().as_secs_f64();
-            metrics.operation_duration.record(
+            byre::histogram!(
+                "example.operation.duration",
+                "Duration of operations in seconds",
+                "s"
+            )
+            .record(
                 duration,
                 &[KeyValue::new(
                     "iteration_type",
@@ -150,7 +136,11 @@ async fn run_telemetry_demo(args: &Arguments, metrics: &AppMetrics) {
                 )],
             );
 
-            metrics.active_operations.add(-1, &[]);
+            byre::up_down_counter!(
+                "example.active_operations",
+                "Number of currently active operations"
+            )
+            .add(-1, &[]);
 
             debug!(
                 iteration = i,
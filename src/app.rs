@@ -0,0 +1,571 @@
+//! # Application Runner
+//!
+//! [`App`] ties the rest of byre together so a service's `main` is a single call:
+//!
+//! 1. Parsing the command line and loading the config file, see [`Cli`]. With
+//!    `--generate-dev-stack`, the app writes its [local telemetry stack](crate::dev_stack) and
+//!    exits
+//! 2. Raising the open file limit, daemonizing and setting up crash reports when configured,
+//!    see [`limits`], [`daemon`] and [`crash`], then starting a multi-threaded tokio runtime,
+//!    see [`runtime::from_settings`]
+//! 3. Initializing telemetry from the loaded settings, see [`telemetry::init`], then warning
+//!    about the [deprecated keys](crate::config::Config::deprecations) of the config, logging
+//!    the [container limits](crate::container) and emitting the [startup report](crate::startup)
+//! 4. Listening for `SIGINT` and `SIGTERM` to request a graceful shutdown
+//! 5. Running the service's async main, then flushing telemetry
+//!
+//! With the `systemd` feature enabled, the app also notifies systemd once telemetry is
+//! initialized, pings its watchdog, and reports when it starts shutting down, see
+//! [`systemd`](crate::systemd).
+//!
+//! With the `hot-reload` feature enabled, the levels of the `[telemetry.log]` section of the
+//! config file are applied while the service runs, see [`LogLevelHandle::spawn_reload`]. So are
+//! the endpoints of the exporters when the `otlp` feature is enabled too, see
+//! [`ExportHandle::spawn_reload`](crate::telemetry::ExportHandle::spawn_reload).
+//!
+//! ```rust,no_run
+//! use doku::Document;
+//! use serde::Deserialize;
+//!
+//! #[derive(Document, Deserialize)]
+//! pub struct Settings {
+//!     /// Telemetry settings.
+//!     pub telemetry: byre::telemetry::TelemetrySettings,
+//! }
+//!
+//! impl byre::app::AppSettings for Settings {
+//!     fn telemetry(&self) -> &byre::telemetry::TelemetrySettings {
+//!         &self.telemetry
+//!     }
+//! }
+//!
+//! fn main() {
+//!     byre::App::<Settings>::new(byre::service_info!(), "MYAPP_").run(|ctx| async move {
+//!         tracing::info!(service = ctx.service_info.name, "started");
+//!         ctx.shutdown().wait().await;
+//!         Ok(())
+//!     })
+//! }
+//! ```
+
+use std::future::Future;
+use std::marker::PhantomData;
+use std::time::Duration;
+
+use clap::Parser;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use snafu::{ResultExt as _, Snafu};
+use tokio::sync::watch;
+
+use crate::cli::{self, Cli, NoArguments};
+use crate::container::ContainerLimits;
+use crate::crash::{self, CrashReporter, CrashSettings};
+use crate::daemon::{self, DaemonSettings};
+use crate::dev_stack;
+use crate::limits::{self, FileLimitSettings};
+use crate::report::Report;
+use crate::runtime::{self, RuntimeSettings};
+use crate::startup::StartupReport;
+use crate::telemetry::{self, LogLevelHandle, TelemetrySettings};
+use crate::ServiceInfo;
+
+/// How long telemetry is given to flush after the main function returns, by default.
+const DEFAULT_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How often the config file is checked for changes of the log levels and endpoints.
+#[cfg(feature = "hot-reload")]
+const LOG_LEVEL_RELOAD_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Errors that can occur while running an [`App`].
+#[derive(Debug, Snafu)]
+pub enum Error {
+    /// The command line or the config file could not be used.
+    #[snafu(display("{source}"))]
+    Cli {
+        /// The underlying CLI error.
+        source: cli::Error,
+    },
+
+    /// The local telemetry stack asked for with `--generate-dev-stack` could not be written.
+    #[snafu(display("Failed to write the local telemetry stack: {source}"))]
+    DevStack {
+        /// The underlying write error.
+        source: crate::Error,
+    },
+
+    /// The process could not be daemonized.
+    #[snafu(display("Failed to daemonize: {source}"))]
+    Daemon {
+        /// The underlying daemonize error.
+        source: daemon::Error,
+    },
+
+    /// The tokio runtime could not be started.
+    #[snafu(display("{source}"))]
+    Runtime {
+        /// The underlying runtime error.
+        source: runtime::Error,
+    },
+
+    /// Telemetry could not be initialized.
+    #[snafu(display("Failed to initialize telemetry: {source}"))]
+    Telemetry {
+        /// The underlying telemetry error.
+        source: telemetry::Error,
+    },
+
+    /// The main function of the service failed.
+    #[snafu(display("{source}"))]
+    Main {
+        /// The error returned by the main function.
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+
+    /// Telemetry could not be flushed after the main function returned.
+    #[snafu(display("Failed to shut down telemetry: {source}"))]
+    Shutdown {
+        /// The underlying shutdown error.
+        source: telemetry::ShutdownError,
+    },
+}
+
+/// Settings that an [`App`] can initialize telemetry from.
+pub trait AppSettings {
+    /// The telemetry settings of the service.
+    fn telemetry(&self) -> &TelemetrySettings;
+
+    /// The settings of the tokio runtime, tokio's defaults are used when `None`.
+    fn runtime(&self) -> Option<&RuntimeSettings> {
+        None
+    }
+
+    /// The settings for raising the open file limit, it is left unchanged when `None`.
+    fn file_limit(&self) -> Option<&FileLimitSettings> {
+        None
+    }
+
+    /// The settings for running the service as a daemon, it stays in the foreground when `None`.
+    fn daemon(&self) -> Option<&DaemonSettings> {
+        None
+    }
+
+    /// The settings for backtraces and crash reports, nothing is changed when `None`.
+    ///
+    /// [`CrashSettings::backtrace`] sets `RUST_BACKTRACE`, the app must then be run before the
+    /// service spawns any thread.
+    fn crash(&self) -> Option<&CrashSettings> {
+        None
+    }
+
+    /// The addresses the service listens on by name, ie: `("http", "0.0.0.0:8080")`, reported in
+    /// the [startup report](crate::startup).
+    fn listen_addresses(&self) -> Vec<(&str, &str)> {
+        Vec::new()
+    }
+}
+
+/// Runs a service: parses the command line, loads the config, initializes telemetry and
+/// handles shutdown signals around an async main function.
+///
+/// The generic parameters are the same as the ones of [`Cli`]:
+/// - `C`: The configuration structure type, it also provides the telemetry settings
+/// - `A`: The arguments structure type (defaults to `NoArguments` if custom arguments aren't needed)
+#[must_use]
+pub struct App<C, A = NoArguments> {
+    service_info: ServiceInfo,
+    env_prefix: String,
+    shutdown_timeout: Duration,
+    _marker: PhantomData<fn() -> (C, A)>,
+}
+
+impl<C, A> App<C, A>
+where
+    A: Parser + Serialize + DeserializeOwned,
+    C: DeserializeOwned + doku::Document + AppSettings,
+{
+    /// Create an app for the service, with config values overridden by environment variables
+    /// that start with `env_prefix`.
+    ///
+    /// Pass `byre::service_info!()` so the service's own `Cargo.toml` describes it.
+    pub fn new(service_info: ServiceInfo, env_prefix: impl Into<String>) -> Self {
+        Self {
+            service_info,
+            env_prefix: env_prefix.into(),
+            shutdown_timeout: DEFAULT_SHUTDOWN_TIMEOUT,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Set how long telemetry is given to flush after the main function returns, 5 seconds by
+    /// default.
+    pub fn with_shutdown_timeout(mut self, timeout: Duration) -> Self {
+        self.shutdown_timeout = timeout;
+        self
+    }
+
+    /// Run `main` to completion, exiting the process on errors.
+    ///
+    /// This is a convenience wrapper around [`try_run`](Self::try_run) that prints errors the
+    /// same way [`Cli::new`] does, see [`Report::exit`].
+    ///
+    /// # Exits
+    ///
+    /// Calls `std::process::exit()` with the code of the error's
+    /// [`Category`](crate::report::Category) if any error occurs. Errors returned by `main`
+    /// exit with `1`, unless they wrap a byre error.
+    pub fn run<F, Fut>(self, main: F)
+    where
+        F: FnOnce(AppContext<C, A>) -> Fut,
+        Fut: Future<Output = Result<(), Box<dyn std::error::Error + Send + Sync>>>,
+    {
+        if let Err(err) = self.try_run(main) {
+            Report::new(err).exit();
+        }
+    }
+
+    /// Run `main` to completion with the arguments of the process.
+    ///
+    /// `main` is not called when the command line asked for a config file to be generated.
+    ///
+    /// # Errors
+    ///
+    /// - `Cli` if the arguments cannot be parsed, or the config cannot be generated or loaded.
+    /// - `Daemon` if the process cannot be daemonized.
+    /// - `Runtime` if the runtime settings are invalid, or the tokio runtime cannot be started.
+    /// - `Telemetry` if telemetry cannot be initialized.
+    /// - `Main` if `main` returns an error.
+    /// - `Shutdown` if telemetry cannot be flushed once `main` returned `Ok`. After an error of
+    ///   `main`, the shutdown error goes to
+    ///   [`set_shutdown_error_hook`](telemetry::set_shutdown_error_hook) instead.
+    pub fn try_run<F, Fut>(self, main: F) -> Result<(), Error>
+    where
+        F: FnOnce(AppContext<C, A>) -> Fut,
+        Fut: Future<Output = Result<(), Box<dyn std::error::Error + Send + Sync>>>,
+    {
+        self.try_run_from(std::env::args_os(), main)
+    }
+
+    /// Run `main` to completion with arguments from an iterator.
+    ///
+    /// This is like [`try_run`](Self::try_run) but accepts arguments from an iterator instead of
+    /// reading from `std::env::args()`. This is useful for testing.
+    ///
+    /// # Errors
+    ///
+    /// The same as [`try_run`](Self::try_run).
+    pub fn try_run_from<I, T, F, Fut>(self, args: I, main: F) -> Result<(), Error>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<std::ffi::OsString> + Clone,
+        F: FnOnce(AppContext<C, A>) -> Fut,
+        Fut: Future<Output = Result<(), Box<dyn std::error::Error + Send + Sync>>>,
+    {
+        let Some(cli) = Cli::<C, A>::try_new_from(args, &self.service_info, &self.env_prefix)
+            .context(CliSnafu)?
+        else {
+            // Config file was generated, there is nothing to run
+            return Ok(());
+        };
+        if let Some(dir) = &cli.dev_stack_dir {
+            dev_stack::write(dir, &self.service_info, cli.config.telemetry())
+                .context(DevStackSnafu)?;
+            return Ok(());
+        }
+
+        // Raised before any thread or socket exists, logged once telemetry is up
+        let file_limit = cli
+            .config
+            .file_limit()
+            .map(|settings| (settings, limits::raise_file_limit(settings)));
+        // Absolute, daemonizing leaves the working directory, `cli.config_path` already is
+        let report_dir = cli
+            .config
+            .crash()
+            .and_then(|settings| settings.report_dir.as_deref())
+            .map(|dir| std::path::absolute(dir).unwrap_or_else(|_| dir.to_path_buf()));
+        // SAFETY: The runtime and its threads are not started yet, and `run` is meant to be
+        // called at the start of `main`. The pid file is removed once the service is done
+        let _daemon = match cli.config.daemon() {
+            Some(settings) => unsafe { daemon::daemonize(settings) }.context(DaemonSnafu)?,
+            None => None,
+        };
+        if let Some(mode) = cli.config.crash().and_then(|settings| settings.backtrace) {
+            // SAFETY: The runtime and its threads are not started yet, and `run` is meant to be
+            // called at the start of `main`
+            unsafe { crash::set_backtrace(mode) };
+        }
+
+        let runtime = runtime::from_settings(cli.config.runtime().unwrap_or(&Default::default()))
+            .context(RuntimeSnafu)?;
+        // The exporters spawn their tasks on the runtime while telemetry is initialized
+        let _runtime = runtime.enter();
+
+        // The environment setting overrides the detected environment
+        let mut service_info = self.service_info;
+        if let Some(environment) = cli.config.telemetry().environment {
+            service_info.environment = Some(environment);
+        }
+
+        // Installed before the telemetry panic hook, which then records the panic first
+        if let Some(dir) = &report_dir {
+            CrashReporter::new(&service_info, dir)
+                .with_config(&cli.config_path, &cli.config_fingerprint)
+                .install();
+        }
+
+        let telemetry =
+            telemetry::init(&service_info, cli.config.telemetry()).context(TelemetrySnafu)?;
+
+        cli.config_deprecations
+            .iter()
+            .for_each(crate::config::Deprecation::warn);
+        ContainerLimits::detect().log();
+
+        match file_limit {
+            Some((settings, Ok(Some(limit)))) => limit.log(settings),
+            Some((_, Err(err))) => {
+                tracing::warn!(error = %err, "could not raise the open file limit")
+            }
+            Some((_, Ok(None))) | None => {}
+        }
+
+        let report = StartupReport::new(&service_info)
+            .with_config(&cli.config_path, &cli.config_fingerprint)
+            .with_exporters(&telemetry);
+        cli.config
+            .listen_addresses()
+            .into_iter()
+            .fold(report, |report, (name, address)| {
+                report.with_listener(name, address)
+            })
+            .emit();
+
+        #[cfg(feature = "systemd")]
+        {
+            log_systemd_error(crate::systemd::ready());
+            // The pings stop with the runtime
+            let _ = crate::systemd::spawn_watchdog();
+        }
+
+        #[cfg(feature = "hot-reload")]
+        if let Some(log_levels) = telemetry.log_levels() {
+            // The reload stops with the runtime
+            drop(log_levels.spawn_reload(
+                &cli.config_path,
+                &self.env_prefix,
+                LOG_LEVEL_RELOAD_INTERVAL,
+            ));
+        }
+        #[cfg(all(feature = "hot-reload", feature = "otlp"))]
+        drop(telemetry.exports().spawn_reload(
+            &service_info,
+            cli.config.telemetry(),
+            &cli.config_path,
+            &self.env_prefix,
+            LOG_LEVEL_RELOAD_INTERVAL,
+        ));
+
+        let (requested, shutdown) = watch::channel(false);
+        runtime.spawn(async move {
+            shutdown_signal().await;
+            tracing::info!("Shutdown requested");
+            #[cfg(feature = "systemd")]
+            log_systemd_error(crate::systemd::stopping());
+            let _ = requested.send(true);
+        });
+
+        let ctx = AppContext {
+            service_info,
+            config: cli.config,
+            args: cli.args,
+            log_levels: telemetry.log_levels().cloned(),
+            shutdown: ShutdownSignal(shutdown),
+        };
+        let result = runtime.block_on(main(ctx));
+        #[cfg(feature = "systemd")]
+        log_systemd_error(crate::systemd::stopping());
+
+        // Flush telemetry even when main failed, its error is the one returned
+        let shutdown = telemetry.shutdown(self.shutdown_timeout);
+        if let Err(source) = result {
+            if let Err(err) = shutdown {
+                telemetry::report_shutdown_error(err);
+            }
+            return Err(Error::Main { source });
+        }
+        shutdown.context(ShutdownSnafu)
+    }
+}
+
+/// Everything the main function of an [`App`] is given.
+#[non_exhaustive]
+pub struct AppContext<C, A = NoArguments> {
+    /// Service information the app was created with.
+    pub service_info: ServiceInfo,
+
+    /// Application configuration loaded from the config file and environment variables.
+    pub config: C,
+
+    /// Parsed command-line arguments.
+    pub args: A,
+
+    log_levels: Option<LogLevelHandle>,
+    shutdown: ShutdownSignal,
+}
+
+impl<C, A> AppContext<C, A> {
+    /// Handle for changing the log levels at runtime, ie: to serve it with the admin endpoint.
+    pub fn log_levels(&self) -> Option<&LogLevelHandle> {
+        self.log_levels.as_ref()
+    }
+
+    /// Signal that resolves once the process receives `SIGINT` or `SIGTERM`.
+    pub fn shutdown(&self) -> ShutdownSignal {
+        self.shutdown.clone()
+    }
+}
+
+/// Tells the tasks of an [`App`] that the service was asked to shut down.
+///
+/// Clone it into every task that needs to stop gracefully.
+#[derive(Clone, Debug)]
+pub struct ShutdownSignal(watch::Receiver<bool>);
+
+impl ShutdownSignal {
+    /// Wait until the service is asked to shut down.
+    ///
+    /// The future does not borrow the signal, so it can be handed to servers such as
+    /// `axum::serve(...).with_graceful_shutdown(ctx.shutdown().wait())`.
+    pub fn wait(&self) -> impl Future<Output = ()> + Send + use<> {
+        let mut receiver = self.0.clone();
+        async move {
+            // The sender is only dropped once the shutdown was requested
+            let _ = receiver.wait_for(|requested| *requested).await;
+        }
+    }
+
+    /// Whether the service was asked to shut down.
+    pub fn is_requested(&self) -> bool {
+        *self.0.borrow()
+    }
+}
+
+/// systemd notifications are best effort, the service keeps running when they fail.
+#[cfg(feature = "systemd")]
+fn log_systemd_error(result: Result<bool, crate::systemd::Error>) {
+    if let Err(err) = result {
+        tracing::warn!(error = %err, "could not notify systemd");
+    }
+}
+
+/// Resolves once the process receives `SIGINT` (Ctrl-C) or, on unix, `SIGTERM`.
+///
+/// A signal that cannot be listened for never resolves.
+async fn shutdown_signal() {
+    let interrupt = async {
+        if tokio::signal::ctrl_c().await.is_err() {
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                terminate.recv().await;
+            }
+            Err(_) => std::future::pending::<()>().await,
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        () = interrupt => {}
+        () = terminate => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use doku::Document;
+    use serde::Deserialize;
+
+    #[derive(Document, Deserialize)]
+    struct Settings {
+        /// Telemetry settings.
+        telemetry: TelemetrySettings,
+    }
+
+    impl AppSettings for Settings {
+        fn telemetry(&self) -> &TelemetrySettings {
+            &self.telemetry
+        }
+    }
+
+    #[test]
+    fn test_generate_does_not_run_main() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+
+        let result = App::<Settings>::new(ServiceInfo::default(), "BYRE_TEST_APP_").try_run_from(
+            ["app", "--generate", path.to_str().unwrap()],
+            |_ctx| async { panic!("main must not run when generating a config") },
+        );
+
+        assert!(result.is_ok());
+        assert!(path.exists());
+    }
+
+    #[test]
+    fn test_generate_dev_stack_does_not_run_main() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = dir.path().join("config.toml");
+        std::fs::write(
+            &config,
+            "[telemetry.trace]\n[telemetry.log]\n[telemetry.metric]\n",
+        )
+        .unwrap();
+        let stack = dir.path().join("stack");
+
+        let result = App::<Settings>::new(ServiceInfo::default(), "BYRE_TEST_APP_").try_run_from(
+            [
+                "app",
+                "--config",
+                config.to_str().unwrap(),
+                "--generate-dev-stack",
+                stack.to_str().unwrap(),
+            ],
+            |_ctx| async { panic!("main must not run when generating the dev stack") },
+        );
+
+        assert!(result.is_ok());
+        assert!(stack.join(dev_stack::COMPOSE_FILE).exists());
+    }
+
+    #[test]
+    fn test_missing_config_is_a_cli_error() {
+        let result = App::<Settings>::new(ServiceInfo::default(), "BYRE_TEST_APP_")
+            .try_run_from(["app", "--config", "/does/not/exist.toml"], |_ctx| async {
+                Ok(())
+            });
+
+        assert!(matches!(result, Err(Error::Cli { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_signal_resolves_once_requested() {
+        let (requested, receiver) = watch::channel(false);
+        let shutdown = ShutdownSignal(receiver);
+        let wait = tokio::spawn(shutdown.wait());
+
+        assert!(!shutdown.is_requested());
+        requested.send(true).unwrap();
+
+        wait.await.unwrap();
+        assert!(shutdown.is_requested());
+    }
+}
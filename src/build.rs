@@ -0,0 +1,138 @@
+//! # Build Metadata
+//!
+//! Helpers for the service's `build.rs`, which record how the service was built so that
+//! [`service_info!`](crate::service_info) can read it at compile time. Add byre as a build
+//! dependency and call [`emit_build_info`]:
+//!
+//! ```toml
+//! [build-dependencies]
+//! byre = "0.6"
+//! ```
+//!
+//! ```rust,ignore
+//! // build.rs
+//! fn main() {
+//!     byre::build::emit_build_info();
+//! }
+//! ```
+//!
+//! The variables have the names [vergen](https://docs.rs/vergen) gives them, so services that
+//! already use vergen don't need this module.
+
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Set the `VERGEN_GIT_SHA`, `VERGEN_GIT_DIRTY`, `VERGEN_BUILD_TIMESTAMP` and
+/// `VERGEN_RUSTC_SEMVER` environment variables for the crate being built.
+///
+/// Only call it from a build script. Values that cannot be found, ie: when building outside of a
+/// git checkout, are not set and are empty in the [`ServiceInfo`](crate::ServiceInfo).
+///
+/// The build script is rerun when the checked out branch, the commit it points to or the staged
+/// files change, the build timestamp is the time it last ran. Edits to tracked files that are
+/// not staged don't rerun it, so `VERGEN_GIT_DIRTY` only tells whether the working tree had
+/// uncommitted changes when the script last ran. Set `SOURCE_DATE_EPOCH` for reproducible
+/// builds, the timestamp is then taken from it.
+pub fn emit_build_info() {
+    if let Some(git_sha) = git(&["rev-parse", "HEAD"]) {
+        println!("cargo:rustc-env=VERGEN_GIT_SHA={git_sha}");
+        let dirty = git(&["status", "--porcelain", "--untracked-files=no"])
+            .is_some_and(|status| !status.is_empty());
+        println!("cargo:rustc-env=VERGEN_GIT_DIRTY={dirty}");
+    }
+    if let Some(git_dir) = git(&["rev-parse", "--absolute-git-dir"]) {
+        println!("cargo:rerun-if-changed={git_dir}/HEAD");
+        println!("cargo:rerun-if-changed={git_dir}/index");
+    }
+    // A commit moves the branch, not HEAD, and `git gc` moves the branch into `packed-refs`.
+    // `--git-path` resolves both in the common directory of a worktree. Missing files are left
+    // out, cargo would rerun the script on every build
+    let head_ref = git(&["symbolic-ref", "-q", "HEAD"]);
+    for path in head_ref.as_deref().into_iter().chain(["packed-refs"]) {
+        if let Some(path) = git(&["rev-parse", "--git-path", path]) {
+            let path = std::path::absolute(&path).unwrap_or_else(|_| path.into());
+            if path.exists() {
+                println!("cargo:rerun-if-changed={}", path.display());
+            }
+        }
+    }
+
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+    let build_time = std::env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|epoch| epoch.trim().parse().ok())
+        .unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|elapsed| elapsed.as_secs())
+                .unwrap_or_default()
+        });
+    println!(
+        "cargo:rustc-env=VERGEN_BUILD_TIMESTAMP={}",
+        rfc3339(build_time)
+    );
+
+    if let Some(version) = rustc_version() {
+        println!("cargo:rustc-env=VERGEN_RUSTC_SEMVER={version}");
+    }
+}
+
+/// The trimmed output of a successful git command.
+fn git(args: &[&str]) -> Option<String> {
+    let output = Command::new("git").args(args).output().ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// The version of the compiler cargo builds the crate with, ie: `1.85.0`.
+fn rustc_version() -> Option<String> {
+    let rustc = std::env::var_os("RUSTC").unwrap_or_else(|| "rustc".into());
+    let output = Command::new(rustc).arg("--version").output().ok()?;
+    // `rustc 1.85.0 (4d91de4e4 2025-02-17)`
+    String::from_utf8_lossy(&output.stdout)
+        .split_whitespace()
+        .nth(1)
+        .map(ToString::to_string)
+}
+
+/// Format seconds since the unix epoch as an RFC 3339 UTC timestamp.
+fn rfc3339(secs: u64) -> String {
+    let (days, time) = (secs / 86_400, secs % 86_400);
+    let (year, month, day) = civil_from_days(days as i64);
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        time / 3600,
+        time % 3600 / 60,
+        time % 60
+    )
+}
+
+/// The proleptic Gregorian date of a number of days since 1970-01-01, from
+/// <https://howardhinnant.github.io/date_algorithms.html#civil_from_days>.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rfc3339_timestamps() {
+        assert_eq!(rfc3339(0), "1970-01-01T00:00:00Z");
+        assert_eq!(rfc3339(951_782_400), "2000-02-29T00:00:00Z");
+        assert_eq!(rfc3339(1_792_227_845), "2026-10-17T09:04:05Z");
+    }
+}
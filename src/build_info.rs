@@ -0,0 +1,180 @@
+//! The `/buildinfo` route shared by the admin and health servers.
+//!
+//! The route answers with `key: value` lines by default, and with OpenMetrics when the request
+//! accepts `application/openmetrics-text`, so a Prometheus scrape or a `curl` in a probe can read
+//! which build and configuration an instance runs, and for how long.
+
+use std::time::Instant;
+
+use http::header::{ACCEPT, CONTENT_TYPE};
+use http::{HeaderMap, HeaderValue};
+
+use crate::ServiceInfo;
+
+/// The path both servers serve the build info at.
+pub(crate) const BUILD_INFO_PATH: &str = "/buildinfo";
+
+const OPENMETRICS: &str = "application/openmetrics-text";
+const OPENMETRICS_CONTENT_TYPE: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+/// The build of the service and when it started serving its build info.
+#[derive(Clone, Debug)]
+pub(crate) struct BuildInfo {
+    name: &'static str,
+    version: &'static str,
+    git_sha: &'static str,
+    git_dirty: bool,
+    build_timestamp: &'static str,
+    rustc_version: &'static str,
+    started: Instant,
+}
+
+impl BuildInfo {
+    /// The build of `service_info`, its uptime counts from now.
+    pub(crate) fn new(service_info: &ServiceInfo) -> Self {
+        Self {
+            name: service_info.name,
+            version: service_info.version,
+            git_sha: service_info.git_sha,
+            git_dirty: service_info.git_dirty,
+            build_timestamp: service_info.build_timestamp,
+            rustc_version: service_info.rustc_version,
+            started: Instant::now(),
+        }
+    }
+
+    /// The body of a response to a request with `headers`, and its content type when it is not
+    /// plain text. The `config_fingerprint` of the loaded configuration is reported with the
+    /// build.
+    pub(crate) fn render(
+        &self,
+        config_fingerprint: Option<&str>,
+        headers: &HeaderMap,
+    ) -> (String, Option<HeaderValue>) {
+        let openmetrics = headers
+            .get_all(ACCEPT)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .any(|value| value.contains(OPENMETRICS));
+        if openmetrics {
+            (
+                self.render_openmetrics(config_fingerprint),
+                Some(HeaderValue::from_static(OPENMETRICS_CONTENT_TYPE)),
+            )
+        } else {
+            (self.render_text(config_fingerprint), None)
+        }
+    }
+
+    /// The `(key, value)` pairs describing the build, leaving out what the build did not record.
+    fn fields(&self, config_fingerprint: Option<&str>) -> Vec<(&'static str, String)> {
+        let mut fields = vec![
+            ("name", self.name.to_string()),
+            ("version", self.version.to_string()),
+        ];
+        if !self.git_sha.is_empty() {
+            fields.push(("git_sha", self.git_sha.to_string()));
+            fields.push(("git_dirty", self.git_dirty.to_string()));
+        }
+        if !self.build_timestamp.is_empty() {
+            fields.push(("build_timestamp", self.build_timestamp.to_string()));
+        }
+        if !self.rustc_version.is_empty() {
+            fields.push(("rustc_version", self.rustc_version.to_string()));
+        }
+        if let Some(fingerprint) = config_fingerprint {
+            fields.push(("config_fingerprint", fingerprint.to_string()));
+        }
+        fields
+    }
+
+    fn render_text(&self, config_fingerprint: Option<&str>) -> String {
+        let mut out = String::new();
+        for (key, value) in self.fields(config_fingerprint) {
+            out.push_str(&format!("{key}: {value}\n"));
+        }
+        out.push_str(&format!(
+            "uptime_secs: {}\n",
+            self.started.elapsed().as_secs()
+        ));
+        out
+    }
+
+    fn render_openmetrics(&self, config_fingerprint: Option<&str>) -> String {
+        let labels: Vec<_> = self
+            .fields(config_fingerprint)
+            .into_iter()
+            .map(|(key, value)| format!("{key}=\"{}\"", escape_label_value(&value)))
+            .collect();
+        format!(
+            "# TYPE service_build info\n\
+             # HELP service_build The build and configuration of the service.\n\
+             service_build_info{{{}}} 1\n\
+             # TYPE process_uptime_seconds gauge\n\
+             # UNIT process_uptime_seconds seconds\n\
+             # HELP process_uptime_seconds Time since the service started serving its build info.\n\
+             process_uptime_seconds {}\n\
+             # EOF\n",
+            labels.join(","),
+            self.started.elapsed().as_secs_f64()
+        )
+    }
+}
+
+/// Set the content type of a response rendered by [`BuildInfo::render`].
+pub(crate) fn set_content_type(headers: &mut HeaderMap, content_type: Option<HeaderValue>) {
+    if let Some(content_type) = content_type {
+        headers.insert(CONTENT_TYPE, content_type);
+    }
+}
+
+/// Escape a label value of the OpenMetrics text format.
+fn escape_label_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_openmetrics_is_negotiated() {
+        let service_info = ServiceInfo {
+            name: "inventory",
+            version: "1.2.3",
+            git_sha: "0123abcd",
+            ..Default::default()
+        };
+        let build_info = BuildInfo::new(&service_info);
+
+        let (text, content_type) = build_info.render(Some("4f\"2"), &HeaderMap::new());
+        assert!(content_type.is_none());
+        assert!(
+            text.starts_with(
+                "name: inventory\nversion: 1.2.3\ngit_sha: 0123abcd\ngit_dirty: false\n\
+                 config_fingerprint: 4f\"2\nuptime_secs: "
+            ),
+            "{text}"
+        );
+
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            ACCEPT,
+            HeaderValue::from_static("application/openmetrics-text;version=1.0.0,text/plain;q=0.5"),
+        );
+        let (metrics, content_type) = build_info.render(Some("4f\"2"), &headers);
+        assert_eq!(content_type.unwrap(), OPENMETRICS_CONTENT_TYPE);
+        assert!(
+            metrics.contains(
+                "service_build_info{name=\"inventory\",version=\"1.2.3\",git_sha=\"0123abcd\",\
+                 git_dirty=\"false\",config_fingerprint=\"4f\\\"2\"} 1\n"
+            ),
+            "{metrics}"
+        );
+        assert!(metrics.contains("\nprocess_uptime_seconds "), "{metrics}");
+        assert!(metrics.ends_with("# EOF\n"), "{metrics}");
+    }
+}
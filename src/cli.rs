@@ -5,6 +5,7 @@
 //! - Command-line argument parsing based on `clap`
 //! - TOML configuration file generation and loading
 //! - Environment variable overrides for configuration values
+//! - Checking a configuration file without running the service, see [`check`]
 //!
 //! The design goal is to simplify the common CLI application pattern of:
 //! 1. Parsing command-line arguments
@@ -15,10 +16,16 @@ use clap::{Arg, ArgAction, Command, Parser};
 use serde::{Deserialize, Serialize};
 use snafu::Snafu;
 
-use crate::{config::Config, ServiceInfo};
+use crate::{config::Config, report::Report, ServiceInfo};
 
 const GENERATE_CONFIG_OPT_ID: &str = "generate";
 const USE_CONFIG_OPT_ID: &str = "config";
+const CHECK_CONFIG_OPT_ID: &str = "check-config";
+const GENERATE_CONFIGMAP_OPT_ID: &str = "generate-configmap";
+const CONFIGMAP_NAME_OPT_ID: &str = "configmap-name";
+const CONFIGMAP_NAMESPACE_OPT_ID: &str = "configmap-namespace";
+const GENERATE_DEV_STACK_OPT_ID: &str = "generate-dev-stack";
+const CHECK_FORMAT_OPT_ID: &str = "check-format";
 
 /// Errors that can occur during CLI initialization.
 #[derive(Debug, Snafu)]
@@ -30,11 +37,13 @@ pub enum Error {
         source: crate::Error,
     },
 
-    /// Command-line argument parsing failed.
+    /// Command-line argument parsing failed, or the help or the version was asked for.
     #[snafu(display("Failed to parse command-line arguments: {message}"))]
     ArgParse {
-        /// Description of the parsing error.
+        /// Description of the parsing error, or the help or the version.
         message: String,
+        /// What clap stopped on, ie: `DisplayHelp` when the help was asked for.
+        kind: clap::error::ErrorKind,
     },
 
     /// Configuration file generation failed.
@@ -43,6 +52,13 @@ pub enum Error {
         /// The underlying error from config generation.
         source: crate::Error,
     },
+
+    /// `--check-config` found the configuration file invalid or missing.
+    #[snafu(display("{check}"))]
+    ConfigCheckFailed {
+        /// What the check found.
+        check: check::ConfigCheck,
+    },
 }
 
 /// An empty arguments structure for use when no custom CLI arguments are needed.
@@ -80,6 +96,23 @@ pub struct Cli<C, A = NoArguments> {
     /// 2. Values from the specified configuration file
     /// 3. Overrides from environment variables (using the prefix specified in `try_new()`)
     pub config: C,
+
+    /// Path of the configuration file given with `--config`, made absolute so it still points
+    /// to the file once the working directory changes, ie: when the service daemonizes.
+    pub config_path: std::path::PathBuf,
+
+    /// Fingerprint of the loaded configuration, see [`Config::fingerprint`].
+    pub config_fingerprint: String,
+
+    /// Deprecated keys the configuration sets, see [`Config::deprecations`].
+    pub config_deprecations: Vec<crate::config::Deprecation>,
+
+    /// Directory given with `--generate-dev-stack`, where the service should write its
+    /// [local telemetry stack](crate::dev_stack) instead of running.
+    ///
+    /// [`App`](crate::App) writes the stack and exits, services that use `Cli` directly call
+    /// [`dev_stack::write`](crate::dev_stack::write) with their telemetry settings.
+    pub dev_stack_dir: Option<std::path::PathBuf>,
 }
 
 impl<'a, C, A> Cli<C, A>
@@ -96,10 +129,15 @@ where
     /// 1. Builds a command-line parser with your application info and arguments from type `A`
     /// 2. Adds the built-in `--config` and `--generate` options
     /// 3. Parses the command line
-    /// 4. If `--generate` is specified, creates a sample config file and returns `Ok(None)`
-    /// 5. If `--config` is specified, loads and parses the configuration file
-    /// 6. Applies any environment variable overrides using the specified prefix
-    /// 7. Returns `Some(Cli)` with the parsed arguments and configuration
+    /// 4. If `--generate` is specified, creates a sample config file and returns `Ok(None)`,
+    ///    `--generate-configmap` creates it as a Kubernetes ConfigMap, named with
+    ///    `--configmap-name` and `--configmap-namespace`
+    /// 5. If `--check-config` is specified, checks the configuration file, see [`check`]: a
+    ///    valid one is printed to stdout and returns `Ok(None)`, otherwise returns
+    ///    `Err(Error::ConfigCheckFailed)`
+    /// 6. If `--config` is specified, loads and parses the configuration file
+    /// 7. Applies any environment variable overrides using the specified prefix
+    /// 8. Returns `Some(Cli)` with the parsed arguments and configuration
     ///
     /// # Arguments
     ///
@@ -109,10 +147,12 @@ where
     /// # Returns
     ///
     /// - `Ok(Some(cli))` - Successfully parsed arguments and loaded configuration
-    /// - `Ok(None)` - Configuration file was generated successfully; application should exit
+    /// - `Ok(None)` - Configuration file was generated, or checked and is valid; application
+    ///   should exit
     /// - `Err(Error::ConfigGenerateFailed)` - Configuration generation failed
     /// - `Err(Error::ArgParse)` - Command-line argument parsing failed
     /// - `Err(Error::ConfigLoad)` - Configuration loading or parsing failed
+    /// - `Err(Error::ConfigCheckFailed)` - `--check-config` found the configuration invalid
     pub fn try_new(
         service_info: &ServiceInfo,
         env_prefix: impl AsRef<str>,
@@ -143,6 +183,8 @@ where
 
         let cmd = Command::new(service_info.name)
             .version(service_info.version)
+            .long_version(service_info.long_version())
+            .after_help(help_footer(service_info))
             .author(service_info.author)
             .about(
                 arg_command
@@ -152,7 +194,10 @@ where
             .args(arg_command.get_arguments())
             .arg(
                 Arg::new("config")
-                    .required_unless_present(GENERATE_CONFIG_OPT_ID)
+                    .required_unless_present_any([
+                        GENERATE_CONFIG_OPT_ID,
+                        GENERATE_CONFIGMAP_OPT_ID,
+                    ])
                     .action(ArgAction::Set)
                     .long(USE_CONFIG_OPT_ID)
                     .short('c')
@@ -164,12 +209,65 @@ where
                     .long(GENERATE_CONFIG_OPT_ID)
                     .short('g')
                     .help("Generates a new default toml config file for the service"),
+            )
+            .arg(
+                Arg::new(GENERATE_CONFIGMAP_OPT_ID)
+                    .action(ArgAction::Set)
+                    .long(GENERATE_CONFIGMAP_OPT_ID)
+                    .conflicts_with(GENERATE_CONFIG_OPT_ID)
+                    .help(
+                        "Generates a Kubernetes ConfigMap yaml file holding the default toml \
+                         config file",
+                    ),
+            )
+            .arg(
+                Arg::new(CONFIGMAP_NAME_OPT_ID)
+                    .action(ArgAction::Set)
+                    .long(CONFIGMAP_NAME_OPT_ID)
+                    .requires(GENERATE_CONFIGMAP_OPT_ID)
+                    .help("Name of the generated ConfigMap, the name of the service by default"),
+            )
+            .arg(
+                Arg::new(CONFIGMAP_NAMESPACE_OPT_ID)
+                    .action(ArgAction::Set)
+                    .long(CONFIGMAP_NAMESPACE_OPT_ID)
+                    .requires(GENERATE_CONFIGMAP_OPT_ID)
+                    .help("Namespace of the generated ConfigMap"),
+            )
+            .arg(
+                Arg::new(GENERATE_DEV_STACK_OPT_ID)
+                    .action(ArgAction::Set)
+                    .long(GENERATE_DEV_STACK_OPT_ID)
+                    .requires(USE_CONFIG_OPT_ID)
+                    .help(
+                        "Writes a docker compose stack that receives the telemetry of the \
+                         service to the directory, then exits",
+                    ),
+            )
+            .arg(
+                Arg::new(CHECK_CONFIG_OPT_ID)
+                    .action(ArgAction::SetTrue)
+                    .long(CHECK_CONFIG_OPT_ID)
+                    .requires(USE_CONFIG_OPT_ID)
+                    .help(
+                        "Checks the config file and exits: 0 valid, 2 parse error, \
+                         3 invalid value, 4 missing file",
+                    ),
+            )
+            .arg(
+                Arg::new(CHECK_FORMAT_OPT_ID)
+                    .action(ArgAction::Set)
+                    .long(CHECK_FORMAT_OPT_ID)
+                    .value_parser(clap::value_parser!(check::Format))
+                    .requires(CHECK_CONFIG_OPT_ID)
+                    .help("Prints the result of --check-config as text (default) or json"),
             );
 
         let mut arg_matches = cmd
             .try_get_matches_from(args)
             .map_err(|e| Error::ArgParse {
                 message: e.to_string(),
+                kind: e.kind(),
             })?;
 
         if let Some(config_file_path_str) = arg_matches.remove_one::<String>(GENERATE_CONFIG_OPT_ID)
@@ -180,28 +278,63 @@ where
             return Ok(None);
         }
 
+        if let Some(path) = arg_matches.remove_one::<String>(GENERATE_CONFIGMAP_OPT_ID) {
+            let name = arg_matches
+                .remove_one::<String>(CONFIGMAP_NAME_OPT_ID)
+                .unwrap_or_else(|| service_info.name.to_string());
+            let namespace = arg_matches.remove_one::<String>(CONFIGMAP_NAMESPACE_OPT_ID);
+            crate::config::create_configmap_file::<C>(path, &name, namespace.as_deref())
+                .map_err(|source| Error::ConfigGenerateFailed { source })?;
+
+            return Ok(None);
+        }
+
         let Some(config_path_str) = arg_matches.remove_one::<String>(USE_CONFIG_OPT_ID) else {
-            unreachable!("config is required unless generate is present")
+            unreachable!("config is required unless a generate option is present")
         };
 
+        let dev_stack_dir = arg_matches
+            .remove_one::<String>(GENERATE_DEV_STACK_OPT_ID)
+            .map(Into::into);
+        let env_prefix = env_prefix.as_ref();
+        if arg_matches.get_flag(CHECK_CONFIG_OPT_ID) {
+            let format = arg_matches
+                .remove_one::<check::Format>(CHECK_FORMAT_OPT_ID)
+                .unwrap_or_default();
+            let check = check::ConfigCheck::run::<C>(config_path_str.as_ref(), env_prefix, format);
+            if check.status != check::Status::Valid {
+                return Err(Error::ConfigCheckFailed { check });
+            }
+            println!("{check}");
+            return Ok(None);
+        }
+
         let args = A::from_arg_matches_mut(&mut arg_matches).map_err(|e| Error::ArgParse {
             message: e.to_string(),
+            kind: e.kind(),
         })?;
 
-        let env_prefix = env_prefix.as_ref();
-        let config_result = Config::new(Some(config_path_str), Some(env_prefix));
-
-        let config = config_result
-            .map(|c| c.config)
+        let loaded = Config::new(Some(&config_path_str), Some(env_prefix))
             .map_err(|source| Error::ConfigLoad { source })?;
-
-        Ok(Some(Self { args, config }))
+        let config_fingerprint = loaded.fingerprint().to_string();
+        let config_deprecations = loaded.deprecations().to_vec();
+
+        Ok(Some(Self {
+            args,
+            config: loaded.config,
+            // The file was just read, the working directory exists
+            config_path: std::path::absolute(&config_path_str)
+                .unwrap_or_else(|_| config_path_str.into()),
+            config_fingerprint,
+            config_deprecations,
+            dev_stack_dir,
+        }))
     }
 
     /// Creates a new CLI instance, exiting the process on errors.
     ///
     /// This is a convenience wrapper around [`try_new`](Self::try_new) that handles errors
-    /// by printing them to stderr and calling `std::process::exit()`. This is suitable
+    /// by printing them and exiting the process, see [`Report::exit`]. This is suitable
     /// for typical CLI applications where you want clap-style error handling.
     ///
     /// # Arguments
@@ -211,8 +344,12 @@ where
     ///
     /// # Exits
     ///
-    /// Calls `std::process::exit(0)` if config generation was requested.
-    /// Calls `std::process::exit(1)` if any error occurs.
+    /// Calls `std::process::exit(0)` if config generation was requested, `--check-config` found
+    /// the config valid, or the help or the version was printed.
+    /// Calls `std::process::exit()` with the code of the [`check`] if `--check-config` found the
+    /// config invalid.
+    /// Calls `std::process::exit()` with the code of the error's
+    /// [`Category`](crate::report::Category) if any error occurs.
     pub fn new(service_info: &ServiceInfo, env_prefix: impl AsRef<str>) -> Self {
         match Self::try_new(service_info, env_prefix) {
             Ok(Some(cli)) => cli,
@@ -220,22 +357,28 @@ where
                 // Config was generated successfully
                 std::process::exit(0);
             }
-            Err(Error::ConfigGenerateFailed { source }) => {
-                eprintln!("Failed to generate config file: {source}");
-                std::process::exit(1);
-            }
-            Err(Error::ArgParse { message }) => {
-                eprintln!("{message}");
-                std::process::exit(1);
-            }
-            Err(Error::ConfigLoad { source }) => {
-                eprintln!("{source}");
-                std::process::exit(1);
-            }
+            Err(err) => Report::new(err).exit(),
         }
     }
 }
 
+/// The end of `--help`, where the service says where it is deployed, ie:
+/// `Environment: prod, region: eu-west-1`.
+fn help_footer(service_info: &ServiceInfo) -> String {
+    let mut footer = format!(
+        "Environment: {}",
+        service_info.environment.unwrap_or_default()
+    );
+    if !service_info.region.is_empty() {
+        footer.push_str(&format!(", region: {}", service_info.region));
+    }
+    footer
+}
+
+pub mod check;
+#[cfg(any(test, feature = "test-util"))]
+pub mod testing;
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -265,9 +408,38 @@ mod tests {
             version: "1.0.0",
             author: "Test Author",
             description: "Test service description",
+            git_sha: "",
+            git_dirty: false,
+            build_timestamp: "",
+            rustc_version: "",
+            environment: Some(crate::Environment::Dev),
+            region: String::new(),
         }
     }
 
+    #[test]
+    fn test_relative_config_path_is_made_absolute() {
+        // Created in the working directory, so it can be given with a relative path
+        let mut config_file = tempfile::Builder::new()
+            .suffix(".toml")
+            .tempfile_in(".")
+            .unwrap();
+        writeln!(config_file, "setting = \"hello\"").unwrap();
+        let file_name = config_file.path().file_name().unwrap().to_str().unwrap();
+        let relative = format!("./{file_name}");
+
+        let args = vec!["test-program", "--config", &relative];
+        let cli = Cli::<TestConfig, TestArgs>::try_new_from(args, &test_service_info(), "TEST")
+            .unwrap()
+            .unwrap();
+
+        assert!(cli.config_path.is_absolute());
+        assert_eq!(
+            cli.config_path,
+            std::env::current_dir().unwrap().join(file_name)
+        );
+    }
+
     #[test]
     fn test_try_new_from_with_config_returns_some() {
         // Create a temporary config file
@@ -318,6 +490,38 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_try_new_from_generate_configmap_returns_none() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let output_path = temp_dir.path().join("configmap.yaml");
+        let generate = |args: &[&str]| {
+            let args = ["test-program", "--generate-configmap"]
+                .into_iter()
+                .chain([output_path.to_str().unwrap()])
+                .chain(args.iter().copied());
+            let result =
+                Cli::<TestConfig, TestArgs>::try_new_from(args, &test_service_info(), "TEST");
+            assert!(matches!(result, Ok(None)));
+            std::fs::read_to_string(&output_path).unwrap()
+        };
+
+        assert_eq!(
+            generate(&["--configmap-namespace", "prod"]),
+            format!(
+                "apiVersion: v1\nkind: ConfigMap\nmetadata:\n  name: \"test-service\"\n  \
+                 namespace: \"prod\"\ndata:\n  config.toml: |\n{}",
+                doku::to_toml::<TestConfig>()
+                    .lines()
+                    .map(|line| format!("    {line}\n"))
+                    .collect::<String>()
+            )
+        );
+
+        let yaml = generate(&["--configmap-name", "archive"]);
+        assert!(yaml.contains("  name: \"archive\"\n"));
+        assert!(!yaml.contains("namespace"));
+    }
+
     #[test]
     fn test_try_new_from_missing_config_fails() {
         let args = vec!["test-program"];
@@ -335,6 +539,52 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_long_version_shows_the_build_metadata() {
+        let service_info = crate::ServiceInfo {
+            git_sha: "0123abcd",
+            git_dirty: true,
+            build_timestamp: "2026-10-17T09:04:05Z",
+            rustc_version: "1.85.0",
+            ..test_service_info()
+        };
+
+        let version = |flag| match Cli::<TestConfig, TestArgs>::try_new_from(
+            ["test-program", flag],
+            &service_info,
+            "TEST",
+        ) {
+            Err(Error::ArgParse { message, .. }) => message,
+            _ => panic!("expected the version to be printed"),
+        };
+
+        assert_eq!(version("-V"), "test-service 1.0.0\n");
+        assert_eq!(
+            version("--version"),
+            "test-service 1.0.0 (0123abcd-dirty, built 2026-10-17T09:04:05Z, rustc 1.85.0)\n"
+        );
+    }
+
+    #[test]
+    fn test_help_ends_with_the_environment_and_region() {
+        let service_info = crate::ServiceInfo {
+            environment: Some(crate::Environment::Prod),
+            region: "eu-west-1".to_string(),
+            ..test_service_info()
+        };
+
+        let result = Cli::<TestConfig, TestArgs>::try_new_from(
+            ["test-program", "--help"],
+            &service_info,
+            "TEST",
+        );
+
+        let Err(Error::ArgParse { message, .. }) = result else {
+            panic!("expected the help to be printed");
+        };
+        assert!(message.ends_with("Environment: prod, region: eu-west-1\n"));
+    }
+
     #[test]
     fn test_try_new_from_with_malformed_config_fails() {
         // Create a temporary config file with invalid TOML
@@ -353,4 +603,97 @@ mod tests {
             "expected ConfigLoad error"
         );
     }
+
+    #[test]
+    fn test_run_with_config_parses_inline_config_and_args() {
+        let cli =
+            testing::run_with_config::<TestConfig, TestArgs>("setting = \"hello\"", ["--verbose"])
+                .unwrap();
+        assert_eq!(cli.config.setting, Some("hello".to_string()));
+        assert!(cli.args.verbose);
+
+        let result = testing::run_with_config::<TestConfig, TestArgs>("setting = [", ["--verbose"]);
+        assert!(matches!(result, Err(Error::ConfigLoad { .. })));
+    }
+
+    #[test]
+    fn test_service_arguments_can_be_called_format() {
+        #[derive(Parser, Serialize, Deserialize)]
+        struct FormatArgs {
+            #[arg(long)]
+            format: Option<String>,
+        }
+
+        let mut config_file = NamedTempFile::new().unwrap();
+        writeln!(config_file, "setting = \"hello\"").unwrap();
+        let args = [
+            "test-program",
+            "--config",
+            config_file.path().to_str().unwrap(),
+            "--format",
+            "csv",
+        ];
+        let cli = Cli::<TestConfig, FormatArgs>::try_new_from(args, &test_service_info(), "TEST")
+            .unwrap()
+            .unwrap();
+        assert_eq!(cli.args.format.as_deref(), Some("csv"));
+    }
+
+    #[test]
+    fn test_check_config_tells_the_failures_apart() {
+        #[derive(Deserialize, Document)]
+        #[allow(dead_code)]
+        struct Settings {
+            port: u16,
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        let run = |contents: Option<&str>, format: &str| {
+            let _ = std::fs::remove_file(&path);
+            if let Some(contents) = contents {
+                std::fs::write(&path, contents).unwrap();
+            }
+            let args = [
+                "test-program",
+                "--config",
+                path.to_str().unwrap(),
+                "--check-config",
+                "--check-format",
+                format,
+            ];
+            Cli::<Settings>::try_new_from(args, &test_service_info(), "BYRE_TEST_CHECK_")
+        };
+        let check = |contents: Option<&str>, format: &str| match run(contents, format) {
+            Err(err @ Error::ConfigCheckFailed { .. }) => {
+                let report = Report::new(err);
+                let Error::ConfigCheckFailed { check } = report.error().downcast_ref().unwrap()
+                else {
+                    unreachable!()
+                };
+                (check.status, report.exit_code(), check.to_string())
+            }
+            _ => panic!("expected the config check to fail"),
+        };
+
+        // A valid config is not an error, the service exits like after `--generate`
+        assert!(matches!(run(Some("port = 8080"), "text"), Ok(None)));
+        let valid =
+            check::ConfigCheck::run::<Settings>(&path, "BYRE_TEST_CHECK_", check::Format::Text);
+        assert_eq!(valid.status, check::Status::Valid);
+        assert!(valid
+            .to_string()
+            .ends_with("config.toml: the configuration is valid"));
+        let (status, code, _) = check(Some("port = "), "text");
+        assert_eq!((status, code), (check::Status::ParseError, 2));
+        let (status, code, _) = check(Some("port = \"eighty\""), "text");
+        assert_eq!((status, code), (check::Status::ValidationError, 3));
+
+        let (status, code, printed) = check(None, "json");
+        assert_eq!((status, code), (check::Status::MissingFile, 4));
+        assert!(
+            printed.starts_with(r#"{"status":"missing_file","exit_code":4,"path":"#),
+            "{printed}"
+        );
+    }
 }
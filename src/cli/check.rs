@@ -0,0 +1,130 @@
+//! `--check-config` loads the config like the service would and exits with a code that tells
+//! the failures apart, so CI can check a config before it is deployed:
+//!
+//! | Exit code | Status             | The config file...                       |
+//! |-----------|--------------------|------------------------------------------|
+//! | `0`       | `valid`            | loads                                    |
+//! | `2`       | `parse_error`      | could not be read or is not valid TOML   |
+//! | `3`       | `validation_error` | has a value that is invalid or missing   |
+//! | `4`       | `missing_file`     | does not exist                           |
+//!
+//! The result is printed as text, or as one JSON object with `--check-format json`:
+//!
+//! ```json
+//! {"status":"validation_error","exit_code":3,"path":"app.toml","message":"Could not load application configuration: ..."}
+//! ```
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+
+/// How `--check-config` prints its result.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Format {
+    /// One line for people.
+    #[default]
+    Text,
+    /// One JSON object for scripts.
+    Json,
+}
+
+/// The result of `--check-config`, which sets the exit code of the process.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum Status {
+    /// The config loads. Exits with `0`.
+    Valid,
+    /// The config file could not be read or parsed. Exits with `2`.
+    ParseError,
+    /// A value of the config is invalid or missing. Exits with `3`.
+    ValidationError,
+    /// The config file does not exist. Exits with `4`.
+    MissingFile,
+}
+
+impl Status {
+    /// The exit code of the process for the status.
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Self::Valid => 0,
+            Self::ParseError => 2,
+            Self::ValidationError => 3,
+            Self::MissingFile => 4,
+        }
+    }
+}
+
+/// What `--check-config` found, printed in its [`Format`].
+#[derive(Clone, Debug, Serialize)]
+pub struct ConfigCheck {
+    /// Whether the config loads, or why it doesn't.
+    pub status: Status,
+    /// The exit code of the status.
+    pub exit_code: u8,
+    /// The config file that was checked.
+    pub path: PathBuf,
+    /// The error of the config, `None` when it is valid.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    /// How the result is printed.
+    #[serde(skip)]
+    pub format: Format,
+}
+
+impl ConfigCheck {
+    /// Load the config at `path` with the environment overrides of `env_prefix`.
+    pub(super) fn run<'a, C>(path: &Path, env_prefix: &str, format: Format) -> Self
+    where
+        C: Deserialize<'a> + doku::Document,
+    {
+        let (status, message) = if !path.is_file() {
+            (
+                Status::MissingFile,
+                Some(format!(
+                    "Could not find the configuration file {}",
+                    path.display()
+                )),
+            )
+        } else {
+            match Config::<C>::new(Some(path), Some(env_prefix)) {
+                Ok(_) => (Status::Valid, None),
+                Err(err @ crate::Error::ConfigParse { .. }) => {
+                    (Status::ParseError, Some(err.to_string()))
+                }
+                Err(err) => (Status::ValidationError, Some(err.to_string())),
+            }
+        };
+        Self {
+            status,
+            exit_code: status.exit_code(),
+            path: path.to_path_buf(),
+            message,
+            format,
+        }
+    }
+
+    /// Whether the result is printed to stdout, failures in text are printed to stderr.
+    pub fn prints_to_stdout(&self) -> bool {
+        self.status == Status::Valid || self.format == Format::Json
+    }
+}
+
+/// The result in its format, ie: `app.toml: the configuration is valid`.
+impl fmt::Display for ConfigCheck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.format, &self.message) {
+            (Format::Json, _) => {
+                let json = serde_json::to_string(self).map_err(|_| fmt::Error)?;
+                f.write_str(&json)
+            }
+            (Format::Text, None) => {
+                write!(f, "{}: the configuration is valid", self.path.display())
+            }
+            (Format::Text, Some(message)) => f.write_str(message),
+        }
+    }
+}
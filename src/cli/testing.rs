@@ -0,0 +1,93 @@
+//! Command line tests without config files on disk, requires the `test-util` feature.
+//!
+//! [`run_with_config`] parses a command line of the service against an inline config:
+//!
+//! ```
+//! # use clap::Parser;
+//! # use serde::{Deserialize, Serialize};
+//! #[derive(Deserialize, doku::Document)]
+//! struct Settings {
+//!     /// Port to listen on
+//!     #[doku(example = "8080")]
+//!     port: u16,
+//! }
+//!
+//! #[derive(Parser, Serialize, Deserialize)]
+//! struct Arguments {
+//!     /// Check the config and exit
+//!     #[arg(long)]
+//!     check: bool,
+//! }
+//!
+//! let cli = byre::cli::testing::run_with_config::<Settings, Arguments>("port = 8080", ["--check"])
+//!     .unwrap();
+//! assert_eq!(cli.config.port, 8080);
+//! assert!(cli.args.check);
+//! ```
+
+use std::ffi::OsString;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use clap::Parser;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use super::{Cli, Error};
+use crate::ServiceInfo;
+
+/// The prefix of the environment variables that override the config, ie: `BYRE_TEST_PORT`.
+pub const ENV_PREFIX: &str = "BYRE_TEST_";
+
+/// Tells apart the config files of tests running in parallel.
+static CONFIG_FILES: AtomicUsize = AtomicUsize::new(0);
+
+/// Parse `args`, the arguments after the program name, with `toml` as the config file.
+///
+/// The config is written to a temporary file that is removed once it is loaded, `--config` is
+/// added to the arguments. Values are overridden by the environment variables that start with
+/// [`ENV_PREFIX`], set them with [`config::testing::sandbox`](crate::config::testing::sandbox).
+///
+/// # Errors
+///
+/// - `ArgParse` if the arguments cannot be parsed.
+/// - `ConfigLoad` if the config cannot be loaded.
+///
+/// # Panics
+///
+/// If the config file cannot be written, or `args` asks for a config file to be generated.
+pub fn run_with_config<C, A>(
+    toml: &str,
+    args: impl IntoIterator<Item = impl Into<OsString>>,
+) -> Result<Cli<C, A>, Error>
+where
+    A: Parser + Serialize + DeserializeOwned,
+    C: DeserializeOwned + doku::Document,
+{
+    let path = std::env::temp_dir().join(format!(
+        "byre-cli-test-{}-{}.toml",
+        std::process::id(),
+        CONFIG_FILES.fetch_add(1, Ordering::Relaxed)
+    ));
+    std::fs::write(&path, toml).expect("could not write the config file of the test");
+
+    let argv = [
+        OsString::from("service"),
+        "--config".into(),
+        path.clone().into(),
+    ]
+    .into_iter()
+    .chain(args.into_iter().map(Into::into));
+    let service_info = ServiceInfo {
+        name: "service",
+        name_in_metrics: "service".to_string(),
+        ..Default::default()
+    };
+    let result = Cli::<C, A>::try_new_from(argv, &service_info, ENV_PREFIX);
+    let _ = std::fs::remove_file(&path);
+
+    match result {
+        Ok(Some(cli)) => Ok(cli),
+        Ok(None) => panic!("run_with_config loads a config, it can't generate one"),
+        Err(err) => Err(err),
+    }
+}
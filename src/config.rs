@@ -3,13 +3,22 @@
 //! This module provides functionality for:
 //!
 //! - Loading configuration from TOML files
-//! - Generating sample configuration files with documentation
+//! - Generating sample configuration files with documentation, also as Kubernetes ConfigMaps
 //! - Overriding configuration values with environment variables
 //! - Expanding environment variable references in config values (`${VAR}` syntax)
+//! - Expanding a leading `~` in paths to the user's home directory, ie: `~/.local/share/app`,
+//!   see [`ExpandedPath`]
+//! - Pointing at the line of the config file, or the environment variable, that sets a value
+//!   that can't be loaded
+//! - Finding the deprecated keys the configuration still sets, see [`Config::deprecations`]
+//! - Listen addresses given as `"host:port"` or as `host` and `port` fields, see [`ListenAddr`]
+//! - Timestamps and times of day as config values, with the `chrono` or `time` feature, see
+//!   `datetime`
 //!
 //! The implementation uses [figment](https://docs.rs/figment) for configuration loading and
 //! [doku](https://docs.rs/doku) for generating documented sample configuration files.
 
+use std::fmt;
 use std::path::{Path, PathBuf};
 
 use figment::{
@@ -54,6 +63,43 @@ pub fn expand_env_var(value: &str) -> String {
     }
 }
 
+/// Expand a leading `~` to the home directory of the user, for paths like `~/.local/share/app`.
+///
+/// Only `~` alone and `~/...` are expanded, `~user/...` and values that don't start with `~` are
+/// returned as-is. The home directory is the `HOME` environment variable, or `USERPROFILE` on
+/// Windows, the value is returned unchanged when it is not set.
+///
+/// # Examples
+///
+/// ```
+/// use byre::config::expand_home_dir;
+///
+/// let home = std::env::var("HOME").unwrap();
+/// assert_eq!(expand_home_dir("~/.local/share/app"), format!("{home}/.local/share/app"));
+/// assert_eq!(expand_home_dir("~"), home);
+/// assert_eq!(expand_home_dir("/var/lib/app"), "/var/lib/app");
+/// assert_eq!(expand_home_dir("~other/app"), "~other/app");
+/// ```
+pub fn expand_home_dir(value: &str) -> String {
+    let Some(rest) = value.strip_prefix('~') else {
+        return value.to_string();
+    };
+    if !(rest.is_empty() || rest.starts_with('/') || (cfg!(windows) && rest.starts_with('\\'))) {
+        return value.to_string();
+    }
+    let home = std::env::var_os("HOME").or_else(|| {
+        if cfg!(windows) {
+            std::env::var_os("USERPROFILE")
+        } else {
+            None
+        }
+    });
+    match home {
+        Some(home) => format!("{}{rest}", home.to_string_lossy()),
+        None => value.to_string(),
+    }
+}
+
 /// Recursively expand environment variable references in a configuration value.
 fn expand_value(value: Value) -> Value {
     match value {
@@ -86,7 +132,7 @@ struct EnvExpander {
 
 impl EnvExpander {
     /// Create a new EnvExpander from a Figment's merged data.
-    fn from_figment(figment: &Figment) -> Result<Self, figment::Error> {
+    fn from_figment(figment: &Figment) -> Result<Self, Box<figment::Error>> {
         let data = figment.data()?;
         let expanded_data = data
             .into_iter()
@@ -100,7 +146,8 @@ impl EnvExpander {
 
 impl Provider for EnvExpander {
     fn metadata(&self) -> Metadata {
-        Metadata::named("env-expander")
+        // Named in the errors of the values, which come from the file or the environment
+        Metadata::named("configuration")
     }
 
     fn data(&self) -> Result<Map<Profile, Dict>, figment::Error> {
@@ -138,6 +185,63 @@ where
     Ok(())
 }
 
+/// Generates a Kubernetes ConfigMap at the specified path, holding the documented configuration
+/// file of [`create_config_file`] under the `config.toml` key.
+///
+/// The ConfigMap is named `name`, and is in `namespace` when one is given, ie:
+///
+/// ```yaml
+/// apiVersion: v1
+/// kind: ConfigMap
+/// metadata:
+///   name: "my-service"
+///   namespace: "prod"
+/// data:
+///   config.toml: |
+///     [telemetry]
+///     ...
+/// ```
+///
+/// # Errors
+/// - `ConfigFileWrite` if the ConfigMap cannot be written.
+pub fn create_configmap_file<C>(
+    path: impl Into<PathBuf>,
+    name: &str,
+    namespace: Option<&str>,
+) -> Result<(), Error>
+where
+    C: doku::Document,
+{
+    let path = path.into();
+    let contents = configmap(&doku::to_toml::<C>(), name, namespace);
+    std::fs::write(&path, contents).with_context(|_| ConfigFileWriteSnafu { path })?;
+    Ok(())
+}
+
+/// The YAML of a ConfigMap named `name` that holds `toml` under the `config.toml` key.
+fn configmap(toml: &str, name: &str, namespace: Option<&str>) -> String {
+    // JSON strings are YAML strings, and serializing a string can't fail
+    let quote = |value: &str| serde_json::to_string(value).unwrap_or_default();
+
+    let mut yaml = format!(
+        "apiVersion: v1\nkind: ConfigMap\nmetadata:\n  name: {}\n",
+        quote(name)
+    );
+    if let Some(namespace) = namespace {
+        yaml.push_str(&format!("  namespace: {}\n", quote(namespace)));
+    }
+    yaml.push_str("data:\n  config.toml: |\n");
+    for line in toml.lines() {
+        // Blank lines don't need the indentation of the block
+        if !line.trim().is_empty() {
+            yaml.push_str("    ");
+            yaml.push_str(line.trim_end());
+        }
+        yaml.push('\n');
+    }
+    yaml
+}
+
 /// Container for loaded and merged configuration.
 ///
 /// This struct loads configuration from multiple sources and makes it available
@@ -156,6 +260,34 @@ pub struct Config<C> {
     /// This contains the final configuration after applying all defaults,
     /// file-based configuration values, and environment variable overrides.
     pub config: C,
+
+    path: Option<PathBuf>,
+    fingerprint: String,
+    deprecations: Vec<Deprecation>,
+}
+
+impl<C> Config<C> {
+    /// The path of the configuration file, `None` if no file was loaded.
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// A hex digest of the merged configuration values, after environment variable overrides and
+    /// expansion.
+    ///
+    /// Instances running with the same configuration report the same fingerprint, so configuration
+    /// drift shows up without logging the configuration itself. The digest is not cryptographic.
+    pub fn fingerprint(&self) -> &str {
+        &self.fingerprint
+    }
+
+    /// The deprecated keys the configuration sets, which are still read for now.
+    ///
+    /// Logging is usually not set up while the configuration loads, log them once it is with
+    /// [`Deprecation::warn`].
+    pub fn deprecations(&self) -> &[Deprecation] {
+        &self.deprecations
+    }
 }
 
 impl<'a, C> Config<C>
@@ -181,45 +313,348 @@ where
     /// * `E` - Type that can be converted to a string for the environment prefix
     ///
     /// # Errors
-    /// - `ConfigLoad` if the config file cannot be loaded or parsed.
+    /// - `ConfigParse` if the config file cannot be read or parsed.
+    /// - `ConfigLoad` if the values of the config cannot be loaded.
     pub fn new<P, E>(config_path: Option<P>, env_prefix: Option<E>) -> Result<Self, Error>
     where
         P: AsRef<Path>,
         E: AsRef<str>,
     {
+        let env_prefix = env_prefix.as_ref().map(AsRef::as_ref);
+
         // Load information from the command line
         let f = Figment::new();
 
         // from the config file
-        let f = match config_path {
+        let path = config_path.map(|config_file| config_file.as_ref().to_path_buf());
+        let f = match &path {
             Some(config_file) => f.merge(Toml::file(config_file)),
             None => f,
         };
 
         // and from the environment
         let f = match env_prefix {
-            Some(env_prefix) => {
-                let env_prefix = env_prefix.as_ref();
-                f.merge(Env::prefixed(env_prefix).split("__"))
-            }
+            Some(env_prefix) => f.merge(Env::prefixed(env_prefix).split("__")),
             None => f,
         };
 
         // Expand environment variable references in string values (${VAR} and $VAR syntax)
+        // Syntax errors already show the line of the file
         let expander =
-            EnvExpander::from_figment(&f).map_err(|err| super::Error::ConfigLoad {
-                source: Box::new(err),
-            })?;
+            EnvExpander::from_figment(&f).map_err(|source| super::Error::ConfigParse { source })?;
+        let fingerprint = fingerprint(&expander.data);
+        let deprecations = crate::telemetry::DEPRECATED_KEYS
+            .iter()
+            .filter(|(key, _)| is_set(&expander.data, key))
+            .map(|&(key, replacement)| Deprecation {
+                key,
+                replacement,
+                location: locate_key(path.as_deref(), env_prefix, key),
+            })
+            .collect();
         let f = Figment::from(expander);
 
-        let config = f.extract().map_err(|err| super::Error::ConfigLoad {
-            source: Box::new(err),
+        let config = f.extract().map_err(|err| {
+            let location = locate(path.as_deref(), env_prefix, &err.path).map(Box::new);
+            super::Error::ConfigLoad {
+                source: Box::new(err),
+                location,
+            }
         })?;
 
-        Ok(Self { config })
+        Ok(Self {
+            config,
+            path,
+            fingerprint,
+            deprecations,
+        })
     }
 }
 
+/// Call `on_change` on the current tokio runtime every time the modification time of `path`
+/// changes, checking every `interval`.
+///
+/// A file that is removed is not a change, `on_change` is called once it is written again.
+#[cfg(feature = "hot-reload")]
+pub(crate) fn spawn_file_watch(
+    path: PathBuf,
+    interval: std::time::Duration,
+    mut on_change: impl FnMut() + Send + 'static,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let modified = || std::fs::metadata(&path).and_then(|m| m.modified()).ok();
+        let mut last = modified();
+        let mut ticks = tokio::time::interval(interval);
+        ticks.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            ticks.tick().await;
+            let current = modified();
+            if current.is_some() && current != last {
+                on_change();
+            }
+            last = current;
+        }
+    })
+}
+
+/// Where a value of the configuration is set, shown below the errors about the value.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Location {
+    /// A value of the configuration file.
+    File {
+        /// The configuration file.
+        path: PathBuf,
+        /// The line of the value, starting at 1.
+        line: usize,
+        /// The column of the value, in characters, starting at 1.
+        column: usize,
+        /// The text of the line.
+        text: String,
+        /// How many characters of the line the value spans, at least 1.
+        width: usize,
+    },
+    /// An environment variable that overrides the configuration file.
+    Env {
+        /// The name of the variable.
+        name: String,
+    },
+}
+
+/// The location as the Rust compiler shows it, ie:
+///
+/// ```text
+///  --> /etc/my-service.toml:2:8
+///   |
+/// 2 | port = "eighty"
+///   |        ^^^^^^^^
+/// ```
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::File {
+                path,
+                line,
+                column,
+                text,
+                width,
+            } => {
+                let gutter = " ".repeat(line.to_string().len());
+                writeln!(f, "{gutter}--> {}:{line}:{column}", path.display())?;
+                writeln!(f, "{gutter} |")?;
+                writeln!(f, "{line} | {text}")?;
+                write!(
+                    f,
+                    "{gutter} | {}{}",
+                    " ".repeat(column - 1),
+                    "^".repeat(*width)
+                )
+            }
+            Self::Env { name } => write!(f, "  = set by the environment variable {name}"),
+        }
+    }
+}
+
+/// A deprecated key that the configuration sets, see [`Config::deprecations`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Deprecation {
+    /// The deprecated key, ie: `telemetry.logs`.
+    pub key: &'static str,
+    /// The key to use instead, ie: `telemetry.log`.
+    pub replacement: &'static str,
+    /// Where the key is set, `None` if it could not be found.
+    pub location: Option<Location>,
+}
+
+impl Deprecation {
+    /// Log a warning about the key, with where it is set.
+    pub fn warn(&self) {
+        let set_by = match &self.location {
+            Some(Location::File {
+                path, line, column, ..
+            }) => format!("{}:{line}:{column}", path.display()),
+            Some(Location::Env { name }) => name.clone(),
+            None => String::new(),
+        };
+        tracing::warn!(
+            key = self.key,
+            replacement = self.replacement,
+            set_by,
+            "deprecated config key, it will be removed"
+        );
+    }
+}
+
+/// The key, its replacement and where it is set, ie:
+///
+/// ```text
+/// `telemetry.logs` is deprecated, use `telemetry.log` instead
+///  --> /etc/my-service.toml:4:1
+///   |
+/// 4 | [telemetry.logs]
+///   | ^^^^^^^^^^^^^^^^
+/// ```
+impl fmt::Display for Deprecation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "`{}` is deprecated, use `{}` instead",
+            self.key, self.replacement
+        )?;
+        match &self.location {
+            Some(location) => write!(f, "\n{location}"),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Find where the value at `key`, a path of figment, is set: the environment variable that
+/// overrides it, or else its place in the config file.
+///
+/// A key that is not in the file points at the closest table that is, ie: the table a field is
+/// missing from.
+fn locate(path: Option<&Path>, env_prefix: Option<&str>, key: &[String]) -> Option<Location> {
+    if let Some(env_prefix) = env_prefix.filter(|_| !key.is_empty()) {
+        // Env matches the names of the variables without regard to case
+        let wanted = format!("{env_prefix}{}", key.join("__"));
+        let name = std::env::vars_os()
+            .filter_map(|(name, _)| name.into_string().ok())
+            .find(|name| name.eq_ignore_ascii_case(&wanted));
+        if let Some(name) = name {
+            return Some(Location::Env { name });
+        }
+    }
+
+    let path = path?;
+    let contents = std::fs::read_to_string(path).ok()?;
+    let document = toml_edit::ImDocument::parse(contents.as_str()).ok()?;
+
+    let mut table: &dyn toml_edit::TableLike = document.as_table();
+    let mut span = None;
+    for name in key {
+        let Some((name, item)) = table.get_key_value(name) else {
+            break;
+        };
+        span = item.span().or_else(|| name.span()).or(span);
+        match item.as_table_like() {
+            Some(inner) => table = inner,
+            None => break,
+        }
+    }
+    let span = span?;
+
+    let start = contents[..span.start]
+        .rfind('\n')
+        .map_or(0, |newline| newline + 1);
+    let end = contents[start..]
+        .find('\n')
+        .map_or(contents.len(), |newline| start + newline);
+    let text = contents[start..end].trim_end_matches('\r');
+    let column = contents[start..span.start].chars().count() + 1;
+    let width = contents[span.start..span.end.min(start + text.len()).max(span.start)]
+        .chars()
+        .count();
+
+    Some(Location::File {
+        path: path.to_path_buf(),
+        line: contents[..span.start].matches('\n').count() + 1,
+        column,
+        text: text.to_string(),
+        width: width.max(1),
+    })
+}
+
+/// Find where the dotted `key`, or a value under it, is set: the first environment variable that
+/// sets it, or else its place in the config file.
+fn locate_key(path: Option<&Path>, env_prefix: Option<&str>, key: &str) -> Option<Location> {
+    if let Some(env_prefix) = env_prefix {
+        let wanted = format!("{env_prefix}{}", key.replace('.', "__")).to_ascii_uppercase();
+        let name = std::env::vars_os()
+            .filter_map(|(name, _)| name.into_string().ok())
+            .find(|name| {
+                let upper = name.to_ascii_uppercase();
+                upper == wanted
+                    || upper
+                        .strip_prefix(&wanted)
+                        .is_some_and(|rest| rest.starts_with("__"))
+            });
+        if let Some(name) = name {
+            return Some(Location::Env { name });
+        }
+    }
+    let key: Vec<_> = key.split('.').map(str::to_string).collect();
+    locate(path, None, &key)
+}
+
+/// Whether the configuration sets the dotted `key`, from the file or the environment.
+fn is_set(data: &Map<Profile, Dict>, key: &str) -> bool {
+    let (first, rest) = key
+        .split_once('.')
+        .map_or((key, None), |(first, rest)| (first, Some(rest)));
+    data.values()
+        .filter_map(|dict| dict.get(first))
+        .any(|value| rest.is_none_or(|rest| value.find_ref(rest).is_some()))
+}
+
+/// FNV-1a of the configuration values, stable across Rust versions unlike `DefaultHasher`.
+fn fingerprint(data: &Map<Profile, Dict>) -> String {
+    struct Fnv(u64);
+
+    impl Fnv {
+        fn write(&mut self, bytes: &[u8]) {
+            for byte in bytes {
+                self.0 ^= u64::from(*byte);
+                self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+            }
+            // Separates the values, so `ab`, `c` and `a`, `bc` differ
+            self.0 ^= 0xff;
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+
+        fn value(&mut self, value: &Value) {
+            match value {
+                Value::String(_, value) => self.write(format!("s{value}").as_bytes()),
+                Value::Char(_, value) => self.write(format!("c{value}").as_bytes()),
+                Value::Bool(_, value) => self.write(format!("b{value}").as_bytes()),
+                Value::Num(_, value) => self.write(format!("n{value:?}").as_bytes()),
+                Value::Empty(_, value) => self.write(format!("e{value:?}").as_bytes()),
+                Value::Dict(_, dict) => self.dict(dict),
+                Value::Array(_, values) => {
+                    self.write(format!("a{}", values.len()).as_bytes());
+                    values.iter().for_each(|value| self.value(value));
+                }
+            }
+        }
+
+        fn dict(&mut self, dict: &Dict) {
+            self.write(format!("d{}", dict.len()).as_bytes());
+            for (key, value) in dict {
+                self.write(key.as_bytes());
+                self.value(value);
+            }
+        }
+    }
+
+    let mut hasher = Fnv(0xcbf2_9ce4_8422_2325);
+    for (profile, dict) in data {
+        hasher.write(profile.as_str().as_str().as_bytes());
+        hasher.dict(dict);
+    }
+    format!("{:016x}", hasher.0)
+}
+
+#[cfg(any(feature = "chrono", feature = "time"))]
+pub mod datetime;
+mod expanded_path;
+mod listen_addr;
+
+pub use expanded_path::ExpandedPath;
+pub use listen_addr::ListenAddr;
+
+#[cfg(any(test, feature = "test-util"))]
+pub mod testing;
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -236,41 +671,33 @@ mod tests {
     #[test]
     fn expand_env_var_with_braces() {
         // Set a test env var
-        // SAFETY: Test runs in a single thread, no concurrent env access
-        unsafe {
-            std::env::set_var("BYRE_TEST_EXPAND_VAR_BRACES", "expanded-value");
-        }
+        testing::sandbox(|sandbox| {
+            sandbox.set_env("BYRE_TEST_EXPAND_VAR_BRACES", "expanded-value");
 
-        // ${VAR} syntax should expand
-        assert_eq!(
-            expand_env_var("${BYRE_TEST_EXPAND_VAR_BRACES}"),
-            "expanded-value"
-        );
+            // ${VAR} syntax should expand
+            assert_eq!(
+                expand_env_var("${BYRE_TEST_EXPAND_VAR_BRACES}"),
+                "expanded-value"
+            );
 
-        // SAFETY: Test runs in a single thread, no concurrent env access
-        unsafe {
-            std::env::remove_var("BYRE_TEST_EXPAND_VAR_BRACES");
-        }
+            Ok(())
+        });
     }
 
     #[test]
     fn expand_env_var_without_braces() {
         // Set a test env var
-        // SAFETY: Test runs in a single thread, no concurrent env access
-        unsafe {
-            std::env::set_var("BYRE_TEST_EXPAND_VAR_NO_BRACES", "expanded-value");
-        }
+        testing::sandbox(|sandbox| {
+            sandbox.set_env("BYRE_TEST_EXPAND_VAR_NO_BRACES", "expanded-value");
 
-        // $VAR syntax should expand
-        assert_eq!(
-            expand_env_var("$BYRE_TEST_EXPAND_VAR_NO_BRACES"),
-            "expanded-value"
-        );
+            // $VAR syntax should expand
+            assert_eq!(
+                expand_env_var("$BYRE_TEST_EXPAND_VAR_NO_BRACES"),
+                "expanded-value"
+            );
 
-        // SAFETY: Test runs in a single thread, no concurrent env access
-        unsafe {
-            std::env::remove_var("BYRE_TEST_EXPAND_VAR_NO_BRACES");
-        }
+            Ok(())
+        });
     }
 
     #[test]
@@ -285,22 +712,18 @@ mod tests {
 
     #[test]
     fn expand_value_handles_strings() {
-        // SAFETY: Test runs in a single thread, no concurrent env access
-        unsafe {
-            std::env::set_var("BYRE_TEST_VALUE_STRING", "test-value");
-        }
+        testing::sandbox(|sandbox| {
+            sandbox.set_env("BYRE_TEST_VALUE_STRING", "test-value");
 
-        let value = Value::String(Default::default(), "${BYRE_TEST_VALUE_STRING}".to_string());
-        let expanded = expand_value(value);
-        match expanded {
-            Value::String(_, s) => assert_eq!(s, "test-value"),
-            _ => panic!("Expected String value"),
-        }
+            let value = Value::String(Default::default(), "${BYRE_TEST_VALUE_STRING}".to_string());
+            let expanded = expand_value(value);
+            match expanded {
+                Value::String(_, s) => assert_eq!(s, "test-value"),
+                _ => panic!("Expected String value"),
+            }
 
-        // SAFETY: Test runs in a single thread, no concurrent env access
-        unsafe {
-            std::env::remove_var("BYRE_TEST_VALUE_STRING");
-        }
+            Ok(())
+        });
     }
 
     #[test]
@@ -317,134 +740,326 @@ mod tests {
 
     #[test]
     fn expand_value_handles_arrays() {
-        // SAFETY: Test runs in a single thread, no concurrent env access
-        unsafe {
-            std::env::set_var("BYRE_TEST_ARRAY_VAR", "array-value");
-        }
+        testing::sandbox(|sandbox| {
+            sandbox.set_env("BYRE_TEST_ARRAY_VAR", "array-value");
 
-        let arr = Value::Array(
-            Default::default(),
-            vec![
-                Value::String(Default::default(), "${BYRE_TEST_ARRAY_VAR}".to_string()),
-                Value::String(Default::default(), "literal".to_string()),
-            ],
-        );
-        let expanded = expand_value(arr);
-        match expanded {
-            Value::Array(_, items) => {
-                assert_eq!(items.len(), 2);
-                match &items[0] {
-                    Value::String(_, s) => assert_eq!(s, "array-value"),
-                    _ => panic!("Expected String value"),
-                }
-                match &items[1] {
-                    Value::String(_, s) => assert_eq!(s, "literal"),
-                    _ => panic!("Expected String value"),
+            let arr = Value::Array(
+                Default::default(),
+                vec![
+                    Value::String(Default::default(), "${BYRE_TEST_ARRAY_VAR}".to_string()),
+                    Value::String(Default::default(), "literal".to_string()),
+                ],
+            );
+            let expanded = expand_value(arr);
+            match expanded {
+                Value::Array(_, items) => {
+                    assert_eq!(items.len(), 2);
+                    match &items[0] {
+                        Value::String(_, s) => assert_eq!(s, "array-value"),
+                        _ => panic!("Expected String value"),
+                    }
+                    match &items[1] {
+                        Value::String(_, s) => assert_eq!(s, "literal"),
+                        _ => panic!("Expected String value"),
+                    }
                 }
+                _ => panic!("Expected Array value"),
             }
-            _ => panic!("Expected Array value"),
-        }
 
-        // SAFETY: Test runs in a single thread, no concurrent env access
-        unsafe {
-            std::env::remove_var("BYRE_TEST_ARRAY_VAR");
-        }
+            Ok(())
+        });
     }
 
     #[test]
     fn expand_dict_handles_nested_values() {
-        // SAFETY: Test runs in a single thread, no concurrent env access
-        unsafe {
-            std::env::set_var("BYRE_TEST_DICT_VAR", "dict-value");
-        }
+        testing::sandbox(|sandbox| {
+            sandbox.set_env("BYRE_TEST_DICT_VAR", "dict-value");
 
-        let mut dict = Dict::new();
-        dict.insert(
-            "key1".to_string(),
-            Value::String(Default::default(), "${BYRE_TEST_DICT_VAR}".to_string()),
-        );
-        dict.insert(
-            "key2".to_string(),
-            Value::String(Default::default(), "literal".to_string()),
-        );
+            let mut dict = Dict::new();
+            dict.insert(
+                "key1".to_string(),
+                Value::String(Default::default(), "${BYRE_TEST_DICT_VAR}".to_string()),
+            );
+            dict.insert(
+                "key2".to_string(),
+                Value::String(Default::default(), "literal".to_string()),
+            );
 
-        let expanded = expand_dict(dict);
-        match expanded.get("key1") {
-            Some(Value::String(_, s)) => assert_eq!(s, "dict-value"),
-            _ => panic!("Expected String value for key1"),
-        }
-        match expanded.get("key2") {
-            Some(Value::String(_, s)) => assert_eq!(s, "literal"),
-            _ => panic!("Expected String value for key2"),
-        }
+            let expanded = expand_dict(dict);
+            match expanded.get("key1") {
+                Some(Value::String(_, s)) => assert_eq!(s, "dict-value"),
+                _ => panic!("Expected String value for key1"),
+            }
+            match expanded.get("key2") {
+                Some(Value::String(_, s)) => assert_eq!(s, "literal"),
+                _ => panic!("Expected String value for key2"),
+            }
 
-        // SAFETY: Test runs in a single thread, no concurrent env access
-        unsafe {
-            std::env::remove_var("BYRE_TEST_DICT_VAR");
-        }
+            Ok(())
+        });
     }
 
     #[test]
     fn expand_dict_handles_nested_dicts() {
-        // SAFETY: Test runs in a single thread, no concurrent env access
-        unsafe {
-            std::env::set_var("BYRE_TEST_NESTED_VAR", "nested-value");
+        testing::sandbox(|sandbox| {
+            sandbox.set_env("BYRE_TEST_NESTED_VAR", "nested-value");
+
+            let mut inner_dict = Dict::new();
+            inner_dict.insert(
+                "nested_key".to_string(),
+                Value::String(Default::default(), "${BYRE_TEST_NESTED_VAR}".to_string()),
+            );
+
+            let mut outer_dict = Dict::new();
+            outer_dict.insert(
+                "outer".to_string(),
+                Value::Dict(Default::default(), inner_dict),
+            );
+
+            let expanded = expand_dict(outer_dict);
+            match expanded.get("outer") {
+                Some(Value::Dict(_, inner)) => match inner.get("nested_key") {
+                    Some(Value::String(_, s)) => assert_eq!(s, "nested-value"),
+                    _ => panic!("Expected String value for nested_key"),
+                },
+                _ => panic!("Expected Dict value for outer"),
+            }
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn home_dir_is_expanded_in_config_paths() {
+        #[derive(Deserialize, doku::Document)]
+        struct Settings {
+            data_dir: ExpandedPath,
+            cache_dir: ExpandedPath,
+            pattern: ExpandedPath,
+            password: String,
         }
 
-        let mut inner_dict = Dict::new();
-        inner_dict.insert(
-            "nested_key".to_string(),
-            Value::String(Default::default(), "${BYRE_TEST_NESTED_VAR}".to_string()),
-        );
+        testing::sandbox(|sandbox| {
+            sandbox.set_env("HOME", "/home/byre");
+            sandbox.set_env("BYRE_TEST_HOME_CACHE", "~/.cache/app");
+            let config = sandbox.load::<Settings>(
+                r#"
+                data_dir = "~/.local/share/app"
+                cache_dir = "${BYRE_TEST_HOME_CACHE}"
+                pattern = "~user"
+                password = "~secret"
+                "#,
+                "BYRE_TEST_TILDE_",
+            )?;
 
-        let mut outer_dict = Dict::new();
-        outer_dict.insert(
-            "outer".to_string(),
-            Value::Dict(Default::default(), inner_dict),
-        );
+            assert_eq!(
+                config.config.data_dir.as_path(),
+                Path::new("/home/byre/.local/share/app")
+            );
+            // Expanded once the variable is
+            assert_eq!(
+                config.config.cache_dir.as_path(),
+                Path::new("/home/byre/.cache/app")
+            );
+            assert_eq!(config.config.pattern.as_path(), Path::new("~user"));
+            // Only paths are expanded
+            assert_eq!(config.config.password, "~secret");
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn env_expander_creates_from_figment() {
+        testing::sandbox(|sandbox| {
+            sandbox.set_env("BYRE_TEST_FIGMENT_VAR", "figment-value");
+
+            // Create a minimal figment with raw data
+            let figment = Figment::new().merge(("node_id", "${BYRE_TEST_FIGMENT_VAR}"));
+
+            let expander = EnvExpander::from_figment(&figment).unwrap();
+            let data = expander.data().unwrap();
+
+            // Check that the value was expanded - find any profile that has the data
+            let mut found = false;
+            for (_profile, profile_data) in data.iter() {
+                if let Some(Value::String(_, s)) = profile_data.get("node_id") {
+                    assert_eq!(s, "figment-value");
+                    found = true;
+                    break;
+                }
+            }
+            assert!(found, "Expected to find node_id in some profile");
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn fingerprint_follows_the_values() {
+        let data = |port: i64, host: &str| {
+            let figment = Figment::new()
+                .merge(("server.port", port))
+                .merge(("server.host", host));
+            figment.data().unwrap()
+        };
+
+        let fingerprint_a = fingerprint(&data(8080, "localhost"));
+        assert_eq!(fingerprint_a.len(), 16);
+        assert_eq!(fingerprint_a, fingerprint(&data(8080, "localhost")));
+        assert_ne!(fingerprint_a, fingerprint(&data(8081, "localhost")));
+        assert_ne!(fingerprint_a, fingerprint(&data(8080, "127.0.0.1")));
+    }
 
-        let expanded = expand_dict(outer_dict);
-        match expanded.get("outer") {
-            Some(Value::Dict(_, inner)) => match inner.get("nested_key") {
-                Some(Value::String(_, s)) => assert_eq!(s, "nested-value"),
-                _ => panic!("Expected String value for nested_key"),
-            },
-            _ => panic!("Expected Dict value for outer"),
+    #[test]
+    fn generated_config_files_load() {
+        #[derive(Deserialize, doku::Document)]
+        struct Settings {
+            telemetry: crate::telemetry::TelemetrySettings,
+            crash: crate::crash::CrashSettings,
         }
 
-        // SAFETY: Test runs in a single thread, no concurrent env access
-        unsafe {
-            std::env::remove_var("BYRE_TEST_NESTED_VAR");
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        create_config_file::<Settings>(&path).unwrap();
+
+        // The examples of the enum settings are values, not the list of their variants
+        let config = Config::<Settings>::new(Some(&path), None::<&str>).unwrap();
+        assert_eq!(
+            config.config.telemetry.environment,
+            Some(crate::Environment::Prod)
+        );
+        assert_eq!(
+            config.config.crash.backtrace,
+            Some(crate::crash::BacktraceMode::Full)
+        );
+    }
+
+    #[test]
+    fn config_reports_its_path_and_fingerprint() {
+        #[derive(Deserialize, doku::Document)]
+        struct Settings {
+            #[doku(example = "8080")]
+            port: u16,
         }
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(&path, "port = 8080\n").unwrap();
+
+        let config = Config::<Settings>::new(Some(&path), None::<&str>).unwrap();
+        assert_eq!(config.config.port, 8080);
+        assert_eq!(config.path(), Some(path.as_path()));
+
+        // The same values give the same fingerprint, whatever the file looks like
+        std::fs::write(&path, "# comment\nport   = 8080\n").unwrap();
+        let again = Config::<Settings>::new(Some(&path), None::<&str>).unwrap();
+        assert_eq!(config.fingerprint(), again.fingerprint());
     }
 
     #[test]
-    fn env_expander_creates_from_figment() {
-        // SAFETY: Test runs in a single thread, no concurrent env access
-        unsafe {
-            std::env::set_var("BYRE_TEST_FIGMENT_VAR", "figment-value");
+    fn config_errors_point_at_the_value() {
+        #[derive(Debug, Deserialize, doku::Document)]
+        #[allow(dead_code)]
+        struct Settings {
+            server: Server,
+        }
+        #[derive(Debug, Deserialize, doku::Document)]
+        #[allow(dead_code)]
+        struct Server {
+            port: u16,
+            host: String,
         }
 
-        // Create a minimal figment with raw data
-        let figment = Figment::new().merge(("node_id", "${BYRE_TEST_FIGMENT_VAR}"));
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        let load = |contents: &str, env_prefix: Option<&str>| {
+            std::fs::write(&path, contents).unwrap();
+            match Config::<Settings>::new(Some(&path), env_prefix) {
+                Err(Error::ConfigLoad { location, .. }) => location.map(|location| *location),
+                _ => panic!("expected the config not to load"),
+            }
+        };
 
-        let expander = EnvExpander::from_figment(&figment).unwrap();
-        let data = expander.data().unwrap();
+        let location = load("[server]\nport = \"eighty\"\nhost = \"x\"\n", None).unwrap();
+        assert_eq!(
+            location.to_string(),
+            format!(
+                " --> {}:2:8\n  |\n2 | port = \"eighty\"\n  |        ^^^^^^^^",
+                path.display()
+            )
+        );
 
-        // Check that the value was expanded - find any profile that has the data
-        let mut found = false;
-        for (_profile, profile_data) in data.iter() {
-            if let Some(Value::String(_, s)) = profile_data.get("node_id") {
-                assert_eq!(s, "figment-value");
-                found = true;
-                break;
+        // A missing field points at its table
+        let location = load("# Settings\n[server]\nport = 8080\n", None).unwrap();
+        assert!(matches!(
+            location,
+            Location::File {
+                line: 2,
+                column: 1,
+                ..
             }
-        }
-        assert!(found, "Expected to find node_id in some profile");
+        ));
+
+        testing::sandbox(|sandbox| {
+            sandbox.set_env("BYRE_TEST_LOCATE_SERVER__PORT", "eighty");
+            let location = load(
+                "[server]\nport = 8080\nhost = \"x\"\n",
+                Some("BYRE_TEST_LOCATE_"),
+            );
+            assert_eq!(
+                location,
+                Some(Location::Env {
+                    name: "BYRE_TEST_LOCATE_SERVER__PORT".to_string()
+                })
+            );
+            Ok(())
+        });
+    }
 
-        // SAFETY: Test runs in a single thread, no concurrent env access
-        unsafe {
-            std::env::remove_var("BYRE_TEST_FIGMENT_VAR");
+    #[test]
+    fn config_reports_the_deprecated_keys_it_sets() {
+        #[derive(Deserialize, doku::Document)]
+        struct Settings {
+            telemetry: crate::telemetry::TelemetrySettings,
         }
+
+        testing::sandbox(|sandbox| {
+            sandbox.set_env(
+                "BYRE_TEST_DEPRECATED_TELEMETRY__METRICS__RUNTIME_METRICS",
+                true,
+            );
+            let config = sandbox.load::<Settings>(
+                r#"
+                [telemetry.trace]
+
+                [telemetry.logs]
+                console_level = "debug"
+                "#,
+                "BYRE_TEST_DEPRECATED_",
+            )?;
+
+            // The deprecated keys are still read
+            assert_eq!(config.config.telemetry.log.console_level, "debug");
+            assert!(config.config.telemetry.metric.runtime_metrics);
+
+            let deprecations = config.deprecations();
+            assert_eq!(deprecations.len(), 2);
+            assert_eq!(
+                deprecations[0].to_string(),
+                format!(
+                    "`telemetry.logs` is deprecated, use `telemetry.log` instead\n \
+                     --> {}:3:1\n  |\n3 | [telemetry.logs]\n  | ^^^^^^^^^^^^^^^^",
+                    sandbox.directory().join("config.toml").display()
+                )
+            );
+            assert_eq!(deprecations[1].key, "telemetry.metrics");
+            assert_eq!(
+                deprecations[1].location,
+                Some(Location::Env {
+                    name: "BYRE_TEST_DEPRECATED_TELEMETRY__METRICS__RUNTIME_METRICS".to_string()
+                })
+            );
+            Ok(())
+        });
     }
 }
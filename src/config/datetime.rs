@@ -0,0 +1,121 @@
+//! # Timestamps and Times of Day
+//!
+//! Settings types for schedules, so every service parses and documents them the same way:
+//!
+//! - `Timestamp`, an RFC 3339 timestamp, ie: `2026-01-01T09:00:00Z`
+//! - `LocalTime`, a time of day without a time zone, `HH:MM` or `HH:MM:SS`, ie: `02:30`
+//!
+//! They are in [`chrono`], with the `chrono` feature, and in [`time`], with the `time`
+//! feature, wrapping the types of the crate of the same name.
+//!
+//! ```toml
+//! [backup]
+//! # Time of day the backups start at
+//! start = "02:30"
+//! # Backups made before are removed
+//! keep_since = "2026-01-01T00:00:00Z"
+//! ```
+
+use snafu::Snafu;
+
+/// Serializes `$ty` with its `Display`, deserializes it with its `FromStr`, and documents it as a
+/// string with `$example`.
+macro_rules! string_setting {
+    ($ty:ty, $example:ident) => {
+        impl serde::Serialize for $ty {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.collect_str(self)
+            }
+        }
+
+        impl<'de> serde::Deserialize<'de> for $ty {
+            fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                let value = <String as serde::Deserialize>::deserialize(deserializer)?;
+                value.parse().map_err(serde::de::Error::custom)
+            }
+        }
+
+        impl doku::Document for $ty {
+            fn ty() -> doku::Type {
+                $crate::config::datetime::string_type($example)
+            }
+        }
+    };
+}
+
+#[cfg(feature = "chrono")]
+pub mod chrono;
+#[cfg(feature = "time")]
+pub mod time;
+
+/// The example of the timestamp settings in generated config files.
+const TIMESTAMP_EXAMPLE: &str = "2026-01-01T09:00:00Z";
+
+/// The example of the time of day settings in generated config files.
+const LOCAL_TIME_EXAMPLE: &str = "02:30";
+
+/// A value that is not a timestamp or a time of day.
+#[derive(Debug, Snafu)]
+#[snafu(display("Invalid {expected} {value:?}, expected ie: {example}"))]
+pub struct ParseError {
+    /// The value that was parsed.
+    value: String,
+    /// What the value should be.
+    expected: &'static str,
+    /// A valid value.
+    example: &'static str,
+}
+
+impl ParseError {
+    fn timestamp(value: &str) -> Self {
+        Self {
+            value: value.to_string(),
+            expected: "RFC 3339 timestamp",
+            example: TIMESTAMP_EXAMPLE,
+        }
+    }
+
+    fn local_time(value: &str) -> Self {
+        Self {
+            value: value.to_string(),
+            expected: "time of day",
+            example: LOCAL_TIME_EXAMPLE,
+        }
+    }
+}
+
+/// The hour, minute and second of `HH:MM` or `HH:MM:SS`, not checked against their ranges.
+fn parse_local_time(value: &str) -> Option<(u8, u8, u8)> {
+    let mut parts = value.split(':').map(|part| {
+        (part.len() == 2 && part.bytes().all(|b| b.is_ascii_digit()))
+            .then(|| part.parse().ok())
+            .flatten()
+    });
+    let hour = parts.next()??;
+    let minute = parts.next()??;
+    let second = parts.next().unwrap_or(Some(0))?;
+    parts.next().is_none().then_some((hour, minute, second))
+}
+
+/// Documented as a string, with `example` unless the field has its own.
+fn string_type(example: &'static str) -> doku::Type {
+    doku::Type {
+        example: Some(doku::Example::Simple(example)),
+        ..doku::TypeKind::String.into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_local_time() {
+        assert_eq!(parse_local_time("02:30"), Some((2, 30, 0)));
+        assert_eq!(parse_local_time("23:59:58"), Some((23, 59, 58)));
+        assert_eq!(parse_local_time("2:30"), None);
+        assert_eq!(parse_local_time("02:30:00:00"), None);
+        assert_eq!(parse_local_time("02"), None);
+        assert_eq!(parse_local_time("+2:30"), None);
+    }
+}
@@ -0,0 +1,96 @@
+//! Timestamps and times of day backed by [chrono](https://docs.rs/chrono), requires the
+//! `chrono` feature.
+//!
+//! ```
+//! use byre::config::datetime::chrono::{LocalTime, Timestamp};
+//!
+//! #[derive(serde::Deserialize, doku::Document)]
+//! struct Backup {
+//!     /// Time of day the backups start at
+//!     start: LocalTime,
+//!     /// Backups made before are removed
+//!     keep_since: Timestamp,
+//! }
+//!
+//! let backup: Backup = toml::from_str(
+//!     r#"
+//!     start = "02:30"
+//!     keep_since = "2026-01-01T00:00:00+01:00"
+//!     "#,
+//! )
+//! .unwrap();
+//! assert_eq!(backup.start.0, chrono::NaiveTime::from_hms_opt(2, 30, 0).unwrap());
+//! assert_eq!(backup.keep_since.to_string(), "2026-01-01T00:00:00+01:00");
+//! ```
+
+use std::fmt;
+use std::str::FromStr;
+
+use super::{parse_local_time, ParseError, LOCAL_TIME_EXAMPLE, TIMESTAMP_EXAMPLE};
+use chrono::{DateTime, FixedOffset, NaiveTime, SecondsFormat};
+
+/// An RFC 3339 timestamp, with the offset it was written with, ie: `2026-01-01T09:00:00Z`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Timestamp(pub DateTime<FixedOffset>);
+
+impl FromStr for Timestamp {
+    type Err = ParseError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        DateTime::parse_from_rfc3339(value)
+            .map(Self)
+            .map_err(|_| ParseError::timestamp(value))
+    }
+}
+
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0.to_rfc3339_opts(SecondsFormat::AutoSi, true))
+    }
+}
+
+/// A time of day without a time zone, `HH:MM` or `HH:MM:SS`, ie: `02:30`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct LocalTime(pub NaiveTime);
+
+impl FromStr for LocalTime {
+    type Err = ParseError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        parse_local_time(value)
+            .and_then(|(hour, minute, second)| {
+                NaiveTime::from_hms_opt(hour.into(), minute.into(), second.into())
+            })
+            .map(Self)
+            .ok_or_else(|| ParseError::local_time(value))
+    }
+}
+
+impl fmt::Display for LocalTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0.format("%H:%M:%S"))
+    }
+}
+
+string_setting!(Timestamp, TIMESTAMP_EXAMPLE);
+string_setting!(LocalTime, LOCAL_TIME_EXAMPLE);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_values_parse_and_print_back() {
+        let timestamp: Timestamp = "2026-10-17T09:04:05.250-07:00".parse().unwrap();
+        assert_eq!(timestamp.to_string(), "2026-10-17T09:04:05.250-07:00");
+        assert!("2026-10-17 09:04:05".parse::<Timestamp>().is_err());
+
+        let time: LocalTime = "23:59".parse().unwrap();
+        assert_eq!(time.to_string(), "23:59:00");
+        let err = "24:00".parse::<LocalTime>().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Invalid time of day \"24:00\", expected ie: 02:30"
+        );
+    }
+}
@@ -0,0 +1,98 @@
+//! Timestamps and times of day backed by [time](https://docs.rs/time), requires the `time`
+//! feature.
+//!
+//! ```
+//! use byre::config::datetime::time::{LocalTime, Timestamp};
+//!
+//! #[derive(serde::Deserialize, doku::Document)]
+//! struct Backup {
+//!     /// Time of day the backups start at
+//!     start: LocalTime,
+//!     /// Backups made before are removed
+//!     keep_since: Timestamp,
+//! }
+//!
+//! let backup: Backup = toml::from_str(
+//!     r#"
+//!     start = "02:30"
+//!     keep_since = "2026-01-01T00:00:00+01:00"
+//!     "#,
+//! )
+//! .unwrap();
+//! assert_eq!(backup.start.0, time::Time::from_hms(2, 30, 0).unwrap());
+//! assert_eq!(backup.keep_since.to_string(), "2026-01-01T00:00:00+01:00");
+//! ```
+
+use std::fmt;
+use std::str::FromStr;
+
+use ::time::format_description::well_known::Rfc3339;
+use ::time::{OffsetDateTime, Time};
+
+use super::{parse_local_time, ParseError, LOCAL_TIME_EXAMPLE, TIMESTAMP_EXAMPLE};
+
+/// An RFC 3339 timestamp, with the offset it was written with, ie: `2026-01-01T09:00:00Z`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Timestamp(pub OffsetDateTime);
+
+impl FromStr for Timestamp {
+    type Err = ParseError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        OffsetDateTime::parse(value, &Rfc3339)
+            .map(Self)
+            .map_err(|_| ParseError::timestamp(value))
+    }
+}
+
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let formatted = self.0.format(&Rfc3339).map_err(|_| fmt::Error)?;
+        f.write_str(&formatted)
+    }
+}
+
+/// A time of day without a time zone, `HH:MM` or `HH:MM:SS`, ie: `02:30`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct LocalTime(pub Time);
+
+impl FromStr for LocalTime {
+    type Err = ParseError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        parse_local_time(value)
+            .and_then(|(hour, minute, second)| Time::from_hms(hour, minute, second).ok())
+            .map(Self)
+            .ok_or_else(|| ParseError::local_time(value))
+    }
+}
+
+impl fmt::Display for LocalTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (hour, minute, second) = self.0.as_hms();
+        write!(f, "{hour:02}:{minute:02}:{second:02}")
+    }
+}
+
+string_setting!(Timestamp, TIMESTAMP_EXAMPLE);
+string_setting!(LocalTime, LOCAL_TIME_EXAMPLE);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_values_parse_and_print_back() {
+        let timestamp: Timestamp = "2026-10-17T09:04:05.25-07:00".parse().unwrap();
+        assert_eq!(timestamp.to_string(), "2026-10-17T09:04:05.25-07:00");
+        assert!("2026-10-17 09:04:05".parse::<Timestamp>().is_err());
+
+        let time: LocalTime = "23:59".parse().unwrap();
+        assert_eq!(time.to_string(), "23:59:00");
+        let err = "24:00".parse::<LocalTime>().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Invalid time of day \"24:00\", expected ie: 02:30"
+        );
+    }
+}
@@ -0,0 +1,93 @@
+//! A path in the config, with a leading `~` expanded to the home directory.
+
+use std::fmt;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+
+use doku::Document;
+use serde::{Deserialize, Serialize};
+
+use super::expand_home_dir;
+
+/// A path whose leading `~` is expanded to the home directory when it is deserialized, see
+/// [`expand_home_dir`].
+///
+/// Only paths are expanded, a `String` setting starting with `~`, ie: a password, is left as it
+/// is. Environment variable references are expanded first, so `"${DATA_DIR}"` may also be
+/// `~/...`.
+///
+/// ```
+/// use byre::config::ExpandedPath;
+///
+/// #[derive(serde::Deserialize)]
+/// struct Settings {
+///     data_dir: ExpandedPath,
+/// }
+///
+/// let home = std::env::var("HOME").unwrap();
+/// let settings: Settings = toml::from_str(r#"data_dir = "~/.local/share/app""#).unwrap();
+/// assert_eq!(settings.data_dir.to_str().unwrap(), format!("{home}/.local/share/app"));
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(from = "PathBuf", into = "PathBuf")]
+pub struct ExpandedPath(PathBuf);
+
+impl ExpandedPath {
+    /// The path.
+    pub fn as_path(&self) -> &Path {
+        &self.0
+    }
+
+    /// The path, as an owned `PathBuf`.
+    pub fn into_path_buf(self) -> PathBuf {
+        self.0
+    }
+}
+
+/// Expands a leading `~`, paths that are not UTF-8 are kept as they are.
+impl From<PathBuf> for ExpandedPath {
+    fn from(path: PathBuf) -> Self {
+        match path.to_str() {
+            Some(value) => Self(expand_home_dir(value).into()),
+            None => Self(path),
+        }
+    }
+}
+
+impl From<&str> for ExpandedPath {
+    fn from(path: &str) -> Self {
+        PathBuf::from(path).into()
+    }
+}
+
+impl From<ExpandedPath> for PathBuf {
+    fn from(path: ExpandedPath) -> Self {
+        path.0
+    }
+}
+
+impl Deref for ExpandedPath {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl AsRef<Path> for ExpandedPath {
+    fn as_ref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl fmt::Display for ExpandedPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.display().fmt(f)
+    }
+}
+
+impl Document for ExpandedPath {
+    fn ty() -> doku::Type {
+        PathBuf::ty()
+    }
+}
@@ -0,0 +1,221 @@
+//! The address a service listens on, as `"host:port"` or as `host` and `port` fields.
+
+use std::fmt;
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::str::FromStr;
+
+use doku::Document;
+use serde::{Deserialize, Serialize};
+use snafu::{ensure, OptionExt as _, ResultExt as _};
+
+use crate::{Error, InvalidListenAddrSnafu, NoListenAddrSnafu, ResolveListenAddrSnafu};
+
+/// The example of the listen addresses in generated config files.
+const EXAMPLE: &str = "0.0.0.0:8080";
+
+/// An address to listen on, the host is a hostname or an IP address.
+///
+/// In the config it is either `"host:port"`, with IPv6 addresses in brackets, or a table with
+/// `host` and `port` fields:
+///
+/// ```toml
+/// listen = "localhost:8080"
+/// admin_listen = { host = "::1", port = 9000 }
+/// ```
+///
+/// ```
+/// use byre::config::ListenAddr;
+///
+/// let listen: ListenAddr = "127.0.0.1:8080".parse().unwrap();
+/// assert_eq!(listen.resolve().unwrap(), vec![([127, 0, 0, 1], 8080).into()]);
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Deserialize)]
+#[serde(try_from = "Repr")]
+pub struct ListenAddr {
+    /// The hostname or IP address, without brackets.
+    pub host: String,
+    /// The port.
+    pub port: u16,
+}
+
+/// The forms of a listen address in the config.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Repr {
+    Address(String),
+    Fields { host: String, port: u16 },
+}
+
+impl TryFrom<Repr> for ListenAddr {
+    type Error = Error;
+
+    fn try_from(repr: Repr) -> Result<Self, Self::Error> {
+        match repr {
+            Repr::Address(address) => address.parse(),
+            Repr::Fields { host, port } => Ok(Self::new(host, port)),
+        }
+    }
+}
+
+impl ListenAddr {
+    /// The address of `port` on `host`.
+    pub fn new(host: impl Into<String>, port: u16) -> Self {
+        Self {
+            host: host.into(),
+            port,
+        }
+    }
+
+    /// The socket addresses of the host, resolving it when it is a hostname.
+    ///
+    /// # Errors
+    ///
+    /// - `ResolveListenAddr` if the hostname cannot be resolved.
+    /// - `NoListenAddr` if the hostname does not resolve to any address.
+    pub fn resolve(&self) -> Result<Vec<SocketAddr>, Error> {
+        let addrs: Vec<_> = (self.host.as_str(), self.port)
+            .to_socket_addrs()
+            .with_context(|_| ResolveListenAddrSnafu {
+                address: self.to_string(),
+            })?
+            .collect();
+        ensure!(
+            !addrs.is_empty(),
+            NoListenAddrSnafu {
+                address: self.to_string()
+            }
+        );
+        Ok(addrs)
+    }
+}
+
+/// Resolves the host, so the address can be given to `std::net::TcpListener::bind`.
+impl ToSocketAddrs for ListenAddr {
+    type Iter = std::vec::IntoIter<SocketAddr>;
+
+    fn to_socket_addrs(&self) -> std::io::Result<Self::Iter> {
+        (self.host.as_str(), self.port).to_socket_addrs()
+    }
+}
+
+impl FromStr for ListenAddr {
+    type Err = Error;
+
+    fn from_str(address: &str) -> Result<Self, Self::Err> {
+        let (host, port) = address
+            .rsplit_once(':')
+            .context(InvalidListenAddrSnafu { address })?;
+        let host = match host.strip_prefix('[') {
+            Some(bracketed) => bracketed
+                .strip_suffix(']')
+                .context(InvalidListenAddrSnafu { address })?,
+            // Unbracketed IPv6 addresses are ambiguous, ie: `::1:80`
+            None if host.contains(':') => return InvalidListenAddrSnafu { address }.fail(),
+            None => host,
+        };
+        let port = port
+            .parse()
+            .ok()
+            .context(InvalidListenAddrSnafu { address })?;
+        ensure!(!host.is_empty(), InvalidListenAddrSnafu { address });
+        Ok(Self::new(host, port))
+    }
+}
+
+/// The address as `host:port`, ie: `[::1]:8080`.
+impl fmt::Display for ListenAddr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.host.contains(':') {
+            write!(f, "[{}]:{}", self.host, self.port)
+        } else {
+            write!(f, "{}:{}", self.host, self.port)
+        }
+    }
+}
+
+impl Serialize for ListenAddr {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl Document for ListenAddr {
+    fn ty() -> doku::Type {
+        doku::Type {
+            example: Some(doku::Example::Simple(EXAMPLE)),
+            ..doku::TypeKind::String.into()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_addresses_parse_and_print_back() {
+        for (address, host, port) in [
+            ("localhost:8080", "localhost", 8080),
+            ("0.0.0.0:80", "0.0.0.0", 80),
+            ("[::1]:9000", "::1", 9000),
+        ] {
+            let listen: ListenAddr = address.parse().unwrap();
+            assert_eq!(listen, ListenAddr::new(host, port));
+            assert_eq!(listen.to_string(), address);
+        }
+        for invalid in [
+            "localhost",
+            ":8080",
+            "::1:8080",
+            "[::1:8080",
+            "localhost:http",
+        ] {
+            assert!(
+                matches!(
+                    invalid.parse::<ListenAddr>(),
+                    Err(Error::InvalidListenAddr { .. })
+                ),
+                "{invalid}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_both_forms_load_from_the_config() {
+        #[derive(Deserialize, Document)]
+        struct Settings {
+            listen: ListenAddr,
+            admin_listen: ListenAddr,
+        }
+
+        crate::config::testing::sandbox(|sandbox| {
+            sandbox.set_env("BYRE_TEST_LISTEN_ADMIN_LISTEN__PORT", 9001);
+            let config = sandbox.load::<Settings>(
+                r#"
+                listen = "127.0.0.1:8080"
+                admin_listen = { host = "::1", port = 9000 }
+                "#,
+                "BYRE_TEST_LISTEN_",
+            )?;
+            assert_eq!(
+                config.config.listen.resolve().unwrap(),
+                vec![SocketAddr::from(([127, 0, 0, 1], 8080))]
+            );
+            assert_eq!(config.config.admin_listen.to_string(), "[::1]:9001");
+            Ok(())
+        });
+
+        crate::config::testing::sandbox(|sandbox| {
+            let Err(err) = sandbox.load::<Settings>(
+                "listen = \"localhost\"\nadmin_listen = \"[::1]:9000\"\n",
+                "BYRE_TEST_LISTEN_",
+            ) else {
+                panic!("an address without a port loaded");
+            };
+            assert!(err
+                .to_string()
+                .contains("Invalid listen address \"localhost\""));
+            Ok(())
+        });
+    }
+}
@@ -0,0 +1,147 @@
+//! Sandboxed config loading for tests, requires the `test-util` feature.
+//!
+//! [`sandbox`] runs a test in a temporary directory with environment variables that are
+//! restored once it returns, so tests of environment overrides don't have to set and remove
+//! the variables of the process themselves. It wraps [`figment::Jail`].
+//!
+//! ```
+//! #[derive(serde::Deserialize, doku::Document)]
+//! struct Settings {
+//!     /// Port to listen on
+//!     #[doku(example = "8080")]
+//!     port: u16,
+//! }
+//!
+//! byre::config::testing::sandbox(|sandbox| {
+//!     sandbox.set_env("MYAPP_PORT", 9090);
+//!
+//!     let config = sandbox.load::<Settings>(
+//!         r#"
+//!         port = 8080
+//!         "#,
+//!         "MYAPP_",
+//!     )?;
+//!
+//!     assert_eq!(config.config.port, 9090);
+//!     Ok(())
+//! });
+//! ```
+
+use std::error::Error as StdError;
+use std::path::{Path, PathBuf};
+
+use figment::Jail;
+use serde::Deserialize;
+
+use super::Config;
+
+/// The file [`Sandbox::load`] writes the config to.
+const CONFIG_FILE: &str = "config.toml";
+
+/// Run `test` in a [`Sandbox`], panicking if it returns an error.
+///
+/// Sandboxes run one at a time, the working directory and the environment belong to the
+/// process. The working directory is the sandbox's directory while `test` runs, tests that
+/// don't use a sandbox should not depend on it.
+// The closure returns the error type `Jail` asks for
+#[allow(clippy::result_large_err)]
+pub fn sandbox<F>(test: F)
+where
+    F: FnOnce(&mut Sandbox<'_>) -> Result<(), Box<dyn StdError>>,
+{
+    Jail::expect_with(|jail| {
+        test(&mut Sandbox { jail }).map_err(|err| figment::Error::from(err.to_string()))
+    });
+}
+
+/// A temporary directory and scoped environment variables, see [`sandbox`].
+pub struct Sandbox<'a> {
+    jail: &'a mut Jail,
+}
+
+impl Sandbox<'_> {
+    /// The temporary directory, removed once the sandbox is done.
+    pub fn directory(&self) -> &Path {
+        self.jail.directory()
+    }
+
+    /// Set an environment variable until the sandbox is done.
+    pub fn set_env(&mut self, name: impl AsRef<str>, value: impl std::fmt::Display) {
+        self.jail.set_env(name, value);
+    }
+
+    /// Remove every environment variable until the sandbox is done.
+    pub fn clear_env(&mut self) {
+        self.jail.clear_env();
+    }
+
+    /// Write `toml` to the file `name` of the directory, returns the path of the file.
+    ///
+    /// The indentation common to the lines of `toml`, and its leading blank lines, are removed
+    /// so fixtures can be written inline, indented with the test.
+    ///
+    /// # Errors
+    ///
+    /// The IO error if the file cannot be written.
+    pub fn write_config(&self, name: impl AsRef<Path>, toml: &str) -> std::io::Result<PathBuf> {
+        let path = self.directory().join(name);
+        std::fs::write(&path, dedent(toml))?;
+        Ok(path)
+    }
+
+    /// Write `toml` to `config.toml` and load it with the environment overrides of
+    /// `env_prefix`, like the service would.
+    ///
+    /// # Errors
+    ///
+    /// - `ConfigFileWrite` if the file cannot be written.
+    /// - `ConfigParse` if the config cannot be parsed.
+    /// - `ConfigLoad` if the values of the config cannot be loaded.
+    pub fn load<C>(&self, toml: &str, env_prefix: &str) -> Result<Config<C>, crate::Error>
+    where
+        C: for<'de> Deserialize<'de> + doku::Document,
+    {
+        let path = self.write_config(CONFIG_FILE, toml).map_err(|source| {
+            crate::Error::ConfigFileWrite {
+                path: self.directory().join(CONFIG_FILE),
+                source,
+            }
+        })?;
+        Config::new(Some(path), Some(env_prefix))
+    }
+}
+
+/// `text` without its leading blank lines and the indentation common to its lines.
+fn dedent(text: &str) -> String {
+    let text = text.trim_start_matches(['\n', '\r']);
+    let indent = text
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.len() - line.trim_start().len())
+        .min()
+        .unwrap_or(0);
+
+    text.lines()
+        .map(|line| line.get(indent..).unwrap_or_else(|| line.trim_start()))
+        .fold(String::new(), |mut dedented, line| {
+            dedented.push_str(line);
+            dedented.push('\n');
+            dedented
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dedent_inline_fixtures() {
+        let toml = "
+            [server]
+            port = 8080
+
+              # nested
+            ";
+        assert_eq!(dedent(toml), "[server]\nport = 8080\n\n  # nested\n\n");
+    }
+}
@@ -0,0 +1,216 @@
+//! # Container Limits
+//!
+//! Detects the CPU and memory limits that the container runtime sets through cgroups, v2 or v1,
+//! so a service sized for its host doesn't start more workers or grow larger caches than its
+//! container allows.
+//!
+//! ```rust
+//! let limits = byre::container::ContainerLimits::detect();
+//! if let Some(cpus) = limits.cpus {
+//!     println!("limited to {cpus} CPUs, run {} workers", limits.worker_threads().unwrap());
+//! }
+//! ```
+//!
+//! [`App`](crate::App) logs the limits once telemetry is initialized, and
+//! [`telemetry::init`](crate::telemetry::init) publishes them as the `container.cpu.limit` and
+//! `container.memory.limit` gauges. Setting
+//! [`RuntimeSettings::worker_threads_from_cpu_quota`](crate::runtime::RuntimeSettings::worker_threads_from_cpu_quota)
+//! sizes the runtime from the CPU quota.
+//!
+//! Only Linux has cgroups, no limit is detected on other platforms.
+
+use std::path::Path;
+
+/// The resource limits of the container the process runs in, `None` when unlimited.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ContainerLimits {
+    /// CPU time the process may use per unit of wall time, ie: `1.5` for one and a half CPUs.
+    pub cpus: Option<f64>,
+    /// Memory the process may use, in bytes.
+    pub memory_bytes: Option<u64>,
+}
+
+impl ContainerLimits {
+    /// Read the limits of the cgroup of the process.
+    ///
+    /// The limits that can't be read, ie: outside of a container, are `None`.
+    pub fn detect() -> Self {
+        detect_in(Path::new(CGROUP_ROOT), Path::new("/proc/self/cgroup"))
+    }
+
+    /// Whether any limit was detected.
+    pub fn is_limited(&self) -> bool {
+        self.cpus.is_some() || self.memory_bytes.is_some()
+    }
+
+    /// Number of worker threads that use the CPU quota without exceeding it by more than one,
+    /// `None` without a quota.
+    pub fn worker_threads(&self) -> Option<usize> {
+        self.cpus.map(|cpus| (cpus.ceil() as usize).max(1))
+    }
+
+    /// Log the limits as an `INFO` event when any is set.
+    pub fn log(&self) {
+        if self.is_limited() {
+            tracing::info!(
+                cpus = self.cpus,
+                memory_bytes = self.memory_bytes,
+                "container resource limits detected"
+            );
+        }
+    }
+}
+
+/// Where the cgroup filesystem is mounted.
+const CGROUP_ROOT: &str = "/sys/fs/cgroup";
+
+/// Read the limits below `root`, for the cgroups listed in `proc_cgroup`.
+fn detect_in(root: &Path, proc_cgroup: &Path) -> ContainerLimits {
+    let read = |path: &Path| std::fs::read_to_string(path).ok();
+    let groups = read(proc_cgroup).unwrap_or_default();
+
+    // With a cgroup namespace the process sees its own cgroup at the root, without one the
+    // root is usually mounted from it
+    let dirs = |controller: &str| {
+        let mut dirs = Vec::new();
+        if let Some(path) = cgroup_path(&groups, controller) {
+            let path = path.trim_start_matches('/');
+            let base = if controller.is_empty() {
+                root.to_path_buf()
+            } else {
+                root.join(controller)
+            };
+            if !path.is_empty() {
+                dirs.push(base.join(path));
+            }
+            dirs.push(base);
+        }
+        dirs
+    };
+
+    // cgroup v2 has a single hierarchy, listed with an empty controller
+    let v2 = dirs("");
+    let cpus = v2
+        .iter()
+        .find_map(|dir| read(&dir.join("cpu.max")))
+        .map(|max| parse_cpu_max(&max))
+        .or_else(|| {
+            dirs("cpu").iter().find_map(|dir| {
+                let quota = read(&dir.join("cpu.cfs_quota_us"))?;
+                let period = read(&dir.join("cpu.cfs_period_us"))?;
+                Some(parse_cfs_quota(&quota, &period))
+            })
+        })
+        .flatten();
+    let memory_bytes = v2
+        .iter()
+        .find_map(|dir| read(&dir.join("memory.max")))
+        .map(|max| parse_memory_max(&max))
+        .or_else(|| {
+            dirs("memory").iter().find_map(|dir| {
+                read(&dir.join("memory.limit_in_bytes")).map(|limit| parse_memory_max(&limit))
+            })
+        })
+        .flatten();
+
+    ContainerLimits { cpus, memory_bytes }
+}
+
+/// The path of the cgroup of `controller` in `/proc/self/cgroup`, the empty controller is the
+/// cgroup v2 hierarchy.
+fn cgroup_path<'a>(groups: &'a str, controller: &str) -> Option<&'a str> {
+    groups.lines().find_map(|line| {
+        let mut fields = line.splitn(3, ':');
+        let (_, controllers, path) = (fields.next()?, fields.next()?, fields.next()?);
+        let matches = if controller.is_empty() {
+            controllers.is_empty()
+        } else {
+            controllers.split(',').any(|name| name == controller)
+        };
+        matches.then_some(path)
+    })
+}
+
+/// Parse the cgroup v2 `cpu.max`, `$MAX $PERIOD` with `max` for no limit.
+fn parse_cpu_max(max: &str) -> Option<f64> {
+    let mut fields = max.split_whitespace();
+    let quota = fields.next()?.parse::<f64>().ok()?;
+    let period = fields.next().unwrap_or("100000").parse::<f64>().ok()?;
+    (quota > 0.0 && period > 0.0).then(|| quota / period)
+}
+
+/// Parse the cgroup v1 `cpu.cfs_quota_us` and `cpu.cfs_period_us`, a quota of `-1` is no limit.
+fn parse_cfs_quota(quota: &str, period: &str) -> Option<f64> {
+    let quota = quota.trim().parse::<f64>().ok()?;
+    let period = period.trim().parse::<f64>().ok()?;
+    (quota > 0.0 && period > 0.0).then(|| quota / period)
+}
+
+/// Parse the cgroup v2 `memory.max` or v1 `memory.limit_in_bytes`.
+///
+/// v2 writes `max` for no limit, v1 a number close to `i64::MAX` rounded to the page size.
+fn parse_memory_max(max: &str) -> Option<u64> {
+    const UNLIMITED: u64 = 1 << 62;
+    max.trim()
+        .parse::<u64>()
+        .ok()
+        .filter(|&bytes| bytes < UNLIMITED)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cgroup_limits_are_parsed() {
+        assert_eq!(parse_cpu_max("150000 100000\n"), Some(1.5));
+        assert_eq!(parse_cpu_max("max 100000\n"), None);
+        assert_eq!(parse_cfs_quota("50000\n", "100000\n"), Some(0.5));
+        assert_eq!(parse_cfs_quota("-1\n", "100000\n"), None);
+        assert_eq!(parse_memory_max("536870912\n"), Some(536_870_912));
+        assert_eq!(parse_memory_max("max\n"), None);
+        assert_eq!(parse_memory_max("9223372036854771712\n"), None);
+    }
+
+    #[test]
+    fn test_worker_threads_round_the_quota_up() {
+        let limits = |cpus| ContainerLimits {
+            cpus,
+            memory_bytes: None,
+        };
+        assert_eq!(limits(Some(1.5)).worker_threads(), Some(2));
+        assert_eq!(limits(Some(0.25)).worker_threads(), Some(1));
+        assert_eq!(limits(None).worker_threads(), None);
+    }
+
+    #[test]
+    fn test_limits_are_read_from_the_cgroup_of_the_process() {
+        let dir = tempfile::tempdir().unwrap();
+        let proc_cgroup = dir.path().join("cgroup");
+
+        // cgroup v2, without a cgroup namespace
+        let group = dir.path().join("v2/system.slice/app.scope");
+        std::fs::create_dir_all(&group).unwrap();
+        std::fs::write(group.join("cpu.max"), "200000 100000\n").unwrap();
+        std::fs::write(group.join("memory.max"), "1073741824\n").unwrap();
+        std::fs::write(&proc_cgroup, "0::/system.slice/app.scope\n").unwrap();
+        let limits = detect_in(&dir.path().join("v2"), &proc_cgroup);
+        assert_eq!(limits.cpus, Some(2.0));
+        assert_eq!(limits.memory_bytes, Some(1_073_741_824));
+
+        // cgroup v1, with a cgroup namespace
+        let v1 = dir.path().join("v1");
+        std::fs::create_dir_all(v1.join("cpu")).unwrap();
+        std::fs::create_dir_all(v1.join("memory")).unwrap();
+        std::fs::write(v1.join("cpu/cpu.cfs_quota_us"), "-1\n").unwrap();
+        std::fs::write(v1.join("cpu/cpu.cfs_period_us"), "100000\n").unwrap();
+        std::fs::write(v1.join("memory/memory.limit_in_bytes"), "268435456\n").unwrap();
+        std::fs::write(&proc_cgroup, "5:cpu,cpuacct:/\n3:memory:/\n").unwrap();
+        let limits = detect_in(&v1, &proc_cgroup);
+        assert_eq!(limits.cpus, None);
+        assert_eq!(limits.memory_bytes, Some(268_435_456));
+        assert!(limits.is_limited());
+
+        assert!(!detect_in(&dir.path().join("missing"), &proc_cgroup).is_limited());
+    }
+}
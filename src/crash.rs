@@ -0,0 +1,284 @@
+//! # Crash Reports
+//!
+//! Controls the backtraces printed when the service panics, and writes a crash report file so a
+//! crash can be investigated after the process is gone, even when its logs were not collected:
+//!
+//! ```toml
+//! [crash]
+//! # Optional, `off`, `short` or `full`. Defaults to the RUST_BACKTRACE environment variable
+//! backtrace = "full"
+//! # Optional, omit to not write crash reports
+//! report_dir = "/var/crash/my-service"
+//! ```
+//!
+//! A report is a text file named `<service>-<unix time>-<pid>.crash`, with the panic message,
+//! its location and backtrace, the service's build info, and the path and fingerprint of the
+//! loaded config. Reports are only written for panics, a process killed by a signal does not
+//! get one. With `panic = "abort"` the report is written before the process aborts.
+//!
+//! [`App`](crate::App) applies the settings before it starts the runtime when the settings
+//! provide [`AppSettings::crash`](crate::app::AppSettings::crash). Services that don't use it
+//! apply them themselves, before any thread is spawned:
+//!
+//! ```rust,no_run
+//! # use doku::Document;
+//! # use serde::Deserialize;
+//! # #[derive(Deserialize, Document)]
+//! # struct Settings {
+//! #     crash: byre::crash::CrashSettings,
+//! # }
+//! # fn demo() -> Result<(), Box<dyn std::error::Error>> {
+//! let service_info = byre::service_info!();
+//! let Some(cli) = byre::cli::Cli::<Settings>::try_new(&service_info, "MYAPP_")? else {
+//!     return Ok(());
+//! };
+//!
+//! if let Some(mode) = cli.config.crash.backtrace {
+//!     // SAFETY: No other thread has been spawned yet
+//!     unsafe { byre::crash::set_backtrace(mode) };
+//! }
+//! if let Some(dir) = &cli.config.crash.report_dir {
+//!     byre::crash::CrashReporter::new(&service_info, dir)
+//!         .with_config(&cli.config_path, &cli.config_fingerprint)
+//!         .install();
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Backtraces of `ERROR` events are configured with the telemetry settings, see
+//! [`LogSettings::error_backtraces`](crate::telemetry::LogSettings::error_backtraces).
+
+use std::any::Any;
+use std::backtrace::Backtrace;
+use std::fmt::Write as _;
+use std::panic::PanicHookInfo;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use doku::Document;
+use serde::{Deserialize, Serialize};
+
+use crate::config::ExpandedPath;
+use crate::{Environment, ServiceInfo};
+
+/// Settings for backtraces and crash reports.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, Document)]
+pub struct CrashSettings {
+    /// Backtraces printed when the service panics: `off`, `short` or `full`. Sets `RUST_BACKTRACE` at startup.
+    /// Omit to use the `RUST_BACKTRACE` environment variable.
+    #[doku(as = "Option<String>", example = "full")]
+    #[serde(default)]
+    pub backtrace: Option<BacktraceMode>,
+
+    /// Directory to write a crash report to when the service panics. Omit to not write crash reports.
+    #[doku(example = "/var/crash/my-service")]
+    #[serde(default)]
+    pub report_dir: Option<ExpandedPath>,
+}
+
+/// The backtraces printed on panics, the values of `RUST_BACKTRACE`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, Document)]
+#[serde(rename_all = "lowercase")]
+pub enum BacktraceMode {
+    /// No backtrace.
+    Off,
+    /// A backtrace without the frames of the standard library and the panic machinery.
+    Short,
+    /// Every frame of the backtrace.
+    Full,
+}
+
+impl BacktraceMode {
+    /// The value of `RUST_BACKTRACE` for the mode.
+    pub fn as_env_value(&self) -> &'static str {
+        match self {
+            Self::Off => "0",
+            Self::Short => "1",
+            Self::Full => "full",
+        }
+    }
+}
+
+/// Set `RUST_BACKTRACE`, overriding the environment of the process.
+///
+/// The standard library reads the variable once, on the first panic.
+///
+/// # Safety
+///
+/// No other thread may read or write the environment at the same time, see
+/// [`std::env::set_var`]. Call it at the start of `main`, before any thread is spawned.
+pub unsafe fn set_backtrace(mode: BacktraceMode) {
+    // SAFETY: The caller guarantees that no other thread accesses the environment
+    unsafe { std::env::set_var("RUST_BACKTRACE", mode.as_env_value()) };
+}
+
+/// Writes a crash report file when the service panics, see the [module documentation](self).
+#[derive(Clone, Debug)]
+#[must_use = "a CrashReporter does nothing until it is installed"]
+pub struct CrashReporter {
+    dir: PathBuf,
+    service_name: &'static str,
+    service_version: &'static str,
+    git_sha: &'static str,
+    git_dirty: bool,
+    build_timestamp: &'static str,
+    rustc_version: &'static str,
+    environment: Environment,
+    config_path: Option<PathBuf>,
+    config_fingerprint: Option<String>,
+}
+
+impl CrashReporter {
+    /// A reporter for the service described by `service_info`, writing to `dir`.
+    pub fn new(service_info: &ServiceInfo, dir: impl AsRef<Path>) -> Self {
+        Self {
+            dir: dir.as_ref().to_path_buf(),
+            service_name: service_info.name,
+            service_version: service_info.version,
+            git_sha: service_info.git_sha,
+            git_dirty: service_info.git_dirty,
+            build_timestamp: service_info.build_timestamp,
+            rustc_version: service_info.rustc_version,
+            environment: service_info.environment.unwrap_or_default(),
+            config_path: None,
+            config_fingerprint: None,
+        }
+    }
+
+    /// Include the configuration file and the fingerprint of the loaded configuration.
+    pub fn with_config(mut self, path: impl AsRef<Path>, fingerprint: impl Into<String>) -> Self {
+        self.config_path = Some(path.as_ref().to_path_buf());
+        self.config_fingerprint = Some(fingerprint.into());
+        self
+    }
+
+    /// Install a panic hook that writes the report, then calls the previously installed hook.
+    ///
+    /// Failing to write the report is printed to stderr, the panic is still reported by the
+    /// previous hook.
+    pub fn install(self) {
+        let previous = std::panic::take_hook();
+
+        std::panic::set_hook(Box::new(move |info: &PanicHookInfo<'_>| {
+            let location = info.location().map(ToString::to_string).unwrap_or_default();
+            if let Err(err) = self.write(&location, panic_message(info.payload())) {
+                eprintln!(
+                    "Could not write the crash report to {}: {err}",
+                    self.dir.display()
+                );
+            }
+
+            previous(info);
+        }));
+    }
+
+    /// Write the report of a panic of the current thread, returns the path of the report.
+    fn write(&self, location: &str, message: &str) -> std::io::Result<PathBuf> {
+        let path = self.dir.join(format!(
+            "{}-{}-{}.crash",
+            self.service_name,
+            unix_time(),
+            std::process::id()
+        ));
+        let report = self.report(location, message, &Backtrace::force_capture());
+        std::fs::create_dir_all(&self.dir)?;
+        std::fs::write(&path, report)?;
+        Ok(path)
+    }
+
+    /// The contents of the report of a panic of the current thread.
+    fn report(&self, location: &str, message: &str, backtrace: &Backtrace) -> String {
+        let thread = std::thread::current();
+        let config_path = self
+            .config_path
+            .as_ref()
+            .map(|path| path.display().to_string());
+
+        let mut report = String::new();
+        let fields = [
+            ("service.name", self.service_name),
+            ("service.version", self.service_version),
+            ("git_sha", self.git_sha),
+            ("git_dirty", if self.git_dirty { "true" } else { "false" }),
+            ("build_timestamp", self.build_timestamp),
+            ("rustc_version", self.rustc_version),
+            ("environment", self.environment.as_str()),
+            ("config.path", config_path.as_deref().unwrap_or_default()),
+            (
+                "config.fingerprint",
+                self.config_fingerprint.as_deref().unwrap_or_default(),
+            ),
+            ("thread", thread.name().unwrap_or("<unnamed>")),
+            ("location", location),
+            ("message", message),
+        ];
+        for (name, value) in fields {
+            let _ = writeln!(report, "{name}: {value}");
+        }
+        let _ = write!(report, "backtrace:\n{backtrace}");
+        report
+    }
+}
+
+/// Seconds since the unix epoch, the reports of a service sort by the time they were written.
+fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default()
+}
+
+/// The message passed to `panic!`, panics with other payloads don't have one.
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message
+    } else {
+        "Box<dyn Any>"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backtrace_modes_are_rust_backtrace_values() {
+        assert_eq!(BacktraceMode::Off.as_env_value(), "0");
+        assert_eq!(BacktraceMode::Short.as_env_value(), "1");
+        assert_eq!(BacktraceMode::Full.as_env_value(), "full");
+
+        let settings: CrashSettings = toml::from_str("backtrace = \"full\"").unwrap();
+        assert_eq!(settings.backtrace, Some(BacktraceMode::Full));
+        assert_eq!(settings.report_dir, None);
+    }
+
+    #[test]
+    fn test_crash_report_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let service_info = ServiceInfo {
+            name: "inventory",
+            version: "1.2.3",
+            git_sha: "abc123",
+            ..Default::default()
+        };
+        let reporter = CrashReporter::new(&service_info, dir.path().join("crashes"))
+            .with_config("/etc/inventory.toml", "0123456789abcdef");
+
+        let path = reporter.write("src/main.rs:3:5", "out of widgets").unwrap();
+
+        let name = path.file_name().unwrap().to_str().unwrap();
+        assert!(name.starts_with("inventory-") && name.ends_with(".crash"));
+        let report = std::fs::read_to_string(path).unwrap();
+        assert!(report.contains("service.name: inventory\n"));
+        assert!(report.contains("service.version: 1.2.3\n"));
+        assert!(report.contains("git_sha: abc123\n"));
+        assert!(report.contains("config.path: /etc/inventory.toml\n"));
+        assert!(report.contains("config.fingerprint: 0123456789abcdef\n"));
+        assert!(report.contains("message: out of widgets\n"));
+        assert!(report.contains("location: src/main.rs:3:5\n"));
+        assert!(report.contains("backtrace:\n"));
+    }
+}
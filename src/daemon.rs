@@ -0,0 +1,372 @@
+//! # Daemonization
+//!
+//! Detaches the service from the terminal that started it, for traditional deployments without
+//! systemd or a container runtime. Daemonizing is opt-in:
+//!
+//! ```toml
+//! [daemon]
+//! detach = true
+//! # Optional, stdout and stderr go to /dev/null when omitted
+//! log_file = "/var/log/my-service.log"
+//! # Optional, written and locked so a second instance can't start
+//! pid_file = "/run/my-service.pid"
+//! ```
+//!
+//! The process forks twice and starts a new session, so it is not the leader of one and can't
+//! acquire a controlling terminal again. Stdin is read from `/dev/null`, stdout and stderr are
+//! appended to the log file, and the working directory becomes `/`. Paths are opened before
+//! that, they can be relative to the directory the service was started from. The pid file is
+//! locked before forking, so a second instance fails in the foreground, and the pid of the
+//! daemon is written once it is forked.
+//!
+//! Forking only keeps the calling thread, [`App`](crate::App) daemonizes before it starts the
+//! runtime when the settings provide [`AppSettings::daemon`](crate::app::AppSettings::daemon).
+//!
+//! ```rust,no_run
+//! # fn demo() -> Result<(), Box<dyn std::error::Error>> {
+//! let settings = byre::daemon::DaemonSettings {
+//!     detach: true,
+//!     log_file: Some("my-service.log".into()),
+//!     pid_file: Some("my-service.pid".into()),
+//! };
+//! // SAFETY: No other thread was started yet
+//! let _daemon = unsafe { byre::daemon::daemonize(&settings)? };
+//! // ... start the runtime and serve, the pid file is removed when `_daemon` is dropped ...
+//! # Ok(())
+//! # }
+//! ```
+
+use std::path::{Path, PathBuf};
+
+use doku::Document;
+use serde::{Deserialize, Serialize};
+use snafu::{ResultExt as _, Snafu};
+
+use crate::config::ExpandedPath;
+
+/// Errors daemonizing the process.
+#[derive(Debug, Snafu)]
+pub enum Error {
+    /// The process could not be forked.
+    #[snafu(display("Could not fork the process: {source}"))]
+    Fork {
+        /// The IO error that occurred.
+        source: std::io::Error,
+    },
+
+    /// The new session could not be started.
+    #[snafu(display("Could not start a new session: {source}"))]
+    Setsid {
+        /// The IO error that occurred.
+        source: std::io::Error,
+    },
+
+    /// The log file could not be opened.
+    #[snafu(display("Could not open the log file {path:?}: {source}"))]
+    OpenLogFile {
+        /// Path of the log file.
+        path: PathBuf,
+        /// The IO error that occurred.
+        source: std::io::Error,
+    },
+
+    /// Stdin, stdout or stderr could not be redirected.
+    #[snafu(display("Could not redirect the standard streams: {source}"))]
+    Redirect {
+        /// The IO error that occurred.
+        source: std::io::Error,
+    },
+
+    /// The working directory could not be changed to `/`.
+    #[snafu(display("Could not leave the working directory: {source}"))]
+    WorkingDir {
+        /// The IO error that occurred.
+        source: std::io::Error,
+    },
+
+    /// The pid file could not be written.
+    #[snafu(display("Could not write the pid file {path:?}: {source}"))]
+    PidFile {
+        /// Path of the pid file.
+        path: PathBuf,
+        /// The IO error that occurred.
+        source: std::io::Error,
+    },
+
+    /// The pid file is locked by another running instance.
+    #[snafu(display("The pid file {path:?} is locked, is the service already running?"))]
+    PidFileLocked {
+        /// Path of the pid file.
+        path: PathBuf,
+    },
+
+    /// Daemonizing is not supported on this platform.
+    #[snafu(display("Daemonizing is not supported on this platform"))]
+    Unsupported,
+}
+
+/// Settings for running the service as a daemon.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, Document)]
+pub struct DaemonSettings {
+    /// Detach from the terminal and run in the background at startup.
+    #[doku(example = "true")]
+    #[serde(default)]
+    pub detach: bool,
+
+    /// File that stdout and stderr are appended to once detached. Omit to discard them.
+    #[doku(example = "/var/log/my-service.log")]
+    #[serde(default)]
+    pub log_file: Option<ExpandedPath>,
+
+    /// File the pid of the daemon is written to, locked while it runs and removed when it exits.
+    #[doku(example = "/run/my-service.pid")]
+    #[serde(default)]
+    pub pid_file: Option<ExpandedPath>,
+}
+
+/// A running daemon, the pid file is removed when it is dropped.
+#[derive(Debug)]
+#[must_use = "dropping the Daemon removes its pid file"]
+pub struct Daemon {
+    pid_file: Option<PidFile>,
+}
+
+impl Daemon {
+    /// The pid file of the daemon, `None` when none is configured.
+    pub fn pid_file(&self) -> Option<&Path> {
+        self.pid_file
+            .as_ref()
+            .map(|pid_file| pid_file.path.as_path())
+    }
+}
+
+/// Detach the process as configured by `settings`, returning in the daemon.
+///
+/// Returns `Ok(None)` without doing anything when `settings.detach` is not set. The process
+/// that called it exits with status 0 once the daemon is forked.
+///
+/// # Safety
+///
+/// Only the calling thread survives the fork, no other thread may be running, ie: call it at the
+/// start of `main`, before the tokio runtime is started.
+///
+/// # Errors
+///
+/// - `OpenLogFile` if the log file cannot be opened.
+/// - `PidFileLocked` if another instance holds the pid file, before the process is detached.
+/// - `Fork` or `Setsid` if the process cannot be detached.
+/// - `Redirect` if the standard streams cannot be redirected, `WorkingDir` if the working
+///   directory cannot be changed.
+/// - `PidFile` if the pid file cannot be opened or written.
+/// - `Unsupported` on platforms without `fork`.
+pub unsafe fn daemonize(settings: &DaemonSettings) -> Result<Option<Daemon>, Error> {
+    if !settings.detach {
+        return Ok(None);
+    }
+    let log_file = settings
+        .log_file
+        .as_deref()
+        .map(|path| {
+            std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .context(OpenLogFileSnafu { path })
+        })
+        .transpose()?;
+    // The lock is held by the open file, which the daemon inherits
+    let mut pid_file = settings
+        .pid_file
+        .as_deref()
+        .map(PidFile::lock)
+        .transpose()?;
+
+    // SAFETY: Upheld by the caller
+    unsafe { imp::detach(log_file)? };
+
+    if let Some(pid_file) = &mut pid_file {
+        pid_file.write_pid()?;
+    }
+    imp::leave_working_dir()?;
+    Ok(Some(Daemon { pid_file }))
+}
+
+/// A locked pid file, removed when dropped.
+#[derive(Debug)]
+struct PidFile {
+    path: PathBuf,
+    // Holds the lock until the daemon exits
+    file: std::fs::File,
+}
+
+impl PidFile {
+    /// Open and lock the file at `path`, failing if another process holds it.
+    fn lock(path: &Path) -> Result<Self, Error> {
+        // Absolute, so it is still removed from the right place after leaving the working dir
+        let path = std::path::absolute(path).context(PidFileSnafu { path })?;
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(false)
+            .open(&path)
+            .context(PidFileSnafu { path: &path })?;
+        imp::lock(&file, &path)?;
+        Ok(Self { path, file })
+    }
+
+    /// Replace the content of the file with the pid of the process.
+    fn write_pid(&mut self) -> Result<(), Error> {
+        use std::io::Write as _;
+
+        self.file
+            .set_len(0)
+            .and_then(|()| writeln!(self.file, "{}", std::process::id()))
+            .context(PidFileSnafu { path: &self.path })
+    }
+}
+
+impl Drop for PidFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(unix)]
+mod imp {
+    use std::os::fd::AsRawFd as _;
+    use std::path::Path;
+
+    use snafu::ResultExt as _;
+
+    use super::{Error, ForkSnafu, PidFileSnafu, RedirectSnafu, SetsidSnafu, WorkingDirSnafu};
+
+    /// Fork twice around a new session, the parents exit.
+    pub(super) unsafe fn detach(log_file: Option<std::fs::File>) -> Result<(), Error> {
+        // SAFETY: The caller guarantees no other thread is running
+        unsafe { fork_and_exit_parent()? };
+        // SAFETY: setsid has no preconditions, the child is not a process group leader
+        if unsafe { libc::setsid() } < 0 {
+            return Err(std::io::Error::last_os_error()).context(SetsidSnafu);
+        }
+        // SAFETY: As above, the process is still single threaded
+        unsafe { fork_and_exit_parent()? };
+        redirect(log_file)
+    }
+
+    unsafe fn fork_and_exit_parent() -> Result<(), Error> {
+        // SAFETY: Upheld by the caller
+        match unsafe { libc::fork() } {
+            pid if pid < 0 => Err(std::io::Error::last_os_error()).context(ForkSnafu),
+            0 => Ok(()),
+            // SAFETY: Exits without running the destructors the child still relies on
+            _ => unsafe { libc::_exit(0) },
+        }
+    }
+
+    /// Read stdin from `/dev/null`, append stdout and stderr to `log_file` or discard them.
+    fn redirect(log_file: Option<std::fs::File>) -> Result<(), Error> {
+        let null = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open("/dev/null")
+            .context(RedirectSnafu)?;
+        let output = log_file.as_ref().unwrap_or(&null);
+        for (file, fd) in [
+            (&null, libc::STDIN_FILENO),
+            (output, libc::STDOUT_FILENO),
+            (output, libc::STDERR_FILENO),
+        ] {
+            // SAFETY: Both descriptors are open, dup2 replaces the standard one atomically
+            if unsafe { libc::dup2(file.as_raw_fd(), fd) } < 0 {
+                return Err(std::io::Error::last_os_error()).context(RedirectSnafu);
+            }
+        }
+        Ok(())
+    }
+
+    pub(super) fn leave_working_dir() -> Result<(), Error> {
+        std::env::set_current_dir("/").context(WorkingDirSnafu)
+    }
+
+    /// Lock `file` for as long as it is open, failing if another process holds the lock.
+    pub(super) fn lock(file: &std::fs::File, path: &Path) -> Result<(), Error> {
+        // SAFETY: The descriptor of `file` is open
+        if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } == 0 {
+            return Ok(());
+        }
+        let err = std::io::Error::last_os_error();
+        if err.kind() == std::io::ErrorKind::WouldBlock {
+            Err(Error::PidFileLocked {
+                path: path.to_path_buf(),
+            })
+        } else {
+            Err(err).context(PidFileSnafu { path })
+        }
+    }
+}
+
+#[cfg(not(unix))]
+mod imp {
+    use std::path::Path;
+
+    use super::Error;
+
+    pub(super) unsafe fn detach(_log_file: Option<std::fs::File>) -> Result<(), Error> {
+        Err(Error::Unsupported)
+    }
+
+    pub(super) fn leave_working_dir() -> Result<(), Error> {
+        Err(Error::Unsupported)
+    }
+
+    pub(super) fn lock(_file: &std::fs::File, _path: &Path) -> Result<(), Error> {
+        Err(Error::Unsupported)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_daemonize_is_opt_in() {
+        let settings = DaemonSettings::default();
+        // SAFETY: Nothing is forked when `detach` is not set
+        assert!(unsafe { daemonize(&settings) }.unwrap().is_none());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_pid_file_is_locked_and_removed() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("service.pid");
+
+        let mut pid_file = PidFile::lock(&path).unwrap();
+        pid_file.write_pid().unwrap();
+        let written = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(written.trim(), std::process::id().to_string());
+
+        let err = PidFile::lock(&path).unwrap_err();
+        assert!(matches!(err, Error::PidFileLocked { .. }));
+
+        drop(pid_file);
+        assert!(!path.exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_locked_pid_file_fails_before_forking() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("service.pid");
+        let _running = PidFile::lock(&path).unwrap();
+
+        let settings = DaemonSettings {
+            detach: true,
+            log_file: None,
+            pid_file: Some(path.into()),
+        };
+        // SAFETY: The lock fails before anything is forked
+        let err = unsafe { daemonize(&settings) }.unwrap_err();
+        assert!(matches!(err, Error::PidFileLocked { .. }));
+    }
+}
@@ -0,0 +1,244 @@
+//! # Local Telemetry Stack
+//!
+//! Writes a docker compose stack that receives the telemetry of the service on a developer's
+//! machine: an OpenTelemetry collector listening on the endpoints of the
+//! [`TelemetrySettings`], Jaeger for the traces, Prometheus for the metrics, and the
+//! collector's console for the logs.
+//!
+//! ```sh
+//! ❯ ./my-service --config app.toml --generate-dev-stack dev-stack
+//! ❯ docker compose --file dev-stack/docker-compose.yaml up
+//! ```
+//!
+//! Jaeger is then at <http://localhost:16686> and Prometheus at <http://localhost:9090>.
+//!
+//! Signals without an endpoint are sent to the local collector, like outside of the `dev`
+//! environment, signals with an empty endpoint are left out. The collector listens on the port
+//! of each endpoint, whatever its host.
+
+use std::collections::BTreeSet;
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+
+use snafu::ResultExt as _;
+
+use crate::telemetry::TelemetrySettings;
+use crate::{ConfigFileWriteSnafu, Error, ServiceInfo};
+
+/// The compose file, the one to start the stack with.
+pub const COMPOSE_FILE: &str = "docker-compose.yaml";
+
+/// The config of the collector, next to the compose file.
+const COLLECTOR_CONFIG_FILE: &str = "otel-collector.yaml";
+
+/// The config of Prometheus, next to the compose file.
+const PROMETHEUS_CONFIG_FILE: &str = "prometheus.yaml";
+
+/// The port of the OTLP gRPC receiver, when the endpoint does not have one.
+const OTLP_GRPC_PORT: u16 = 4317;
+
+/// The port the collector exposes the metrics on, for Prometheus to scrape.
+const COLLECTOR_METRICS_PORT: u16 = 8889;
+
+/// Write the compose file of the stack, and the configs of its services, to `dir`.
+///
+/// The directory is created if it does not exist, the files it already has are overwritten.
+/// Returns the path of the compose file.
+///
+/// # Errors
+/// - `ConfigFileWrite` if the directory or a file cannot be written.
+pub fn write(
+    dir: impl AsRef<Path>,
+    service_info: &ServiceInfo,
+    settings: &TelemetrySettings,
+) -> Result<PathBuf, Error> {
+    let dir = dir.as_ref();
+    std::fs::create_dir_all(dir).context(ConfigFileWriteSnafu { path: dir })?;
+
+    let pipelines = Pipelines::new(settings);
+    for (name, contents) in [
+        (COMPOSE_FILE, compose_file(service_info, &pipelines)),
+        (COLLECTOR_CONFIG_FILE, collector_config(&pipelines)),
+        (PROMETHEUS_CONFIG_FILE, prometheus_config()),
+    ] {
+        let path = dir.join(name);
+        std::fs::write(&path, contents).context(ConfigFileWriteSnafu { path })?;
+    }
+    Ok(dir.join(COMPOSE_FILE))
+}
+
+/// The port the collector receives each signal on, `None` for the signals that are not exported.
+struct Pipelines {
+    traces: Option<u16>,
+    metrics: Option<u16>,
+    logs: Option<u16>,
+}
+
+impl Pipelines {
+    fn new(settings: &TelemetrySettings) -> Self {
+        Self {
+            traces: port(&settings.trace.endpoint),
+            metrics: port(&settings.metric.endpoint),
+            logs: port(&settings.log.endpoint),
+        }
+    }
+
+    /// The ports the collector listens on.
+    fn ports(&self) -> BTreeSet<u16> {
+        [self.traces, self.metrics, self.logs]
+            .into_iter()
+            .flatten()
+            .collect()
+    }
+}
+
+/// The port of `endpoint`, the OTLP gRPC port when it has none or is not set, `None` when the
+/// signal is disabled with an empty endpoint.
+fn port(endpoint: &Option<String>) -> Option<u16> {
+    let Some(endpoint) = endpoint else {
+        return Some(OTLP_GRPC_PORT);
+    };
+    if endpoint.is_empty() {
+        return None;
+    }
+    let authority = endpoint
+        .split_once("://")
+        .map_or(endpoint.as_str(), |(_, rest)| rest)
+        .split('/')
+        .next()
+        .unwrap_or_default();
+    let port = authority
+        .rsplit_once(':')
+        .and_then(|(_, port)| port.parse().ok());
+    Some(port.unwrap_or(OTLP_GRPC_PORT))
+}
+
+fn compose_file(service_info: &ServiceInfo, pipelines: &Pipelines) -> String {
+    let mut yaml = format!(
+        "# Local telemetry stack of {}, generated by byre.\n\
+         # Start it with `docker compose up`, then open Jaeger at http://localhost:16686\n\
+         # and Prometheus at http://localhost:9090.\n\
+         services:\n  \
+           otel-collector:\n    \
+             image: otel/opentelemetry-collector-contrib:latest\n    \
+             command: [\"--config=/etc/otelcol/config.yaml\"]\n    \
+             volumes:\n      \
+               - ./{COLLECTOR_CONFIG_FILE}:/etc/otelcol/config.yaml:ro\n    \
+             ports:\n",
+        service_info.name
+    );
+    for port in pipelines.ports() {
+        let _ = writeln!(yaml, "      - \"{port}:{port}\"");
+    }
+    yaml.push_str(&format!(
+        "  jaeger:\n    \
+           image: jaegertracing/all-in-one:latest\n    \
+           environment:\n      \
+             COLLECTOR_OTLP_ENABLED: \"true\"\n    \
+           ports:\n      \
+             - \"16686:16686\"\n  \
+         prometheus:\n    \
+           image: prom/prometheus:latest\n    \
+           command: [\"--config.file=/etc/prometheus/prometheus.yml\"]\n    \
+           volumes:\n      \
+             - ./{PROMETHEUS_CONFIG_FILE}:/etc/prometheus/prometheus.yml:ro\n    \
+           ports:\n      \
+             - \"9090:9090\"\n"
+    ));
+    yaml
+}
+
+fn collector_config(pipelines: &Pipelines) -> String {
+    let mut yaml = String::from("receivers:\n");
+    for port in pipelines.ports() {
+        let _ = write!(
+            yaml,
+            "  otlp/{port}:\n    \
+               protocols:\n      \
+                 grpc:\n        \
+                   endpoint: 0.0.0.0:{port}\n"
+        );
+    }
+    yaml.push_str(&format!(
+        "processors:\n  \
+           batch: {{}}\n\
+         exporters:\n  \
+           otlp/jaeger:\n    \
+             endpoint: jaeger:4317\n    \
+             tls:\n      \
+               insecure: true\n  \
+           prometheus:\n    \
+             endpoint: 0.0.0.0:{COLLECTOR_METRICS_PORT}\n  \
+           debug:\n    \
+             verbosity: basic\n\
+         service:\n  \
+           pipelines:\n"
+    ));
+    for (signal, port, exporter) in [
+        ("traces", pipelines.traces, "otlp/jaeger"),
+        ("metrics", pipelines.metrics, "prometheus"),
+        ("logs", pipelines.logs, "debug"),
+    ] {
+        if let Some(port) = port {
+            let _ = write!(
+                yaml,
+                "    {signal}:\n      \
+                   receivers: [otlp/{port}]\n      \
+                   processors: [batch]\n      \
+                   exporters: [{exporter}]\n"
+            );
+        }
+    }
+    yaml
+}
+
+fn prometheus_config() -> String {
+    format!(
+        "scrape_configs:\n  \
+           - job_name: otel-collector\n    \
+             scrape_interval: 5s\n    \
+             static_configs:\n      \
+               - targets: [\"otel-collector:{COLLECTOR_METRICS_PORT}\"]\n"
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_the_collector_listens_on_the_endpoints_of_the_settings() {
+        let mut settings: TelemetrySettings = toml::from_str(
+            r#"
+            [trace]
+            [log]
+            endpoint = ""
+            [metric]
+            endpoint = "http://localhost:4318/v1/metrics"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(port(&Some("unix:///run/otel.sock".to_string())), Some(4317));
+
+        let dir = tempfile::tempdir().unwrap();
+        let compose = write(dir.path().join("stack"), &ServiceInfo::default(), &settings).unwrap();
+        assert_eq!(compose, dir.path().join("stack").join(COMPOSE_FILE));
+
+        let compose = std::fs::read_to_string(compose).unwrap();
+        assert!(compose.contains("      - \"4317:4317\"\n      - \"4318:4318\"\n  jaeger:"));
+        let collector =
+            std::fs::read_to_string(dir.path().join("stack").join(COLLECTOR_CONFIG_FILE)).unwrap();
+        assert!(collector.contains(
+            "    traces:\n      receivers: [otlp/4317]\n      processors: [batch]\n      \
+             exporters: [otlp/jaeger]\n    metrics:\n      receivers: [otlp/4318]\n"
+        ));
+        // The logs are disabled
+        assert!(!collector.contains("    logs:"));
+
+        settings.log.endpoint = None;
+        write(dir.path().join("stack"), &ServiceInfo::default(), &settings).unwrap();
+        let collector =
+            std::fs::read_to_string(dir.path().join("stack").join(COLLECTOR_CONFIG_FILE)).unwrap();
+        assert!(collector.contains("    logs:\n      receivers: [otlp/4317]\n"));
+    }
+}
@@ -0,0 +1,149 @@
+//! # Deployment Environment
+//!
+//! The tier a service is deployed to, so it can pick defaults that suit it. byre's telemetry
+//! defaults to readable console logs without exporting anything in [`Environment::Dev`], and to
+//! JSON console logs exported to the local OpenTelemetry collector otherwise.
+//!
+//! The environment is read from the `BYRE_ENV` environment variable by
+//! [`service_info!`](crate::service_info), and the `environment` telemetry setting overrides it:
+//!
+//! ```sh
+//! BYRE_ENV=prod ./my-service --config config.toml
+//! ```
+//!
+//! The region the service runs in, ie: `eu-west-1`, is read from the `BYRE_REGION` environment
+//! variable, see [`detect_region`].
+
+use std::fmt;
+use std::str::FromStr;
+
+use doku::Document;
+use serde::{Deserialize, Serialize};
+use snafu::Snafu;
+
+/// Environment variable the environment is detected from.
+pub const ENVIRONMENT_VAR: &str = "BYRE_ENV";
+
+/// Environment variable the region is detected from.
+pub const REGION_VAR: &str = "BYRE_REGION";
+
+/// Errors parsing an [`Environment`].
+#[derive(Debug, Snafu)]
+pub enum Error {
+    /// The value does not name an environment.
+    #[snafu(display("Unknown environment {value:?}, expected dev, staging or prod"))]
+    Unknown {
+        /// The value that was parsed.
+        value: String,
+    },
+}
+
+/// The tier a service is deployed to.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize, Document)]
+#[serde(rename_all = "lowercase")]
+pub enum Environment {
+    /// Development, ie: running on a developer's machine.
+    #[default]
+    #[serde(alias = "development", alias = "local")]
+    Dev,
+    /// Staging, a production-like deployment for testing releases.
+    #[serde(alias = "stage")]
+    Staging,
+    /// Production.
+    #[serde(alias = "production")]
+    Prod,
+}
+
+impl Environment {
+    /// Detect the environment from the `BYRE_ENV` environment variable, `None` when the variable
+    /// is unset or does not name an environment.
+    ///
+    /// An unknown environment is treated as [`Environment::Dev`], so a service never exports
+    /// telemetry by accident, but keeps the single-line console logs of the `full` format.
+    pub fn detect() -> Option<Self> {
+        std::env::var(ENVIRONMENT_VAR)
+            .ok()
+            .and_then(|value| value.parse().ok())
+    }
+
+    /// The short name of the environment, ie: `prod`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Dev => "dev",
+            Self::Staging => "staging",
+            Self::Prod => "prod",
+        }
+    }
+
+    /// Whether this is [`Environment::Dev`].
+    pub fn is_dev(self) -> bool {
+        self == Self::Dev
+    }
+}
+
+/// Detect the region the service runs in from the `BYRE_REGION` environment variable, empty
+/// when it is unset.
+pub fn detect_region() -> String {
+    std::env::var(REGION_VAR)
+        .map(|region| region.trim().to_string())
+        .unwrap_or_default()
+}
+
+impl fmt::Display for Environment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Environment {
+    type Err = Error;
+
+    /// Parse an environment name case-insensitively, accepting the long forms such as
+    /// `production`.
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "dev" | "development" | "local" => Ok(Self::Dev),
+            "staging" | "stage" => Ok(Self::Staging),
+            "prod" | "production" => Ok(Self::Prod),
+            _ => UnknownSnafu { value }.fail(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_environment_names_parse() {
+        assert_eq!("dev".parse::<Environment>().unwrap(), Environment::Dev);
+        assert_eq!("Local".parse::<Environment>().unwrap(), Environment::Dev);
+        assert_eq!(
+            "stage".parse::<Environment>().unwrap(),
+            Environment::Staging
+        );
+        assert_eq!(
+            " PRODUCTION ".parse::<Environment>().unwrap(),
+            Environment::Prod
+        );
+        assert!(matches!(
+            "qa".parse::<Environment>(),
+            Err(Error::Unknown { value }) if value == "qa"
+        ));
+    }
+
+    #[test]
+    fn test_environment_round_trips_through_serde() {
+        #[derive(Deserialize, Serialize)]
+        struct Settings {
+            environment: Environment,
+        }
+
+        let settings: Settings = toml::from_str("environment = \"production\"").unwrap();
+        assert_eq!(settings.environment, Environment::Prod);
+        assert_eq!(
+            toml::to_string(&settings).unwrap(),
+            "environment = \"prod\"\n"
+        );
+    }
+}
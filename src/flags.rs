@@ -0,0 +1,283 @@
+//! # Feature Flags
+//!
+//! Turns features of the service on and off from the `[features]` section of the config file:
+//!
+//! ```toml
+//! [features]
+//! new_checkout = true
+//! bulk_import = false
+//! ```
+//!
+//! Like any other value, a flag is overridden by an environment variable, ie:
+//! `MYAPP_FEATURES__BULK_IMPORT=true`. A flag that is not configured is disabled.
+//!
+//! ```rust,no_run
+//! use doku::Document;
+//! use serde::Deserialize;
+//!
+//! #[derive(Document, Deserialize)]
+//! pub struct Settings {
+//!     /// Feature flags, by name.
+//!     #[serde(default)]
+//!     pub features: byre::flags::FeatureSettings,
+//! }
+//!
+//! # async fn demo(settings: Settings) {
+//! let flags = byre::flags::Flags::new(&settings.features);
+//! if flags.is_enabled("new_checkout") {
+//!     // ...
+//! }
+//!
+//! let mut bulk_import = flags.subscribe("bulk_import");
+//! tokio::spawn(async move {
+//!     while let Some(enabled) = bulk_import.changed().await {
+//!         tracing::info!(enabled, "bulk imports toggled");
+//!     }
+//! });
+//! # }
+//! ```
+//!
+//! With the `hot-reload` feature, [`Flags::spawn_reload`] applies the changes made to the
+//! config file while the service runs.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, RwLock};
+
+use doku::Document;
+use serde::{Deserialize, Serialize};
+use tokio::sync::watch;
+
+/// The `[features]` section of the config, whether each flag is enabled by name.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize, Document)]
+#[serde(transparent)]
+pub struct FeatureSettings {
+    /// Whether each flag is enabled, by name.
+    pub flags: BTreeMap<String, bool>,
+}
+
+/// The feature flags of the service, cheap to clone and share between tasks.
+#[derive(Clone, Debug, Default)]
+pub struct Flags {
+    flags: Arc<RwLock<HashMap<String, watch::Sender<bool>>>>,
+}
+
+impl Flags {
+    /// The flags of `settings`.
+    pub fn new(settings: &FeatureSettings) -> Self {
+        let flags = Self::default();
+        flags.update(settings);
+        flags
+    }
+
+    /// Whether the flag `name` is enabled, `false` for flags that are not configured.
+    pub fn is_enabled(&self, name: &str) -> bool {
+        let flags = self.flags.read().unwrap_or_else(|err| err.into_inner());
+        flags.get(name).is_some_and(|flag| *flag.borrow())
+    }
+
+    /// Enable or disable the flag `name`, until the flags are updated or reloaded.
+    pub fn set(&self, name: &str, enabled: bool) {
+        let mut flags = self.flags.write().unwrap_or_else(|err| err.into_inner());
+        set_flag(&mut flags, name, enabled);
+    }
+
+    /// Replace the flags with the ones of `settings`, flags that are not in `settings` are
+    /// disabled.
+    ///
+    /// Only the flags whose value changes notify their subscribers.
+    pub fn update(&self, settings: &FeatureSettings) {
+        let mut flags = self.flags.write().unwrap_or_else(|err| err.into_inner());
+        let removed: Vec<_> = flags
+            .keys()
+            .filter(|name| !settings.flags.contains_key(*name))
+            .cloned()
+            .collect();
+        for name in removed {
+            set_flag(&mut flags, &name, false);
+        }
+        for (name, enabled) in &settings.flags {
+            set_flag(&mut flags, name, *enabled);
+        }
+    }
+
+    /// Every flag that was configured, set or subscribed to, and whether it is enabled.
+    pub fn snapshot(&self) -> BTreeMap<String, bool> {
+        let flags = self.flags.read().unwrap_or_else(|err| err.into_inner());
+        flags
+            .iter()
+            .map(|(name, flag)| (name.clone(), *flag.borrow()))
+            .collect()
+    }
+
+    /// Watch the flag `name`, which does not need to be configured yet.
+    pub fn subscribe(&self, name: &str) -> FlagWatch {
+        let mut flags = self.flags.write().unwrap_or_else(|err| err.into_inner());
+        let flag = flags
+            .entry(name.to_string())
+            .or_insert_with(|| watch::Sender::new(false));
+        FlagWatch(flag.subscribe())
+    }
+
+    /// Reload the `[features]` section of the config file at `path` when the file changes,
+    /// checking every `interval` on the current tokio runtime.
+    ///
+    /// Values are overridden by the environment variables that start with `env_prefix`, like
+    /// when the config was loaded. A file that can't be loaded is logged and the flags are left
+    /// as they are. The reload stops when the returned task is aborted, or with the runtime.
+    #[cfg(feature = "hot-reload")]
+    pub fn spawn_reload(
+        &self,
+        path: impl Into<std::path::PathBuf>,
+        env_prefix: impl Into<String>,
+        interval: std::time::Duration,
+    ) -> tokio::task::JoinHandle<()> {
+        /// The part of the config the flags are reloaded from.
+        #[derive(Deserialize, Document)]
+        struct Section {
+            #[serde(default)]
+            features: FeatureSettings,
+        }
+
+        let path = path.into();
+        let env_prefix = env_prefix.into();
+        let flags = self.clone();
+        crate::config::spawn_file_watch(
+            path.clone(),
+            interval,
+            move || match crate::config::Config::<Section>::new(Some(&path), Some(&env_prefix)) {
+                Ok(loaded) => flags.update(&loaded.config.features),
+                Err(err) => tracing::warn!(error = %err, "could not reload the feature flags"),
+            },
+        )
+    }
+}
+
+/// Set the flag `name`, notifying its subscribers and logging the change if its value changes.
+fn set_flag(flags: &mut HashMap<String, watch::Sender<bool>>, name: &str, enabled: bool) {
+    let Some(flag) = flags.get(name) else {
+        flags.insert(name.to_string(), watch::Sender::new(enabled));
+        return;
+    };
+    let changed = flag.send_if_modified(|current| {
+        let changed = *current != enabled;
+        *current = enabled;
+        changed
+    });
+    if changed {
+        tracing::info!(flag = name, enabled, "feature flag changed");
+    }
+}
+
+/// Notified when a feature flag is enabled or disabled, see [`Flags::subscribe`].
+#[derive(Clone, Debug)]
+pub struct FlagWatch(watch::Receiver<bool>);
+
+impl FlagWatch {
+    /// Whether the flag is enabled.
+    pub fn is_enabled(&self) -> bool {
+        *self.0.borrow()
+    }
+
+    /// Wait until the flag changes, returns whether it is enabled then.
+    ///
+    /// Returns `None` once every clone of the [`Flags`] is dropped, the flag can't change
+    /// anymore.
+    pub async fn changed(&mut self) -> Option<bool> {
+        self.0.changed().await.ok()?;
+        Some(*self.0.borrow_and_update())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(flags: &[(&str, bool)]) -> FeatureSettings {
+        FeatureSettings {
+            flags: flags
+                .iter()
+                .map(|(name, enabled)| (name.to_string(), *enabled))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_flags_are_loaded_with_environment_overrides() {
+        #[derive(Deserialize, Document)]
+        struct Settings {
+            #[serde(default)]
+            features: FeatureSettings,
+        }
+
+        crate::config::testing::sandbox(|sandbox| {
+            sandbox.set_env("BYRE_TEST_FLAGS_FEATURES__BULK_IMPORT", true);
+            let config = sandbox.load::<Settings>(
+                r#"
+                [features]
+                new_checkout = true
+                bulk_import = false
+                "#,
+                "BYRE_TEST_FLAGS_",
+            )?;
+
+            let flags = Flags::new(&config.config.features);
+            assert!(flags.is_enabled("new_checkout"));
+            assert!(flags.is_enabled("bulk_import"));
+            assert!(!flags.is_enabled("not_configured"));
+            Ok(())
+        });
+    }
+
+    #[tokio::test]
+    async fn test_subscribers_are_notified_of_their_flag_changes() {
+        let flags = Flags::new(&settings(&[("new_checkout", false), ("bulk_import", true)]));
+        let mut new_checkout = flags.subscribe("new_checkout");
+        let mut later = flags.subscribe("not_configured_yet");
+
+        flags.update(&settings(&[
+            ("new_checkout", true),
+            ("not_configured_yet", true),
+        ]));
+
+        assert_eq!(new_checkout.changed().await, Some(true));
+        assert_eq!(later.changed().await, Some(true));
+        assert!(!flags.is_enabled("bulk_import"));
+        assert_eq!(
+            flags.snapshot(),
+            BTreeMap::from([
+                ("bulk_import".to_string(), false),
+                ("new_checkout".to_string(), true),
+                ("not_configured_yet".to_string(), true),
+            ])
+        );
+
+        // Updates that leave a flag as it is don't notify
+        flags.set("new_checkout", true);
+        flags.set("bulk_import", true);
+        assert!(!new_checkout.0.has_changed().unwrap());
+        drop(flags);
+        assert_eq!(new_checkout.changed().await, None);
+    }
+
+    #[cfg(feature = "hot-reload")]
+    #[tokio::test]
+    async fn test_flags_are_reloaded_when_the_file_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(&path, "[features]\nnew_checkout = false\n").unwrap();
+        let flags = Flags::new(&settings(&[("new_checkout", false)]));
+        let mut new_checkout = flags.subscribe("new_checkout");
+
+        let reload = flags.spawn_reload(
+            &path,
+            "BYRE_TEST_RELOAD_",
+            std::time::Duration::from_millis(10),
+        );
+        // Modification times can be as coarse as a second
+        tokio::time::sleep(std::time::Duration::from_millis(1100)).await;
+        std::fs::write(&path, "[features]\nnew_checkout = true\n").unwrap();
+
+        assert_eq!(new_checkout.changed().await, Some(true));
+        reload.abort();
+    }
+}
@@ -0,0 +1,420 @@
+//! # Health HTTP Endpoint
+//!
+//! This module provides the liveness and readiness endpoints deployment platforms probe. It is
+//! only available with the `health` feature enabled.
+//!
+//! The server exposes the following routes:
+//!
+//! - `GET /healthz` - always `200 OK` while the process is serving requests
+//! - `GET /readyz` - `200 OK` when every registered readiness probe passes,
+//!   `503 Service Unavailable` otherwise
+//! - `GET /buildinfo` - the name, version, git SHA, config fingerprint and uptime of the
+//!   service, once added with [`Health::with_build_info`], as OpenMetrics when the request
+//!   accepts `application/openmetrics-text`
+//!
+//! Readiness probes are registered on a [`Health`] at any time, ie: once the database pool is
+//! created. Every probe runs concurrently on each request to `/readyz` and fails when it does
+//! not finish within one second. The response lists the result of each probe:
+//!
+//! ```sh
+//! $ curl -i http://localhost:8081/readyz
+//! HTTP/1.1 503 Service Unavailable
+//!
+//! cache: ok
+//! database: failed: connection refused
+//! ```
+//!
+//! ```rust,no_run
+//! # async fn demo() -> Result<(), Box<dyn std::error::Error>> {
+//! let health_settings = byre::health::HealthSettings {
+//!     listen: Some("0.0.0.0:8081".to_string()),
+//! };
+//!
+//! # let service_info = byre::ServiceInfo::default();
+//! let health = byre::health::Health::default()
+//!     .with_build_info(&service_info)
+//!     .with_config_fingerprint("4f0a3c1b2d9e8f76");
+//! health.add_probe("database", || async {
+//!     // ie: run `SELECT 1` against the database pool
+//!     Ok::<_, std::io::Error>(())
+//! });
+//!
+//! // Keep the server alive for as long as the endpoints should be reachable.
+//! let _health = byre::health::serve(&health_settings, health.clone()).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::future::Future;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use doku::Document;
+use http::{Method, Request, Response, StatusCode};
+use http_body_util::Full;
+use hyper::body::Bytes;
+use serde::{Deserialize, Serialize};
+use snafu::{ResultExt as _, Snafu};
+
+use crate::build_info::{self, BuildInfo, BUILD_INFO_PATH};
+use crate::http_server::{self, respond};
+use crate::ServiceInfo;
+
+const LIVENESS_PATH: &str = "/healthz";
+const READINESS_PATH: &str = "/readyz";
+
+/// A probe that does not finish in time fails, deployment platforms give up on a request soon.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Errors starting the health server.
+#[derive(Debug, Snafu)]
+pub enum Error {
+    /// The health server could not bind to its listen address.
+    #[snafu(display("Could not bind the health server to {listen}: {source}"))]
+    Bind {
+        /// The address the server tried to bind to.
+        listen: String,
+        /// The IO error that occurred.
+        source: std::io::Error,
+    },
+}
+
+/// Settings for the health HTTP endpoint.
+#[derive(Debug, Default, Serialize, Deserialize, Document)]
+pub struct HealthSettings {
+    /// Address for the health HTTP endpoint to listen on. Omit to disable the endpoint.
+    #[doku(example = "0.0.0.0:8081")]
+    pub listen: Option<String>,
+}
+
+type ProbeFuture = Pin<Box<dyn Future<Output = Result<(), String>> + Send>>;
+type Probe = Arc<dyn Fn() -> ProbeFuture + Send + Sync>;
+
+/// The readiness probes of a service, and the build it reports.
+///
+/// Clones share their probes, keep one to register probes while the server uses another. The
+/// build info is not shared, add it before cloning.
+#[derive(Clone, Default)]
+pub struct Health {
+    probes: Arc<Mutex<Vec<(String, Probe)>>>,
+    build_info: Option<BuildInfo>,
+    config_fingerprint: Option<String>,
+}
+
+impl std::fmt::Debug for Health {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let probes = self.probes.lock().unwrap_or_else(|err| err.into_inner());
+        f.debug_struct("Health")
+            .field(
+                "probes",
+                &probes.iter().map(|(name, _)| name).collect::<Vec<_>>(),
+            )
+            .field("build_info", &self.build_info)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Health {
+    /// Serve the build of the service at `GET /buildinfo`, with the uptime counted from now.
+    pub fn with_build_info(mut self, service_info: &ServiceInfo) -> Self {
+        self.build_info = Some(BuildInfo::new(service_info));
+        self
+    }
+
+    /// Report the fingerprint of the loaded configuration, ie: `cli.config_fingerprint`, at
+    /// `GET /buildinfo`.
+    pub fn with_config_fingerprint(mut self, fingerprint: impl Into<String>) -> Self {
+        self.config_fingerprint = Some(fingerprint.into());
+        self
+    }
+
+    /// Register a readiness probe, the service is only ready while every probe returns `Ok`.
+    ///
+    /// A probe registered with the name of an existing one replaces it.
+    pub fn add_probe<F, Fut, E>(&self, name: impl Into<String>, probe: F)
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), E>> + Send + 'static,
+        E: std::fmt::Display,
+    {
+        let probe: Probe = Arc::new(move || {
+            let check = probe();
+            Box::pin(async move { check.await.map_err(|err| err.to_string()) })
+        });
+
+        let name = name.into();
+        let mut probes = self.probes.lock().unwrap_or_else(|err| err.into_inner());
+        match probes.iter_mut().find(|(existing, _)| *existing == name) {
+            Some((_, existing)) => *existing = probe,
+            None => probes.push((name, probe)),
+        }
+    }
+
+    /// Remove the readiness probe registered with `name`.
+    pub fn remove_probe(&self, name: &str) {
+        let mut probes = self.probes.lock().unwrap_or_else(|err| err.into_inner());
+        probes.retain(|(existing, _)| existing != name);
+    }
+
+    /// Run every probe concurrently, returning each probe's name and result in registration
+    /// order.
+    pub async fn check(&self) -> Vec<(String, Result<(), String>)> {
+        let probes = self
+            .probes
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .clone();
+
+        let checks: Vec<_> = probes
+            .into_iter()
+            .map(|(name, probe)| {
+                (
+                    name,
+                    tokio::spawn(tokio::time::timeout(PROBE_TIMEOUT, probe())),
+                )
+            })
+            .collect();
+
+        let mut results = Vec::with_capacity(checks.len());
+        for (name, check) in checks {
+            let result = match check.await {
+                Ok(Ok(result)) => result,
+                Ok(Err(_)) => Err("timed out".to_string()),
+                Err(err) => Err(format!("panicked: {err}")),
+            };
+            results.push((name, result));
+        }
+        results
+    }
+}
+
+/// A running health server.
+///
+/// The server is stopped when this value is dropped.
+#[derive(Debug)]
+#[must_use = "dropping HealthServer will stop the health endpoint"]
+pub struct HealthServer {
+    local_addr: SocketAddr,
+    task: tokio::task::JoinHandle<()>,
+}
+
+impl HealthServer {
+    /// The address the server is listening on.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+}
+
+impl Drop for HealthServer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Starts the health HTTP server on the current tokio runtime.
+///
+/// Returns `Ok(None)` if no listen address is configured.
+///
+/// # Errors
+///
+/// - `Bind` if the listen address cannot be bound.
+pub async fn serve(
+    settings: &HealthSettings,
+    health: Health,
+) -> Result<Option<HealthServer>, Error> {
+    let Some(listen) = &settings.listen else {
+        return Ok(None);
+    };
+
+    let listener = tokio::net::TcpListener::bind(listen)
+        .await
+        .with_context(|_| BindSnafu { listen })?;
+    let local_addr = listener
+        .local_addr()
+        .with_context(|_| BindSnafu { listen })?;
+
+    let task = tokio::spawn(http_server::accept_loop(
+        listener,
+        "health",
+        move |request| {
+            let health = health.clone();
+            async move { handle(request, &health).await }
+        },
+    ));
+
+    tracing::info!(%local_addr, "health endpoint listening");
+
+    Ok(Some(HealthServer { local_addr, task }))
+}
+
+async fn handle<B>(request: Request<B>, health: &Health) -> Response<Full<Bytes>> {
+    let path = request.uri().path();
+    let build_info = health
+        .build_info
+        .as_ref()
+        .filter(|_| path == BUILD_INFO_PATH);
+    if path != LIVENESS_PATH && path != READINESS_PATH && build_info.is_none() {
+        return respond(StatusCode::NOT_FOUND, "not found\n");
+    }
+    if request.method() != Method::GET {
+        return respond(StatusCode::METHOD_NOT_ALLOWED, "method not allowed\n");
+    }
+    if path == LIVENESS_PATH {
+        return respond(StatusCode::OK, "ok\n");
+    }
+    if let Some(build_info) = build_info {
+        let (body, content_type) =
+            build_info.render(health.config_fingerprint.as_deref(), request.headers());
+        let mut response = respond(StatusCode::OK, body);
+        build_info::set_content_type(response.headers_mut(), content_type);
+        return response;
+    }
+
+    let results = health.check().await;
+    let mut status = StatusCode::OK;
+    let mut body = String::new();
+    for (name, result) in results {
+        match result {
+            Ok(()) => body.push_str(&format!("{name}: ok\n")),
+            Err(err) => {
+                tracing::debug!(probe = %name, error = %err, "readiness probe failed");
+                status = StatusCode::SERVICE_UNAVAILABLE;
+                body.push_str(&format!("{name}: failed: {err}\n"));
+            }
+        }
+    }
+    if body.is_empty() {
+        body.push_str("ok\n");
+    }
+    respond(status, body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http_body_util::BodyExt as _;
+
+    fn request(method: Method, uri: &str) -> Request<()> {
+        Request::builder().method(method).uri(uri).body(()).unwrap()
+    }
+
+    async fn body_string(response: Response<Full<Bytes>>) -> String {
+        let bytes = response.into_body().collect().await.unwrap().to_bytes();
+        String::from_utf8(bytes.to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_health_routes() {
+        let health = Health::default();
+
+        let response = handle(request(Method::GET, "/readyz"), &health).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(body_string(response).await, "ok\n");
+
+        health.add_probe("cache", || async { Ok::<_, String>(()) });
+        health.add_probe("database", || async { Err("connection refused") });
+
+        let response = handle(request(Method::GET, "/readyz"), &health).await;
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(
+            body_string(response).await,
+            "cache: ok\ndatabase: failed: connection refused\n"
+        );
+
+        let response = handle(request(Method::GET, "/healthz"), &health).await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        health.add_probe("database", || async { Ok::<_, String>(()) });
+        let response = handle(request(Method::GET, "/readyz"), &health).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(body_string(response).await, "cache: ok\ndatabase: ok\n");
+
+        health.remove_probe("cache");
+        let response = handle(request(Method::GET, "/readyz"), &health).await;
+        assert_eq!(body_string(response).await, "database: ok\n");
+
+        let response = handle(request(Method::POST, "/readyz"), &health).await;
+        assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+
+        let response = handle(request(Method::GET, "/other"), &health).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_build_info_route() {
+        let health = Health::default();
+        let response = handle(request(Method::GET, "/buildinfo"), &health).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let service_info = crate::ServiceInfo {
+            name: "inventory",
+            version: "1.2.3",
+            ..Default::default()
+        };
+        let health = health
+            .with_build_info(&service_info)
+            .with_config_fingerprint("4f0a3c1b2d9e8f76");
+        let response = handle(request(Method::GET, "/buildinfo"), &health).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            body_string(response).await,
+            "name: inventory\nversion: 1.2.3\nconfig_fingerprint: 4f0a3c1b2d9e8f76\nuptime_secs: 0\n"
+        );
+
+        let openmetrics = Request::get("/buildinfo")
+            .header(http::header::ACCEPT, "application/openmetrics-text")
+            .body(())
+            .unwrap();
+        let response = handle(openmetrics, &health).await;
+        assert!(response.headers()[http::header::CONTENT_TYPE]
+            .to_str()
+            .unwrap()
+            .starts_with("application/openmetrics-text"));
+        let metrics = body_string(response).await;
+        assert!(
+            metrics.contains("service_build_info{name=\"inventory\",version=\"1.2.3\",config_fingerprint=\"4f0a3c1b2d9e8f76\"} 1\n"),
+            "{metrics}"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_serve_without_listen_returns_none() {
+        let server = serve(&HealthSettings::default(), Health::default())
+            .await
+            .unwrap();
+        assert!(server.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_serve_responds_over_http() {
+        use tokio::io::{AsyncReadExt as _, AsyncWriteExt as _};
+
+        let settings = HealthSettings {
+            listen: Some("127.0.0.1:0".to_string()),
+        };
+        let health = Health::default();
+        let server = serve(&settings, health.clone()).await.unwrap().unwrap();
+        health.add_probe("database", || async { Err("connection refused") });
+
+        let mut stream = tokio::net::TcpStream::connect(server.local_addr())
+            .await
+            .unwrap();
+        stream
+            .write_all(b"GET /readyz HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+            .await
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+
+        assert!(
+            response.starts_with("HTTP/1.1 503 Service Unavailable"),
+            "{response}"
+        );
+        assert!(
+            response.ends_with("database: failed: connection refused\n"),
+            "{response}"
+        );
+    }
+}
@@ -0,0 +1,114 @@
+//! The HTTP/1 server shared by the admin and health endpoints.
+
+use std::convert::Infallible;
+use std::future::Future;
+use std::time::Duration;
+
+use http::{Request, Response, StatusCode};
+use http_body_util::Full;
+use hyper::body::{Bytes, Incoming};
+use hyper_util::rt::TokioIo;
+
+/// The first pause after a failed `accept`, doubled on every failure in a row.
+const MIN_ACCEPT_BACKOFF: Duration = Duration::from_millis(5);
+
+/// The longest pause after a failed `accept`.
+const MAX_ACCEPT_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Serve the connections of `listener` with `handle`, the `endpoint` names the server in logs.
+///
+/// Errors accepting a connection, ie: when the process runs out of file descriptors, pause the
+/// loop instead of retrying at once, as the listener stays readable until one is freed.
+pub(crate) async fn accept_loop<F, Fut>(
+    listener: tokio::net::TcpListener,
+    endpoint: &'static str,
+    handle: F,
+) where
+    F: Fn(Request<Incoming>) -> Fut + Clone + Send + 'static,
+    Fut: Future<Output = Response<Full<Bytes>>> + Send + 'static,
+{
+    let mut backoff = None;
+    loop {
+        let stream = match listener.accept().await {
+            Ok((stream, _)) => {
+                backoff = None;
+                stream
+            }
+            Err(err) if is_connection_error(&err) => continue,
+            Err(err) => {
+                let delay = next_backoff(backoff);
+                tracing::warn!(
+                    error = %err,
+                    retry_in_ms = delay.as_millis() as u64,
+                    "{endpoint} endpoint could not accept connection"
+                );
+                backoff = Some(delay);
+                tokio::time::sleep(delay).await;
+                continue;
+            }
+        };
+
+        let handle = handle.clone();
+        tokio::spawn(async move {
+            let service = hyper::service::service_fn(move |request| {
+                let response = handle(request);
+                async move { Ok::<_, Infallible>(response.await) }
+            });
+            if let Err(err) = hyper::server::conn::http1::Builder::new()
+                .serve_connection(TokioIo::new(stream), service)
+                .await
+            {
+                tracing::debug!(error = %err, "{endpoint} endpoint connection failed");
+            }
+        });
+    }
+}
+
+/// Errors of a single connection, that the next `accept` does not run into.
+fn is_connection_error(err: &std::io::Error) -> bool {
+    matches!(
+        err.kind(),
+        std::io::ErrorKind::ConnectionRefused
+            | std::io::ErrorKind::ConnectionAborted
+            | std::io::ErrorKind::ConnectionReset
+    )
+}
+
+/// The pause after a failed `accept` that follows a pause of `previous`.
+fn next_backoff(previous: Option<Duration>) -> Duration {
+    previous.map_or(MIN_ACCEPT_BACKOFF, |previous| {
+        (previous * 2).min(MAX_ACCEPT_BACKOFF)
+    })
+}
+
+/// A response with `status` and a plain text `body`.
+pub(crate) fn respond(status: StatusCode, body: impl Into<Bytes>) -> Response<Full<Bytes>> {
+    let mut response = Response::new(Full::new(body.into()));
+    *response.status_mut() = status;
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_accept_errors_back_off() {
+        let delays: Vec<_> = std::iter::successors(Some(next_backoff(None)), |delay| {
+            Some(next_backoff(Some(*delay)))
+        })
+        .take(10)
+        .collect();
+        assert_eq!(delays[0], Duration::from_millis(5));
+        assert_eq!(delays[1], Duration::from_millis(10));
+        assert_eq!(delays[7], Duration::from_millis(640));
+        assert_eq!(delays[8], MAX_ACCEPT_BACKOFF);
+        assert_eq!(delays[9], MAX_ACCEPT_BACKOFF);
+
+        let emfile = std::io::Error::from_raw_os_error(24);
+        assert!(!is_connection_error(&emfile));
+        assert!(is_connection_error(
+            &std::io::ErrorKind::ConnectionAborted.into()
+        ));
+    }
+}
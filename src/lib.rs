@@ -94,6 +94,31 @@ let _telemetry = byre::telemetry::init(&service_info, &cli.config.telemetry)?;
 # }
 ```
 
+Alternatively, let [`App`] do steps 3 and 4, start the tokio runtime and wait for a shutdown signal:
+
+```rust,no_run
+# use doku::Document;
+# use serde::Deserialize;
+# #[derive(Document, Deserialize)]
+# pub struct Settings {
+#     /// Telemetry settings.
+#     pub telemetry: byre::telemetry::TelemetrySettings,
+# }
+impl byre::app::AppSettings for Settings {
+    fn telemetry(&self) -> &byre::telemetry::TelemetrySettings {
+        &self.telemetry
+    }
+}
+
+fn main() {
+    byre::App::<Settings>::new(byre::service_info!(), "MYAPP_").run(|ctx| async move {
+        // Serve until SIGINT or SIGTERM
+        ctx.shutdown().wait().await;
+        Ok(())
+    })
+}
+```
+
 ### Override config value via environment values
 
 Environment variables can be used to override a setting from a config file.
@@ -175,10 +200,59 @@ There is a `full` example in the [source tree](https://github.com/halzy/byre/tre
 // Document ALL THE THINGS!
 #![deny(missing_docs)]
 
+#[cfg(feature = "admin")]
+pub mod admin;
+
+pub mod app;
+pub mod build;
+#[cfg(any(feature = "admin", feature = "health"))]
+mod build_info;
 pub mod cli;
 pub mod config;
+pub mod container;
+pub mod crash;
+pub mod daemon;
+pub mod dev_stack;
+pub mod environment;
+pub mod flags;
+#[cfg(feature = "health")]
+pub mod health;
+#[cfg(any(feature = "admin", feature = "health"))]
+mod http_server;
+pub mod limits;
+#[cfg(feature = "net")]
+pub mod net;
+pub mod report;
+pub mod runtime;
+pub mod secrets;
+pub mod startup;
+#[cfg(feature = "systemd")]
+pub mod systemd;
 pub mod telemetry;
 
+pub use app::App;
+pub use environment::Environment;
+pub use report::{Report, Result};
+
+/// Records call count, error count and duration metrics for a function.
+///
+/// ```
+/// #[byre::timed]
+/// fn parse_port(value: &str) -> Result<u16, std::num::ParseIntError> {
+///     Ok(value.parse()?)
+/// }
+///
+/// #[byre::timed(name = "db.load_user")]
+/// #[tracing::instrument]
+/// async fn load_user(id: u64) -> Option<String> {
+///     None
+/// }
+/// # assert!(parse_port("8080").is_ok());
+/// ```
+///
+/// See [`telemetry::metrics`] for where the instruments come from.
+pub use byre_macros::timed;
+
 /// Errors that can occur during byre operations.
 ///
 /// This is the main error type for the byre crate. For more specific error handling,
@@ -189,10 +263,22 @@ pub mod telemetry;
 #[non_exhaustive]
 pub enum Error {
     /// Figment could not extract a config from the file with env overrides.
-    #[snafu(display("Could not load application configuration: {source}"))]
+    #[snafu(display(
+        "Could not load application configuration: {source}{}",
+        location.as_ref().map(|location| format!("\n{location}")).unwrap_or_default()
+    ))]
     ConfigLoad {
         /// The source figment error.
         source: Box<figment::Error>,
+        /// Where the value the error is about is set, when it is known.
+        location: Option<Box<config::Location>>,
+    },
+
+    /// The config file could not be read or parsed.
+    #[snafu(display("Could not parse the application configuration: {source}"))]
+    ConfigParse {
+        /// The source figment error.
+        source: Box<figment::Error>,
     },
 
     /// Writing to the config file was not possible.
@@ -203,6 +289,29 @@ pub enum Error {
         /// The IO error that occurred.
         source: std::io::Error,
     },
+
+    /// A listen address is not `host:port`.
+    #[snafu(display("Invalid listen address {address:?}, expected ie: 0.0.0.0:8080"))]
+    InvalidListenAddr {
+        /// The address that was parsed.
+        address: String,
+    },
+
+    /// The host of a listen address could not be resolved.
+    #[snafu(display("Could not resolve the listen address {address}: {source}"))]
+    ResolveListenAddr {
+        /// The address that was resolved.
+        address: String,
+        /// The IO error that occurred.
+        source: std::io::Error,
+    },
+
+    /// The host of a listen address does not resolve to any address.
+    #[snafu(display("The listen address {address} does not resolve to any address"))]
+    NoListenAddr {
+        /// The address that was resolved.
+        address: String,
+    },
 }
 
 /// Global memory allocator backed by [jemalloc].
@@ -238,6 +347,171 @@ pub struct ServiceInfo {
     pub author: &'static str,
     /// The description of the service.
     pub description: &'static str,
+
+    /// The git commit the service was built from, empty when unknown.
+    pub git_sha: &'static str,
+
+    /// Whether the working tree had uncommitted changes when the build script last ran, edits
+    /// that are not staged don't rerun it, see [`build::emit_build_info`].
+    pub git_dirty: bool,
+
+    /// When the service was built, as an RFC 3339 UTC timestamp, empty when unknown.
+    pub build_timestamp: &'static str,
+
+    /// The version of the Rust compiler the service was built with, empty when unknown.
+    pub rustc_version: &'static str,
+
+    /// The environment the service runs in, `None` when it is unknown, see
+    /// [`Environment::detect`].
+    pub environment: Option<Environment>,
+
+    /// The region the service runs in, ie: `eu-west-1`, empty when unknown. See
+    /// [`environment::detect_region`].
+    pub region: String,
+}
+
+impl ServiceInfo {
+    /// Build service information at runtime, starting from empty values.
+    ///
+    /// Use [`into_builder`](Self::into_builder) to override some of the values of
+    /// [`service_info!`] instead, ie: when one binary runs as several services.
+    pub fn builder() -> ServiceInfoBuilder {
+        Self::default().into_builder()
+    }
+
+    /// Override some of the values of the service information at runtime.
+    ///
+    /// ```
+    /// let tenant = "acme";
+    /// let service_info = byre::service_info!()
+    ///     .into_builder()
+    ///     .with_name(format!("ingest-{tenant}"))
+    ///     .with_description(format!("Ingests the events of {tenant}"))
+    ///     .build();
+    ///
+    /// assert_eq!(service_info.name, "ingest-acme");
+    /// assert_eq!(service_info.name_in_metrics, "ingest_acme");
+    /// ```
+    pub fn into_builder(self) -> ServiceInfoBuilder {
+        ServiceInfoBuilder {
+            info: self,
+            name: None,
+            description: None,
+            version: None,
+            author: None,
+        }
+    }
+
+    /// The version with the build metadata that is known, ie:
+    /// `1.2.3 (0123abcd-dirty, built 2026-10-17T09:04:05Z, rustc 1.85.0)`.
+    ///
+    /// Printed by `--version`, while `-V` prints the version alone.
+    pub fn long_version(&self) -> String {
+        let mut details = Vec::new();
+        if !self.git_sha.is_empty() {
+            let dirty = if self.git_dirty { "-dirty" } else { "" };
+            details.push(format!("{}{dirty}", self.git_sha));
+        }
+        if !self.build_timestamp.is_empty() {
+            details.push(format!("built {}", self.build_timestamp));
+        }
+        if !self.rustc_version.is_empty() {
+            details.push(format!("rustc {}", self.rustc_version));
+        }
+
+        if details.is_empty() {
+            self.version.to_string()
+        } else {
+            format!("{} ({})", self.version, details.join(", "))
+        }
+    }
+}
+
+/// Builder for [`ServiceInfo`], created by [`ServiceInfo::builder`] or
+/// [`ServiceInfo::into_builder`].
+///
+/// `ServiceInfo` holds `&'static str`s so that [`service_info!`] costs nothing. The strings set
+/// at runtime are leaked by [`build`](Self::build) to live as long, build it once when the
+/// service starts.
+#[derive(Clone, Debug)]
+#[must_use = "a ServiceInfoBuilder does nothing until it is built"]
+pub struct ServiceInfoBuilder {
+    info: ServiceInfo,
+    // Kept owned until `build`, so setting them again leaks nothing
+    name: Option<String>,
+    description: Option<String>,
+    version: Option<String>,
+    author: Option<String>,
+}
+
+impl ServiceInfoBuilder {
+    /// Set the name of the service, and the name used in metrics with hyphens (`-`) replaced by
+    /// underscores (`_`).
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        let name = name.into();
+        self.info.name_in_metrics = name.replace('-', "_");
+        self.name = Some(name);
+        self
+    }
+
+    /// Set the name of the service used in metrics, after [`with_name`](Self::with_name).
+    pub fn with_name_in_metrics(mut self, name_in_metrics: impl Into<String>) -> Self {
+        self.info.name_in_metrics = name_in_metrics.into();
+        self
+    }
+
+    /// Set the description of the service.
+    pub fn with_description(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    /// Set the version of the service.
+    pub fn with_version(mut self, version: impl Into<String>) -> Self {
+        self.version = Some(version.into());
+        self
+    }
+
+    /// Set the author of the service.
+    pub fn with_author(mut self, author: impl Into<String>) -> Self {
+        self.author = Some(author.into());
+        self
+    }
+
+    /// Set the environment the service runs in.
+    pub fn with_environment(mut self, environment: Environment) -> Self {
+        self.info.environment = Some(environment);
+        self
+    }
+
+    /// Set the region the service runs in.
+    pub fn with_region(mut self, region: impl Into<String>) -> Self {
+        self.info.region = region.into();
+        self
+    }
+
+    /// Build the service information, leaking the strings that were set.
+    pub fn build(self) -> ServiceInfo {
+        let mut info = self.info;
+        if let Some(name) = self.name {
+            info.name = leak(name);
+        }
+        if let Some(description) = self.description {
+            info.description = leak(description);
+        }
+        if let Some(version) = self.version {
+            info.version = leak(version);
+        }
+        if let Some(author) = self.author {
+            info.author = leak(author);
+        }
+        info
+    }
+}
+
+/// Leak `value` so it lives as long as the [`ServiceInfo`] it is stored in.
+fn leak(value: String) -> &'static str {
+    Box::leak(value.into_boxed_str())
 }
 
 // # #[tokio::main] async fn main() -> anyhow::Result<()> {
@@ -283,6 +557,30 @@ let _telemetry = byre::telemetry::init(&service_info, &cli.config.telemetry)?;
 
 [`ServiceInfo::name_in_metrics`] is the same as the package name, with hyphens (`-`) replaced
 by underscores (`_`).
+
+[`ServiceInfo::git_sha`], [`ServiceInfo::git_dirty`], [`ServiceInfo::build_timestamp`] and
+[`ServiceInfo::rustc_version`] are read from the `VERGEN_GIT_SHA`, `VERGEN_GIT_DIRTY`,
+`VERGEN_BUILD_TIMESTAMP` and `VERGEN_RUSTC_SEMVER` environment variables at compile time, and are
+empty when they are not set. [vergen](https://docs.rs/vergen) sets them from the service's
+`build.rs`, as does [`build::emit_build_info`] with byre as a build dependency:
+```rust,ignore
+// build.rs
+fn main() {
+    byre::build::emit_build_info();
+}
+```
+
+[`ServiceInfo::environment`] and [`ServiceInfo::region`] are detected from the `BYRE_ENV` and
+`BYRE_REGION` environment variables when the service starts, see [`Environment::detect`] and
+[`environment::detect_region`]. Pass them to the macro to set them in the code instead, the same
+as any other field:
+```rust
+let service_info = byre::service_info!(
+    environment = byre::Environment::Prod,
+    region = "eu-west-1",
+);
+assert_eq!(service_info.region, "eu-west-1");
+```
 */
 #[macro_export]
 macro_rules! service_info {
@@ -293,6 +591,27 @@ macro_rules! service_info {
             version: env!("CARGO_PKG_VERSION"),
             author: env!("CARGO_PKG_AUTHORS"),
             description: env!("CARGO_PKG_DESCRIPTION"),
+            git_sha: match option_env!("VERGEN_GIT_SHA") {
+                Some(git_sha) => git_sha,
+                None => "",
+            },
+            git_dirty: matches!(option_env!("VERGEN_GIT_DIRTY"), Some("true")),
+            build_timestamp: match option_env!("VERGEN_BUILD_TIMESTAMP") {
+                Some(build_timestamp) => build_timestamp,
+                None => "",
+            },
+            rustc_version: match option_env!("VERGEN_RUSTC_SEMVER") {
+                Some(rustc_version) => rustc_version,
+                None => "",
+            },
+            environment: $crate::Environment::detect(),
+            region: $crate::environment::detect_region(),
+        }
+    };
+    ($($field:ident = $value:expr_2021),+ $(,)?) => {
+        $crate::ServiceInfo {
+            $($field: ::core::convert::Into::into($value),)+
+            ..$crate::service_info!()
         }
     };
 }
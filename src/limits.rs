@@ -0,0 +1,222 @@
+//! # Process Limits
+//!
+//! Raises the limit on open file descriptors (`RLIMIT_NOFILE`) at startup. Many distributions
+//! default the soft limit to 1024, which a busy server runs out of, while the hard limit allows
+//! far more. Raising the soft limit is opt-in:
+//!
+//! ```toml
+//! [file_limit]
+//! raise = true
+//! # Optional, defaults to the hard limit
+//! target = 65536
+//! ```
+//!
+//! [`App`](crate::App) raises the limit before it starts the runtime when the settings provide
+//! [`AppSettings::file_limit`](crate::app::AppSettings::file_limit), and logs the outcome once
+//! telemetry is initialized.
+//!
+//! ```rust,no_run
+//! # fn demo() -> Result<(), Box<dyn std::error::Error>> {
+//! let settings = byre::limits::FileLimitSettings {
+//!     raise: true,
+//!     target: None,
+//! };
+//! if let Some(raised) = byre::limits::raise_file_limit(&settings)? {
+//!     println!("open file limit raised from {} to {}", raised.previous, raised.current);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use doku::Document;
+use serde::{Deserialize, Serialize};
+use snafu::Snafu;
+
+/// Errors raising a process limit.
+#[derive(Debug, Snafu)]
+pub enum Error {
+    /// The current limit could not be read.
+    #[snafu(display("Could not read the open file limit: {source}"))]
+    GetLimit {
+        /// The IO error that occurred.
+        source: std::io::Error,
+    },
+
+    /// The limit could not be raised.
+    #[snafu(display("Could not raise the open file limit to {target}: {source}"))]
+    SetLimit {
+        /// The soft limit that was requested.
+        target: u64,
+        /// The IO error that occurred.
+        source: std::io::Error,
+    },
+
+    /// Process limits are not supported on this platform.
+    #[snafu(display("Raising the open file limit is not supported on this platform"))]
+    Unsupported,
+}
+
+/// Settings for the open file descriptor limit.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, Document)]
+pub struct FileLimitSettings {
+    /// Raise the soft limit on open file descriptors (`RLIMIT_NOFILE`) at startup.
+    #[doku(example = "true")]
+    #[serde(default)]
+    pub raise: bool,
+
+    /// Soft limit to raise to, capped at the hard limit. Omit to raise it to the hard limit.
+    #[doku(example = "65536")]
+    #[serde(default)]
+    pub target: Option<u64>,
+}
+
+/// The outcome of raising the open file limit.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FileLimit {
+    /// The soft limit before it was raised.
+    pub previous: u64,
+    /// The soft limit now in effect.
+    pub current: u64,
+    /// The hard limit, the soft limit can not be raised past it.
+    pub hard: u64,
+}
+
+impl FileLimit {
+    /// Log the outcome as an `INFO` event, or a `WARN` event if the limit could not reach the
+    /// configured target.
+    pub fn log(&self, settings: &FileLimitSettings) {
+        match settings.target {
+            Some(target) if self.current < target => tracing::warn!(
+                previous = self.previous,
+                current = self.current,
+                hard = self.hard,
+                target,
+                "open file limit is capped below its target by the hard limit"
+            ),
+            _ if self.current == self.previous => tracing::info!(
+                current = self.current,
+                hard = self.hard,
+                "open file limit already at its target"
+            ),
+            _ => tracing::info!(
+                previous = self.previous,
+                current = self.current,
+                hard = self.hard,
+                "open file limit raised"
+            ),
+        }
+    }
+}
+
+/// Raise the soft limit on open file descriptors as configured by `settings`.
+///
+/// Returns `Ok(None)` when `settings.raise` is not set. The limit is never lowered: a target
+/// below the current soft limit leaves it unchanged.
+///
+/// # Errors
+///
+/// - `GetLimit` if the current limit cannot be read.
+/// - `SetLimit` if the limit cannot be raised.
+/// - `Unsupported` on platforms without `setrlimit`.
+pub fn raise_file_limit(settings: &FileLimitSettings) -> Result<Option<FileLimit>, Error> {
+    if !settings.raise {
+        return Ok(None);
+    }
+    imp::raise(settings.target).map(Some)
+}
+
+/// The soft limit to set, given the current soft and hard limits.
+fn next_soft_limit(soft: u64, hard: u64, target: Option<u64>) -> u64 {
+    target.unwrap_or(hard).min(hard).max(soft)
+}
+
+#[cfg(unix)]
+mod imp {
+    use snafu::ResultExt as _;
+
+    use super::{next_soft_limit, Error, FileLimit, GetLimitSnafu, SetLimitSnafu};
+
+    // `rlim_t` is `u64` on Linux and macOS, but signed on some BSDs
+    #[allow(clippy::unnecessary_cast)]
+    pub(super) fn raise(target: Option<u64>) -> Result<FileLimit, Error> {
+        let mut limit = libc::rlimit {
+            rlim_cur: 0,
+            rlim_max: 0,
+        };
+        // SAFETY: `limit` is a valid `rlimit` for getrlimit to write to.
+        if unsafe { libc::getrlimit(libc::RLIMIT_NOFILE, &mut limit) } != 0 {
+            return Err(std::io::Error::last_os_error()).context(GetLimitSnafu);
+        }
+
+        let previous = limit.rlim_cur as u64;
+        let hard = hard_limit(limit.rlim_max as u64);
+        let current = next_soft_limit(previous, hard, target);
+        if current != previous {
+            limit.rlim_cur = current as libc::rlim_t;
+            // SAFETY: `limit` is a valid `rlimit` for setrlimit to read.
+            if unsafe { libc::setrlimit(libc::RLIMIT_NOFILE, &limit) } != 0 {
+                return Err(std::io::Error::last_os_error())
+                    .context(SetLimitSnafu { target: current });
+            }
+        }
+
+        Ok(FileLimit {
+            previous,
+            current,
+            hard,
+        })
+    }
+
+    /// macOS rejects soft limits above `OPEN_MAX`, even when the hard limit is unlimited.
+    #[cfg(target_os = "macos")]
+    fn hard_limit(hard: u64) -> u64 {
+        hard.min(libc::OPEN_MAX as u64)
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    fn hard_limit(hard: u64) -> u64 {
+        hard
+    }
+}
+
+#[cfg(not(unix))]
+mod imp {
+    use super::{Error, FileLimit};
+
+    pub(super) fn raise(_target: Option<u64>) -> Result<FileLimit, Error> {
+        Err(Error::Unsupported)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_soft_limit_is_raised_up_to_the_hard_limit() {
+        assert_eq!(next_soft_limit(1024, 524_288, None), 524_288);
+        assert_eq!(next_soft_limit(1024, 524_288, Some(65_536)), 65_536);
+        assert_eq!(next_soft_limit(1024, 4096, Some(65_536)), 4096);
+        // Never lowered
+        assert_eq!(next_soft_limit(8192, 524_288, Some(4096)), 8192);
+    }
+
+    #[test]
+    fn test_raise_is_opt_in() {
+        let settings = FileLimitSettings::default();
+        assert_eq!(raise_file_limit(&settings).unwrap(), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_raise_to_the_current_limit() {
+        // Targeting a limit of 1 keeps the current one, so other tests are not affected
+        let settings = FileLimitSettings {
+            raise: true,
+            target: Some(1),
+        };
+        let limit = raise_file_limit(&settings).unwrap().unwrap();
+        assert_eq!(limit.current, limit.previous);
+        assert!(limit.current <= limit.hard);
+    }
+}
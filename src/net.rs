@@ -0,0 +1,125 @@
+//! # Listening Sockets
+//!
+//! Binds the sockets a service listens on, requires the `net` feature.
+//!
+//! [`bind`] resolves a [`ListenAddr`] of the config, binds a tokio `TcpListener` to the first
+//! address that can be bound, and reports it:
+//!
+//! - a `server listening` `INFO` event with the `listen` address of the config and the
+//!   `server.address` and `server.port` that were bound, which tells the port apart when the
+//!   config asks for port `0`
+//! - the `server.listening` counter, with the same `server.address` and `server.port`
+//!   attributes
+//!
+//! ```rust,no_run
+//! # use doku::Document;
+//! # use serde::Deserialize;
+//! #[derive(Document, Deserialize)]
+//! pub struct Settings {
+//!     /// Address to listen on, ie: "0.0.0.0:8080"
+//!     pub listen: byre::config::ListenAddr,
+//! }
+//!
+//! # async fn demo(settings: Settings) -> Result<(), byre::net::Error> {
+//! let listener = byre::net::bind(&settings.listen).await?;
+//! // accept connections
+//! # Ok(())
+//! # }
+//! ```
+
+use opentelemetry::KeyValue;
+use snafu::{ResultExt as _, Snafu};
+use tokio::net::TcpListener;
+
+use crate::config::ListenAddr;
+
+/// Errors binding a listening socket.
+#[derive(Debug, Snafu)]
+pub enum Error {
+    /// The listen address could not be resolved.
+    #[snafu(display("Could not listen: {source}"))]
+    Resolve {
+        /// The error resolving the address.
+        source: crate::Error,
+    },
+
+    /// None of the addresses of the listen address could be bound.
+    #[snafu(display("Could not bind to {listen}: {source}"))]
+    Bind {
+        /// The address of the config.
+        listen: String,
+        /// The error binding the last address that was tried.
+        source: std::io::Error,
+    },
+}
+
+/// Bind a TCP listener to `listen` on the current tokio runtime, and report the address that
+/// was bound.
+///
+/// The addresses the host resolves to are tried in order, the listener is bound to the first
+/// one that can be bound.
+///
+/// # Errors
+///
+/// - `Resolve` if the host cannot be resolved.
+/// - `Bind` if none of its addresses can be bound.
+pub async fn bind(listen: &ListenAddr) -> Result<TcpListener, Error> {
+    let addrs = listen.resolve().context(ResolveSnafu)?;
+    let listener = TcpListener::bind(addrs.as_slice())
+        .await
+        .with_context(|_| BindSnafu {
+            listen: listen.to_string(),
+        })?;
+    let local_addr = listener.local_addr().with_context(|_| BindSnafu {
+        listen: listen.to_string(),
+    })?;
+
+    let server_address = local_addr.ip().to_string();
+    tracing::info!(
+        listen = %listen,
+        server.address = server_address,
+        server.port = local_addr.port(),
+        "server listening"
+    );
+    crate::counter!("server.listening", "Number of listening sockets bound").add(
+        1,
+        &[
+            KeyValue::new("server.address", server_address),
+            KeyValue::new("server.port", i64::from(local_addr.port())),
+        ],
+    );
+
+    Ok(listener)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_bind_reports_the_bound_address() {
+        let capture = crate::telemetry::test::capture();
+
+        let listener = bind(&ListenAddr::new("localhost", 0)).await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        assert_ne!(port, 0);
+
+        let logs = capture.logs();
+        let log = logs
+            .iter()
+            .find(|log| log.body == "server listening")
+            .unwrap();
+        assert!(log
+            .attributes
+            .contains(&KeyValue::new("server.port", port.to_string())));
+        assert_eq!(capture.metric_value("server.listening", &[]), 1.0);
+
+        let err = bind(&ListenAddr::new(
+            listener.local_addr().unwrap().ip().to_string(),
+            port,
+        ))
+        .await
+        .unwrap_err();
+        assert!(matches!(err, Error::Bind { .. }));
+    }
+}
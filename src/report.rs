@@ -0,0 +1,348 @@
+//! # Error Reports
+//!
+//! [`Report`] holds any error, from byre or from the service, prints it with the chain of its
+//! causes and maps it to the exit code of the process, so every service fails the same way:
+//!
+//! ```rust,no_run
+//! # use doku::Document;
+//! # use serde::Deserialize;
+//! # #[derive(Document, Deserialize)]
+//! # pub struct Settings {}
+//! fn run() -> byre::Result<()> {
+//!     let service_info = byre::service_info!();
+//!     let Some(cli) = byre::cli::Cli::<Settings>::try_new(&service_info, "MYAPP_")? else {
+//!         return Ok(());
+//!     };
+//!     let port: u16 = std::env::var("PORT")?.parse()?;
+//!     // ...
+//!     # let _ = (cli, port);
+//!     Ok(())
+//! }
+//!
+//! fn main() {
+//!     if let Err(report) = run() {
+//!         report.exit();
+//!     }
+//! }
+//! ```
+//!
+//! `fn main() -> byre::Result<()>` prints the same report, but the standard library always
+//! exits with `1` when `main` returns an error. [`Report::exit`] exits with the code of the
+//! [`Category`] of the error instead.
+//!
+//! The category is the one of the first byre error found in the chain of causes, errors of the
+//! service are [`Category::Failure`] unless they are given another one with
+//! [`Report::with_category`].
+
+use std::error::Error as StdError;
+use std::fmt;
+
+use clap::error::ErrorKind;
+
+/// A `Result` that fails with a [`Report`], the return type of a service's `main`.
+pub type Result<T = (), E = Report> = std::result::Result<T, E>;
+
+/// What kind of failure stopped the service, which sets the exit code of the process.
+///
+/// The codes are the ones of `sysexits.h`, which supervisors and shell scripts know.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Category {
+    /// The command line asked for the help or the version, printed to stdout. Exits with `0`.
+    Help,
+    /// The service failed, the default for errors of the service. Exits with `1`.
+    Failure,
+    /// The command line could not be parsed. Exits with `64` (`EX_USAGE`).
+    Usage,
+    /// A server could not listen, or telemetry could not be flushed. Exits with `69`
+    /// (`EX_UNAVAILABLE`).
+    Unavailable,
+    /// byre was used incorrectly, ie: telemetry was initialized twice. Exits with `70`
+    /// (`EX_SOFTWARE`).
+    Software,
+    /// The operating system refused a resource, ie: the runtime's threads. Exits with `71`
+    /// (`EX_OSERR`).
+    OsError,
+    /// A file could not be written, ie: the generated config. Exits with `73` (`EX_CANTCREAT`).
+    CantCreate,
+    /// The config could not be loaded or has invalid values. Exits with `78` (`EX_CONFIG`).
+    Config,
+    /// `--check-config` found the config invalid. Exits with the code of the
+    /// [`Status`](crate::cli::check::Status) of the check.
+    ConfigCheck(crate::cli::check::Status),
+}
+
+impl Category {
+    /// The exit code of the process for the category.
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Self::Help => 0,
+            Self::Failure => 1,
+            Self::Usage => 64,
+            Self::Unavailable => 69,
+            Self::Software => 70,
+            Self::OsError => 71,
+            Self::CantCreate => 73,
+            Self::Config => 78,
+            Self::ConfigCheck(status) => status.exit_code(),
+        }
+    }
+}
+
+/// An error of a service, with its [`Category`].
+///
+/// Any error converts into a report with `?`. A report is not an error itself, so it can't be
+/// the cause of another error.
+pub struct Report {
+    error: Box<dyn StdError + Send + Sync + 'static>,
+    category: Category,
+}
+
+impl Report {
+    /// A report of `error`, categorized by the first byre error in its chain of causes.
+    pub fn new(error: impl StdError + Send + Sync + 'static) -> Self {
+        Self::from_boxed(Box::new(error))
+    }
+
+    /// A report of an error that is already boxed, ie: the error of an [`App`](crate::App)'s
+    /// main function.
+    pub fn from_boxed(error: Box<dyn StdError + Send + Sync + 'static>) -> Self {
+        let category = chain(&*error)
+            .find_map(categorize)
+            .unwrap_or(Category::Failure);
+        Self { error, category }
+    }
+
+    /// A report of a failure described by `message`.
+    pub fn msg(message: impl Into<String>) -> Self {
+        Self::from_boxed(message.into().into())
+    }
+
+    /// Set the category of the report, ie: for the errors of the service's own config checks.
+    pub fn with_category(mut self, category: Category) -> Self {
+        self.category = category;
+        self
+    }
+
+    /// The category of the error.
+    pub fn category(&self) -> Category {
+        self.category
+    }
+
+    /// The exit code of the process for the error.
+    pub fn exit_code(&self) -> u8 {
+        self.category.exit_code()
+    }
+
+    /// The error that was reported.
+    pub fn error(&self) -> &(dyn StdError + Send + Sync + 'static) {
+        &*self.error
+    }
+
+    /// Print the report and exit the process with the code of its category.
+    ///
+    /// Errors are printed to stderr after `Error: `, like `main` does. The messages of clap are
+    /// printed as clap prints them, the help and the version to stdout. The failed checks of
+    /// `--check-config` are printed as they are, see [`ConfigCheck`](crate::cli::check::ConfigCheck).
+    pub fn exit(self) -> ! {
+        let cli_error =
+            chain(&*self.error).find_map(|error| error.downcast_ref::<crate::cli::Error>());
+        match cli_error {
+            Some(crate::cli::Error::ArgParse { message, .. })
+                if self.category == Category::Help =>
+            {
+                print!("{message}")
+            }
+            Some(crate::cli::Error::ArgParse { message, .. }) => eprint!("{message}"),
+            Some(crate::cli::Error::ConfigCheckFailed { check }) if check.prints_to_stdout() => {
+                println!("{check}")
+            }
+            Some(crate::cli::Error::ConfigCheckFailed { check }) => eprintln!("{check}"),
+            _ => eprintln!("Error: {self}"),
+        }
+        std::process::exit(self.exit_code().into())
+    }
+}
+
+impl<E> From<E> for Report
+where
+    E: StdError + Send + Sync + 'static,
+{
+    fn from(error: E) -> Self {
+        Self::new(error)
+    }
+}
+
+/// The message of the error, followed by the causes it does not already include.
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut printed = self.error.to_string();
+        f.write_str(&printed)?;
+
+        // byre's errors include their cause in their message, other errors usually don't
+        let mut header = false;
+        for cause in chain(&*self.error).skip(1) {
+            let message = cause.to_string();
+            if printed.contains(&message) {
+                continue;
+            }
+            if !header {
+                f.write_str("\n\nCaused by:")?;
+                header = true;
+            }
+            write!(f, "\n    {message}")?;
+            printed = message;
+        }
+        Ok(())
+    }
+}
+
+/// Prints the report like [`Display`](fmt::Display), `main` prints its error with `Debug`.
+impl fmt::Debug for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+/// `error` followed by its causes.
+fn chain<'a>(
+    error: &'a (dyn StdError + 'static),
+) -> impl Iterator<Item = &'a (dyn StdError + 'static)> {
+    std::iter::successors(Some(error), |&error| error.source())
+}
+
+/// The category of a byre error, `None` for other errors and for byre errors that wrap the
+/// error that is categorized.
+fn categorize(error: &(dyn StdError + 'static)) -> Option<Category> {
+    if let Some(error) = error.downcast_ref::<crate::Error>() {
+        return Some(match error {
+            crate::Error::ConfigLoad { .. }
+            | crate::Error::ConfigParse { .. }
+            | crate::Error::InvalidListenAddr { .. }
+            | crate::Error::NoListenAddr { .. } => Category::Config,
+            crate::Error::ConfigFileWrite { .. } => Category::CantCreate,
+            // The name server may be down
+            crate::Error::ResolveListenAddr { .. } => Category::Unavailable,
+        });
+    }
+    if let Some(error) = error.downcast_ref::<crate::cli::Error>() {
+        return match error {
+            crate::cli::Error::ArgParse { kind, .. } => Some(match kind {
+                ErrorKind::DisplayHelp
+                | ErrorKind::DisplayVersion
+                | ErrorKind::DisplayHelpOnMissingArgumentOrSubcommand => Category::Help,
+                _ => Category::Usage,
+            }),
+            crate::cli::Error::ConfigCheckFailed { check } => {
+                Some(Category::ConfigCheck(check.status))
+            }
+            crate::cli::Error::ConfigLoad { .. }
+            | crate::cli::Error::ConfigGenerateFailed { .. } => None,
+        };
+    }
+    if let Some(error) = error.downcast_ref::<crate::runtime::Error>() {
+        return Some(match error {
+            crate::runtime::Error::ZeroThreads { .. } => Category::Config,
+            crate::runtime::Error::Build { .. } => Category::OsError,
+        });
+    }
+    if let Some(error) = error.downcast_ref::<crate::telemetry::Error>() {
+        return Some(match error {
+            crate::telemetry::Error::AlreadyInitialized => Category::Software,
+            _ => Category::Config,
+        });
+    }
+    if error.is::<crate::telemetry::ShutdownError>() {
+        return Some(Category::Unavailable);
+    }
+    if error.is::<crate::limits::Error>() {
+        return Some(Category::OsError);
+    }
+    if let Some(error) = error.downcast_ref::<crate::daemon::Error>() {
+        return Some(match error {
+            crate::daemon::Error::OpenLogFile { .. } | crate::daemon::Error::PidFile { .. } => {
+                Category::CantCreate
+            }
+            // Another instance is running
+            crate::daemon::Error::PidFileLocked { .. } => Category::Unavailable,
+            _ => Category::OsError,
+        });
+    }
+    #[cfg(feature = "admin")]
+    if error.is::<crate::admin::Error>() {
+        return Some(Category::Unavailable);
+    }
+    #[cfg(feature = "health")]
+    if error.is::<crate::health::Error>() {
+        return Some(Category::Unavailable);
+    }
+    #[cfg(feature = "net")]
+    if let Some(error) = error.downcast_ref::<crate::net::Error>() {
+        return match error {
+            crate::net::Error::Resolve { .. } => None,
+            crate::net::Error::Bind { .. } => Some(Category::Unavailable),
+        };
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, snafu::Snafu)]
+    #[snafu(display("Could not import the catalog"))]
+    struct ImportError {
+        source: std::io::Error,
+    }
+
+    #[test]
+    fn test_byre_errors_set_the_exit_code() {
+        let config = crate::Error::ConfigFileWrite {
+            path: "/etc/app.toml".into(),
+            source: std::io::Error::other("read-only file system"),
+        };
+        let report = Report::from(crate::app::Error::Cli {
+            source: crate::cli::Error::ConfigGenerateFailed { source: config },
+        });
+        assert_eq!(report.category(), Category::CantCreate);
+        assert_eq!(report.exit_code(), 73);
+
+        let help = crate::cli::Error::ArgParse {
+            message: "Usage: app".to_string(),
+            kind: ErrorKind::DisplayHelp,
+        };
+        assert_eq!(Report::from(help).exit_code(), 0);
+    }
+
+    #[test]
+    fn test_service_errors_are_failures() {
+        let main: Box<dyn StdError + Send + Sync> = Box::new(ImportError {
+            source: std::io::Error::other("connection reset"),
+        });
+        let report = Report::from(crate::app::Error::Main { source: main });
+
+        assert_eq!(report.category(), Category::Failure);
+        assert_eq!(report.exit_code(), 1);
+        assert_eq!(report.with_category(Category::Unavailable).exit_code(), 69);
+    }
+
+    #[test]
+    fn test_report_prints_the_causes_not_in_the_message() {
+        let report = Report::new(ImportError {
+            source: std::io::Error::other("connection reset"),
+        });
+        assert_eq!(
+            format!("{report:?}"),
+            "Could not import the catalog\n\nCaused by:\n    connection reset"
+        );
+
+        let report = Report::from(crate::runtime::Error::Build {
+            source: std::io::Error::other("out of threads"),
+        });
+        assert_eq!(
+            report.to_string(),
+            "Failed to start the tokio runtime: out of threads"
+        );
+    }
+}
@@ -0,0 +1,158 @@
+//! # Tokio Runtime
+//!
+//! Builds the multi-threaded tokio runtime from [`RuntimeSettings`], so the thread pools of a
+//! service can be sized from its config file instead of being fixed at compile time.
+//!
+//! ```rust,no_run
+//! # fn demo() -> Result<(), Box<dyn std::error::Error>> {
+//! let settings = byre::runtime::RuntimeSettings {
+//!     worker_threads: Some(4),
+//!     thread_name_prefix: Some("inventory".to_string()),
+//!     ..Default::default()
+//! };
+//!
+//! let runtime = byre::runtime::from_settings(&settings)?;
+//! runtime.block_on(async {
+//!     // ...
+//! });
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [`App`](crate::App) builds its runtime the same way when the settings provide
+//! [`AppSettings::runtime`](crate::app::AppSettings::runtime).
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use doku::Document;
+use serde::{Deserialize, Serialize};
+use snafu::{ensure, ResultExt as _, Snafu};
+
+use crate::container::ContainerLimits;
+
+/// Errors building the tokio runtime.
+#[derive(Debug, Snafu)]
+pub enum Error {
+    /// A thread count setting was zero, tokio needs at least one thread.
+    #[snafu(display("The runtime setting {setting} must be at least 1"))]
+    ZeroThreads {
+        /// The name of the setting.
+        setting: &'static str,
+    },
+
+    /// The runtime could not be started.
+    #[snafu(display("Failed to start the tokio runtime: {source}"))]
+    Build {
+        /// The IO error that occurred.
+        source: std::io::Error,
+    },
+}
+
+/// Settings for the tokio runtime, tokio's defaults are used for omitted values.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, Document)]
+pub struct RuntimeSettings {
+    /// Number of worker threads running async tasks, defaults to the number of CPU cores.
+    #[doku(example = "4")]
+    pub worker_threads: Option<usize>,
+
+    /// Most threads the blocking pool grows to for `spawn_blocking` and blocking IO, defaults to 512.
+    #[doku(example = "64")]
+    pub max_blocking_threads: Option<usize>,
+
+    /// Prefix of the runtime's thread names, followed by a number, ie: `inventory-3`.
+    #[doku(example = "inventory")]
+    pub thread_name_prefix: Option<String>,
+
+    /// Stack size of the runtime's threads in bytes, defaults to 2 MiB.
+    #[doku(example = "4194304")]
+    pub thread_stack_size: Option<usize>,
+
+    /// Run one worker thread per CPU of the container's quota, rounded up, when `worker_threads` is omitted.
+    /// Without a quota the number of CPU cores is used.
+    #[doku(example = "true")]
+    #[serde(default)]
+    pub worker_threads_from_cpu_quota: bool,
+}
+
+/// Build a multi-threaded tokio runtime with all drivers enabled, sized by `settings`.
+///
+/// # Errors
+///
+/// - `ZeroThreads` if `worker_threads` or `max_blocking_threads` is zero.
+/// - `Build` if the runtime cannot be started.
+pub fn from_settings(settings: &RuntimeSettings) -> Result<tokio::runtime::Runtime, Error> {
+    let mut builder = tokio::runtime::Builder::new_multi_thread();
+    builder.enable_all();
+
+    if let Some(worker_threads) = settings.worker_threads {
+        ensure!(
+            worker_threads > 0,
+            ZeroThreadsSnafu {
+                setting: "worker_threads"
+            }
+        );
+        builder.worker_threads(worker_threads);
+    } else if settings.worker_threads_from_cpu_quota {
+        if let Some(worker_threads) = ContainerLimits::detect().worker_threads() {
+            builder.worker_threads(worker_threads);
+        }
+    }
+    if let Some(max_blocking_threads) = settings.max_blocking_threads {
+        ensure!(
+            max_blocking_threads > 0,
+            ZeroThreadsSnafu {
+                setting: "max_blocking_threads"
+            }
+        );
+        builder.max_blocking_threads(max_blocking_threads);
+    }
+    if let Some(prefix) = settings.thread_name_prefix.clone() {
+        let next = AtomicUsize::new(0);
+        builder
+            .thread_name_fn(move || format!("{prefix}-{}", next.fetch_add(1, Ordering::Relaxed)));
+    }
+    if let Some(thread_stack_size) = settings.thread_stack_size {
+        builder.thread_stack_size(thread_stack_size);
+    }
+
+    builder.build().context(BuildSnafu)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_runtime_threads_are_named_with_the_prefix() {
+        let settings = RuntimeSettings {
+            worker_threads: Some(2),
+            thread_name_prefix: Some("inventory".to_string()),
+            ..Default::default()
+        };
+        let runtime = from_settings(&settings).unwrap();
+
+        let name = runtime
+            .block_on(async {
+                // Spawned tasks run on the worker threads
+                tokio::spawn(async { std::thread::current().name().map(str::to_string) }).await
+            })
+            .unwrap()
+            .unwrap();
+        assert!(name.starts_with("inventory-"), "{name}");
+    }
+
+    #[test]
+    fn test_zero_threads_are_rejected() {
+        let settings = RuntimeSettings {
+            max_blocking_threads: Some(0),
+            ..Default::default()
+        };
+        let err = from_settings(&settings).unwrap_err();
+        assert!(matches!(
+            err,
+            Error::ZeroThreads {
+                setting: "max_blocking_threads"
+            }
+        ));
+    }
+}
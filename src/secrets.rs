@@ -0,0 +1,377 @@
+//! # Secrets
+//!
+//! Reads secrets that live outside of the config file, and picks up their rotations while the
+//! service runs. A config value that starts with `file:` is read from the file it names, ie: a
+//! mounted Kubernetes or Docker secret, other values are the secret itself:
+//!
+//! ```toml
+//! [database]
+//! password = "file:/run/secrets/db_password"
+//! ```
+//!
+//! Secrets from elsewhere, ie: Vault, are fetched by a function given to
+//! [`Secret::from_resolver`].
+//!
+//! ```rust,no_run
+//! use doku::Document;
+//! use serde::Deserialize;
+//!
+//! #[derive(Document, Deserialize)]
+//! pub struct Database {
+//!     /// Password of the database user, or `file:` and the file it is read from.
+//!     #[doku(example = "file:/run/secrets/db_password")]
+//!     pub password: byre::secrets::SecretRef,
+//! }
+//!
+//! # fn demo(settings: Database) -> Result<(), byre::secrets::Error> {
+//! let password = byre::secrets::Secret::load(&settings.password)?;
+//! // Connections opened from now on use the rotated password
+//! let current = password.get();
+//! # let _ = current;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! With the `hot-reload` feature, [`Secret::spawn_rotation`] reads the secret again when its
+//! file changes, or every interval for secrets of a resolver.
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use doku::Document;
+use serde::{Deserialize, Serialize};
+use snafu::{ResultExt as _, Snafu};
+use tokio::sync::watch;
+
+/// The prefix of the config values that name the file a secret is read from.
+const FILE_PREFIX: &str = "file:";
+
+/// Replaces the secrets that are serialized, ie: in the config shown by the admin endpoint.
+pub(crate) const REDACTED: &str = "[redacted]";
+
+/// Errors reading a secret.
+#[derive(Debug, Snafu)]
+pub enum Error {
+    /// The file of the secret could not be read.
+    #[snafu(display("Could not read the secret file {}: {source}", path.display()))]
+    ReadFile {
+        /// The file of the secret.
+        path: PathBuf,
+        /// The IO error that occurred.
+        source: std::io::Error,
+    },
+
+    /// The resolver of the secret failed.
+    #[snafu(display("Could not resolve the secret: {source}"))]
+    Resolve {
+        /// The error of the resolver.
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+}
+
+/// A secret in the config: the secret itself, or `file:` followed by the path of the file it
+/// is read from.
+///
+/// It serializes as `[redacted]` when the value is the secret itself, ie: when the effective
+/// config is shown by the admin endpoint.
+#[derive(Clone, Default, PartialEq, Eq, Deserialize, Document)]
+#[serde(transparent)]
+pub struct SecretRef(pub String);
+
+impl SecretRef {
+    /// The file the secret is read from, `None` when the value is the secret itself.
+    pub fn file(&self) -> Option<&Path> {
+        self.0.strip_prefix(FILE_PREFIX).map(Path::new)
+    }
+}
+
+// The value may be the secret itself, keep it out of logs.
+impl fmt::Debug for SecretRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.file() {
+            Some(path) => write!(f, "SecretRef({FILE_PREFIX}{})", path.display()),
+            None => f.write_str("SecretRef(<redacted>)"),
+        }
+    }
+}
+
+// The value may be the secret itself, keep it out of serialized configs.
+impl Serialize for SecretRef {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.file() {
+            Some(_) => serializer.serialize_str(&self.0),
+            None => serializer.serialize_str(REDACTED),
+        }
+    }
+}
+
+/// A function that fetches a secret, ie: from Vault.
+type Resolver =
+    Arc<dyn Fn() -> Result<String, Box<dyn std::error::Error + Send + Sync>> + Send + Sync>;
+
+/// Where a secret is read from.
+#[derive(Clone)]
+enum Source {
+    Value,
+    File(PathBuf),
+    Resolver(Resolver),
+}
+
+/// The current value of a secret, cheap to clone and share between tasks.
+///
+/// Code that holds on to the secret, ie: a connection pool, reads it with [`get`](Self::get)
+/// whenever it opens a connection, or waits for rotations with [`subscribe`](Self::subscribe).
+/// It serializes as `[redacted]`, or as the file it is read from.
+#[derive(Clone)]
+pub struct Secret {
+    source: Source,
+    value: Arc<watch::Sender<Arc<str>>>,
+}
+
+// Keep the value out of logs.
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let source = match &self.source {
+            Source::Value => "value".to_string(),
+            Source::File(path) => format!("{FILE_PREFIX}{}", path.display()),
+            Source::Resolver(_) => "resolver".to_string(),
+        };
+        f.debug_struct("Secret")
+            .field("source", &source)
+            .finish_non_exhaustive()
+    }
+}
+
+// Keep the value out of serialized configs, only its file is shown.
+impl Serialize for Secret {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match &self.source {
+            Source::File(path) => {
+                serializer.collect_str(&format_args!("{FILE_PREFIX}{}", path.display()))
+            }
+            Source::Value | Source::Resolver(_) => serializer.serialize_str(REDACTED),
+        }
+    }
+}
+
+impl Secret {
+    /// Read the secret of a config value.
+    ///
+    /// # Errors
+    ///
+    /// - `ReadFile` if the value names a file that cannot be read.
+    pub fn load(secret: &SecretRef) -> Result<Self, Error> {
+        match secret.file() {
+            Some(path) => Self::from_file(path),
+            None => Ok(Self::from_value(secret.0.clone())),
+        }
+    }
+
+    /// A secret that never rotates.
+    pub fn from_value(value: impl Into<String>) -> Self {
+        Self::new(Source::Value, value.into())
+    }
+
+    /// Read a secret from a file, without the line break that ends it.
+    ///
+    /// # Errors
+    ///
+    /// - `ReadFile` if the file cannot be read.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let source = Source::File(path.as_ref().to_path_buf());
+        let value = resolve(&source)?;
+        Ok(Self::new(source, value))
+    }
+
+    /// Fetch a secret with `resolver`, which is called again on every rotation.
+    ///
+    /// The resolver may block, ie: on a request to Vault, the rotations of
+    /// [`spawn_rotation`](Self::spawn_rotation) call it on tokio's blocking threads.
+    ///
+    /// # Errors
+    ///
+    /// - `Resolve` if `resolver` fails.
+    pub fn from_resolver<F>(resolver: F) -> Result<Self, Error>
+    where
+        F: Fn() -> Result<String, Box<dyn std::error::Error + Send + Sync>> + Send + Sync + 'static,
+    {
+        let source = Source::Resolver(Arc::new(resolver));
+        let value = resolve(&source)?;
+        Ok(Self::new(source, value))
+    }
+
+    fn new(source: Source, value: String) -> Self {
+        Self {
+            source,
+            value: Arc::new(watch::Sender::new(value.into())),
+        }
+    }
+
+    /// The current value of the secret.
+    pub fn get(&self) -> Arc<str> {
+        self.value.borrow().clone()
+    }
+
+    /// Watch the rotations of the secret.
+    pub fn subscribe(&self) -> watch::Receiver<Arc<str>> {
+        self.value.subscribe()
+    }
+
+    /// Read the secret again, returns whether it changed.
+    ///
+    /// Subscribers are only notified when the value changes.
+    ///
+    /// # Errors
+    ///
+    /// The error of the file or the resolver, the secret keeps its value.
+    pub fn refresh(&self) -> Result<bool, Error> {
+        if matches!(self.source, Source::Value) {
+            return Ok(false);
+        }
+        let value = resolve(&self.source)?;
+        Ok(self.value.send_if_modified(|current| {
+            let changed = **current != *value;
+            if changed {
+                *current = value.into();
+            }
+            changed
+        }))
+    }
+
+    /// Read the secret again on the current tokio runtime when its file changes, checking every
+    /// `interval`, or every `interval` for the secrets of a resolver, which is their time to live.
+    ///
+    /// Rotations are logged without the secret, failures are logged and the secret keeps its
+    /// value. The rotation stops when the returned task is aborted, or with the runtime.
+    /// Secrets that are values never rotate, the task ends at once. Resolvers are called with
+    /// `tokio::task::spawn_blocking`, so they don't hold up the runtime's workers.
+    #[cfg(feature = "hot-reload")]
+    pub fn spawn_rotation(&self, interval: std::time::Duration) -> tokio::task::JoinHandle<()> {
+        let secret = self.clone();
+        let rotate = move || match secret.refresh() {
+            Ok(true) => tracing::info!(secret = ?secret, "secret rotated"),
+            Ok(false) => {}
+            Err(err) => tracing::warn!(error = %err, "could not rotate the secret"),
+        };
+
+        match &self.source {
+            Source::Value => tokio::spawn(async {}),
+            Source::File(path) => crate::config::spawn_file_watch(path.clone(), interval, rotate),
+            Source::Resolver(_) => tokio::spawn(async move {
+                let mut ticks = tokio::time::interval(interval);
+                ticks.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+                // The first tick completes at once, the secret was just resolved
+                ticks.tick().await;
+                loop {
+                    ticks.tick().await;
+                    // The next rotation waits for this one, a panicking resolver is logged by
+                    // the panic hook
+                    let _ = tokio::task::spawn_blocking(rotate.clone()).await;
+                }
+            }),
+        }
+    }
+}
+
+/// Read the value of a secret from `source`.
+fn resolve(source: &Source) -> Result<String, Error> {
+    match source {
+        Source::Value => unreachable!("values are not resolved"),
+        Source::File(path) => {
+            let value = std::fs::read_to_string(path).context(ReadFileSnafu { path })?;
+            Ok(value.trim_end_matches(['\n', '\r']).to_string())
+        }
+        Source::Resolver(resolver) => resolver().context(ResolveSnafu),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    #[test]
+    fn test_secrets_come_from_values_and_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("db_password");
+        std::fs::write(&path, "hunter2\n").unwrap();
+
+        let from_file = SecretRef(format!("file:{}", path.display()));
+        assert_eq!(from_file.file(), Some(path.as_path()));
+        let secret = Secret::load(&from_file).unwrap();
+        assert_eq!(&*secret.get(), "hunter2");
+
+        let value = SecretRef("hunter3".to_string());
+        assert_eq!(&*Secret::load(&value).unwrap().get(), "hunter3");
+        assert!(!format!("{value:?} {secret:?}").contains("hunter"));
+
+        let missing = SecretRef("file:/does/not/exist".to_string());
+        assert!(matches!(
+            Secret::load(&missing),
+            Err(Error::ReadFile { .. })
+        ));
+    }
+
+    #[test]
+    fn test_refresh_notifies_on_rotation() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let secret = Secret::from_resolver(move || {
+            let call = counter.fetch_add(1, Ordering::Relaxed);
+            Ok(format!("token-{}", call / 2))
+        })
+        .unwrap();
+        let mut rotations = secret.subscribe();
+
+        // The second call returns the same token
+        assert!(!secret.refresh().unwrap());
+        assert!(!rotations.has_changed().unwrap());
+
+        assert!(secret.refresh().unwrap());
+        assert!(rotations.has_changed().unwrap());
+        assert_eq!(&**rotations.borrow_and_update(), "token-1");
+        assert_eq!(&*secret.get(), "token-1");
+    }
+
+    #[cfg(feature = "hot-reload")]
+    #[tokio::test]
+    async fn test_file_secrets_rotate_when_the_file_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("token");
+        std::fs::write(&path, "old").unwrap();
+        let secret = Secret::from_file(&path).unwrap();
+        let mut rotations = secret.subscribe();
+
+        let rotation = secret.spawn_rotation(std::time::Duration::from_millis(10));
+        // Modification times can be as coarse as a second
+        tokio::time::sleep(std::time::Duration::from_millis(1100)).await;
+        std::fs::write(&path, "new").unwrap();
+
+        rotations.changed().await.unwrap();
+        assert_eq!(&*secret.get(), "new");
+        rotation.abort();
+    }
+
+    #[cfg(feature = "hot-reload")]
+    #[tokio::test]
+    async fn test_resolvers_rotate_on_blocking_threads() {
+        let runtime_thread = std::thread::current().id();
+        let secret = Secret::from_resolver(move || {
+            // Only the rotations run on a blocking thread
+            let rotated = std::thread::current().id() != runtime_thread;
+            Ok(format!("rotated: {rotated}"))
+        })
+        .unwrap();
+        let mut rotations = secret.subscribe();
+
+        let rotation = secret.spawn_rotation(std::time::Duration::from_millis(10));
+        tokio::time::timeout(std::time::Duration::from_secs(5), rotations.changed())
+            .await
+            .expect("the resolver was not called on a blocking thread")
+            .unwrap();
+        assert_eq!(&*secret.get(), "rotated: true");
+        rotation.abort();
+    }
+}
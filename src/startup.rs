@@ -0,0 +1,200 @@
+//! # Startup Report
+//!
+//! A single structured `service started` event summarizing how a service started, so every
+//! service reports the same fields in the same shape:
+//!
+//! - `service.name`, `service.version` and `git_sha` from the [`ServiceInfo`]
+//! - `environment` and `region`, see [`Environment`](crate::Environment)
+//! - `config.path` and `config.fingerprint` of the loaded configuration
+//! - `exporters`, the OpenTelemetry signals that are exported, ie: `traces,metrics`
+//! - `listen`, the addresses the service listens on by name, ie: `http=0.0.0.0:8080`
+//!
+//! [`App`](crate::App) emits the report once telemetry is initialized, with the listen
+//! addresses from [`AppSettings::listen_addresses`](crate::app::AppSettings::listen_addresses).
+//! Services that don't use it build the report themselves:
+//!
+//! ```rust,no_run
+//! # use doku::Document;
+//! # use serde::Deserialize;
+//! # #[derive(Deserialize, Document)]
+//! # struct Settings {
+//! #     telemetry: byre::telemetry::TelemetrySettings,
+//! # }
+//! # fn demo() -> Result<(), Box<dyn std::error::Error>> {
+//! let service_info = byre::service_info!();
+//! let Some(cli) = byre::cli::Cli::<Settings>::try_new(&service_info, "MYAPP_")? else {
+//!     return Ok(());
+//! };
+//! let telemetry = byre::telemetry::init(&service_info, &cli.config.telemetry)?;
+//!
+//! byre::startup::StartupReport::new(&service_info)
+//!     .with_config(&cli.config_path, &cli.config_fingerprint)
+//!     .with_exporters(&telemetry)
+//!     .with_listener("http", "0.0.0.0:8080")
+//!     .emit();
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+
+use crate::telemetry::TelemetryProviders;
+use crate::{Environment, ServiceInfo};
+
+/// Summary of how a service started, see the [module documentation](self).
+#[derive(Clone, Debug)]
+#[must_use = "a StartupReport does nothing until it is emitted"]
+pub struct StartupReport {
+    service_name: &'static str,
+    service_version: &'static str,
+    git_sha: &'static str,
+    environment: Environment,
+    region: String,
+    config_path: Option<PathBuf>,
+    config_fingerprint: Option<String>,
+    exporters: Vec<&'static str>,
+    listeners: Vec<(String, String)>,
+}
+
+impl StartupReport {
+    /// A report of the service described by `service_info`.
+    pub fn new(service_info: &ServiceInfo) -> Self {
+        Self {
+            service_name: service_info.name,
+            service_version: service_info.version,
+            git_sha: service_info.git_sha,
+            environment: service_info.environment.unwrap_or_default(),
+            region: service_info.region.clone(),
+            config_path: None,
+            config_fingerprint: None,
+            exporters: Vec::new(),
+            listeners: Vec::new(),
+        }
+    }
+
+    /// Report the configuration file and the fingerprint of the loaded configuration.
+    pub fn with_config(mut self, path: impl AsRef<Path>, fingerprint: impl Into<String>) -> Self {
+        self.config_path = Some(path.as_ref().to_path_buf());
+        self.config_fingerprint = Some(fingerprint.into());
+        self
+    }
+
+    /// Report which signals `telemetry` exports.
+    pub fn with_exporters(mut self, telemetry: &TelemetryProviders) -> Self {
+        self.exporters = [
+            ("traces", telemetry.tracer_provider().is_some()),
+            ("metrics", telemetry.meter_provider().is_some()),
+            ("logs", telemetry.logger_provider().is_some()),
+        ]
+        .into_iter()
+        .filter_map(|(signal, exported)| exported.then_some(signal))
+        .collect();
+        self
+    }
+
+    /// Report an address the service listens on, ie: `("admin", "127.0.0.1:9000")`.
+    pub fn with_listener(mut self, name: impl Into<String>, address: impl Display) -> Self {
+        self.listeners.push((name.into(), address.to_string()));
+        self
+    }
+
+    /// Emit the report as an `INFO` event with the message `service started`.
+    pub fn emit(&self) {
+        let config_path = self
+            .config_path
+            .as_ref()
+            .map(|path| path.display().to_string());
+        let listen: Vec<_> = self
+            .listeners
+            .iter()
+            .map(|(name, address)| format!("{name}={address}"))
+            .collect();
+
+        tracing::info!(
+            service.name = self.service_name,
+            service.version = self.service_version,
+            git_sha = self.git_sha,
+            environment = %self.environment,
+            region = self.region,
+            config.path = config_path.as_deref().unwrap_or_default(),
+            config.fingerprint = self.config_fingerprint.as_deref().unwrap_or_default(),
+            exporters = %self.exporters.join(","),
+            listen = %listen.join(","),
+            "service started"
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+
+    use tracing::field::{Field, Visit};
+    use tracing_subscriber::layer::{Context, SubscriberExt as _};
+
+    use super::*;
+
+    #[derive(Default)]
+    struct Fields(HashMap<String, String>);
+
+    /// Records the fields of every event.
+    #[derive(Clone, Default)]
+    struct Events(Arc<Mutex<Vec<Fields>>>);
+
+    impl Visit for Fields {
+        fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+            self.0
+                .insert(field.name().to_string(), format!("{value:?}"));
+        }
+
+        fn record_str(&mut self, field: &Field, value: &str) {
+            self.0.insert(field.name().to_string(), value.to_string());
+        }
+    }
+
+    impl<S: tracing::Subscriber> tracing_subscriber::Layer<S> for Events {
+        fn on_event(&self, event: &tracing::Event<'_>, _ctx: Context<'_, S>) {
+            let mut fields = Fields::default();
+            event.record(&mut fields);
+            self.0.lock().unwrap().push(fields);
+        }
+    }
+
+    #[test]
+    fn test_report_is_a_single_event() {
+        let service_info = ServiceInfo {
+            name: "inventory",
+            version: "1.2.3",
+            environment: Some(Environment::Prod),
+            region: "eu-west-1".to_string(),
+            ..Default::default()
+        };
+        let report = StartupReport::new(&service_info)
+            .with_config("/etc/inventory.toml", "0123456789abcdef")
+            .with_exporters(&TelemetryProviders::default())
+            .with_listener("http", "0.0.0.0:8080")
+            .with_listener("admin", "127.0.0.1:9000");
+
+        let events = Events::default();
+        let subscriber = tracing_subscriber::registry().with(events.clone());
+        tracing::subscriber::with_default(subscriber, || report.emit());
+
+        let events = events.0.lock().unwrap();
+        assert_eq!(events.len(), 1);
+        let field = |name: &str| events[0].0.get(name).map(String::as_str);
+        assert_eq!(field("message"), Some("service started"));
+        assert_eq!(field("service.name"), Some("inventory"));
+        assert_eq!(field("service.version"), Some("1.2.3"));
+        assert_eq!(field("environment"), Some("prod"));
+        assert_eq!(field("region"), Some("eu-west-1"));
+        assert_eq!(field("config.path"), Some("/etc/inventory.toml"));
+        assert_eq!(field("config.fingerprint"), Some("0123456789abcdef"));
+        assert_eq!(field("exporters"), Some(""));
+        assert_eq!(
+            field("listen"),
+            Some("http=0.0.0.0:8080,admin=127.0.0.1:9000")
+        );
+    }
+}
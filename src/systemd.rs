@@ -0,0 +1,197 @@
+//! # systemd Notifications
+//!
+//! Implements the `sd_notify` protocol so services run under `Type=notify` units. It is only
+//! available with the `systemd` feature enabled.
+//!
+//! - [`ready`] tells systemd the service finished starting, units that depend on it start then
+//! - [`spawn_watchdog`] pings systemd's watchdog when the unit sets `WatchdogSec=`
+//! - [`stopping`] tells systemd the service is shutting down gracefully
+//!
+//! Every function does nothing when the process was not started by systemd, so services can
+//! call them unconditionally. [`App`](crate::App) calls all three when the feature is enabled.
+//!
+//! ```rust,no_run
+//! # async fn demo() -> Result<(), Box<dyn std::error::Error>> {
+//! // ... load the config and initialize telemetry ...
+//! byre::systemd::ready()?;
+//! let _watchdog = byre::systemd::spawn_watchdog();
+//!
+//! // ... serve until asked to shut down ...
+//! byre::systemd::stopping()?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::time::Duration;
+
+use snafu::{ResultExt as _, Snafu};
+
+/// The socket systemd listens on for notifications, set for `Type=notify` units.
+const NOTIFY_SOCKET: &str = "NOTIFY_SOCKET";
+
+/// The watchdog timeout in microseconds, set when the unit has `WatchdogSec=`.
+const WATCHDOG_USEC: &str = "WATCHDOG_USEC";
+
+/// The process the watchdog applies to, set with [`WATCHDOG_USEC`].
+const WATCHDOG_PID: &str = "WATCHDOG_PID";
+
+/// Errors sending a notification to systemd.
+#[derive(Debug, Snafu)]
+pub enum Error {
+    /// The notification could not be sent to the socket.
+    #[snafu(display("Could not notify systemd at {socket}: {source}"))]
+    Notify {
+        /// The notification socket.
+        socket: String,
+        /// The IO error that occurred.
+        source: std::io::Error,
+    },
+}
+
+/// Send `state` to systemd, ie: `READY=1`.
+///
+/// Returns `Ok(false)` without sending anything when the process was not started by systemd.
+///
+/// # Errors
+///
+/// - `Notify` if the notification socket cannot be reached.
+pub fn notify(state: &str) -> Result<bool, Error> {
+    let Some(socket) = std::env::var_os(NOTIFY_SOCKET) else {
+        return Ok(false);
+    };
+    let socket = socket.to_string_lossy();
+    send(&socket, state).context(NotifySnafu { socket })?;
+    Ok(true)
+}
+
+/// Tell systemd the service finished starting up.
+///
+/// # Errors
+///
+/// - `Notify` if the notification socket cannot be reached.
+pub fn ready() -> Result<bool, Error> {
+    notify("READY=1")
+}
+
+/// Tell systemd the service is shutting down.
+///
+/// # Errors
+///
+/// - `Notify` if the notification socket cannot be reached.
+pub fn stopping() -> Result<bool, Error> {
+    notify("STOPPING=1")
+}
+
+/// Tell systemd's watchdog the service is still alive.
+///
+/// # Errors
+///
+/// - `Notify` if the notification socket cannot be reached.
+pub fn watchdog() -> Result<bool, Error> {
+    notify("WATCHDOG=1")
+}
+
+/// The watchdog timeout of the unit, `None` if the watchdog is disabled for this process.
+pub fn watchdog_timeout() -> Option<Duration> {
+    parse_watchdog_timeout(
+        std::env::var(WATCHDOG_USEC).ok().as_deref(),
+        std::env::var(WATCHDOG_PID).ok().as_deref(),
+        std::process::id(),
+    )
+}
+
+fn parse_watchdog_timeout(usec: Option<&str>, pid: Option<&str>, own_pid: u32) -> Option<Duration> {
+    // The watchdog is meant for another process, ie: the parent of this one
+    if let Some(pid) = pid {
+        if pid.parse::<u32>().ok()? != own_pid {
+            return None;
+        }
+    }
+    let usec = usec?.parse::<u64>().ok().filter(|usec| *usec > 0)?;
+    Some(Duration::from_micros(usec))
+}
+
+/// Ping the watchdog at half its timeout on the current tokio runtime, as systemd recommends.
+///
+/// Returns `None` when the watchdog is disabled for this process. The pings stop when the
+/// returned task is aborted or the runtime shuts down.
+pub fn spawn_watchdog() -> Option<tokio::task::JoinHandle<()>> {
+    let interval = watchdog_timeout()? / 2;
+    Some(tokio::spawn(async move {
+        let mut ticks = tokio::time::interval(interval);
+        loop {
+            ticks.tick().await;
+            if let Err(err) = watchdog() {
+                tracing::warn!(error = %err, "could not ping the systemd watchdog");
+            }
+        }
+    }))
+}
+
+#[cfg(unix)]
+fn send(socket: &str, state: &str) -> std::io::Result<()> {
+    use std::os::unix::net::{SocketAddr, UnixDatagram};
+
+    // Sockets starting with `@` are in Linux's abstract namespace
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    let address = match socket.strip_prefix('@') {
+        Some(name) => {
+            #[cfg(target_os = "android")]
+            use std::os::android::net::SocketAddrExt as _;
+            #[cfg(target_os = "linux")]
+            use std::os::linux::net::SocketAddrExt as _;
+            SocketAddr::from_abstract_name(name)?
+        }
+        None => SocketAddr::from_pathname(socket)?,
+    };
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    let address = SocketAddr::from_pathname(socket)?;
+
+    let sender = UnixDatagram::unbound()?;
+    sender.send_to_addr(state.as_bytes(), &address)?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn send(_socket: &str, _state: &str) -> std::io::Result<()> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "systemd notifications need unix sockets",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_send_writes_the_state_to_the_socket() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notify.sock");
+        let receiver = std::os::unix::net::UnixDatagram::bind(&path).unwrap();
+
+        send(path.to_str().unwrap(), "READY=1").unwrap();
+
+        let mut buffer = [0; 64];
+        let len = receiver.recv(&mut buffer).unwrap();
+        assert_eq!(&buffer[..len], b"READY=1");
+    }
+
+    #[test]
+    fn test_watchdog_timeout_only_applies_to_its_process() {
+        let timeout = |usec, pid| parse_watchdog_timeout(usec, pid, 42);
+
+        assert_eq!(
+            timeout(Some("30000000"), None),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(
+            timeout(Some("30000000"), Some("42")),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(timeout(Some("30000000"), Some("7")), None);
+        assert_eq!(timeout(Some("0"), None), None);
+        assert_eq!(timeout(None, None), None);
+    }
+}
@@ -31,6 +31,12 @@
 //!     version: "1.0.0",
 //!     author: "Author",
 //!     description: "My service description",
+//!     git_sha: "",
+//!     git_dirty: false,
+//!     build_timestamp: "",
+//!     rustc_version: "",
+//!     environment: Some(byre::Environment::Dev),
+//!     region: String::new(),
 //! };
 //!
 //! // 2. Initialize telemetry (keep the returned handle alive for the app lifetime!)
@@ -47,6 +53,7 @@
 //! ### gRPC Metadata (linking incoming trace context)
 //!
 //! ```
+//! # #[cfg(feature = "grpc")] {
 //! use byre::telemetry::{TraceContextCarrier, TraceContextExt};
 //!
 //! // In a gRPC handler, extract trace context from incoming metadata
@@ -58,22 +65,26 @@
 //!
 //! // Or link it directly to the current span
 //! let _ = metadata.link_distributed_trace();
+//! # }
 //! ```
 //!
 //! ### gRPC Metadata (propagating trace context)
 //!
 //! ```
+//! # #[cfg(feature = "grpc")] {
 //! use byre::telemetry::{TraceContextCarrier, TraceContextExt};
 //!
 //! // Before making outgoing gRPC calls, inject trace context
 //! let mut metadata = tonic::metadata::MetadataMap::new();
 //! metadata.inject_trace_context();
 //! // metadata now contains traceparent header (if there's an active span)
+//! # }
 //! ```
 //!
 //! ### HTTP Headers (linking incoming trace context)
 //!
 //! ```
+//! # #[cfg(feature = "http")] {
 //! use byre::telemetry::{TraceContextCarrier, TraceContextExt};
 //!
 //! // In an HTTP handler, extract trace context from incoming headers
@@ -85,17 +96,20 @@
 //!
 //! // Or link it directly to the current span
 //! let _ = headers.link_distributed_trace();
+//! # }
 //! ```
 //!
 //! ### HTTP Headers (propagating trace context)
 //!
 //! ```
+//! # #[cfg(feature = "http")] {
 //! use byre::telemetry::{TraceContextCarrier, TraceContextExt};
 //!
 //! // Before making outgoing HTTP calls, inject trace context
 //! let mut headers = http::HeaderMap::new();
 //! headers.inject_trace_context();
 //! // headers now contains traceparent header (if there's an active span)
+//! # }
 //! ```
 //!
 //! ### HashMap (for message queues)
@@ -115,26 +129,158 @@
 //! let _ = headers.link_distributed_trace();
 //! ```
 
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+
 use doku::Document;
-use opentelemetry::propagation::{Extractor, Injector};
+use opentelemetry::global;
+use opentelemetry::metrics::MeterProvider as _;
 use opentelemetry::trace::TracerProvider as _;
-use opentelemetry::{global, KeyValue};
 use opentelemetry_appender_tracing::layer::OpenTelemetryTracingBridge;
-use opentelemetry_otlp::{
-    ExporterBuildError, LogExporter, MetricExporter, SpanExporter, WithExportConfig,
-};
+#[cfg(feature = "otlp")]
+use opentelemetry_otlp::ExporterBuildError;
+use opentelemetry_sdk::error::{OTelSdkError, OTelSdkResult};
 use opentelemetry_sdk::logs::SdkLoggerProvider;
-use opentelemetry_sdk::metrics::{PeriodicReader, SdkMeterProvider};
+use opentelemetry_sdk::metrics::SdkMeterProvider;
 use opentelemetry_sdk::propagation::TraceContextPropagator;
-use opentelemetry_sdk::{trace as sdktrace, Resource};
+use opentelemetry_sdk::trace as sdktrace;
 use serde::{Deserialize, Serialize};
 use snafu::{ResultExt as _, Snafu};
 use tracing::Subscriber;
 use tracing_opentelemetry::OpenTelemetryLayer;
+use tracing_subscriber::filter::Directive;
 use tracing_subscriber::prelude::*;
 use tracing_subscriber::EnvFilter;
 
-use crate::ServiceInfo;
+use crate::{Environment, ServiceInfo};
+#[cfg(feature = "grpc")]
+use grpc_context::{grpc_method, is_grpc_server_error};
+#[cfg(feature = "http")]
+use http_context::http_method;
+
+mod alloc_metrics;
+#[cfg(any(feature = "sqlx", feature = "redis"))]
+mod db_client;
+mod error_backtrace;
+mod export_handle;
+mod export_metrics;
+#[cfg(all(feature = "hot-reload", feature = "otlp"))]
+mod export_reload;
+#[cfg(feature = "grpc-client")]
+mod grpc_client;
+#[cfg(feature = "grpc")]
+mod grpc_context;
+#[cfg(feature = "grpc")]
+mod grpc_metrics;
+#[cfg(feature = "http-client")]
+mod http_client;
+#[cfg(feature = "http")]
+mod http_client_span;
+#[cfg(feature = "http")]
+mod http_context;
+#[cfg(feature = "http")]
+mod http_metrics;
+#[cfg(feature = "jemalloc")]
+mod jemalloc_metrics;
+#[cfg(feature = "hot-reload")]
+mod level_reload;
+mod log_rate_limit;
+mod messaging;
+mod metric_views;
+pub mod metrics;
+#[cfg(feature = "admin")]
+mod metrics_snapshot;
+#[cfg(feature = "otlp")]
+mod otlp;
+mod panic_hook;
+mod process_metrics;
+#[cfg(feature = "profiling")]
+mod profiling;
+mod propagation;
+mod record_error;
+#[cfg(feature = "redis")]
+mod redis_connection;
+mod request_fields;
+mod request_id;
+mod runtime_metrics;
+mod sampling;
+mod sdk_errors;
+mod span_metrics;
+#[cfg(feature = "sqlx")]
+mod sqlx_pool;
+mod static_fields;
+#[cfg(feature = "system-metrics")]
+mod system_metrics;
+#[cfg(any(test, feature = "test-util"))]
+pub mod test;
+#[cfg(test)]
+mod testing;
+#[cfg(feature = "tokio-console")]
+mod tokio_console;
+mod trace_ids;
+mod vendor;
+
+pub use alloc_metrics::{register_allocation_metrics, CountingAllocator};
+pub use export_handle::ExportHandle;
+#[cfg(feature = "grpc-client")]
+pub use grpc_client::{
+    grpc_channel, GrpcChannel, GrpcChannelBuilder, GrpcClientSettings, GrpcTlsSettings,
+};
+#[cfg(feature = "grpc")]
+pub use grpc_context::{
+    extract_trace_context, inject_trace_context, link_distributed_trace, record_grpc_status,
+    with_trace_context, GrpcResponseBody, GrpcStatusExt, GrpcTraceContextLayer,
+    GrpcTraceContextService, MetadataExtractor, MetadataInjector, SpanDetails,
+    TraceContextInterceptor,
+};
+#[cfg(feature = "grpc")]
+pub use grpc_metrics::{GrpcMetricsLayer, GrpcMetricsService};
+#[cfg(feature = "http-client")]
+pub use http_client::{http_client, HttpClient, HttpClientBuilder, HttpClientSettings};
+#[cfg(feature = "http")]
+pub use http_client_span::{http_client_span, HttpClientSpan};
+#[cfg(feature = "http")]
+pub use http_context::{
+    extract_trace_context_http, inject_trace_context_http, link_distributed_trace_http,
+    HttpHeaderExtractor, HttpHeaderInjector, HttpRouteFn, HttpTraceContextLayer,
+    HttpTraceContextService,
+};
+#[cfg(feature = "http")]
+pub use http_metrics::{HttpMetricsLayer, HttpMetricsService};
+#[cfg(all(feature = "admin", feature = "jemalloc"))]
+pub(crate) use jemalloc_metrics::heap_stats;
+#[cfg(feature = "jemalloc")]
+pub use jemalloc_metrics::register_jemalloc_metrics;
+pub use log_rate_limit::LogRateLimitSettings;
+pub use messaging::{producer_span, start_consumer_span, ProducerSpan};
+pub use metric_views::MetricView;
+#[cfg(feature = "admin")]
+pub use metrics_snapshot::MetricsSnapshot;
+pub use panic_hook::install_panic_hook;
+pub use process_metrics::{
+    register_build_info, register_container_limits, register_process_metrics,
+};
+#[cfg(feature = "profiling")]
+pub use profiling::{ProfileFormat, ProfileSettings};
+pub use propagation::Propagator;
+pub use record_error::{record_error, RecordErrorExt};
+#[cfg(feature = "redis")]
+pub use redis_connection::InstrumentedConnection;
+pub use request_fields::RequestFields;
+pub use request_id::{current_request_id, RequestId, REQUEST_ID_HEADER};
+pub use runtime_metrics::register_runtime_metrics;
+pub use sampling::SamplingRule;
+pub use span_metrics::{SpanMetricSettings, SpanMetricsLayer};
+#[cfg(feature = "sqlx")]
+pub use sqlx_pool::InstrumentedPool;
+#[cfg(feature = "system-metrics")]
+pub use system_metrics::{register_system_metrics, SystemMetricSettings};
+#[cfg(feature = "tokio-console")]
+pub use tokio_console::TokioConsoleSettings;
+pub use trace_ids::{current_span_id, current_trace_id};
+pub use vendor::{DatadogSettings, Vendor};
+
+use vendor::ExportConfig;
 
 // ============================================================================
 // Trace Context Carrier Traits
@@ -143,12 +289,16 @@ use crate::ServiceInfo;
 /// Trait for types that can carry trace context (e.g., HTTP headers, gRPC metadata).
 ///
 /// This trait provides a unified interface for extracting and injecting
-/// W3C Trace Context headers across different transport types.
+/// trace context headers across different transport types, in the formats of
+/// [`TraceSettings::propagators`], W3C Trace Context by default.
 ///
 /// Implementations are provided for:
-/// - `tonic::metadata::MetadataMap` (gRPC)
-/// - `http::HeaderMap` (HTTP)
+/// - `tonic::metadata::MetadataMap` (gRPC), with the `grpc` feature
+/// - `http::HeaderMap` (HTTP), with the `http` feature
 /// - `HashMap<String, String>` (message queues, generic use)
+///
+/// The provided implementations also carry the [`RequestId`] of the current span in
+/// `x-request-id`.
 pub trait TraceContextCarrier {
     /// Extract trace context from this carrier.
     ///
@@ -156,10 +306,28 @@ pub trait TraceContextCarrier {
     /// to an incoming distributed trace.
     fn extract_trace_context(&self) -> opentelemetry::Context;
 
+    /// Extract trace context from this carrier onto `cx`, rather than onto the current context.
+    ///
+    /// The values the carrier doesn't have are kept from `cx`, so extracting onto
+    /// `opentelemetry::Context::new()` tells a remote trace apart from the current span. The
+    /// default extracts with `cx` as the current context, carriers with an extractor extract onto
+    /// it directly.
+    fn extract_trace_context_with(&self, cx: &opentelemetry::Context) -> opentelemetry::Context {
+        let _guard = cx.clone().attach();
+        self.extract_trace_context()
+    }
+
     /// Inject the current span's trace context into this carrier.
     ///
     /// Call this before making outgoing requests to propagate the trace.
     fn inject_trace_context(&mut self);
+
+    /// Extract the request id from this carrier, `None` if it doesn't carry a valid one.
+    ///
+    /// Carriers that don't carry request ids don't need to implement it.
+    fn extract_request_id(&self) -> Option<RequestId> {
+        None
+    }
 }
 
 /// Extension trait providing convenient methods for trace context propagation.
@@ -168,6 +336,7 @@ pub trait TraceContextCarrier {
 /// [`TraceContextCarrier`]. Import this trait to use the extension methods:
 ///
 /// ```
+/// # #[cfg(feature = "http")] {
 /// use byre::telemetry::{TraceContextCarrier, TraceContextExt};
 ///
 /// // Now you can call these methods on any carrier type:
@@ -175,6 +344,7 @@ pub trait TraceContextCarrier {
 /// let ctx = headers.extract_trace_context();
 /// headers.inject_trace_context();
 /// let _ = headers.link_distributed_trace();
+/// # }
 /// ```
 pub trait TraceContextExt: TraceContextCarrier {
     /// Link the current tracing span to an incoming distributed trace.
@@ -182,35 +352,155 @@ pub trait TraceContextExt: TraceContextCarrier {
     /// This is a convenience method that extracts the trace context and
     /// sets it as the parent of the current span. Call this at the start
     /// of your handler after the `#[tracing::instrument]` span is created.
+    /// The request id the carrier carries, if any, becomes the one of the current span.
     ///
     /// Returns `Ok(())` if successful, or an error if the span context
     /// couldn't be set. Most callers will want to ignore the error:
     ///
     /// ```
+    /// # #[cfg(feature = "http")] {
     /// use byre::telemetry::{TraceContextCarrier, TraceContextExt};
     ///
     /// let headers = http::HeaderMap::new();
     /// let _ = headers.link_distributed_trace();
+    /// # }
     /// ```
     fn link_distributed_trace(&self) -> Result<(), Error>;
+
+    /// Extract the trace context from this carrier, `None` if it doesn't carry a valid one.
+    ///
+    /// [`extract_trace_context`](TraceContextCarrier::extract_trace_context) always returns a
+    /// context, even when the headers are missing or malformed. Use this to tell whether the
+    /// request is part of a remote trace:
+    ///
+    /// ```
+    /// # #[cfg(feature = "http")] {
+    /// use byre::telemetry::TraceContextExt;
+    ///
+    /// let headers = http::HeaderMap::new();
+    /// match headers.try_extract_trace_context() {
+    ///     Some(_remote) => tracing::debug!("continuing a remote trace"),
+    ///     None => tracing::debug!("starting a new trace"),
+    /// }
+    /// # }
+    /// ```
+    fn try_extract_trace_context(&self) -> Option<opentelemetry::Context>;
+
+    /// Link the current tracing span to the trace context carried by `self`, without changing
+    /// the span's parent.
+    ///
+    /// Use this instead of [`link_distributed_trace`](Self::link_distributed_trace) when one
+    /// span handles many messages, such as a batch consumer: every message adds a link to its
+    /// producer, while the batch span stays in its own trace. Carriers without a valid trace
+    /// context are ignored.
+    ///
+    /// ```
+    /// use std::collections::HashMap;
+    /// use byre::telemetry::TraceContextExt;
+    ///
+    /// let batch: Vec<HashMap<String, String>> = Vec::new();
+    /// let span = tracing::info_span!("process_batch", messages = batch.len());
+    /// let _enter = span.enter();
+    /// for headers in &batch {
+    ///     headers.link_remote_context();
+    /// }
+    /// ```
+    fn link_remote_context(&self);
+
+    /// Inject the current span's trace context and return the carrier.
+    ///
+    /// Useful when building a request in one expression, where borrowing the carrier mutably
+    /// is awkward:
+    ///
+    /// ```
+    /// use std::collections::HashMap;
+    /// use byre::telemetry::TraceContextExt;
+    ///
+    /// fn publish(payload: &str, headers: HashMap<String, String>) { /* ... */ }
+    ///
+    /// publish("payload", HashMap::new().with_trace_context());
+    /// ```
+    fn with_trace_context(self) -> Self
+    where
+        Self: Sized;
 }
 
 impl<T: TraceContextCarrier> TraceContextExt for T {
     fn link_distributed_trace(&self) -> Result<(), Error> {
         use tracing_opentelemetry::OpenTelemetrySpanExt;
         let parent_cx = self.extract_trace_context();
+        if let Some(request_id) = self.extract_request_id() {
+            request_id.attach(&tracing::Span::current());
+        }
         tracing::Span::current()
             .set_parent(parent_cx)
             .map_err(|e| Error::LinkDistributedTrace {
                 source: Box::new(e),
             })
     }
+
+    fn try_extract_trace_context(&self) -> Option<opentelemetry::Context> {
+        use opentelemetry::trace::TraceContextExt as _;
+        // Onto an empty context, so the current span isn't mistaken for a remote one
+        let cx = self.extract_trace_context_with(&opentelemetry::Context::new());
+        let span_context = cx.span().span_context().clone();
+        (span_context.is_valid() && span_context.is_remote()).then_some(cx)
+    }
+
+    fn link_remote_context(&self) {
+        use opentelemetry::trace::TraceContextExt as _;
+        use tracing_opentelemetry::OpenTelemetrySpanExt;
+        if let Some(remote_cx) = self.try_extract_trace_context() {
+            let span_context = remote_cx.span().span_context().clone();
+            tracing::Span::current().add_link(span_context);
+        }
+    }
+
+    fn with_trace_context(mut self) -> Self {
+        self.inject_trace_context();
+        self
+    }
 }
 
 /// Errors initializing telemetry
 #[derive(Debug, Snafu)]
 pub enum Error {
+    /// Telemetry was already initialized, or another global tracing subscriber is installed
+    #[snafu(display(
+        "Telemetry is already initialized, a global tracing subscriber is installed"
+    ))]
+    AlreadyInitialized,
+
+    /// Could not build the instrumented HTTP client
+    #[cfg(feature = "http-client")]
+    #[snafu(display("Could not build the HTTP client: {source}"))]
+    BuildHttpClient {
+        /// The error from reqwest
+        source: reqwest::Error,
+    },
+
+    /// Could not build the instrumented gRPC channel
+    #[cfg(feature = "grpc-client")]
+    #[snafu(display("Could not build the gRPC channel to {endpoint:?}: {source}"))]
+    BuildGrpcChannel {
+        /// The endpoint of the channel
+        endpoint: String,
+        /// The error from tonic
+        source: tonic::transport::Error,
+    },
+
+    /// Could not read a certificate or key file of the gRPC channel's TLS settings
+    #[cfg(feature = "grpc-client")]
+    #[snafu(display("Could not read the gRPC TLS file {}: {source}", path.display()))]
+    ReadGrpcTlsFile {
+        /// The file
+        path: std::path::PathBuf,
+        /// The error reading the file
+        source: std::io::Error,
+    },
+
     /// Could not initialize the logger
+    #[cfg(feature = "otlp")]
     #[snafu(display("Could not initialize logging: {source}"))]
     InitLog {
         /// The error from initializing the gRPC connection
@@ -218,13 +508,43 @@ pub enum Error {
     },
 
     /// Could not initialize metrics
+    #[cfg(feature = "otlp")]
     #[snafu(display("Could not initialize metrics: {source}"))]
     InitMetric {
         /// The error from initializing the gRPC connection
         source: ExporterBuildError,
     },
 
+    /// Could not create the profile file
+    #[cfg(feature = "profiling")]
+    #[snafu(display("Could not create the profile file {}: {source}", path.display()))]
+    InitProfile {
+        /// The path of the profile file
+        path: std::path::PathBuf,
+        /// The error from creating the file
+        source: std::io::Error,
+    },
+
+    /// The tokio-console listen address could not be resolved
+    #[cfg(feature = "tokio-console")]
+    #[snafu(display("Could not resolve the tokio-console address {listen}: {source}"))]
+    ResolveTokioConsoleAddress {
+        /// The configured listen address
+        listen: String,
+        /// The error from resolving the address
+        source: std::io::Error,
+    },
+
+    /// The tokio-console listen address did not resolve to any address
+    #[cfg(feature = "tokio-console")]
+    #[snafu(display("The tokio-console address {listen} did not resolve to any address"))]
+    InvalidTokioConsoleAddress {
+        /// The configured listen address
+        listen: String,
+    },
+
     /// Could not initialize tracing
+    #[cfg(feature = "otlp")]
     #[snafu(display("Could not initialize tracing: {source}"))]
     InitTrace {
         /// The error from initializing the gRPC connection
@@ -237,6 +557,50 @@ pub enum Error {
         /// The underlying error from tracing-opentelemetry
         source: Box<dyn std::error::Error + Send + Sync>,
     },
+
+    /// A metric view from the settings cannot be applied
+    #[snafu(display("Invalid metric view for instrument {instrument:?}: {reason}"))]
+    InvalidMetricView {
+        /// The instrument name of the view
+        instrument: String,
+        /// Why the view is invalid
+        reason: String,
+    },
+
+    /// A log level could not be parsed as a filter directive
+    #[snafu(display("Invalid log level {level:?}: {source}"))]
+    InvalidLogLevel {
+        /// The log level that was rejected
+        level: String,
+        /// The parse error from tracing-subscriber
+        source: tracing_subscriber::filter::ParseError,
+    },
+
+    /// A vendor preset setting could not be sent as an export header
+    #[cfg(feature = "otlp")]
+    #[snafu(display("Invalid value for the {header} export header"))]
+    InvalidExportHeader {
+        /// The header that was rejected
+        header: &'static str,
+        /// The error from tonic
+        source: tonic::metadata::errors::InvalidMetadataValue,
+    },
+
+    /// A suppressed target could not be parsed as a filter directive
+    #[snafu(display("Invalid suppressed target {target:?}: {source}"))]
+    InvalidSuppressedTarget {
+        /// The target that was rejected
+        target: String,
+        /// The parse error from tracing-subscriber
+        source: tracing_subscriber::filter::ParseError,
+    },
+
+    /// The log level filter could not be reloaded
+    #[snafu(display("Could not reload log level: {source}"))]
+    ReloadLogLevel {
+        /// The reload error from tracing-subscriber
+        source: tracing_subscriber::reload::Error,
+    },
 }
 
 /// Settings for metrics collection and export.
@@ -245,9 +609,35 @@ pub enum Error {
 /// Examples include request counts, error rates, response times, and resource usage.
 #[derive(Debug, Default, Serialize, Deserialize, Document)]
 pub struct MetricSettings {
-    /// gRPC endpoint to send metrics to. Omit to disable opentelemetry metrics.
+    /// gRPC endpoint to send metrics to. Omit to use the local collector outside of the `dev` environment, set to "" to
+    /// disable opentelemetry metrics. Use `unix:///path/to.sock` for a Unix domain socket.
     #[doku(example = "http://localhost:4318/v1/metrics")]
     pub endpoint: Option<String>,
+
+    /// Derive request, error and duration metrics from spans.
+    #[serde(default)]
+    pub span_metrics: SpanMetricSettings,
+
+    /// Export metrics about the tokio runtime that `init` is called from, ie: worker count and queue depth.
+    #[doku(example = "true")]
+    #[serde(default)]
+    pub runtime_metrics: bool,
+
+    /// Prefix the names of application metrics with the service's `name_in_metrics`, ie: `requests`
+    /// becomes `my_service.requests`. Metrics recorded by byre itself are not prefixed.
+    #[doku(example = "true")]
+    #[serde(default)]
+    pub prefix_service_name: bool,
+
+    /// Views that change how instruments are aggregated, ie: histogram bucket boundaries.
+    /// Views match the instrument name before any service name prefix is added.
+    #[serde(default)]
+    pub views: Vec<MetricView>,
+
+    /// Export metrics about the host system, requires the `system-metrics` feature.
+    #[cfg(feature = "system-metrics")]
+    #[serde(default)]
+    pub system: SystemMetricSettings,
 }
 
 /// Settings for logging configuration.
@@ -256,31 +646,189 @@ pub struct MetricSettings {
 /// for debugging and monitoring application behavior.
 ///
 /// Note: `otel_level` will filter the logs before they are sent to the console, so if `otel_level` is `warn`, then `console_level` can only be `warn`, `error`, or `off`.
-#[derive(Debug, Default, Serialize, Deserialize, Document)]
+#[derive(Debug, Serialize, Deserialize, Document)]
 pub struct LogSettings {
     /// log level used when filtering console logs. Uses env-logger style syntax. Set to "off" to disable console logging.
     /// `console_level` is limited by `otel_level`, so if `otel_level` is `warn`, then `console_level` can only be `warn`, `error`, or `off`.
+    /// Leave empty to use the `RUST_LOG` environment variable.
     #[doku(example = "debug,yourcrate=info")]
+    #[serde(default)]
     pub console_level: String,
 
+    /// Prefix console logs emitted inside a span with its `trace_id` and `span_id`, to find the trace of a log line.
+    #[doku(example = "true")]
+    #[serde(default)]
+    pub console_trace_ids: bool,
+
+    /// Format of the console logs: `full` lines, multi-line `pretty` output, or one `json` object per line.
+    /// Omit to use `pretty` in the `dev` environment, `json` in the others, and `full` when the environment is unknown.
+    #[doku(as = "Option<String>", example = "json")]
+    #[serde(default)]
+    pub console_format: Option<ConsoleFormat>,
+
+    /// Attach the backtrace of the code that emitted them to `ERROR` events, as `exception.stacktrace`.
+    /// Capturing a backtrace is slow, enable it when errors are rare.
+    #[doku(example = "true")]
+    #[serde(default)]
+    pub error_backtraces: bool,
+
     /// log level used when filtering opentelemetry logs. Uses env-logger style syntax.
+    /// Leave empty to use the `RUST_LOG` environment variable.
     #[doku(example = "warn,yourcrate=debug")]
+    #[serde(default)]
     pub otel_level: String,
 
-    /// gRPC endpoint to send the opentelemetry logs. Omit to disable opentelemetry logs, will not disable console logs.
+    /// log level used when filtering the spans exported to opentelemetry traces. Uses env-logger style syntax.
+    /// Leave empty to use `otel_level`.
+    #[doku(example = "debug")]
+    #[serde(default)]
+    pub trace_level: String,
+
+    /// gRPC endpoint to send the opentelemetry logs. Omit to use the local collector outside of the `dev` environment,
+    /// set to "" to disable opentelemetry logs, will not disable console logs.
+    /// Use `unix:///path/to.sock` for a Unix domain socket.
     #[doku(example = "http://localhost:4317")]
     pub endpoint: Option<String>,
+
+    /// Rate limit identical log events, for both console and opentelemetry logs. Omit to log every event.
+    #[serde(default)]
+    pub rate_limit: Option<LogRateLimitSettings>,
+
+    /// Targets whose events are never sent to opentelemetry, so the exporters don't report on themselves.
+    /// Defaults to hyper, opentelemetry, opentelemetry_sdk, tonic, h2 and reqwest. Console logs are not affected.
+    #[doku(example = "hyper")]
+    #[serde(default = "default_otel_suppressed_targets")]
+    pub otel_suppressed_targets: Vec<String>,
+
+    /// Level of the OpenTelemetry SDK's own diagnostics on the console, ie: a rejected export, whatever `console_level` is.
+    /// They are never sent to opentelemetry and are rate limited. Set to "" to filter them with `console_level`.
+    #[doku(example = "warn")]
+    #[serde(default = "default_otel_diagnostics_level")]
+    pub otel_diagnostics_level: String,
+
+    /// Fields added to every log record and span, in both the console and opentelemetry outputs,
+    /// ie: the team, shard or datacenter of the service. A field set by the event or span wins.
+    #[serde(default)]
+    pub fields: std::collections::BTreeMap<String, String>,
+}
+
+impl Default for LogSettings {
+    fn default() -> Self {
+        Self {
+            console_level: String::new(),
+            console_trace_ids: false,
+            console_format: None,
+            error_backtraces: false,
+            otel_level: String::new(),
+            trace_level: String::new(),
+            endpoint: None,
+            rate_limit: None,
+            otel_suppressed_targets: default_otel_suppressed_targets(),
+            otel_diagnostics_level: default_otel_diagnostics_level(),
+            fields: Default::default(),
+        }
+    }
 }
 
 /// Settings for distributed tracing.
 ///
 /// Traces track the flow of requests as they propagate through your system, helping you
 /// understand the execution path and identify performance bottlenecks.
-#[derive(Debug, Default, Serialize, Deserialize, Document)]
+#[derive(Debug, Serialize, Deserialize, Document)]
 pub struct TraceSettings {
-    /// gRPC endpoint to send opentelemetry traces to, omit to disable.
+    /// gRPC endpoint to send opentelemetry traces to. Omit to use the local collector outside of the `dev` environment,
+    /// set to "" to disable. Use `unix:///path/to.sock` for a Unix domain socket.
     #[doku(example = "http://localhost:4317")]
     pub endpoint: Option<String>,
+
+    /// Limits on what a span keeps, anything past a limit is dropped from the exported span.
+    #[serde(default)]
+    pub limits: SpanLimitSettings,
+
+    /// Sample a ratio of the requests to a route, given to the server layers with `with_sampling`. The first matching
+    /// rule decides, requests that match none are sampled.
+    #[serde(default)]
+    pub sampling: Vec<SamplingRule>,
+
+    /// Trace context formats read from incoming requests and written to outgoing ones: `tracecontext` for W3C Trace
+    /// Context, `xray` for the `X-Amzn-Trace-Id` header of AWS load balancers, `cloudtrace` for the
+    /// `X-Cloud-Trace-Context` header of GCP load balancers and Cloud Run. When a request carries several, the last one
+    /// listed wins. Defaults to `tracecontext`.
+    #[doku(as = "Vec<String>", example = "tracecontext")]
+    #[serde(default = "propagation::default_propagators")]
+    pub propagators: Vec<Propagator>,
+}
+
+impl Default for TraceSettings {
+    fn default() -> Self {
+        Self {
+            endpoint: None,
+            limits: SpanLimitSettings::default(),
+            sampling: Vec::new(),
+            propagators: propagation::default_propagators(),
+        }
+    }
+}
+
+/// Limits on the attributes, events and links of exported spans.
+///
+/// Past a limit the latest additions are dropped, and the exported span counts what was
+/// dropped. Raise them for spans that record many events, such as one per database query.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Document)]
+pub struct SpanLimitSettings {
+    /// Maximum number of attributes on a span.
+    #[doku(example = "128")]
+    #[serde(default = "default_span_limit")]
+    pub max_attributes_per_span: u32,
+
+    /// Maximum number of events, ie: log events emitted inside the span, on a span.
+    #[doku(example = "128")]
+    #[serde(default = "default_span_limit")]
+    pub max_events_per_span: u32,
+
+    /// Maximum number of links on a span.
+    #[doku(example = "128")]
+    #[serde(default = "default_span_limit")]
+    pub max_links_per_span: u32,
+
+    /// Maximum number of attributes on a span event.
+    #[doku(example = "128")]
+    #[serde(default = "default_span_limit")]
+    pub max_attributes_per_event: u32,
+
+    /// Maximum number of attributes on a span link.
+    #[doku(example = "128")]
+    #[serde(default = "default_span_limit")]
+    pub max_attributes_per_link: u32,
+}
+
+impl Default for SpanLimitSettings {
+    fn default() -> Self {
+        Self {
+            max_attributes_per_span: default_span_limit(),
+            max_events_per_span: default_span_limit(),
+            max_links_per_span: default_span_limit(),
+            max_attributes_per_event: default_span_limit(),
+            max_attributes_per_link: default_span_limit(),
+        }
+    }
+}
+
+/// The OpenTelemetry SDK's default for every span limit.
+fn default_span_limit() -> u32 {
+    128
+}
+
+impl From<SpanLimitSettings> for sdktrace::SpanLimits {
+    fn from(limits: SpanLimitSettings) -> Self {
+        Self {
+            max_events_per_span: limits.max_events_per_span,
+            max_attributes_per_span: limits.max_attributes_per_span,
+            max_links_per_span: limits.max_links_per_span,
+            max_attributes_per_event: limits.max_attributes_per_event,
+            max_attributes_per_link: limits.max_attributes_per_link,
+        }
+    }
 }
 
 /**
@@ -313,12 +861,85 @@ pub struct Application {
 ``` */
 #[derive(Debug, Default, Serialize, Deserialize, Document)]
 pub struct TelemetrySettings {
+    /// Environment the service runs in: `dev`, `staging` or `prod`. Picks the console format, and whether
+    /// traces, logs and metrics are exported to the local collector when they don't set an endpoint.
+    /// Omit to use the `BYRE_ENV` environment variable, without it the console logs are `full` and nothing is exported.
+    #[doku(as = "Option<String>", example = "prod")]
+    #[serde(default)]
+    pub environment: Option<Environment>,
+    /// Only log to the console: no OpenTelemetry provider is created and nothing is exported,
+    /// whatever the endpoints. For CLI tools, which can also build byre without the `otlp`
+    /// feature to leave the exporters out of the binary.
+    #[doku(example = "false")]
+    #[serde(default)]
+    pub local_only: bool,
     /// Settings for tracing
+    #[serde(alias = "traces")]
     pub trace: TraceSettings,
     /// Settings for logging
+    #[serde(alias = "logs")]
     pub log: LogSettings,
     /// Settings for metrics
+    #[serde(alias = "metrics")]
     pub metric: MetricSettings,
+    /// Record panics as error events and count them before the default panic hook runs.
+    #[doku(example = "true")]
+    #[serde(default)]
+    pub panic_hook: bool,
+    /// Configure the exporters for an observability vendor, ie: `datadog`. Omit to configure them yourself.
+    #[doku(as = "Option<String>", example = "datadog")]
+    #[serde(default)]
+    pub vendor: Option<Vendor>,
+    /// Settings for the Datadog preset, used when `vendor` is `datadog`.
+    #[serde(default)]
+    pub datadog: DatadogSettings,
+    /// Compress the OTLP exports of traces, logs and metrics with `gzip` or `zstd`, which require the
+    /// feature of the same name. Omit to send them uncompressed.
+    #[doku(as = "Option<String>", example = "gzip")]
+    #[serde(default)]
+    pub compression: Option<Compression>,
+    /// Write a flamegraph or chrome://tracing profile of the run to a file, requires the `profiling` feature.
+    /// Meant for development, omit to disable.
+    #[cfg(feature = "profiling")]
+    #[serde(default)]
+    pub profile: Option<ProfileSettings>,
+    /// Serve tokio-console so developers can inspect the running tasks, requires the `tokio-console` feature.
+    #[cfg(feature = "tokio-console")]
+    #[serde(default)]
+    pub tokio_console: TokioConsoleSettings,
+}
+
+/// Formats of the console logs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, Document)]
+#[serde(rename_all = "lowercase")]
+pub enum ConsoleFormat {
+    /// One line per event, with its fields and the spans it is in.
+    Full,
+    /// Multiple indented lines per event, easier to read while developing.
+    Pretty,
+    /// One JSON object per event, for log collectors.
+    Json,
+}
+
+impl ConsoleFormat {
+    /// The format used when none is configured, `full` when the environment is unknown.
+    fn default_for(environment: Option<Environment>) -> Self {
+        match environment {
+            None => Self::Full,
+            Some(Environment::Dev) => Self::Pretty,
+            Some(Environment::Staging | Environment::Prod) => Self::Json,
+        }
+    }
+}
+
+/// Compression algorithms for the OTLP exporters.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, Document)]
+#[serde(rename_all = "lowercase")]
+pub enum Compression {
+    /// gzip, requires the `gzip` feature.
+    Gzip,
+    /// zstd, requires the `zstd` feature.
+    Zstd,
 }
 
 /// Container for the initialized telemetry providers.
@@ -326,649 +947,1183 @@ pub struct TelemetrySettings {
 /// This struct owns the telemetry providers and ensures they are properly
 /// shut down when dropped. You must keep this value alive for the duration
 /// of your application; dropping it will shut down all telemetry.
+///
+/// The errors of a shutdown by drop go to the handler set with
+/// [`on_shutdown_error`](Self::on_shutdown_error), or to the [last-chance
+/// hook](set_shutdown_error_hook), and are printed to stderr without either.
 #[derive(Debug, Default)]
 #[must_use = "dropping TelemetryProviders will shut down all telemetry"]
 pub struct TelemetryProviders {
     meter: Option<SdkMeterProvider>,
     tracer: Option<sdktrace::SdkTracerProvider>,
     logger: Option<SdkLoggerProvider>,
+    log_levels: Option<LogLevelHandle>,
+    exports: ExportHandle,
+    on_shutdown_error: Option<ShutdownErrorHandler>,
+    #[cfg(feature = "admin")]
+    metrics_snapshot: Option<MetricsSnapshot>,
+    #[cfg(feature = "profiling")]
+    profile: Option<profiling::ProfileGuard>,
+}
+
+/// Receives the errors of the providers shut down by drop.
+struct ShutdownErrorHandler(Box<dyn FnOnce(ShutdownError) + Send>);
+
+impl std::fmt::Debug for ShutdownErrorHandler {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("ShutdownErrorHandler")
+    }
+}
+
+impl TelemetryProviders {
+    /// Handle for changing the log levels at runtime.
+    ///
+    /// Returns `None` if the providers were not created by [`init`].
+    pub fn log_levels(&self) -> Option<&LogLevelHandle> {
+        self.log_levels.as_ref()
+    }
+
+    /// Handle for turning the export of traces, logs or metrics off and on at runtime, and for
+    /// pointing the exporters at new endpoints.
+    pub fn exports(&self) -> &ExportHandle {
+        &self.exports
+    }
+
+    /// The meter provider, `None` if metrics are not exported.
+    ///
+    /// Use it to create meters with their own instrumentation scope, or to hand the provider to
+    /// libraries that record metrics. Shutting it down also stops byre's metrics.
+    pub fn meter_provider(&self) -> Option<&SdkMeterProvider> {
+        self.meter.as_ref()
+    }
+
+    /// Snapshot of the current metric values, `None` if metrics are not exported.
+    #[cfg(feature = "admin")]
+    pub fn metrics_snapshot(&self) -> Option<&MetricsSnapshot> {
+        self.metrics_snapshot.as_ref()
+    }
+
+    /// The tracer provider, `None` if traces are not exported.
+    pub fn tracer_provider(&self) -> Option<&sdktrace::SdkTracerProvider> {
+        self.tracer.as_ref()
+    }
+
+    /// The logger provider, `None` if logs are not exported.
+    pub fn logger_provider(&self) -> Option<&SdkLoggerProvider> {
+        self.logger.as_ref()
+    }
+
+    /// Hand the errors of the shutdown by drop to `handler` instead of printing them to stderr,
+    /// ie: to route them to the service's own error reporting.
+    ///
+    /// The handler is only called when a provider fails to shut down, and never after
+    /// [`shutdown`](Self::shutdown), which returns its errors.
+    ///
+    /// ```rust,no_run
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let service = byre::ServiceInfo::default();
+    /// # let settings = byre::telemetry::TelemetrySettings::default();
+    /// # fn report(_: &dyn std::error::Error) {}
+    /// let telemetry = byre::telemetry::init(&service, &settings)?
+    ///     .on_shutdown_error(|err| report(&err));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn on_shutdown_error(
+        mut self,
+        handler: impl FnOnce(ShutdownError) + Send + 'static,
+    ) -> Self {
+        self.on_shutdown_error = Some(ShutdownErrorHandler(Box::new(handler)));
+        self
+    }
+
+    /// Flush and shut down all providers, waiting at most `timeout` in total.
+    ///
+    /// Unlike dropping the providers, errors are returned instead of reported. Every
+    /// provider is shut down even if an earlier one fails. The metrics SDK does not bound its
+    /// shutdown, so the meter provider may take longer than the remaining time.
+    ///
+    /// # Errors
+    ///
+    /// - [`ShutdownError`] listing every provider that did not shut down cleanly.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let service = byre::ServiceInfo::default();
+    /// # let settings = byre::telemetry::TelemetrySettings::default();
+    /// let telemetry = byre::telemetry::init(&service, &settings)?;
+    /// // ... run the service ...
+    /// telemetry.shutdown(std::time::Duration::from_secs(5))?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn shutdown(mut self, timeout: Duration) -> Result<(), ShutdownError> {
+        let deadline = Instant::now() + timeout;
+        let remaining = || deadline.saturating_duration_since(Instant::now());
+
+        let failures = provider_failures([
+            (
+                Provider::Tracer,
+                self.tracer
+                    .take()
+                    .map(|provider| provider.shutdown_with_timeout(remaining())),
+            ),
+            (
+                Provider::Logger,
+                self.logger
+                    .take()
+                    .map(|provider| provider.shutdown_with_timeout(remaining())),
+            ),
+            (
+                Provider::Meter,
+                self.meter
+                    .take()
+                    .map(|provider| provider.shutdown_with_timeout(remaining())),
+            ),
+        ]);
+
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            Err(ShutdownError { failures })
+        }
+    }
+
+    /// Export the pending spans, logs and metrics without shutting anything down.
+    ///
+    /// Use it before forking, before a batch job sleeps for a long time, or before the process
+    /// aborts. Every provider is flushed even if an earlier one fails.
+    ///
+    /// # Errors
+    ///
+    /// - [`FlushError`] listing every provider that could not be flushed.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let service = byre::ServiceInfo::default();
+    /// # let settings = byre::telemetry::TelemetrySettings::default();
+    /// let telemetry = byre::telemetry::init(&service, &settings)?;
+    /// // ... process a batch ...
+    /// telemetry.force_flush()?;
+    /// std::thread::sleep(std::time::Duration::from_secs(3600));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn force_flush(&self) -> Result<(), FlushError> {
+        let failures = provider_failures([
+            (
+                Provider::Tracer,
+                self.tracer.as_ref().map(|provider| provider.force_flush()),
+            ),
+            (
+                Provider::Logger,
+                self.logger.as_ref().map(|provider| provider.force_flush()),
+            ),
+            (
+                Provider::Meter,
+                self.meter.as_ref().map(|provider| provider.force_flush()),
+            ),
+        ]);
+
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            Err(FlushError { failures })
+        }
+    }
+}
+
+/// The errors of the providers that failed, `None` is a provider that isn't configured.
+fn provider_failures<const N: usize>(
+    results: [(Provider, Option<OTelSdkResult>); N],
+) -> Vec<ProviderError> {
+    results
+        .into_iter()
+        .filter_map(|(provider, result)| match result {
+            Some(Err(source)) => Some(ProviderError { provider, source }),
+            _ => None,
+        })
+        .collect()
+}
+
+/// One of the providers owned by [`TelemetryProviders`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Provider {
+    /// The tracer provider exporting traces
+    Tracer,
+    /// The logger provider exporting logs
+    Logger,
+    /// The meter provider exporting metrics
+    Meter,
+}
+
+impl std::fmt::Display for Provider {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Tracer => "tracer",
+            Self::Logger => "logger",
+            Self::Meter => "meter",
+        })
+    }
+}
+
+/// A provider that failed to flush or shut down.
+#[derive(Debug, Snafu)]
+#[snafu(display("{provider} provider: {source}"))]
+pub struct ProviderError {
+    /// The provider that failed
+    pub provider: Provider,
+    /// The error from the OpenTelemetry SDK
+    pub source: OTelSdkError,
+}
+
+/// Errors from [`TelemetryProviders::shutdown`].
+#[derive(Debug)]
+pub struct ShutdownError {
+    failures: Vec<ProviderError>,
+}
+
+impl ShutdownError {
+    /// Every provider that did not shut down cleanly, in shutdown order.
+    pub fn failures(&self) -> &[ProviderError] {
+        &self.failures
+    }
+}
+
+impl std::fmt::Display for ShutdownError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write_failures(f, "Could not shut down telemetry", &self.failures)
+    }
+}
+
+impl std::error::Error for ShutdownError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        first_failure(&self.failures)
+    }
+}
+
+/// The hook set with [`set_shutdown_error_hook`].
+type ShutdownErrorHook = Box<dyn Fn(ShutdownError) + Send + Sync>;
+
+static SHUTDOWN_ERROR_HOOK: RwLock<Option<ShutdownErrorHook>> = RwLock::new(None);
+
+/// Set the last-chance hook that receives the shutdown errors nobody else collects, replacing
+/// the previous one.
+///
+/// That is the errors of [`TelemetryProviders`] dropped without a handler of their own, ie: while
+/// a panic unwinds, and the shutdown errors [`App`](crate::App) cannot return because the main
+/// function failed first. Without a hook they are printed to stderr.
+///
+/// ```rust
+/// byre::telemetry::set_shutdown_error_hook(|err| {
+///     for failure in err.failures() {
+///         eprintln!("telemetry lost: {} provider: {}", failure.provider, failure.source);
+///     }
+/// });
+/// ```
+pub fn set_shutdown_error_hook(hook: impl Fn(ShutdownError) + Send + Sync + 'static) {
+    *SHUTDOWN_ERROR_HOOK
+        .write()
+        .unwrap_or_else(|err| err.into_inner()) = Some(Box::new(hook));
+}
+
+/// Hand `err` to the hook set with [`set_shutdown_error_hook`], or print it to stderr.
+pub(crate) fn report_shutdown_error(err: ShutdownError) {
+    let hook = SHUTDOWN_ERROR_HOOK
+        .read()
+        .unwrap_or_else(|err| err.into_inner());
+    match hook.as_ref() {
+        Some(hook) => hook(err),
+        None => eprintln!("{err}"),
+    }
+}
+
+/// Errors from [`TelemetryProviders::force_flush`].
+#[derive(Debug)]
+pub struct FlushError {
+    failures: Vec<ProviderError>,
+}
+
+impl FlushError {
+    /// Every provider that could not be flushed, in flush order.
+    pub fn failures(&self) -> &[ProviderError] {
+        &self.failures
+    }
+}
+
+impl std::fmt::Display for FlushError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write_failures(f, "Could not flush telemetry", &self.failures)
+    }
+}
+
+impl std::error::Error for FlushError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        first_failure(&self.failures)
+    }
+}
+
+fn write_failures(
+    f: &mut std::fmt::Formatter<'_>,
+    message: &str,
+    failures: &[ProviderError],
+) -> std::fmt::Result {
+    f.write_str(message)?;
+    for (i, failure) in failures.iter().enumerate() {
+        f.write_str(if i == 0 { ": " } else { "; " })?;
+        write!(f, "{failure}")?;
+    }
+    Ok(())
+}
+
+fn first_failure(failures: &[ProviderError]) -> Option<&(dyn std::error::Error + 'static)> {
+    failures
+        .first()
+        .map(|failure| failure as &(dyn std::error::Error + 'static))
 }
 
 impl Drop for TelemetryProviders {
     fn drop(&mut self) {
-        if let Some(tracer_provider) = self.tracer.take() {
-            if let Err(err) = tracer_provider.shutdown() {
-                eprintln!("Error shutting down Telemetry tracer provider: {err}");
+        let failures = provider_failures([
+            (
+                Provider::Tracer,
+                self.tracer.take().map(|provider| provider.shutdown()),
+            ),
+            (
+                Provider::Logger,
+                self.logger.take().map(|provider| provider.shutdown()),
+            ),
+            (
+                Provider::Meter,
+                self.meter.take().map(|provider| provider.shutdown()),
+            ),
+        ]);
+        if !failures.is_empty() {
+            let err = ShutdownError { failures };
+            match self.on_shutdown_error.take() {
+                Some(ShutdownErrorHandler(handler)) => handler(err),
+                None => report_shutdown_error(err),
             }
         }
-        if let Some(logger_provider) = self.logger.take() {
-            if let Err(err) = logger_provider.shutdown() {
-                eprintln!("Error shutting down Telemetry logger provider: {err}");
-            }
-        }
-        if let Some(meter_provider) = self.meter.take() {
-            if let Err(err) = meter_provider.shutdown() {
-                eprintln!("Error shutting down Telemetry meter provider: {err}");
-            }
+        #[cfg(feature = "profiling")]
+        if let Some(profile) = self.profile.take() {
+            profile.finish();
         }
     }
 }
 
-fn init_traces(
-    service_info: &ServiceInfo,
-    settings: &TraceSettings,
-) -> Result<Option<sdktrace::SdkTracerProvider>, ExporterBuildError> {
-    match &settings.endpoint {
-        Some(endpoint) => {
-            let exporter = SpanExporter::builder()
-                .with_tonic()
-                .with_endpoint(endpoint)
-                .build()?;
-
-            let resource = Resource::builder()
-                .with_attribute(KeyValue::new(
-                    opentelemetry_semantic_conventions::resource::SERVICE_NAME,
-                    service_info.name_in_metrics.clone(),
-                ))
-                .build();
-
-            Ok(Some(
-                sdktrace::SdkTracerProvider::builder()
-                    .with_resource(resource)
-                    .with_batch_exporter(exporter)
-                    .build(),
-            ))
-        }
-        None => Ok(None),
+/// Instrumentation scope of the metrics byre records itself.
+const BYRE_METER: &str = "byre";
+
+/// The layer that bridges tracing events to OpenTelemetry logs.
+type OtelLogLayer =
+    OpenTelemetryTracingBridge<SdkLoggerProvider, opentelemetry_sdk::logs::SdkLogger>;
+
+/// Without the `otlp` feature there are no exporters: no provider is created, whatever the
+/// endpoints, and telemetry only goes to the console.
+#[cfg(not(feature = "otlp"))]
+mod otlp {
+    use opentelemetry_sdk::logs::SdkLoggerProvider;
+    use opentelemetry_sdk::metrics::{MeterProviderBuilder, SdkMeterProvider};
+    use opentelemetry_sdk::trace::SdkTracerProvider;
+
+    use super::vendor::ExportConfig;
+    use super::{Error, LogSettings, MetricSettings, OtelLogLayer, TraceSettings};
+    use crate::ServiceInfo;
+
+    pub(super) fn init_traces(
+        _settings: &TraceSettings,
+        _export: &ExportConfig,
+    ) -> Result<Option<SdkTracerProvider>, Error> {
+        Ok(None)
+    }
+
+    pub(super) fn init_metrics(
+        _service_info: &ServiceInfo,
+        _setting: &MetricSettings,
+        _export: &ExportConfig,
+        _builder: MeterProviderBuilder,
+    ) -> Result<Option<SdkMeterProvider>, Error> {
+        Ok(None)
+    }
+
+    pub(super) fn init_otel_logs(
+        _settings: &LogSettings,
+        _export: &ExportConfig,
+    ) -> Result<(Option<SdkLoggerProvider>, Option<OtelLogLayer>), Error> {
+        Ok((None, None))
     }
 }
 
-fn init_metrics(
-    service_info: &ServiceInfo,
-    setting: &MetricSettings,
-) -> Result<Option<opentelemetry_sdk::metrics::SdkMeterProvider>, ExporterBuildError> {
-    match &setting.endpoint {
-        Some(endpoint) => {
-            let exporter = MetricExporter::builder()
-                .with_tonic()
-                .with_endpoint(endpoint)
-                .build()?;
-            let reader = PeriodicReader::builder(exporter).build();
-
-            let resource = Resource::builder()
-                .with_attribute(KeyValue::new(
-                    opentelemetry_semantic_conventions::resource::SERVICE_NAME,
-                    service_info.name_in_metrics.clone(),
-                ))
-                .build();
-
-            Ok(Some(
-                SdkMeterProvider::builder()
-                    .with_reader(reader)
-                    .with_resource(resource)
-                    .build(),
-            ))
-        }
+/// Keys of the telemetry settings that are still read, through a serde alias, but will be
+/// removed, and the keys that replace them. Both assume the settings are the `telemetry` field of
+/// the config, like in the examples.
+pub(crate) const DEPRECATED_KEYS: &[(&str, &str)] = &[
+    ("telemetry.traces", "telemetry.trace"),
+    ("telemetry.logs", "telemetry.log"),
+    ("telemetry.metrics", "telemetry.metric"),
+];
 
-        None => Ok(None),
+/// Targets that are never sent to OpenTelemetry.
+///
+/// OpenTelemetry and its dependent crates (opentelemetry-otlp uses crates like reqwest/tonic
+/// etc.) would otherwise send their own events back to OTel, causing infinite telemetry
+/// generation. Note: This will also drop events from crates like `tonic` etc. even when
+/// they are used outside the OTLP Exporter. For more details, see:
+/// <https://github.com/open-telemetry/opentelemetry-rust/issues/761>
+const OTEL_SUPPRESSED_TARGETS: &[&str] = &[
+    "hyper",
+    "opentelemetry",
+    "opentelemetry_sdk",
+    "tonic",
+    "h2",
+    "reqwest",
+];
+
+fn default_otel_suppressed_targets() -> Vec<String> {
+    OTEL_SUPPRESSED_TARGETS
+        .iter()
+        .map(|target| target.to_string())
+        .collect()
+}
+
+/// Parses the `target=off` directives for [`LogSettings::otel_suppressed_targets`].
+fn otel_suppression_directives(targets: &[String]) -> Result<Vec<Directive>, Error> {
+    targets
+        .iter()
+        .map(|target| {
+            format!("{target}=off")
+                .parse()
+                .with_context(|_| InvalidSuppressedTargetSnafu { target })
+        })
+        .collect()
+}
+
+fn default_otel_diagnostics_level() -> String {
+    "warn".to_string()
+}
+
+/// Parses the directive for [`LogSettings::otel_diagnostics_level`], `None` when it is empty.
+fn otel_diagnostics_directive(level: &str) -> Result<Option<Directive>, Error> {
+    if level.is_empty() {
+        return Ok(None);
     }
+    format!("{}={level}", sdk_errors::SDK_TARGET)
+        .parse()
+        .map(Some)
+        .with_context(|_| InvalidLogLevelSnafu { level })
 }
 
-fn init_otel_logs<S>(
-    service_info: &ServiceInfo,
-    settings: &LogSettings,
-) -> Result<
-    (
-        Option<opentelemetry_sdk::logs::SdkLoggerProvider>,
-        Option<impl tracing_subscriber::layer::Layer<S> + use<S>>,
-    ),
-    Error,
->
-where
-    S: Subscriber + for<'span> tracing_subscriber::registry::LookupSpan<'span>,
-{
-    match &settings.endpoint {
-        None => Ok((None, None)),
-
-        Some(endpoint) => {
-            let builder = init_otel_logs_builder(service_info, endpoint)?;
-
-            let logger_provider = builder.build();
-
-            // Create a new OpenTelemetryTracingBridge using the above LoggerProvider.
-            let otel_layer = OpenTelemetryTracingBridge::new(&logger_provider);
-
-            // For the OpenTelemetry layer, add a tracing filter to filter events from
-            // OpenTelemetry and its dependent crates (opentelemetry-otlp uses crates
-            // like reqwest/tonic etc.) from being sent back to OTel itself, thus
-            // preventing infinite telemetry generation. The filter levels are set as
-            // follows:
-            // - Allow `info` level and above by default.
-            // - Restrict `opentelemetry`, `hyper`, `tonic`, and `reqwest` completely.
-            // Note: This will also drop events from crates like `tonic` etc. even when
-            // they are used outside the OTLP Exporter. For more details, see:
-            // https://github.com/open-telemetry/opentelemetry-rust/issues/761
-            // FIXME: the directives below should be noted in the documentation!
-            let filter_otel = EnvFilter::new(&settings.otel_level)
-                .add_directive("hyper=off".parse().unwrap())
-                .add_directive("opentelemetry=off".parse().unwrap())
-                .add_directive("tonic=off".parse().unwrap())
-                .add_directive("h2=off".parse().unwrap())
-                .add_directive("reqwest=off".parse().unwrap());
-            let otel_layer = otel_layer.with_filter(filter_otel);
-
-            Ok((Some(logger_provider), Some(otel_layer)))
+/// Adds the directive of the OpenTelemetry SDK's diagnostics to the filter of the console logs,
+/// unless they are turned off.
+fn with_otel_diagnostics(
+    filter: EnvFilter,
+    level: &str,
+    directive: Option<&Directive>,
+) -> EnvFilter {
+    match directive {
+        Some(directive) if level != "off" => filter.add_directive(directive.clone()),
+        _ => filter,
+    }
+}
+
+/// The filter for a log level, an empty level falls back to the `RUST_LOG` environment variable.
+///
+/// Invalid directives are ignored, use [`try_level_filter`] to reject them.
+fn level_filter(level: &str) -> EnvFilter {
+    if level.is_empty() {
+        EnvFilter::from_default_env()
+    } else {
+        EnvFilter::new(level)
+    }
+}
+
+/// Like [`level_filter`], but invalid directives are an error.
+fn try_level_filter(level: &str) -> Result<EnvFilter, Error> {
+    if level.is_empty() {
+        Ok(EnvFilter::from_default_env())
+    } else {
+        EnvFilter::try_new(level).with_context(|_| InvalidLogLevelSnafu { level })
+    }
+}
+
+/// Adds the suppression directives to a filter used by an OpenTelemetry layer.
+fn suppress_otel_targets(filter: EnvFilter, directives: &[Directive]) -> EnvFilter {
+    directives
+        .iter()
+        .cloned()
+        .fold(filter, EnvFilter::add_directive)
+}
+
+/// Reloads one of the filters installed by [`LogSubscriberBuilder`].
+///
+/// The concrete `reload::Handle` type depends on the layers beneath the filter, so it is
+/// erased behind a closure.
+type FilterReloader =
+    Arc<dyn Fn(EnvFilter) -> Result<(), tracing_subscriber::reload::Error> + Send + Sync>;
+
+fn filter_reloader<S: 'static>(
+    handle: tracing_subscriber::reload::Handle<EnvFilter, S>,
+) -> FilterReloader {
+    Arc::new(move |filter| handle.reload(filter))
+}
+
+/// The log levels currently applied by a [`LogLevelHandle`].
+#[derive(Clone, Debug)]
+struct LogLevels {
+    console: String,
+    otel: String,
+    /// Empty while the trace level follows `otel`.
+    trace: String,
+}
+
+impl LogLevels {
+    fn trace(&self) -> &str {
+        if self.trace.is_empty() {
+            &self.otel
+        } else {
+            &self.trace
         }
     }
 }
 
-fn init_otel_logs_builder(
-    service_info: &ServiceInfo,
-    endpoint: &String,
-) -> Result<opentelemetry_sdk::logs::LoggerProviderBuilder, Error> {
-    let builder = SdkLoggerProvider::builder();
-    let exporter = LogExporter::builder()
-        .with_tonic()
-        .with_endpoint(endpoint)
-        .build()
-        .with_context(|_| InitLogSnafu {})?;
-    let resource = Resource::builder()
-        .with_attribute(KeyValue::new(
-            opentelemetry_semantic_conventions::resource::SERVICE_NAME,
-            service_info.name_in_metrics.clone(),
-        ))
-        .build();
-    let builder = builder
-        .with_resource(resource)
-        .with_batch_exporter(exporter);
-    Ok(builder)
+/// Handle for changing the log levels of the installed subscriber at runtime.
+///
+/// Obtain one from [`TelemetryProviders::log_levels`]. Handles are cheap to clone and
+/// all clones control the same subscriber, which makes them suitable for sharing with
+/// an admin endpoint (see the `admin` feature).
+///
+/// Levels use the same env-logger style syntax as [`LogSettings`].
+///
+/// # Example
+///
+/// ```rust,no_run
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # let service = byre::ServiceInfo::default();
+/// # let settings = byre::telemetry::TelemetrySettings::default();
+/// let telemetry = byre::telemetry::init(&service, &settings)?;
+/// if let Some(log_levels) = telemetry.log_levels() {
+///     // Turn on debug logging while investigating an incident...
+///     log_levels.set_console_level("debug")?;
+///     // ...and go back to the configured levels afterwards.
+///     log_levels.reset()?;
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct LogLevelHandle {
+    console: FilterReloader,
+    otel: Option<FilterReloader>,
+    trace: Option<FilterReloader>,
+    otel_suppression: Arc<[Directive]>,
+    otel_diagnostics: Option<Directive>,
+    configured: LogLevels,
+    current: Arc<Mutex<LogLevels>>,
 }
 
+impl std::fmt::Debug for LogLevelHandle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LogLevelHandle")
+            .field("configured", &self.configured)
+            .field("current", &self.current)
+            .finish_non_exhaustive()
+    }
+}
+
+impl LogLevelHandle {
+    /// The log level currently used to filter console logs.
+    pub fn console_level(&self) -> String {
+        self.lock_current().console.clone()
+    }
+
+    /// The log level currently used to filter OpenTelemetry logs.
+    pub fn otel_level(&self) -> String {
+        self.lock_current().otel.clone()
+    }
+
+    /// The log level currently used to filter the spans exported to OpenTelemetry traces.
+    pub fn trace_level(&self) -> String {
+        self.lock_current().trace().to_string()
+    }
+
+    /// Replace the console log level, an empty level uses the `RUST_LOG` environment variable.
+    ///
+    /// The [`LogSettings::otel_diagnostics_level`] is always kept, unless the level is `off`.
+    ///
+    /// # Errors
+    ///
+    /// - `InvalidLogLevel` if `level` is not a valid filter directive.
+    /// - `ReloadLogLevel` if the subscriber has been dropped.
+    pub fn set_console_level(&self, level: &str) -> Result<(), Error> {
+        let filter = try_level_filter(level)?;
+        let filter = with_otel_diagnostics(filter, level, self.otel_diagnostics.as_ref());
+        (self.console)(filter).context(ReloadLogLevelSnafu)?;
+        self.lock_current().console = level.to_string();
+        Ok(())
+    }
+
+    /// Replace the log level used for OpenTelemetry logs, an empty level uses the `RUST_LOG`
+    /// environment variable.
+    ///
+    /// Traces use this level too, unless a trace level has been set.
+    ///
+    /// The [`LogSettings::otel_suppressed_targets`] are always kept.
+    ///
+    /// # Errors
+    ///
+    /// - `InvalidLogLevel` if `level` is not a valid filter directive.
+    /// - `ReloadLogLevel` if the subscriber has been dropped.
+    pub fn set_otel_level(&self, level: &str) -> Result<(), Error> {
+        try_level_filter(level)?;
+        let follows = self.lock_current().trace.is_empty();
+        let reloaders = self
+            .otel
+            .iter()
+            .chain(self.trace.iter().filter(|_| follows));
+        for reload in reloaders {
+            reload(self.otel_filter(level)).context(ReloadLogLevelSnafu)?;
+        }
+        self.lock_current().otel = level.to_string();
+        Ok(())
+    }
+
+    /// Replace the log level used for the spans exported to OpenTelemetry traces, an empty level
+    /// goes back to using the OpenTelemetry log level.
+    ///
+    /// The [`LogSettings::otel_suppressed_targets`] are always kept.
+    ///
+    /// # Errors
+    ///
+    /// - `InvalidLogLevel` if `level` is not a valid filter directive.
+    /// - `ReloadLogLevel` if the subscriber has been dropped.
+    pub fn set_trace_level(&self, level: &str) -> Result<(), Error> {
+        let effective = if level.is_empty() {
+            self.otel_level()
+        } else {
+            level.to_string()
+        };
+        try_level_filter(&effective)?;
+        if let Some(reload) = &self.trace {
+            reload(self.otel_filter(&effective)).context(ReloadLogLevelSnafu)?;
+        }
+        self.lock_current().trace = level.to_string();
+        Ok(())
+    }
+
+    /// Restore the log levels from the [`LogSettings`] used at initialization.
+    ///
+    /// # Errors
+    ///
+    /// - `ReloadLogLevel` if the subscriber has been dropped.
+    pub fn reset(&self) -> Result<(), Error> {
+        self.set_console_level(&self.configured.console)?;
+        self.set_otel_level(&self.configured.otel)?;
+        self.set_trace_level(&self.configured.trace)
+    }
+
+    fn otel_filter(&self, level: &str) -> EnvFilter {
+        suppress_otel_targets(level_filter(level), &self.otel_suppression)
+    }
+
+    fn lock_current(&self) -> std::sync::MutexGuard<'_, LogLevels> {
+        self.current
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+}
+
+/// A layer added directly to the registry, before any filtering.
+type RegistryLayer = Box<dyn tracing_subscriber::Layer<tracing_subscriber::Registry> + Send + Sync>;
+
 /// Builder for configuring and initializing the logging/tracing subscriber.
 ///
 /// This builder separates configuration from initialization, making it easier
 /// to test the subscriber configuration without installing it globally.
-struct LogSubscriberBuilder<'a> {
+pub(crate) struct LogSubscriberBuilder<'a> {
     service_info: &'a ServiceInfo,
     settings: &'a LogSettings,
     tracer_provider: Option<&'a sdktrace::SdkTracerProvider>,
+    span_metrics: Option<SpanMetricsLayer>,
+    sdk_errors: Option<sdk_errors::SdkErrorLayer>,
+    export: ExportConfig,
+    registry_layers: Vec<RegistryLayer>,
 }
 
 /// The built subscriber components, ready to be installed or used for testing.
-struct BuiltSubscriber<S> {
+pub(crate) struct BuiltSubscriber<S> {
     /// The logger provider (if OTel logging endpoint was configured)
-    logger_provider: Option<opentelemetry_sdk::logs::SdkLoggerProvider>,
+    pub(crate) logger_provider: Option<opentelemetry_sdk::logs::SdkLoggerProvider>,
+    /// Handle for reloading the filters of the subscriber
+    pub(crate) log_levels: LogLevelHandle,
     /// The fully configured subscriber
-    subscriber: S,
+    pub(crate) subscriber: S,
 }
 
 impl<'a> LogSubscriberBuilder<'a> {
     /// Create a new builder with the required configuration.
-    fn new(service_info: &'a ServiceInfo, settings: &'a LogSettings) -> Self {
+    pub(crate) fn new(service_info: &'a ServiceInfo, settings: &'a LogSettings) -> Self {
         Self {
             service_info,
             settings,
             tracer_provider: None,
+            span_metrics: None,
+            sdk_errors: None,
+            export: ExportConfig::new(service_info),
+            registry_layers: Vec::new(),
         }
     }
 
+    /// Set the layer that writes a profile of the run.
+    #[cfg(feature = "profiling")]
+    fn with_profile(mut self, layer: RegistryLayer) -> Self {
+        self.registry_layers.push(layer);
+        self
+    }
+
+    /// Set the layer that feeds the tokio-console server.
+    #[cfg(feature = "tokio-console")]
+    fn with_tokio_console(mut self, layer: RegistryLayer) -> Self {
+        self.registry_layers.push(layer);
+        self
+    }
+
+    /// Set the exporter configuration from the vendor preset.
+    fn with_export(mut self, export: ExportConfig) -> Self {
+        self.export = export;
+        self
+    }
+
     /// Set the tracer provider for OpenTelemetry trace integration.
     fn with_tracer_provider(mut self, provider: &'a sdktrace::SdkTracerProvider) -> Self {
         self.tracer_provider = Some(provider);
         self
     }
 
+    /// Set the layer that derives metrics from spans.
+    fn with_span_metrics(mut self, layer: SpanMetricsLayer) -> Self {
+        self.span_metrics = Some(layer);
+        self
+    }
+
+    /// Set the layer that counts the errors of the OpenTelemetry SDK.
+    fn with_sdk_errors(mut self, layer: sdk_errors::SdkErrorLayer) -> Self {
+        self.sdk_errors = Some(layer);
+        self
+    }
+
     /// Build the subscriber without installing it globally.
     /// Use this for testing with `tracing::subscriber::with_default`.
-    fn build(
+    pub(crate) fn build(
         self,
-    ) -> Result<
-        BuiltSubscriber<
-            impl Subscriber // + for<'span> tracing_subscriber::registry::LookupSpan<'span>
-                // + Send
-                // + Sync
-                + use<'a>,
-        >,
-        Error,
-    > {
-        let (logger_provider, otel_log_layer) = init_otel_logs(self.service_info, self.settings)?;
+    ) -> Result<BuiltSubscriber<impl Subscriber + Send + Sync + use<>>, Error> {
+        use tracing_subscriber::reload;
+
+        let (logger_provider, otel_log_layer) = otlp::init_otel_logs(self.settings, &self.export)?;
+
+        let otel_suppression = otel_suppression_directives(&self.settings.otel_suppressed_targets)?;
+        let otel_diagnostics = otel_diagnostics_directive(&self.settings.otel_diagnostics_level)?;
+        let levels = LogLevels {
+            console: self.settings.console_level.clone(),
+            otel: self.settings.otel_level.clone(),
+            trace: self.settings.trace_level.clone(),
+        };
+        let mut otel_reloader = None;
+        let mut trace_reloader = None;
+
+        // Filter the OpenTelemetry log layer so OTel does not export its own events.
+        let otel_log_layer = otel_log_layer.map(|layer| {
+            let (filter, handle) = reload::Layer::new(suppress_otel_targets(
+                level_filter(&self.settings.otel_level),
+                &otel_suppression,
+            ));
+            otel_reloader = Some(filter_reloader(handle));
+            layer.with_filter(filter)
+        });
 
         // Create the OpenTelemetry tracing layer if a tracer provider is configured.
         // This bridges tracing spans to OpenTelemetry traces.
         let otel_trace_layer = self.tracer_provider.map(|provider| {
             let tracer = provider.tracer(self.service_info.name_in_metrics.clone());
-            let filter = EnvFilter::new(&self.settings.otel_level)
-                .add_directive("hyper=off".parse().unwrap())
-                .add_directive("opentelemetry=off".parse().unwrap())
-                .add_directive("opentelemetry_sdk=off".parse().unwrap())
-                .add_directive("tonic=off".parse().unwrap())
-                .add_directive("h2=off".parse().unwrap())
-                .add_directive("reqwest=off".parse().unwrap());
+            let (filter, handle) = reload::Layer::new(suppress_otel_targets(
+                level_filter(levels.trace()),
+                &otel_suppression,
+            ));
+            trace_reloader = Some(filter_reloader(handle));
             OpenTelemetryLayer::new(tracer).with_filter(filter)
         });
 
-        // Create a new tracing::Fmt layer to print the logs to stdout.
-        let filter_fmt = EnvFilter::new(&self.settings.console_level);
-        let fmt_layer = tracing_subscriber::fmt::layer()
-            .with_thread_names(true)
-            .with_filter(filter_fmt);
+        // Create a new tracing::Fmt layer to print the logs to stdout, only the layer of the
+        // configured format is created.
+        // The SDK's diagnostics get their own level, the OpenTelemetry layers suppress them
+        let (filter_fmt, console_handle) = reload::Layer::new(with_otel_diagnostics(
+            level_filter(&self.settings.console_level),
+            &self.settings.console_level,
+            otel_diagnostics.as_ref(),
+        ));
+        let console_format = self
+            .settings
+            .console_format
+            .unwrap_or_else(|| ConsoleFormat::default_for(self.export.environment()));
+        let trace_ids = self.settings.console_trace_ids;
+        let backtraces = self.settings.error_backtraces;
+        let event_format = tracing_subscriber::fmt::format().with_thread_names(true);
+        let (mut full_layer, mut pretty_layer, mut json_layer) = (None, None, None);
+        match console_format {
+            ConsoleFormat::Full => {
+                full_layer = Some(
+                    tracing_subscriber::fmt::layer()
+                        .event_format(error_backtrace::BacktraceFormat::new(
+                            self.export.console_format(event_format, trace_ids, false),
+                            backtraces,
+                            false,
+                        ))
+                        .with_filter(filter_fmt),
+                );
+            }
+            ConsoleFormat::Pretty => {
+                pretty_layer = Some(
+                    tracing_subscriber::fmt::layer()
+                        .pretty()
+                        .event_format(error_backtrace::BacktraceFormat::new(
+                            self.export
+                                .console_format(event_format.pretty(), trace_ids, false),
+                            backtraces,
+                            false,
+                        ))
+                        .with_filter(filter_fmt),
+                );
+            }
+            ConsoleFormat::Json => {
+                json_layer = Some(
+                    tracing_subscriber::fmt::layer()
+                        .json()
+                        .event_format(error_backtrace::BacktraceFormat::new(
+                            self.export.console_format(
+                                event_format.json().flatten_event(true),
+                                trace_ids,
+                                true,
+                            ),
+                            backtraces,
+                            true,
+                        ))
+                        .with_filter(filter_fmt),
+                );
+            }
+        }
+        let fmt_layer = tracing_subscriber::Layer::and_then(
+            tracing_subscriber::Layer::and_then(full_layer, pretty_layer),
+            json_layer,
+        );
+
+        // Rate limiting only applies to the log outputs, spans still see every event.
+        let log_layers = log_rate_limit::LogRateLimitLayer::new(
+            tracing_subscriber::Layer::and_then(otel_log_layer, fmt_layer),
+            self.settings.rate_limit.as_ref(),
+        );
 
         // Build the subscriber with all layers (but don't install it)
+        // The development layers see every span, whatever the log levels are. An empty Vec
+        // layer is never interested in a callsite, which would disable every other layer.
+        let registry_layers = (!self.registry_layers.is_empty()).then_some(self.registry_layers);
         let subscriber = tracing_subscriber::registry()
-            .with(otel_log_layer)
+            .with(registry_layers)
             .with(otel_trace_layer)
-            .with(fmt_layer);
+            .with(self.span_metrics)
+            .with(
+                self.sdk_errors
+                    .map(|layer| layer.with_filter(sdk_errors::SdkErrorLayer::filter())),
+            )
+            // Before the log layers, which read the request id and fields of the event they
+            // record
+            .with(request_id::RequestIdLayer)
+            .with(request_fields::RequestFieldsLayer)
+            .with(log_layers);
+
+        let log_levels = LogLevelHandle {
+            console: filter_reloader(console_handle),
+            otel: otel_reloader,
+            trace: trace_reloader,
+            otel_suppression: otel_suppression.into(),
+            otel_diagnostics,
+            current: Arc::new(Mutex::new(levels.clone())),
+            configured: levels,
+        };
 
         Ok(BuiltSubscriber {
             logger_provider,
+            log_levels,
             subscriber,
         })
     }
+}
+
+/// The telemetry described by the settings, built by [`build`] without installing anything
+/// globally.
+#[must_use = "dropping BuiltTelemetry will shut down all telemetry"]
+pub struct BuiltTelemetry<S> {
+    /// The composed subscriber, use it with `tracing::subscriber::with_default` or install it
+    /// with `tracing::subscriber::set_global_default`.
+    pub subscriber: S,
+    /// The providers the subscriber exports to, keep them alive while the subscriber is used.
+    pub providers: TelemetryProviders,
+}
 
-    /// Build and install the subscriber globally.
-    /// Returns the logger provider if OTel logging was configured.
-    fn init(self) -> Result<Option<opentelemetry_sdk::logs::SdkLoggerProvider>, Error> {
-        let built = self.build()?;
-        built.subscriber.init();
-        Ok(built.logger_provider)
+impl<S> std::fmt::Debug for BuiltTelemetry<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BuiltTelemetry")
+            .field("providers", &self.providers)
+            .finish_non_exhaustive()
     }
 }
 
-fn init_logs(
+/// Builds the subscriber and providers that [`init`] installs, without installing them.
+///
+/// Nothing global is changed: the subscriber is not set as the default, the providers are not
+/// registered with `opentelemetry::global`, and neither the propagator nor the panic hook are
+/// installed. The host metrics (runtime, jemalloc and system) are only registered by [`init`].
+///
+/// # Errors
+///
+/// The same as [`init`], except for `AlreadyInitialized`.
+///
+/// # Example
+///
+/// ```
+/// let service = byre::ServiceInfo::default();
+/// let settings = byre::telemetry::TelemetrySettings::default();
+/// let telemetry = byre::telemetry::build(&service, &settings)?;
+///
+/// tracing::subscriber::with_default(telemetry.subscriber, || {
+///     tracing::info!("only seen by this subscriber");
+/// });
+/// # Ok::<(), byre::telemetry::Error>(())
+/// ```
+pub fn build(
     service_info: &ServiceInfo,
-    settings: &LogSettings,
-    tracer_provider: Option<&sdktrace::SdkTracerProvider>,
-) -> Result<Option<opentelemetry_sdk::logs::SdkLoggerProvider>, Error> {
-    let mut builder = LogSubscriberBuilder::new(service_info, settings);
-    if let Some(provider) = tracer_provider {
+    settings: &TelemetrySettings,
+) -> Result<BuiltTelemetry<impl Subscriber + Send + Sync + use<>>, Error> {
+    // Reject invalid settings before any provider is created
+    metric_views::validate_views(&settings.metric.views)?;
+    otel_suppression_directives(&settings.log.otel_suppressed_targets)?;
+    otel_diagnostics_directive(&settings.log.otel_diagnostics_level)?;
+    let export = ExportConfig::from_settings(service_info, settings)?;
+
+    // Initialize traces first so the subscriber can export spans to the tracer provider
+    let tracer_provider = otlp::init_traces(&settings.trace, &export)?;
+
+    // Initialize metrics before logs so the subscriber can derive metrics from spans
+    let meter_builder = SdkMeterProvider::builder();
+    // The admin endpoint reads the current values from its own reader
+    #[cfg(feature = "admin")]
+    let metrics_snapshot = MetricsSnapshot::default();
+    #[cfg(feature = "admin")]
+    let meter_builder = meter_builder.with_reader(metrics_snapshot.reader());
+    let meter_provider =
+        otlp::init_metrics(service_info, &settings.metric, &export, meter_builder)?;
+    #[cfg(feature = "admin")]
+    let metrics_snapshot = meter_provider.is_some().then_some(metrics_snapshot);
+    let span_metrics = meter_provider.as_ref().and_then(|provider| {
+        SpanMetricsLayer::from_settings(&provider.meter(BYRE_METER), &settings.metric.span_metrics)
+    });
+
+    let exports = export.exports().clone();
+    if let Some(provider) = &meter_provider {
+        exports.record_metrics(&provider.meter(BYRE_METER));
+    }
+    let mut builder = LogSubscriberBuilder::new(service_info, &settings.log).with_export(export);
+    if let Some(provider) = &tracer_provider {
         builder = builder.with_tracer_provider(provider);
     }
-    builder.init()
+    if let Some(layer) = span_metrics {
+        builder = builder.with_span_metrics(layer);
+    }
+    if let Some(provider) = &meter_provider {
+        builder =
+            builder.with_sdk_errors(sdk_errors::SdkErrorLayer::new(&provider.meter(BYRE_METER)));
+    }
+    #[cfg(feature = "profiling")]
+    let profile_guard = match &settings.profile {
+        Some(profile) => {
+            let (layer, guard) = profiling::layer(profile)?;
+            builder = builder.with_profile(layer);
+            Some(guard)
+        }
+        None => None,
+    };
+    #[cfg(feature = "tokio-console")]
+    if let Some(layer) = tokio_console::layer(&settings.tokio_console)? {
+        builder = builder.with_tokio_console(layer);
+    }
+    let built = builder.build()?;
+
+    Ok(BuiltTelemetry {
+        subscriber: built.subscriber,
+        providers: TelemetryProviders {
+            meter: meter_provider,
+            tracer: tracer_provider,
+            logger: built.logger_provider,
+            log_levels: Some(built.log_levels),
+            exports,
+            on_shutdown_error: None,
+            #[cfg(feature = "admin")]
+            metrics_snapshot,
+            #[cfg(feature = "profiling")]
+            profile: profile_guard,
+        },
+    })
 }
 
 /// Initializes the telemetry backend for your application.
 ///
-/// This function sets up tracing, metrics, and logging according to the provided settings.
+/// This function sets up tracing, metrics, logging, and the propagators of
+/// [`TraceSettings::propagators`] for distributed tracing according to the provided settings.
 /// It integrates with OpenTelemetry to provide a complete observability solution.
 ///
-/// # Errors
+/// Use [`build`] to get the subscriber without installing it globally.
 ///
-/// - `InitLog` if the logger provider cannot be initialized.
-/// - `InitTrace` if the tracer provider cannot be initialized.
-/// - `InitMetric` if the metric provider cannot be initialized.
-/// Initializes the telemetry backend for your application.
-///
-/// This function sets up tracing, metrics, logging, and the W3C Trace Context
-/// propagator for distributed tracing according to the provided settings.
-/// It integrates with OpenTelemetry to provide a complete observability solution.
+/// Telemetry can only be initialized once per process, later calls return an error and leave
+/// the installed telemetry untouched.
 ///
 /// # Errors
 ///
+/// - `AlreadyInitialized` if `init` was already called, or another global tracing subscriber is
+///   installed.
 /// - `InitLog` if the logger provider cannot be initialized.
 /// - `InitTrace` if the tracer provider cannot be initialized.
 /// - `InitMetric` if the metric provider cannot be initialized.
-#[must_use]
+/// - `InvalidMetricView` if a configured metric view cannot be applied.
+/// - `InvalidSuppressedTarget` if a suppressed log target is not a valid filter target.
+/// - `InvalidExportHeader` if a vendor preset setting cannot be sent as an export header.
+/// - `InitProfile` if the profile file cannot be created.
+/// - `ResolveTokioConsoleAddress` or `InvalidTokioConsoleAddress` if the tokio-console address
+///   cannot be used.
 pub fn init(
     service_info: &ServiceInfo,
     settings: &TelemetrySettings,
 ) -> Result<TelemetryProviders, Error> {
-    // Initialize the W3C Trace Context propagator for distributed tracing
-    init_propagator();
-    // Initialize traces first so we can pass the provider to init_logs for the tracing layer
-    let tracer_provider =
-        init_traces(service_info, &settings.trace).with_context(|_| InitTraceSnafu {})?;
-    if let Some(tracer_provider) = &tracer_provider {
-        global::set_tracer_provider(tracer_provider.clone());
+    // Checked again when installing the subscriber, this avoids creating exporters for nothing
+    if tracing::dispatcher::has_been_set() {
+        return AlreadyInitializedSnafu.fail();
     }
-
-    // Initialize logs with the tracer provider to enable span export via tracing-opentelemetry
-    let logger_provider = init_logs(service_info, &settings.log, tracer_provider.as_ref())?;
-
-    let meter_provider =
-        init_metrics(service_info, &settings.metric).with_context(|_| InitMetricSnafu {})?;
-    if let Some(meter_provider) = &meter_provider {
-        global::set_meter_provider(meter_provider.clone());
+    let BuiltTelemetry {
+        subscriber,
+        providers,
+    } = build(service_info, settings)?;
+    // Install the subscriber first, nothing global is touched if another init won the race
+    subscriber
+        .try_init()
+        .map_err(|_| AlreadyInitializedSnafu.build())?;
+
+    // Initialize the trace context propagators for distributed tracing
+    if !settings.local_only {
+        init_propagators(&settings.trace.propagators);
     }
-
-    Ok(TelemetryProviders {
-        meter: meter_provider,
-        tracer: tracer_provider,
-        logger: logger_provider,
-    })
-}
-
-// ============================================================================
-// Distributed Tracing Propagation
-// ============================================================================
-
-/// Wrapper for tonic::metadata::MetadataMap to implement Extractor trait.
-/// Used for extracting trace context from incoming gRPC requests.
-pub struct MetadataExtractor<'a>(pub &'a tonic::metadata::MetadataMap);
-
-impl Extractor for MetadataExtractor<'_> {
-    fn get(&self, key: &str) -> Option<&str> {
-        self.0.get(key).and_then(|v| v.to_str().ok())
+    if let Some(tracer_provider) = providers.tracer_provider() {
+        global::set_tracer_provider(tracer_provider.clone());
     }
-
-    fn keys(&self) -> Vec<&str> {
-        // W3C Trace Context only uses "traceparent" and optionally "tracestate".
-        // Only return the keys that actually exist in the metadata.
-        ["traceparent", "tracestate"]
-            .into_iter()
-            .filter(|k| self.0.get(*k).is_some())
-            .collect()
+    if let Some(meter_provider) = providers.meter_provider() {
+        global::set_meter_provider(meter_provider.clone());
     }
-}
+    metrics::bind(service_info);
 
-/// Wrapper for tonic::metadata::MetadataMap to implement Injector trait.
-/// Used for injecting trace context into outgoing gRPC requests.
-pub struct MetadataInjector<'a>(pub &'a mut tonic::metadata::MetadataMap);
+    let meter_provider = providers.meter_provider();
 
-impl Injector for MetadataInjector<'_> {
-    fn set(&mut self, key: &str, value: String) {
-        if let Ok(key) = tonic::metadata::MetadataKey::from_bytes(key.as_bytes()) {
-            if let Ok(value) = tonic::metadata::MetadataValue::try_from(&value) {
-                self.0.insert(key, value);
-            }
+    if let Some(provider) = meter_provider {
+        let meter = provider.meter(BYRE_METER);
+        register_process_metrics(&meter);
+        register_build_info(&meter, service_info);
+        register_container_limits(&meter, &crate::container::ContainerLimits::detect());
+        if alloc_metrics::is_counting() {
+            register_allocation_metrics(&meter);
         }
     }
-}
 
-impl TraceContextCarrier for tonic::metadata::MetadataMap {
-    fn extract_trace_context(&self) -> opentelemetry::Context {
-        global::get_text_map_propagator(|propagator| propagator.extract(&MetadataExtractor(self)))
+    #[cfg(feature = "jemalloc")]
+    if let Some(provider) = meter_provider {
+        register_jemalloc_metrics(&provider.meter(BYRE_METER));
     }
 
-    fn inject_trace_context(&mut self) {
-        use tracing_opentelemetry::OpenTelemetrySpanExt;
-        let cx = tracing::Span::current().context();
-        global::get_text_map_propagator(|propagator| {
-            propagator.inject_context(&cx, &mut MetadataInjector(self));
-        });
-    }
-}
-
-/// Extract trace context from incoming gRPC request metadata.
-///
-/// Returns the extracted OpenTelemetry context. Use [`link_distributed_trace`] for a more
-/// convenient way to extract and link the trace context in one call.
-///
-/// # Example
-///
-/// ```
-/// let mut metadata = tonic::metadata::MetadataMap::new();
-/// metadata.insert("traceparent", "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01".parse().unwrap());
-///
-/// let parent_cx = byre::telemetry::extract_trace_context(&metadata);
-/// let _guard = parent_cx.attach();
-/// // Spans created here will be children of the incoming trace
-/// ```
-pub fn extract_trace_context(metadata: &tonic::metadata::MetadataMap) -> opentelemetry::Context {
-    global::get_text_map_propagator(|propagator| propagator.extract(&MetadataExtractor(metadata)))
-}
-
-/// Link the current span to an incoming distributed trace from gRPC metadata.
-///
-/// This is a convenience function that extracts the trace context from the
-/// incoming request metadata and sets it as the parent of the current span.
-/// Call this at the start of your gRPC handler after the `#[tracing::instrument]` span is created.
-///
-/// Returns `Ok(())` if successful, or an error if the span context couldn't be set.
-/// Most callers will want to ignore the error with `let _ = link_distributed_trace(...)`.
-///
-/// # Example
-///
-/// ```
-/// let mut metadata = tonic::metadata::MetadataMap::new();
-/// metadata.insert("traceparent", "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01".parse().unwrap());
-///
-/// let _ = byre::telemetry::link_distributed_trace(&metadata);
-/// // Current span is now part of the distributed trace
-/// ```
-pub fn link_distributed_trace(metadata: &tonic::metadata::MetadataMap) -> Result<(), Error> {
-    use tracing_opentelemetry::OpenTelemetrySpanExt;
-    let parent_cx = extract_trace_context(metadata);
-    tracing::Span::current()
-        .set_parent(parent_cx)
-        .map_err(|e| Error::LinkDistributedTrace {
-            source: Box::new(e),
-        })
-}
-
-/// Inject trace context into outgoing gRPC request metadata.
-///
-/// Call this before making outgoing gRPC calls to propagate the trace context.
-///
-/// # Example
-///
-/// ```
-/// let mut metadata = tonic::metadata::MetadataMap::new();
-/// byre::telemetry::inject_trace_context(&mut metadata);
-/// // metadata now contains traceparent header (if there's an active span)
-/// ```
-pub fn inject_trace_context(metadata: &mut tonic::metadata::MetadataMap) {
-    use tracing_opentelemetry::OpenTelemetrySpanExt;
-    // Get the OpenTelemetry context from the current tracing span
-    let cx = tracing::Span::current().context();
-    global::get_text_map_propagator(|propagator| {
-        propagator.inject_context(&cx, &mut MetadataInjector(metadata));
-    });
-}
-
-/// Initialize the global text map propagator for W3C Trace Context.
-///
-/// This is called automatically by `init()`, but can be called manually if needed.
-pub fn init_propagator() {
-    global::set_text_map_propagator(TraceContextPropagator::new());
-}
-
-// ============================================================================
-// HTTP Header Propagation (for HTTP proxies and clients)
-// ============================================================================
-
-/// Wrapper for http::HeaderMap to implement Extractor trait.
-/// Used for extracting trace context from incoming HTTP requests.
-pub struct HttpHeaderExtractor<'a>(pub &'a http::HeaderMap);
-
-impl Extractor for HttpHeaderExtractor<'_> {
-    fn get(&self, key: &str) -> Option<&str> {
-        self.0.get(key).and_then(|v| v.to_str().ok())
-    }
-
-    fn keys(&self) -> Vec<&str> {
-        // W3C Trace Context only uses "traceparent" and optionally "tracestate".
-        // Only return the keys that actually exist in the headers.
-        ["traceparent", "tracestate"]
-            .into_iter()
-            .filter(|k| self.0.get(*k).is_some())
-            .collect()
+    #[cfg(feature = "system-metrics")]
+    if let Some(provider) = meter_provider {
+        if settings.metric.system.enabled {
+            register_system_metrics(&provider.meter(BYRE_METER), &settings.metric.system);
+        }
     }
-}
 
-/// Wrapper for http::HeaderMap to implement Injector trait.
-/// Used for injecting trace context into outgoing HTTP requests.
-pub struct HttpHeaderInjector<'a>(pub &'a mut http::HeaderMap);
-
-impl Injector for HttpHeaderInjector<'_> {
-    fn set(&mut self, key: &str, value: String) {
-        if let Ok(key) = http::header::HeaderName::from_bytes(key.as_bytes()) {
-            if let Ok(value) = http::header::HeaderValue::from_str(&value) {
-                self.0.insert(key, value);
+    if settings.metric.runtime_metrics {
+        if let Some(provider) = meter_provider {
+            match tokio::runtime::Handle::try_current() {
+                Ok(runtime) => register_runtime_metrics(&provider.meter(BYRE_METER), runtime),
+                Err(err) => tracing::warn!(error = %err, "tokio runtime metrics are unavailable"),
             }
         }
     }
-}
 
-impl TraceContextCarrier for http::HeaderMap {
-    fn extract_trace_context(&self) -> opentelemetry::Context {
-        global::get_text_map_propagator(|propagator| propagator.extract(&HttpHeaderExtractor(self)))
-    }
-
-    fn inject_trace_context(&mut self) {
-        use tracing_opentelemetry::OpenTelemetrySpanExt;
-        let cx = tracing::Span::current().context();
-        global::get_text_map_propagator(|propagator| {
-            propagator.inject_context(&cx, &mut HttpHeaderInjector(self));
+    if settings.panic_hook {
+        let (tracer, logger, meter) = (
+            providers.tracer.clone(),
+            providers.logger.clone(),
+            providers.meter.clone(),
+        );
+        panic_hook::install(&global::meter(BYRE_METER), move || {
+            // Errors are ignored, the previous hook still reports the panic
+            if let Some(logger) = &logger {
+                let _ = logger.force_flush();
+            }
+            if let Some(tracer) = &tracer {
+                let _ = tracer.force_flush();
+            }
+            if let Some(meter) = &meter {
+                let _ = meter.force_flush();
+            }
         });
     }
-}
 
-/// Extract trace context from incoming HTTP request headers.
-///
-/// Returns the extracted OpenTelemetry context. Use [`link_distributed_trace_http`] for a more
-/// convenient way to extract and link the trace context in one call.
-///
-/// # Example
-///
-/// ```
-/// let mut headers = http::HeaderMap::new();
-/// headers.insert("traceparent", "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01".parse().unwrap());
-///
-/// let parent_cx = byre::telemetry::extract_trace_context_http(&headers);
-/// let _guard = parent_cx.attach();
-/// // Spans created here will be children of the incoming trace
-/// ```
-pub fn extract_trace_context_http(headers: &http::HeaderMap) -> opentelemetry::Context {
-    global::get_text_map_propagator(|propagator| propagator.extract(&HttpHeaderExtractor(headers)))
+    Ok(providers)
 }
 
-/// Link the current span to an incoming distributed trace from HTTP headers.
+/// Telemetry that does nothing, for unit tests and short-lived tools that share their start-up
+/// code with the service.
 ///
-/// This is a convenience function that extracts the trace context from the
-/// incoming request headers and sets it as the parent of the current span.
-/// Call this at the start of your HTTP handler after the `#[tracing::instrument]` span is created.
-///
-/// Returns `Ok(())` if successful, or an error if the span context couldn't be set.
-/// Most callers will want to ignore the error with `let _ = link_distributed_trace_http(...)`.
+/// Returns empty [`TelemetryProviders`] without installing a subscriber, a provider, the
+/// propagator or the panic hook, so it never conflicts with a subscriber installed elsewhere
+/// and can be called any number of times.
 ///
-/// # Example
-///
-/// ```
-/// let mut headers = http::HeaderMap::new();
-/// headers.insert("traceparent", "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01".parse().unwrap());
-///
-/// let _ = byre::telemetry::link_distributed_trace_http(&headers);
-/// // Current span is now part of the distributed trace
 /// ```
-pub fn link_distributed_trace_http(headers: &http::HeaderMap) -> Result<(), Error> {
-    use tracing_opentelemetry::OpenTelemetrySpanExt;
-    let parent_cx = extract_trace_context_http(headers);
-    tracing::Span::current()
-        .set_parent(parent_cx)
-        .map_err(|e| Error::LinkDistributedTrace {
-            source: Box::new(e),
-        })
-}
-
-/// Inject trace context into outgoing HTTP request headers.
-///
-/// Call this before making outgoing HTTP calls to propagate the trace context.
-///
-/// # Example
-///
-/// ```
-/// let mut headers = http::HeaderMap::new();
-/// byre::telemetry::inject_trace_context_http(&mut headers);
-/// // headers now contains traceparent header (if there's an active span)
+/// let _telemetry = byre::telemetry::init_noop();
+/// let _again = byre::telemetry::init_noop();
 /// ```
-pub fn inject_trace_context_http(headers: &mut http::HeaderMap) {
-    use tracing_opentelemetry::OpenTelemetrySpanExt;
-    // Get the OpenTelemetry context from the current tracing span
-    let cx = tracing::Span::current().context();
-    global::get_text_map_propagator(|propagator| {
-        propagator.inject_context(&cx, &mut HttpHeaderInjector(headers));
-    });
+pub fn init_noop() -> TelemetryProviders {
+    TelemetryProviders::default()
 }
 
 // ============================================================================
-// Tower Layer for Distributed Trace Context (gRPC/tonic)
+// Distributed Tracing Propagation
 // ============================================================================
 
-/// A Tower layer that extracts distributed trace context from incoming gRPC requests
-/// and creates a parent span for all downstream handlers.
-///
-/// This layer should be added to tonic services to enable distributed tracing.
-/// It extracts the W3C Trace Context headers from incoming requests and creates
-/// a span that becomes the parent of all spans created within the handler.
-///
-/// # Example
-///
-/// ```
-/// use byre::telemetry::GrpcTraceContextLayer;
-///
-/// // Create the layer
-/// let layer = GrpcTraceContextLayer::new("my-service");
+/// Initialize the global text map propagator for W3C Trace Context.
 ///
-/// // Use with tonic Server::builder().layer(layer)
-/// ```
-#[derive(Clone)]
-pub struct GrpcTraceContextLayer {
-    service_name: &'static str,
-}
-
-impl GrpcTraceContextLayer {
-    /// Create a new layer with the given service name.
-    /// The service name is used to identify spans in the trace.
-    pub fn new(service_name: &'static str) -> Self {
-        Self { service_name }
-    }
-}
-
-impl<S> tower::Layer<S> for GrpcTraceContextLayer {
-    type Service = GrpcTraceContextService<S>;
-
-    fn layer(&self, inner: S) -> Self::Service {
-        GrpcTraceContextService {
-            inner,
-            service_name: self.service_name,
-        }
-    }
-}
-
-/// The service that wraps inner services with trace context extraction.
-#[derive(Clone)]
-pub struct GrpcTraceContextService<S> {
-    inner: S,
-    service_name: &'static str,
-}
-
-impl<S, B> tower::Service<http::Request<B>> for GrpcTraceContextService<S>
-where
-    S: tower::Service<http::Request<B>> + Clone + Send + 'static,
-    S::Future: Send,
-    B: Send + 'static,
-{
-    type Response = S::Response;
-    type Error = S::Error;
-    type Future = std::pin::Pin<
-        Box<dyn std::future::Future<Output = Result<Self::Response, Self::Error>> + Send>,
-    >;
-
-    fn poll_ready(
-        &mut self,
-        cx: &mut std::task::Context<'_>,
-    ) -> std::task::Poll<Result<(), Self::Error>> {
-        self.inner.poll_ready(cx)
-    }
-
-    fn call(&mut self, request: http::Request<B>) -> Self::Future {
-        use tracing::Instrument;
-        use tracing_opentelemetry::OpenTelemetrySpanExt;
-
-        // Extract trace context from incoming HTTP/2 headers (gRPC uses HTTP/2)
-        let parent_cx = extract_trace_context_http(request.headers());
-
-        // Create a tracing span and link it to the incoming OpenTelemetry context.
-        // This makes all child spans (from #[tracing::instrument]) part of the distributed trace.
-        let span = tracing::info_span!("grpc_request", service = self.service_name);
-        let _ = span.set_parent(parent_cx);
-
-        // Clone inner service for use in async block
-        let mut inner = self.inner.clone();
+/// This is called automatically by `init()`, but can be called manually if needed.
+pub fn init_propagator() {
+    global::set_text_map_propagator(TraceContextPropagator::new());
+}
 
-        // Instrument the future with our span so it stays active for the entire request
-        Box::pin(async move { inner.call(request).await }.instrument(span))
-    }
+/// Initialize the global text map propagator for the formats of `propagators`.
+///
+/// This is called automatically by `init()` with [`TraceSettings::propagators`].
+///
+/// ```
+/// use byre::telemetry::Propagator;
+///
+/// byre::telemetry::init_propagators(&[Propagator::TraceContext, Propagator::Xray]);
+/// ```
+pub fn init_propagators(propagators: &[Propagator]) {
+    global::set_text_map_propagator(propagation::composite(propagators));
 }
 
 // ============================================================================
@@ -980,19 +2135,24 @@ impl TraceContextCarrier for std::collections::HashMap<String, String> {
         global::get_text_map_propagator(|propagator| propagator.extract(self))
     }
 
+    fn extract_trace_context_with(&self, cx: &opentelemetry::Context) -> opentelemetry::Context {
+        global::get_text_map_propagator(|propagator| propagator.extract_with_context(cx, self))
+    }
+
     fn inject_trace_context(&mut self) {
-        use tracing_opentelemetry::OpenTelemetrySpanExt;
-        let cx = tracing::Span::current().context();
-        global::get_text_map_propagator(|propagator| {
-            propagator.inject_context(&cx, self);
-        });
+        inject_trace_context_map(self);
+    }
+
+    fn extract_request_id(&self) -> Option<RequestId> {
+        RequestId::extract(self)
     }
 }
 
 /// Inject the current trace context into a HashMap suitable for message queue headers.
 ///
 /// This is useful for propagating trace context through message queues like Iggy
-/// where headers are stored as a `HashMap<HeaderKey, HeaderValue>`.
+/// where headers are stored as a `HashMap<HeaderKey, HeaderValue>`. The request id of the
+/// current span is added as `x-request-id`.
 ///
 /// # Example
 ///
@@ -1010,6 +2170,7 @@ pub fn inject_trace_context_map(headers: &mut std::collections::HashMap<String,
     global::get_text_map_propagator(|propagator| {
         propagator.inject_context(&cx, headers);
     });
+    request_id::inject(headers);
 }
 
 /// Extract trace context from a HashMap of message queue headers.
@@ -1036,7 +2197,9 @@ pub fn extract_trace_context_map(
 /// Link the current span to an incoming distributed trace from message queue headers.
 ///
 /// This is a convenience function that extracts the trace context from the
-/// message headers and sets it as the parent of the current span.
+/// message headers and sets it as the parent of the current span. The `x-request-id` the
+/// headers carry becomes the request id of the current span, like
+/// [`TraceContextExt::link_distributed_trace`].
 ///
 /// Returns `Ok(())` if successful, or an error if the span context couldn't be set.
 /// Most callers will want to ignore the error with `let _ = link_distributed_trace_map(...)`.
@@ -1055,13 +2218,7 @@ pub fn extract_trace_context_map(
 pub fn link_distributed_trace_map(
     headers: &std::collections::HashMap<String, String>,
 ) -> Result<(), Error> {
-    use tracing_opentelemetry::OpenTelemetrySpanExt;
-    let parent_cx = extract_trace_context_map(headers);
-    tracing::Span::current()
-        .set_parent(parent_cx)
-        .map_err(|e| Error::LinkDistributedTrace {
-            source: Box::new(e),
-        })
+    headers.link_distributed_trace()
 }
 
 /// Set a span's parent from an OpenTelemetry context.
@@ -1104,17 +2261,32 @@ pub fn set_span_parent(span: &tracing::Span, parent_cx: opentelemetry::Context)
 /// - [`TelemetryProviders`] - Handle to keep telemetry alive
 /// - [`TraceContextCarrier`] - Trait for types that carry trace context
 /// - [`TraceContextExt`] - Extension methods for trace context propagation
-/// - [`GrpcTraceContextLayer`] - Tower layer for gRPC distributed tracing
+/// - `GrpcTraceContextLayer` - Tower layer for gRPC distributed tracing, with the `grpc` feature
+/// - `GrpcMetricsLayer` - Tower layer for gRPC server metrics, with the `grpc` feature
+/// - `HttpTraceContextLayer` - Tower layer for HTTP server spans and distributed tracing, with
+///   the `http` feature
+/// - `HttpMetricsLayer` - Tower layer for HTTP server metrics, with the `http` feature
+/// - `TraceContextInterceptor` - tonic client interceptor that propagates the trace context,
+///   with the `grpc` feature
+/// - [`record_error`] - Record an error on the current span and in metrics
+/// - [`RecordErrorExt`] - Record the error of a `Result` as it passes by
+/// - [`current_trace_id`] - The id of the current trace, ie: for error responses
 pub mod prelude {
     pub use super::{
-        init, GrpcTraceContextLayer, TelemetryProviders, TelemetrySettings, TraceContextCarrier,
-        TraceContextExt,
+        current_trace_id, init, record_error, RecordErrorExt, TelemetryProviders,
+        TelemetrySettings, TraceContextCarrier, TraceContextExt,
     };
+    #[cfg(feature = "grpc")]
+    pub use super::{GrpcMetricsLayer, GrpcTraceContextLayer, TraceContextInterceptor};
+    #[cfg(feature = "http")]
+    pub use super::{HttpMetricsLayer, HttpTraceContextLayer};
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    #[cfg(feature = "http")]
+    use opentelemetry::propagation::{Extractor, Injector};
     use opentelemetry::trace::{
         SpanContext, SpanId, TraceContextExt, TraceFlags, TraceId, TraceState, Tracer,
     };
@@ -1358,6 +2530,7 @@ mod tests {
     }
 
     /// Helper to assert a traceparent value is valid (non-empty trace ID)
+    #[cfg(feature = "grpc")]
     fn assert_valid_traceparent(traceparent: &str) {
         assert!(
             traceparent.starts_with("00-"),
@@ -1369,6 +2542,7 @@ mod tests {
         );
     }
 
+    #[cfg(feature = "grpc")]
     #[test]
     fn test_inject_trace_context_from_tracing_span() {
         let _provider = init_tracing_with_otel();
@@ -1394,6 +2568,140 @@ mod tests {
         assert_valid_traceparent(http_traceparent);
     }
 
+    #[cfg(feature = "grpc")]
+    #[test]
+    fn test_trace_context_interceptor_injects_traceparent() {
+        use tonic::service::Interceptor as _;
+
+        let _provider = init_tracing_with_otel();
+
+        // Without a span there is nothing to propagate
+        let request = TraceContextInterceptor
+            .call(tonic::Request::new(()))
+            .unwrap();
+        assert!(request.metadata().get("traceparent").is_none());
+
+        let span = tracing::info_span!("test_span_for_interceptor");
+        let _enter = span.enter();
+        let request = TraceContextInterceptor
+            .call(tonic::Request::new(()))
+            .unwrap();
+        let traceparent = request.metadata().get("traceparent").unwrap();
+        assert_valid_traceparent(traceparent.to_str().unwrap());
+    }
+
+    #[cfg(feature = "grpc")]
+    #[test]
+    fn test_with_trace_context_returns_injected_carrier() {
+        use super::TraceContextExt as _;
+
+        let _provider = init_tracing_with_otel();
+        let span = tracing::info_span!("test_span_for_with_trace_context");
+        let _enter = span.enter();
+
+        let headers = http::HeaderMap::new().with_trace_context();
+        assert_valid_traceparent(headers.get("traceparent").unwrap().to_str().unwrap());
+
+        let metadata = tonic::metadata::MetadataMap::new().with_trace_context();
+        assert_valid_traceparent(metadata.get("traceparent").unwrap().to_str().unwrap());
+
+        let map = HashMap::<String, String>::new().with_trace_context();
+        assert_valid_traceparent(&map["traceparent"]);
+    }
+
+    #[test]
+    fn test_try_extract_trace_context_requires_a_valid_remote_context() {
+        use super::TraceContextExt as _;
+        use opentelemetry::trace::TraceContextExt as _;
+
+        init_test_propagator();
+        let headers = |traceparent: &str| {
+            HashMap::from([("traceparent".to_string(), traceparent.to_string())])
+        };
+
+        let cx = headers("00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01")
+            .try_extract_trace_context()
+            .unwrap();
+        assert_eq!(
+            cx.span().span_context().trace_id(),
+            TraceId::from_hex("0af7651916cd43dd8448eb211c80319c").unwrap()
+        );
+
+        assert!(HashMap::<String, String>::new()
+            .try_extract_trace_context()
+            .is_none());
+        assert!(headers("garbage").try_extract_trace_context().is_none());
+        assert!(
+            headers("00-00000000000000000000000000000000-b7ad6b7169203331-01")
+                .try_extract_trace_context()
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_try_extract_trace_context_ignores_the_current_context() {
+        use super::TraceContextExt as _;
+        use opentelemetry::trace::TraceContextExt as _;
+
+        init_test_propagator();
+        // A remote context attached by an earlier extraction, ie: the parent of a message batch
+        let remote = HashMap::from([(
+            "traceparent".to_string(),
+            "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01".to_string(),
+        )])
+        .extract_trace_context();
+        assert!(remote.span().span_context().is_remote());
+        let _guard = remote.attach();
+
+        assert!(HashMap::<String, String>::new()
+            .try_extract_trace_context()
+            .is_none());
+        #[cfg(feature = "http")]
+        assert!(http::HeaderMap::new().try_extract_trace_context().is_none());
+        #[cfg(feature = "grpc")]
+        assert!(tonic::metadata::MetadataMap::new()
+            .try_extract_trace_context()
+            .is_none());
+    }
+
+    #[test]
+    fn test_link_remote_context_adds_links_without_reparenting() {
+        use super::TraceContextExt as _;
+
+        init_test_propagator();
+        let capture = test::capture();
+        let message = |trace_id: &str| {
+            HashMap::from([(
+                "traceparent".to_string(),
+                format!("00-{trace_id}-b7ad6b7169203331-01"),
+            )])
+        };
+        let first = "0af7651916cd43dd8448eb211c80319c";
+        let second = "4bf92f3577b34da6a3ce929d0e0e4736";
+
+        tracing::info_span!("process_batch").in_scope(|| {
+            message(first).link_remote_context();
+            message(second).link_remote_context();
+            HashMap::<String, String>::new().link_remote_context();
+        });
+
+        let span = capture.span("process_batch").unwrap();
+        let linked: Vec<_> = span
+            .links
+            .iter()
+            .map(|link| link.span_context.trace_id())
+            .collect();
+        assert_eq!(
+            linked,
+            [
+                TraceId::from_hex(first).unwrap(),
+                TraceId::from_hex(second).unwrap()
+            ]
+        );
+        assert!(!linked.contains(&span.span_context.trace_id()));
+        assert_eq!(span.parent_span_id, SpanId::INVALID);
+    }
+
     #[test]
     fn test_nested_tracing_spans_propagate_trace_id() {
         let _provider = init_tracing_with_otel();
@@ -1478,6 +2786,7 @@ mod tests {
     // Tests for MetadataExtractor (gRPC metadata)
     // ========================================================================
 
+    #[cfg(feature = "grpc")]
     #[test]
     fn test_metadata_extractor_get_returns_value() {
         let mut metadata = tonic::metadata::MetadataMap::new();
@@ -1490,6 +2799,7 @@ mod tests {
         assert_eq!(value.unwrap(), "00-abc123-def456-01");
     }
 
+    #[cfg(feature = "grpc")]
     #[test]
     fn test_metadata_extractor_get_returns_none_for_missing() {
         let metadata = tonic::metadata::MetadataMap::new();
@@ -1499,6 +2809,7 @@ mod tests {
         assert!(value.is_none(), "get should return None for missing key");
     }
 
+    #[cfg(feature = "grpc")]
     #[test]
     fn test_metadata_extractor_keys_returns_trace_context_keys() {
         let mut metadata = tonic::metadata::MetadataMap::new();
@@ -1521,6 +2832,7 @@ mod tests {
         );
     }
 
+    #[cfg(feature = "grpc")]
     #[test]
     fn test_metadata_extractor_keys_returns_only_present_keys() {
         let mut metadata = tonic::metadata::MetadataMap::new();
@@ -1545,6 +2857,7 @@ mod tests {
     // Tests for HttpHeaderExtractor (HTTP headers)
     // ========================================================================
 
+    #[cfg(feature = "http")]
     #[test]
     fn test_http_header_extractor_get_returns_value() {
         let mut headers = http::HeaderMap::new();
@@ -1557,6 +2870,7 @@ mod tests {
         assert_eq!(value.unwrap(), "00-abc123-def456-01");
     }
 
+    #[cfg(feature = "http")]
     #[test]
     fn test_http_header_extractor_get_returns_none_for_missing() {
         let headers = http::HeaderMap::new();
@@ -1566,6 +2880,7 @@ mod tests {
         assert!(value.is_none(), "get should return None for missing key");
     }
 
+    #[cfg(feature = "http")]
     #[test]
     fn test_http_header_extractor_keys_returns_trace_context_keys() {
         let mut headers = http::HeaderMap::new();
@@ -1588,6 +2903,7 @@ mod tests {
         );
     }
 
+    #[cfg(feature = "http")]
     #[test]
     fn test_http_header_extractor_keys_returns_only_present_keys() {
         let mut headers = http::HeaderMap::new();
@@ -1624,6 +2940,7 @@ mod tests {
         );
     }
 
+    #[cfg(feature = "grpc")]
     #[test]
     fn test_extract_trace_context_grpc_with_valid_headers() {
         init_test_propagator();
@@ -1640,6 +2957,7 @@ mod tests {
         assert_extracted_trace_id(&context, "0af7651916cd43dd8448eb211c80319c");
     }
 
+    #[cfg(feature = "http")]
     #[test]
     fn test_extract_trace_context_http_with_valid_headers() {
         init_test_propagator();
@@ -1705,6 +3023,7 @@ mod tests {
         });
     }
 
+    #[cfg(feature = "grpc")]
     #[test]
     fn test_link_distributed_trace_grpc_actually_links() {
         // Test that link_distributed_trace extracts context and calls set_parent.
@@ -1740,6 +3059,7 @@ mod tests {
         });
     }
 
+    #[cfg(feature = "http")]
     #[test]
     fn test_link_distributed_trace_http_actually_links() {
         with_otel_subscriber(|| {
@@ -1792,6 +3112,45 @@ mod tests {
         });
     }
 
+    #[test]
+    fn test_console_format_stays_full_without_an_environment() {
+        let export = ExportConfig::new(&crate::ServiceInfo::default());
+        assert_eq!(export.environment(), None);
+        assert_eq!(
+            ConsoleFormat::default_for(export.environment()),
+            ConsoleFormat::Full
+        );
+        assert_eq!(
+            ConsoleFormat::default_for(Some(Environment::Dev)),
+            ConsoleFormat::Pretty
+        );
+        assert_eq!(
+            ConsoleFormat::default_for(Some(Environment::Prod)),
+            ConsoleFormat::Json
+        );
+    }
+
+    #[test]
+    fn test_link_distributed_trace_map_attaches_the_request_id() {
+        with_otel_subscriber(|| {
+            let mut headers: HashMap<String, String> = HashMap::new();
+            headers.insert(
+                "traceparent".to_string(),
+                "00-33333333333333333333333333333333-cccccccccccccccc-01".to_string(),
+            );
+            headers.insert(REQUEST_ID_HEADER.to_string(), "order-42".to_string());
+
+            let span = tracing::info_span!("test_link_map_request_id");
+            let _enter = span.enter();
+            let _ = link_distributed_trace_map(&headers);
+
+            assert_eq!(
+                current_request_id().as_ref().map(RequestId::as_str),
+                Some("order-42")
+            );
+        });
+    }
+
     // ========================================================================
     // Tests for init_propagator
     // ========================================================================
@@ -1835,6 +3194,7 @@ mod tests {
     // Tests for TraceContextCarrier::extract_trace_context implementations
     // ========================================================================
 
+    #[cfg(feature = "grpc")]
     #[test]
     fn test_metadata_map_extract_trace_context_returns_valid_context() {
         init_test_propagator();
@@ -1861,6 +3221,7 @@ mod tests {
         );
     }
 
+    #[cfg(feature = "http")]
     #[test]
     fn test_http_header_map_extract_trace_context_returns_valid_context() {
         init_test_propagator();
@@ -1915,6 +3276,7 @@ mod tests {
     // Tests for TraceContextCarrier::inject_trace_context implementations
     // ========================================================================
 
+    #[cfg(feature = "grpc")]
     #[test]
     fn test_metadata_map_inject_trace_context_modifies_carrier() {
         let _provider = init_tracing_with_otel();
@@ -1959,6 +3321,7 @@ mod tests {
         );
     }
 
+    #[cfg(feature = "http")]
     #[test]
     fn test_http_header_map_inject_trace_context_modifies_carrier() {
         let _provider = init_tracing_with_otel();
@@ -2029,7 +3392,7 @@ mod tests {
         // Use the TraceContextCarrier trait method
         let mut headers: HashMap<String, String> = HashMap::new();
         assert!(
-            headers.get("traceparent").is_none(),
+            !headers.contains_key("traceparent"),
             "headers should start empty"
         );
 
@@ -2037,7 +3400,7 @@ mod tests {
 
         // Verify injection actually modified the carrier
         assert!(
-            headers.get("traceparent").is_some(),
+            headers.contains_key("traceparent"),
             "inject_trace_context should add traceparent"
         );
         let traceparent = headers.get("traceparent").unwrap();
@@ -2051,6 +3414,7 @@ mod tests {
     // Tests for link_distributed_trace functions
     // ========================================================================
 
+    #[cfg(feature = "grpc")]
     #[test]
     fn test_link_distributed_trace_grpc_calls_set_parent() {
         // This test verifies that link_distributed_trace actually calls set_parent
@@ -2087,6 +3451,7 @@ mod tests {
         );
     }
 
+    #[cfg(feature = "http")]
     #[test]
     fn test_link_distributed_trace_http_extracts_and_links() {
         init_test_propagator();
@@ -2137,6 +3502,7 @@ mod tests {
         );
     }
 
+    #[cfg(feature = "http")]
     #[test]
     fn test_trace_context_ext_link_distributed_trace_extracts_context() {
         init_test_propagator();
@@ -2167,6 +3533,7 @@ mod tests {
     // Tests for MetadataInjector
     // ========================================================================
 
+    #[cfg(feature = "grpc")]
     #[test]
     fn test_metadata_injector_set_adds_header() {
         let mut metadata = tonic::metadata::MetadataMap::new();
@@ -2185,6 +3552,7 @@ mod tests {
         );
     }
 
+    #[cfg(feature = "grpc")]
     #[test]
     fn test_metadata_injector_set_handles_invalid_key_gracefully() {
         let mut metadata = tonic::metadata::MetadataMap::new();
@@ -2205,6 +3573,7 @@ mod tests {
     // Tests for HttpHeaderInjector
     // ========================================================================
 
+    #[cfg(feature = "http")]
     #[test]
     fn test_http_header_injector_set_adds_header() {
         let mut headers = http::HeaderMap::new();
@@ -2223,6 +3592,7 @@ mod tests {
         );
     }
 
+    #[cfg(feature = "http")]
     #[test]
     fn test_http_header_injector_set_handles_invalid_key_gracefully() {
         let mut headers = http::HeaderMap::new();
@@ -2243,6 +3613,7 @@ mod tests {
     // Tests for init_otel_logs_builder
     // ========================================================================
 
+    #[cfg(feature = "otlp")]
     #[test]
     fn test_init_otel_logs_builder_returns_configured_builder() {
         // Create a tokio runtime for the async exporter
@@ -2254,12 +3625,22 @@ mod tests {
                 version: "1.0.0",
                 author: "Test",
                 description: "Test service",
+                git_sha: "",
+                git_dirty: false,
+                build_timestamp: "",
+                rustc_version: "",
+                environment: Some(crate::Environment::Dev),
+                region: String::new(),
             };
 
             // Use a dummy endpoint - the builder doesn't connect until export
             let endpoint = "http://localhost:4317".to_string();
 
-            let result = super::init_otel_logs_builder(&service_info, &endpoint);
+            let result = super::otlp::init_otel_logs_builder(
+                &ExportConfig::new(&service_info),
+                &endpoint,
+                false,
+            );
 
             // The function should succeed and return a configured builder
             assert!(
@@ -2286,6 +3667,7 @@ mod tests {
     // Tests for init_traces and init_metrics
     // ========================================================================
 
+    #[cfg(feature = "otlp")]
     #[test]
     fn test_init_traces_with_endpoint_returns_provider() {
         let rt = tokio::runtime::Runtime::new().unwrap();
@@ -2296,13 +3678,20 @@ mod tests {
                 version: "1.0.0",
                 author: "Test",
                 description: "Test service",
+                git_sha: "",
+                git_dirty: false,
+                build_timestamp: "",
+                rustc_version: "",
+                environment: Some(crate::Environment::Dev),
+                region: String::new(),
             };
 
             let settings = TraceSettings {
                 endpoint: Some("http://localhost:4317".to_string()),
+                ..Default::default()
             };
 
-            let result = super::init_traces(&service_info, &settings);
+            let result = super::otlp::init_traces(&settings, &ExportConfig::new(&service_info));
 
             assert!(result.is_ok(), "init_traces should succeed");
             let provider = result.unwrap();
@@ -2326,11 +3715,17 @@ mod tests {
             version: "1.0.0",
             author: "Test",
             description: "Test service",
+            git_sha: "",
+            git_dirty: false,
+            build_timestamp: "",
+            rustc_version: "",
+            environment: Some(crate::Environment::Dev),
+            region: String::new(),
         };
 
-        let settings = TraceSettings { endpoint: None };
+        let settings = TraceSettings::default();
 
-        let result = super::init_traces(&service_info, &settings);
+        let result = super::otlp::init_traces(&settings, &ExportConfig::new(&service_info));
 
         assert!(result.is_ok(), "init_traces should succeed");
         let provider = result.unwrap();
@@ -2340,6 +3735,86 @@ mod tests {
         );
     }
 
+    #[cfg(all(unix, feature = "otlp"))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_traces_export_to_unix_socket_endpoint() {
+        use opentelemetry::trace::Tracer as _;
+        use tokio::io::AsyncReadExt as _;
+
+        let dir = tempfile::tempdir().unwrap();
+        let socket = dir.path().join("collector.sock");
+        let listener = tokio::net::UnixListener::bind(&socket).unwrap();
+
+        let settings = TraceSettings {
+            endpoint: Some(format!("unix://{}", socket.display())),
+            ..Default::default()
+        };
+        let export = ExportConfig::new(&crate::ServiceInfo::default());
+        let provider = super::otlp::init_traces(&settings, &export)
+            .unwrap()
+            .unwrap();
+        provider.tracer("test").in_span("exported", |_| {});
+        let flush = tokio::task::spawn_blocking(move || {
+            let _ = provider.force_flush();
+            provider
+        });
+
+        let (mut stream, _) = tokio::time::timeout(Duration::from_secs(10), listener.accept())
+            .await
+            .expect("the exporter should connect to the socket")
+            .unwrap();
+        let mut preface = [0; 14];
+        stream.read_exact(&mut preface).await.unwrap();
+        assert_eq!(&preface, b"PRI * HTTP/2.0");
+
+        drop(stream);
+        let _ = flush.await.unwrap().shutdown();
+    }
+
+    #[cfg(all(feature = "otlp", not(feature = "zstd")))]
+    #[test]
+    fn test_compression_requires_its_feature() {
+        let settings = TelemetrySettings {
+            trace: TraceSettings {
+                endpoint: Some("http://localhost:4317".to_string()),
+                ..Default::default()
+            },
+            compression: Some(Compression::Zstd),
+            ..Default::default()
+        };
+        let export =
+            ExportConfig::from_settings(&crate::ServiceInfo::default(), &settings).unwrap();
+
+        let err = super::otlp::init_traces(&settings.trace, &export).unwrap_err();
+        assert!(err.to_string().contains("zstd-tonic"), "{err}");
+    }
+
+    #[test]
+    fn test_span_limits_drop_extra_events() {
+        let settings: TraceSettings = toml::from_str("[limits]\nmax_events_per_span = 2").unwrap();
+        assert_eq!(settings.limits.max_attributes_per_span, 128);
+
+        let exporter = opentelemetry_sdk::trace::InMemorySpanExporter::default();
+        let provider = sdktrace::SdkTracerProvider::builder()
+            .with_span_limits(settings.limits.into())
+            .with_simple_exporter(exporter.clone())
+            .build();
+        let subscriber =
+            tracing_subscriber::registry().with(OpenTelemetryLayer::new(provider.tracer("test")));
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info_span!("query_heavy").in_scope(|| {
+                for query in 0..3 {
+                    tracing::info!(query, "query");
+                }
+            });
+        });
+
+        let spans = exporter.get_finished_spans().unwrap();
+        assert_eq!(spans[0].events.len(), 2);
+        assert_eq!(spans[0].events.dropped_count, 1);
+    }
+
+    #[cfg(feature = "otlp")]
     #[test]
     fn test_init_metrics_with_endpoint_returns_provider() {
         let rt = tokio::runtime::Runtime::new().unwrap();
@@ -2350,13 +3825,25 @@ mod tests {
                 version: "1.0.0",
                 author: "Test",
                 description: "Test service",
+                git_sha: "",
+                git_dirty: false,
+                build_timestamp: "",
+                rustc_version: "",
+                environment: Some(crate::Environment::Dev),
+                region: String::new(),
             };
 
             let settings = MetricSettings {
                 endpoint: Some("http://localhost:4317".to_string()),
+                ..Default::default()
             };
 
-            let result = super::init_metrics(&service_info, &settings);
+            let result = super::otlp::init_metrics(
+                &service_info,
+                &settings,
+                &ExportConfig::new(&service_info),
+                SdkMeterProvider::builder(),
+            );
 
             assert!(result.is_ok(), "init_metrics should succeed");
             let provider = result.unwrap();
@@ -2380,11 +3867,25 @@ mod tests {
             version: "1.0.0",
             author: "Test",
             description: "Test service",
+            git_sha: "",
+            git_dirty: false,
+            build_timestamp: "",
+            rustc_version: "",
+            environment: Some(crate::Environment::Dev),
+            region: String::new(),
         };
 
-        let settings = MetricSettings { endpoint: None };
+        let settings = MetricSettings {
+            endpoint: None,
+            ..Default::default()
+        };
 
-        let result = super::init_metrics(&service_info, &settings);
+        let result = super::otlp::init_metrics(
+            &service_info,
+            &settings,
+            &ExportConfig::new(&service_info),
+            SdkMeterProvider::builder(),
+        );
 
         assert!(result.is_ok(), "init_metrics should succeed");
         let provider = result.unwrap();
@@ -2406,12 +3907,20 @@ mod tests {
             version: "1.0.0",
             author: "Test",
             description: "Test service",
+            git_sha: "",
+            git_dirty: false,
+            build_timestamp: "",
+            rustc_version: "",
+            environment: Some(crate::Environment::Dev),
+            region: String::new(),
         };
 
         let settings = LogSettings {
             console_level: "info".to_string(),
             otel_level: "info".to_string(),
             endpoint: None,
+            rate_limit: None,
+            ..Default::default()
         };
 
         let builder = super::LogSubscriberBuilder::new(&service_info, &settings);
@@ -2432,12 +3941,20 @@ mod tests {
             version: "1.0.0",
             author: "Test",
             description: "Test service",
+            git_sha: "",
+            git_dirty: false,
+            build_timestamp: "",
+            rustc_version: "",
+            environment: Some(crate::Environment::Dev),
+            region: String::new(),
         };
 
         let settings = LogSettings {
             console_level: "info".to_string(),
             otel_level: "info".to_string(),
             endpoint: None,
+            rate_limit: None,
+            ..Default::default()
         };
 
         let tracer_provider = SdkTracerProvider::builder().build();
@@ -2462,12 +3979,20 @@ mod tests {
             version: "1.0.0",
             author: "Test",
             description: "Test service",
+            git_sha: "",
+            git_dirty: false,
+            build_timestamp: "",
+            rustc_version: "",
+            environment: Some(crate::Environment::Dev),
+            region: String::new(),
         };
 
         let settings = LogSettings {
             console_level: "info".to_string(),
             otel_level: "info".to_string(),
             endpoint: None, // No OTel endpoint - just console logging
+            rate_limit: None,
+            ..Default::default()
         };
 
         let result = super::LogSubscriberBuilder::new(&service_info, &settings).build();
@@ -2513,6 +4038,142 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_log_level_handle_reloads_console_level() {
+        let service_info = crate::ServiceInfo::default();
+        let settings = LogSettings {
+            console_level: "info".to_string(),
+            otel_level: "warn".to_string(),
+            endpoint: None,
+            rate_limit: None,
+            ..Default::default()
+        };
+
+        let built = super::LogSubscriberBuilder::new(&service_info, &settings)
+            .build()
+            .unwrap();
+        let log_levels = built.log_levels.clone();
+
+        log_levels.set_console_level("debug").unwrap();
+        log_levels.set_otel_level("error").unwrap();
+        assert_eq!(log_levels.console_level(), "debug");
+        assert_eq!(log_levels.otel_level(), "error");
+
+        // Invalid directives are rejected and leave the level untouched
+        let err = log_levels.set_console_level("foo=notalevel").unwrap_err();
+        assert!(matches!(err, Error::InvalidLogLevel { .. }));
+        assert_eq!(log_levels.console_level(), "debug");
+
+        // An empty level falls back to RUST_LOG
+        log_levels.set_console_level("").unwrap();
+        assert_eq!(log_levels.console_level(), "");
+
+        log_levels.reset().unwrap();
+        assert_eq!(log_levels.console_level(), "info");
+        assert_eq!(log_levels.otel_level(), "warn");
+
+        // Once the subscriber is gone there is nothing left to reload
+        drop(built);
+        let err = log_levels.set_console_level("trace").unwrap_err();
+        assert!(matches!(err, Error::ReloadLogLevel { .. }));
+    }
+
+    #[test]
+    fn test_otel_suppressed_targets() {
+        // Missing from the config file means the built-in list
+        let settings: LogSettings = toml::from_str(
+            r#"
+            console_level = "info"
+            otel_level = "info"
+            "#,
+        )
+        .unwrap();
+        assert!(settings.otel_suppressed_targets.iter().any(|t| t == "h2"));
+
+        let settings: LogSettings = toml::from_str(
+            r#"
+            console_level = "info"
+            otel_level = "info"
+            otel_suppressed_targets = ["hyper", "tonic"]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(settings.otel_suppressed_targets, ["hyper", "tonic"]);
+
+        let err = otel_suppression_directives(&["h2=debug".to_string()]).unwrap_err();
+        assert!(matches!(err, Error::InvalidSuppressedTarget { .. }));
+    }
+
+    #[test]
+    fn test_otel_diagnostics_have_their_own_console_level() {
+        let enabled = |filter: EnvFilter| {
+            let subscriber = tracing_subscriber::registry().with(filter);
+            tracing::subscriber::with_default(subscriber, || {
+                (
+                    tracing::enabled!(target: "opentelemetry_sdk", tracing::Level::WARN),
+                    tracing::enabled!(target: "opentelemetry_otlp", tracing::Level::DEBUG),
+                    tracing::enabled!(target: "yourcrate", tracing::Level::WARN),
+                )
+            })
+        };
+        let directive = otel_diagnostics_directive("warn").unwrap();
+
+        let filter = with_otel_diagnostics(level_filter("error"), "error", directive.as_ref());
+        assert_eq!(enabled(filter), (true, false, false));
+        let filter = with_otel_diagnostics(level_filter("debug"), "debug", directive.as_ref());
+        assert_eq!(enabled(filter), (true, false, true));
+        let filter = with_otel_diagnostics(level_filter("off"), "off", directive.as_ref());
+        assert_eq!(enabled(filter), (false, false, false));
+        let filter = with_otel_diagnostics(level_filter("error"), "error", None);
+        assert_eq!(enabled(filter), (false, false, false));
+
+        assert_eq!(LogSettings::default().otel_diagnostics_level, "warn");
+        let err = otel_diagnostics_directive("notalevel").unwrap_err();
+        assert!(matches!(err, Error::InvalidLogLevel { .. }));
+    }
+
+    #[test]
+    fn test_trace_level_is_independent_of_otel_level() {
+        use opentelemetry_sdk::trace::{InMemorySpanExporter, SdkTracerProvider};
+
+        let service_info = crate::ServiceInfo::default();
+        let settings = LogSettings {
+            console_level: "off".to_string(),
+            otel_level: "warn".to_string(),
+            trace_level: "debug".to_string(),
+            ..Default::default()
+        };
+        let exporter = InMemorySpanExporter::default();
+        let tracer_provider = SdkTracerProvider::builder()
+            .with_simple_exporter(exporter.clone())
+            .build();
+
+        let built = super::LogSubscriberBuilder::new(&service_info, &settings)
+            .with_tracer_provider(&tracer_provider)
+            .build()
+            .unwrap();
+        let log_levels = built.log_levels.clone();
+        assert_eq!(log_levels.trace_level(), "debug");
+
+        tracing::subscriber::with_default(built.subscriber, || {
+            tracing::debug_span!("exported").in_scope(|| {});
+
+            // Clearing the trace level follows the otel level again
+            log_levels.set_trace_level("").unwrap();
+            assert_eq!(log_levels.trace_level(), "warn");
+            tracing::debug_span!("filtered").in_scope(|| {});
+
+            log_levels.set_otel_level("error").unwrap();
+            assert_eq!(log_levels.trace_level(), "error");
+            log_levels.reset().unwrap();
+            assert_eq!(log_levels.trace_level(), "debug");
+        });
+
+        let spans = exporter.get_finished_spans().unwrap();
+        let names: Vec<_> = spans.iter().map(|span| span.name.as_ref()).collect();
+        assert_eq!(names, ["exported"]);
+    }
+
     #[test]
     fn test_log_subscriber_builder_build_with_tracer_provider() {
         // Test that when a tracer_provider is passed, the subscriber includes the OTel trace layer.
@@ -2524,12 +4185,20 @@ mod tests {
             version: "1.0.0",
             author: "Test",
             description: "Test service",
+            git_sha: "",
+            git_dirty: false,
+            build_timestamp: "",
+            rustc_version: "",
+            environment: Some(crate::Environment::Dev),
+            region: String::new(),
         };
 
         let settings = LogSettings {
             console_level: "info".to_string(),
             otel_level: "info".to_string(),
             endpoint: None,
+            rate_limit: None,
+            ..Default::default()
         };
 
         let tracer_provider = SdkTracerProvider::builder().build();
@@ -2554,4 +4223,571 @@ mod tests {
         // Clean up
         let _ = tracer_provider.shutdown();
     }
+
+    #[test]
+    fn test_shutdown_reports_failed_providers() {
+        use opentelemetry_sdk::trace::{InMemorySpanExporter, SdkTracerProvider};
+
+        let tracer_provider = SdkTracerProvider::builder()
+            .with_simple_exporter(InMemorySpanExporter::default())
+            .build();
+        let (meter_provider, _exporter) = testing::meter_provider();
+        let mut providers = TelemetryProviders::default();
+        providers.tracer = Some(tracer_provider.clone());
+        providers.meter = Some(meter_provider);
+        // Shutting down twice is an error, the meter provider is still shut down
+        tracer_provider.shutdown().unwrap();
+
+        let err = providers.shutdown(Duration::from_secs(5)).unwrap_err();
+
+        let failures = err.failures();
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].provider, Provider::Tracer);
+        assert!(matches!(failures[0].source, OTelSdkError::AlreadyShutdown));
+        assert_eq!(
+            err.to_string(),
+            "Could not shut down telemetry: tracer provider: Shutdown already invoked"
+        );
+
+        let (meter_provider, _exporter) = testing::meter_provider();
+        let mut providers = TelemetryProviders::default();
+        providers.meter = Some(meter_provider);
+        providers.shutdown(Duration::from_secs(5)).unwrap();
+    }
+
+    #[test]
+    fn test_drop_hands_shutdown_errors_to_the_handler() {
+        use opentelemetry_sdk::trace::{InMemorySpanExporter, SdkTracerProvider};
+
+        let tracer_provider = SdkTracerProvider::builder()
+            .with_simple_exporter(InMemorySpanExporter::default())
+            .build();
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let mut providers =
+            TelemetryProviders::default().on_shutdown_error(move |err| sender.send(err).unwrap());
+        providers.tracer = Some(tracer_provider.clone());
+        tracer_provider.shutdown().unwrap();
+
+        drop(providers);
+
+        let err = receiver.try_recv().unwrap();
+        assert_eq!(err.failures().len(), 1);
+        assert_eq!(err.failures()[0].provider, Provider::Tracer);
+
+        // Nothing to report when every provider shuts down
+        let (sender, receiver) = std::sync::mpsc::channel::<ShutdownError>();
+        let (meter_provider, _exporter) = testing::meter_provider();
+        let mut providers =
+            TelemetryProviders::default().on_shutdown_error(move |err| sender.send(err).unwrap());
+        providers.meter = Some(meter_provider);
+        drop(providers);
+        assert!(receiver.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_drop_hands_shutdown_errors_to_the_hook() {
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let sender = Mutex::new(sender);
+        set_shutdown_error_hook(move |err| {
+            let _ = sender.lock().unwrap().send(err);
+        });
+        let logger_provider = SdkLoggerProvider::builder().build();
+        let mut providers = TelemetryProviders::default();
+        providers.logger = Some(logger_provider.clone());
+        logger_provider.shutdown().unwrap();
+
+        drop(providers);
+
+        let err = receiver.try_recv().unwrap();
+        assert_eq!(err.failures()[0].provider, Provider::Logger);
+        assert!(matches!(
+            err.failures()[0].source,
+            OTelSdkError::AlreadyShutdown
+        ));
+    }
+
+    #[test]
+    fn test_force_flush_exports_pending_metrics() {
+        let (meter_provider, exporter) = testing::meter_provider();
+        meter_provider
+            .meter("test")
+            .u64_counter("flushed")
+            .build()
+            .add(1, &[]);
+        let mut providers = TelemetryProviders::default();
+        providers.meter = Some(meter_provider);
+
+        providers.force_flush().unwrap();
+        assert!(!exporter.get_finished_metrics().unwrap().is_empty());
+        assert!(providers.meter_provider().is_some());
+        assert!(providers.tracer_provider().is_none());
+
+        // Flushing leaves the providers running
+        providers.force_flush().unwrap();
+        providers.shutdown(Duration::from_secs(5)).unwrap();
+    }
+
+    #[cfg(feature = "grpc")]
+    #[tokio::test]
+    async fn test_grpc_trace_context_layer_records_rpc_attributes() {
+        use opentelemetry::trace::Status;
+        use tower::{Layer as _, ServiceExt as _};
+
+        let capture = test::capture();
+        let service = GrpcTraceContextLayer::new("my-service").layer(tower::service_fn(
+            |request: http::Request<()>| async move {
+                let mut response = http::Response::new(());
+                let status = match request.uri().path() {
+                    "/shop.Cart/Checkout" => "13",
+                    _ => "5",
+                };
+                response
+                    .headers_mut()
+                    .insert("grpc-status", status.parse().unwrap());
+                Ok::<_, std::convert::Infallible>(response)
+            },
+        ));
+
+        for path in ["/shop.Cart/Checkout", "/shop.Cart/Get"] {
+            let request = http::Request::post(path).body(()).unwrap();
+            service.clone().oneshot(request).await.unwrap();
+        }
+
+        let attribute = |span: &opentelemetry_sdk::trace::SpanData, key: &str| {
+            span.attributes
+                .iter()
+                .find(|kv| kv.key.as_str() == key)
+                .map(|kv| kv.value.to_string())
+        };
+
+        let span = capture.span("shop.Cart/Checkout").unwrap();
+        assert_eq!(attribute(&span, "rpc.system").as_deref(), Some("grpc"));
+        assert_eq!(
+            attribute(&span, "rpc.service").as_deref(),
+            Some("shop.Cart")
+        );
+        assert_eq!(attribute(&span, "rpc.method").as_deref(), Some("Checkout"));
+        assert_eq!(
+            attribute(&span, "rpc.grpc.status_code").as_deref(),
+            Some("13")
+        );
+        assert!(attribute(&span, "latency_ms").is_some());
+        assert!(matches!(span.status, Status::Error { .. }));
+
+        // NOT_FOUND is the caller's error
+        let span = capture.span("shop.Cart/Get").unwrap();
+        assert_eq!(
+            attribute(&span, "rpc.grpc.status_code").as_deref(),
+            Some("5")
+        );
+        assert_eq!(span.status, Status::Unset);
+    }
+
+    #[cfg(feature = "grpc")]
+    #[tokio::test]
+    async fn test_grpc_trace_context_layer_custom_span_details() {
+        use tower::{Layer as _, ServiceExt as _};
+
+        let capture = test::capture();
+        let layer = GrpcTraceContextLayer::new("my-service").with_span_details(|request| {
+            let tenant = request.headers["x-tenant"].to_str().unwrap().to_string();
+            SpanDetails::new(format!("checkout for {tenant}")).with_attribute("tenant", tenant)
+        });
+        let service = layer.layer(tower::service_fn(|_: http::Request<()>| async {
+            Ok::<_, std::convert::Infallible>(http::Response::new(()))
+        }));
+
+        let request = http::Request::post("/shop.Cart/Checkout")
+            .header("x-tenant", "acme")
+            .body(())
+            .unwrap();
+        service.oneshot(request).await.unwrap();
+
+        let span = capture.span("checkout for acme").unwrap();
+        assert!(span
+            .attributes
+            .contains(&opentelemetry::KeyValue::new("tenant", "acme")));
+        assert!(span
+            .attributes
+            .contains(&opentelemetry::KeyValue::new("rpc.method", "Checkout")));
+    }
+
+    #[cfg(feature = "grpc")]
+    #[test]
+    fn test_grpc_method_splits_the_path() {
+        assert_eq!(
+            grpc_method("/shop.v1.Cart/Checkout"),
+            Some(("shop.v1.Cart", "Checkout"))
+        );
+        assert_eq!(grpc_method("/shop.Cart/"), None);
+        assert_eq!(grpc_method("/health"), None);
+        assert_eq!(grpc_method("/a/b/c"), None);
+    }
+
+    #[cfg(feature = "grpc")]
+    #[tokio::test]
+    async fn test_grpc_trace_context_layer_reads_the_status_in_the_trailers() {
+        use opentelemetry::trace::Status;
+        use tower::{Layer as _, ServiceExt as _};
+
+        /// A streamed response body, ending with its status in the trailers.
+        struct Stream(Option<http::HeaderMap>);
+
+        impl http_body::Body for Stream {
+            type Data = std::io::Cursor<Vec<u8>>;
+            type Error = std::convert::Infallible;
+
+            fn poll_frame(
+                mut self: std::pin::Pin<&mut Self>,
+                _cx: &mut std::task::Context<'_>,
+            ) -> std::task::Poll<Option<Result<http_body::Frame<Self::Data>, Self::Error>>>
+            {
+                std::task::Poll::Ready(
+                    self.0
+                        .take()
+                        .map(|trailers| Ok(http_body::Frame::trailers(trailers))),
+                )
+            }
+        }
+
+        let capture = test::capture();
+        let service = GrpcTraceContextLayer::new("my-service").layer(tower::service_fn(
+            |_: http::Request<()>| async {
+                let mut trailers = http::HeaderMap::new();
+                trailers.insert("grpc-status", "14".parse().unwrap());
+                Ok::<_, std::convert::Infallible>(http::Response::new(Stream(Some(trailers))))
+            },
+        ));
+
+        let request = http::Request::post("/shop.Cart/Watch").body(()).unwrap();
+        let mut body = service.oneshot(request).await.unwrap().into_body();
+        // The span is open until the body is sent
+        assert!(capture.span("shop.Cart/Watch").is_none());
+        while std::future::poll_fn(|cx| {
+            http_body::Body::poll_frame(std::pin::Pin::new(&mut body), cx)
+        })
+        .await
+        .is_some()
+        {}
+        drop(body);
+
+        let span = capture.span("shop.Cart/Watch").unwrap();
+        assert!(span
+            .attributes
+            .contains(&opentelemetry::KeyValue::new("rpc.grpc.status_code", 14)));
+        assert!(matches!(span.status, Status::Error { .. }));
+    }
+
+    #[cfg(feature = "grpc")]
+    #[test]
+    fn test_record_grpc_status_marks_server_faults() {
+        use opentelemetry::trace::Status;
+
+        let capture = test::capture();
+        tracing::info_span!("unavailable").in_scope(|| {
+            let _ = Err::<(), _>(tonic::Status::unavailable("the warehouse is down"))
+                .record_grpc_status();
+        });
+        tracing::info_span!("not_found").in_scope(|| {
+            record_grpc_status(&tonic::Status::not_found("no such item"));
+        });
+
+        let span = capture.span("unavailable").unwrap();
+        assert_eq!(span.status, Status::error("the warehouse is down"));
+        assert!(span
+            .attributes
+            .contains(&opentelemetry::KeyValue::new("rpc.grpc.status_code", 14)));
+        // NOT_FOUND is the caller's error
+        let span = capture.span("not_found").unwrap();
+        assert_eq!(span.status, Status::Unset);
+        assert!(span
+            .attributes
+            .contains(&opentelemetry::KeyValue::new("rpc.grpc.status_code", 5)));
+    }
+
+    #[cfg(feature = "http")]
+    #[tokio::test]
+    async fn test_http_trace_context_layer_follows_http_semconv() {
+        use opentelemetry::trace::{SpanKind, Status};
+        use tower::{Layer as _, ServiceExt as _};
+
+        init_test_propagator();
+        let capture = test::capture();
+        let layer = HttpTraceContextLayer::new()
+            .with_route(|extensions| extensions.get::<&'static str>().copied());
+        let service = layer.layer(tower::service_fn(|request: http::Request<()>| async move {
+            let status = match request.uri().path() {
+                "/fail" => http::StatusCode::INTERNAL_SERVER_ERROR,
+                _ => http::StatusCode::NOT_FOUND,
+            };
+            let mut response = http::Response::new(());
+            *response.status_mut() = status;
+            Ok::<_, std::convert::Infallible>(response)
+        }));
+
+        let mut request = http::Request::get("/users/42")
+            .header(
+                "traceparent",
+                "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01",
+            )
+            .body(())
+            .unwrap();
+        request.extensions_mut().insert("/users/{id}");
+        service.clone().oneshot(request).await.unwrap();
+        let request = http::Request::post("/fail").body(()).unwrap();
+        service.oneshot(request).await.unwrap();
+
+        let attribute = |span: &opentelemetry_sdk::trace::SpanData, key: &str| {
+            span.attributes
+                .iter()
+                .find(|kv| kv.key.as_str() == key)
+                .map(|kv| kv.value.to_string())
+        };
+
+        let span = capture.span("GET /users/{id}").unwrap();
+        assert_eq!(span.span_kind, SpanKind::Server);
+        assert_eq!(
+            span.span_context.trace_id(),
+            TraceId::from_hex("0af7651916cd43dd8448eb211c80319c").unwrap()
+        );
+        assert_eq!(
+            attribute(&span, "http.route").as_deref(),
+            Some("/users/{id}")
+        );
+        assert_eq!(attribute(&span, "url.path").as_deref(), Some("/users/42"));
+        assert_eq!(
+            attribute(&span, "http.response.status_code").as_deref(),
+            Some("404")
+        );
+        assert_eq!(span.status, Status::Unset, "4xx is not a server error");
+
+        let span = capture.span("POST").unwrap();
+        assert_eq!(attribute(&span, "http.route"), None);
+        assert_eq!(attribute(&span, "error.type").as_deref(), Some("500"));
+        assert!(matches!(span.status, Status::Error { .. }));
+    }
+
+    #[cfg(feature = "http")]
+    #[tokio::test]
+    async fn test_http_trace_context_layer_samples_by_route() {
+        use tower::{Layer as _, ServiceExt as _};
+
+        init_test_propagator();
+        let capture = test::capture();
+        let rules: Vec<SamplingRule> = toml::from_str::<TraceSettings>(
+            r#"
+            [[sampling]]
+            route = "/assets/*"
+            ratio = 0.0
+
+            [[sampling]]
+            route = "/checkout"
+            method = "POST"
+            ratio = 1.0
+            "#,
+        )
+        .unwrap()
+        .sampling;
+        let service = HttpTraceContextLayer::new()
+            .with_sampling(&rules)
+            .layer(tower::service_fn(|_: http::Request<()>| async move {
+                tracing::info_span!("handler").in_scope(|| {});
+                Ok::<_, std::convert::Infallible>(http::Response::new(()))
+            }));
+
+        let request = http::Request::get("/assets/site.css").body(()).unwrap();
+        service.clone().oneshot(request).await.unwrap();
+        let request = http::Request::post("/checkout").body(()).unwrap();
+        service.clone().oneshot(request).await.unwrap();
+        // The caller's decision wins over the rules
+        let request = http::Request::get("/assets/logo.png")
+            .header(
+                "traceparent",
+                "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01",
+            )
+            .body(())
+            .unwrap();
+        service.oneshot(request).await.unwrap();
+
+        let names: Vec<_> = capture
+            .spans()
+            .iter()
+            .map(|span| span.name.to_string())
+            .collect();
+        assert_eq!(names.iter().filter(|name| *name == "GET").count(), 1);
+        assert_eq!(names.iter().filter(|name| *name == "POST").count(), 1);
+        assert_eq!(names.iter().filter(|name| *name == "handler").count(), 2);
+    }
+
+    #[cfg(feature = "http")]
+    #[tokio::test]
+    async fn test_http_trace_context_layer_propagates_the_request_id() {
+        use tower::{Layer as _, ServiceExt as _};
+
+        init_test_propagator();
+        let capture = test::capture();
+        let service = HttpTraceContextLayer::new().layer(tower::service_fn(
+            |request: http::Request<()>| async move {
+                let request_id = request.extensions().get::<RequestId>().cloned().unwrap();
+                assert_eq!(current_request_id(), Some(request_id));
+                tracing::info!("calling the inventory");
+                let mut outgoing = http::HeaderMap::new();
+                outgoing.inject_trace_context();
+                let mut response = http::Response::new(());
+                *response.headers_mut() = outgoing;
+                Ok::<_, std::convert::Infallible>(response)
+            },
+        ));
+
+        let request = http::Request::get("/orders")
+            .header(REQUEST_ID_HEADER, "req-42")
+            .body(())
+            .unwrap();
+        let response = service.clone().oneshot(request).await.unwrap();
+        // The handler copied its outgoing headers to the response
+        assert_eq!(response.headers()[REQUEST_ID_HEADER], "req-42");
+        assert!(response.headers().contains_key("traceparent"));
+        assert!(capture.logs()[0]
+            .attributes
+            .contains(&opentelemetry::KeyValue::new("request.id", "req-42")));
+
+        let request = http::Request::get("/orders").body(()).unwrap();
+        let response = service.oneshot(request).await.unwrap();
+        let generated = response.headers()[REQUEST_ID_HEADER].to_str().unwrap();
+        assert_eq!(generated.len(), 36);
+    }
+
+    #[cfg(feature = "grpc")]
+    #[tokio::test]
+    async fn test_trace_context_layers_attach_the_request_fields() {
+        use tower::{Layer as _, ServiceExt as _};
+
+        let capture = test::capture();
+        let tenant = |request: &http::request::Parts| {
+            let tenant = request.headers["x-tenant"].to_str().unwrap();
+            RequestFields::new().with_field("tenant.id", tenant)
+        };
+        let handler = tower::service_fn(|_: http::Request<()>| async {
+            tracing::info!("handled");
+            Ok::<_, std::convert::Infallible>(http::Response::new(()))
+        });
+
+        let request = || {
+            http::Request::get("/pkg.Orders/List")
+                .header("x-tenant", "acme")
+                .body(())
+                .unwrap()
+        };
+        let http = HttpTraceContextLayer::new().with_request_fields(tenant);
+        http.layer(handler).oneshot(request()).await.unwrap();
+        let grpc = GrpcTraceContextLayer::new("orders").with_request_fields(tenant);
+        grpc.layer(handler).oneshot(request()).await.unwrap();
+
+        let tenant = opentelemetry::KeyValue::new("tenant.id", "acme");
+        let logs = capture.logs();
+        assert_eq!(logs.len(), 2);
+        for log in logs {
+            assert!(log.attributes.contains(&tenant));
+        }
+        for name in ["GET", "pkg.Orders/List"] {
+            assert!(capture.span(name).unwrap().attributes.contains(&tenant));
+        }
+    }
+
+    #[test]
+    fn test_init_noop_has_no_providers() {
+        let providers = init_noop();
+        assert!(providers.meter_provider().is_none());
+        assert!(providers.tracer_provider().is_none());
+        assert!(providers.logger_provider().is_none());
+        assert!(providers.log_levels().is_none());
+
+        providers.force_flush().unwrap();
+        providers.shutdown(Duration::from_secs(1)).unwrap();
+    }
+
+    #[test]
+    fn test_build_does_not_install_the_subscriber() {
+        let service_info = crate::ServiceInfo::default();
+        let mut settings = TelemetrySettings::default();
+        settings.log.console_level = "info".to_string();
+        settings.log.otel_level = "info".to_string();
+
+        let telemetry = super::build(&service_info, &settings).unwrap();
+        let log_levels = telemetry.providers.log_levels().unwrap();
+
+        tracing::subscriber::with_default(telemetry.subscriber, || {
+            let span = tracing::info_span!("scoped");
+            assert!(!span.is_disabled(), "the built subscriber records spans");
+        });
+        assert_eq!(log_levels.console_level(), "info");
+        assert!(
+            tracing::info_span!("unscoped").is_disabled(),
+            "the built subscriber is not installed globally"
+        );
+
+        let mut settings = TelemetrySettings::default();
+        settings.log.otel_suppressed_targets = vec!["h2=debug".to_string()];
+        let result = super::build(&service_info, &settings);
+        assert!(matches!(result, Err(Error::InvalidSuppressedTarget { .. })));
+    }
+
+    #[cfg(feature = "otlp")]
+    #[tokio::test]
+    async fn test_reload_replaces_the_exporters_of_exported_signals() {
+        let mut settings: TelemetrySettings = toml::from_str(
+            r#"
+            [trace]
+            endpoint = "http://localhost:4317"
+            [log]
+            console_level = "info"
+            otel_level = "info"
+            [metric]
+            "#,
+        )
+        .unwrap();
+        let service_info = crate::ServiceInfo::default();
+        let telemetry = super::build(&service_info, &settings).unwrap();
+        let exports = telemetry.providers.exports();
+
+        settings.trace.endpoint = Some("http://collector.internal:4317".to_string());
+        exports.reload(&service_info, &settings).unwrap();
+
+        settings.trace.endpoint = Some("not a uri".to_string());
+        let err = exports.reload(&service_info, &settings).unwrap_err();
+        assert!(matches!(err, Error::InitTrace { .. }), "{err}");
+        // Logs and metrics are not exported, their endpoints are not used
+        settings.trace.endpoint = None;
+        settings.log.endpoint = Some("not a uri".to_string());
+        exports.reload(&service_info, &settings).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_local_only_creates_no_providers() {
+        let settings: TelemetrySettings = toml::from_str(
+            r#"
+            local_only = true
+            [trace]
+            endpoint = "http://localhost:4317"
+            [log]
+            console_level = "info"
+            otel_level = "info"
+            endpoint = "http://localhost:4317"
+            [metric]
+            endpoint = "http://localhost:4317"
+            "#,
+        )
+        .unwrap();
+
+        let telemetry = super::build(&crate::ServiceInfo::default(), &settings).unwrap();
+        assert!(telemetry.providers.tracer_provider().is_none());
+        assert!(telemetry.providers.meter_provider().is_none());
+        assert!(telemetry.providers.logger_provider().is_none());
+        tracing::subscriber::with_default(telemetry.subscriber, || {
+            assert!(
+                tracing::enabled!(tracing::Level::INFO),
+                "console logs are kept"
+            );
+        });
+    }
 }
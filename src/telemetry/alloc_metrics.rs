@@ -0,0 +1,183 @@
+//! Allocation counts as metrics, with any global allocator.
+//!
+//! [`CountingAllocator`] wraps the global allocator and counts what goes through it, so an
+//! allocation regression shows up on a dashboard whether the service uses the system allocator,
+//! jemalloc or mimalloc.
+
+use std::alloc::{GlobalAlloc, Layout};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use opentelemetry::metrics::Meter;
+
+/// Process-wide counts, there is only one global allocator.
+static ALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+static DEALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+static BYTES_ALLOCATED: AtomicU64 = AtomicU64::new(0);
+static BYTES_FREED: AtomicU64 = AtomicU64::new(0);
+
+/// A global allocator that counts the allocations of the allocator it wraps.
+///
+/// Counting costs a few relaxed atomic additions per allocation. Install it as the global
+/// allocator, around the allocator the service would use otherwise. It can't be used with the
+/// `jemalloc` feature, which installs jemalloc as the global allocator itself.
+///
+/// ```rust,ignore
+/// use byre::telemetry::CountingAllocator;
+///
+/// #[global_allocator]
+/// static ALLOCATOR: CountingAllocator<std::alloc::System> =
+///     CountingAllocator::new(std::alloc::System);
+/// ```
+///
+/// [`init`](super::init) then publishes the counts, see [`register_allocation_metrics`].
+#[derive(Debug, Default)]
+pub struct CountingAllocator<A> {
+    inner: A,
+}
+
+impl<A> CountingAllocator<A> {
+    /// Count the allocations of `inner`.
+    pub const fn new(inner: A) -> Self {
+        Self { inner }
+    }
+}
+
+/// Record that `size` bytes were allocated.
+fn record_alloc(size: usize) {
+    ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+    BYTES_ALLOCATED.fetch_add(size as u64, Ordering::Relaxed);
+}
+
+/// Record that `size` bytes were freed.
+fn record_dealloc(size: usize) {
+    DEALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+    BYTES_FREED.fetch_add(size as u64, Ordering::Relaxed);
+}
+
+// SAFETY: Every call is forwarded to `inner` unchanged, the counts never affect the allocations.
+unsafe impl<A: GlobalAlloc> GlobalAlloc for CountingAllocator<A> {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        // SAFETY: The caller upholds the contract of `GlobalAlloc::alloc`
+        let ptr = unsafe { self.inner.alloc(layout) };
+        if !ptr.is_null() {
+            record_alloc(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        // SAFETY: The caller upholds the contract of `GlobalAlloc::alloc_zeroed`
+        let ptr = unsafe { self.inner.alloc_zeroed(layout) };
+        if !ptr.is_null() {
+            record_alloc(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        // SAFETY: The caller upholds the contract of `GlobalAlloc::dealloc`
+        unsafe { self.inner.dealloc(ptr, layout) };
+        record_dealloc(layout.size());
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        // SAFETY: The caller upholds the contract of `GlobalAlloc::realloc`
+        let new_ptr = unsafe { self.inner.realloc(ptr, layout, new_size) };
+        if !new_ptr.is_null() {
+            // A reallocation only changes the bytes in use, it is not counted as an allocation
+            let old_size = layout.size();
+            if new_size > old_size {
+                BYTES_ALLOCATED.fetch_add((new_size - old_size) as u64, Ordering::Relaxed);
+            } else {
+                BYTES_FREED.fetch_add((old_size - new_size) as u64, Ordering::Relaxed);
+            }
+        }
+        new_ptr
+    }
+}
+
+/// Whether a [`CountingAllocator`] is counting the allocations of the process.
+pub(crate) fn is_counting() -> bool {
+    ALLOCATIONS.load(Ordering::Relaxed) > 0
+}
+
+/// Register observable instruments that publish the counts of the [`CountingAllocator`].
+///
+/// - `process.memory.allocations` - number of allocations
+/// - `process.memory.deallocations` - number of deallocations
+/// - `process.memory.in_use` - bytes allocated and not freed yet, in bytes
+///
+/// The counts stay at zero when the global allocator is not a [`CountingAllocator`].
+///
+/// [`init`](super::init) calls this when metrics are exported and a [`CountingAllocator`] is
+/// the global allocator.
+///
+/// # Example
+///
+/// ```
+/// let meter = opentelemetry::global::meter("my_service");
+/// byre::telemetry::register_allocation_metrics(&meter);
+/// ```
+pub fn register_allocation_metrics(meter: &Meter) {
+    meter
+        .u64_observable_counter("process.memory.allocations")
+        .with_description("Number of allocations made through the global allocator")
+        .with_unit("{allocation}")
+        .with_callback(|observer| observer.observe(ALLOCATIONS.load(Ordering::Relaxed), &[]))
+        .build();
+
+    meter
+        .u64_observable_counter("process.memory.deallocations")
+        .with_description("Number of deallocations made through the global allocator")
+        .with_unit("{deallocation}")
+        .with_callback(|observer| observer.observe(DEALLOCATIONS.load(Ordering::Relaxed), &[]))
+        .build();
+
+    meter
+        .u64_observable_gauge("process.memory.in_use")
+        .with_description("Bytes allocated through the global allocator and not freed yet")
+        .with_unit("By")
+        .with_callback(|observer| {
+            // The counts are read one after the other, a concurrent free may be seen first
+            let freed = BYTES_FREED.load(Ordering::Relaxed);
+            let allocated = BYTES_ALLOCATED.load(Ordering::Relaxed);
+            observer.observe(allocated.saturating_sub(freed), &[]);
+        })
+        .build();
+}
+
+#[cfg(test)]
+mod tests {
+    use std::alloc::System;
+
+    use super::*;
+    use crate::telemetry::testing::{meter_provider, metric_value};
+    use opentelemetry::metrics::MeterProvider as _;
+
+    #[test]
+    fn test_counting_allocator_publishes_its_counts() {
+        let (provider, exporter) = meter_provider();
+        register_allocation_metrics(&provider.meter("test"));
+        let allocator = CountingAllocator::new(System);
+        let layout = Layout::from_size_align(1 << 20, 8).unwrap();
+
+        // The test binary does not use the allocator, only these calls are counted
+        // SAFETY: `layout` has a non-zero size, and the pointers come from `allocator`
+        unsafe {
+            let ptr = allocator.alloc(layout);
+            assert!(!ptr.is_null());
+            let ptr = allocator.realloc(ptr, layout, 2 << 20);
+            assert!(!ptr.is_null());
+
+            assert!(is_counting());
+            let value = |name| metric_value(&provider, &exporter, name, &[]);
+            assert_eq!(value("process.memory.allocations"), 1.0);
+            assert_eq!(value("process.memory.deallocations"), 0.0);
+            assert_eq!(value("process.memory.in_use"), (2 << 20) as f64);
+
+            allocator.dealloc(ptr, Layout::from_size_align(2 << 20, 8).unwrap());
+            assert_eq!(value("process.memory.deallocations"), 1.0);
+            assert_eq!(value("process.memory.in_use"), 0.0);
+        }
+    }
+}
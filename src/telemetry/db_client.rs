@@ -0,0 +1,121 @@
+//! Spans and measurements of database client operations, following the OpenTelemetry database
+//! semantic conventions. Shared by the instrumented sqlx pool and Redis connection.
+
+use std::time::Instant;
+
+use opentelemetry::KeyValue;
+use tracing::field::Empty;
+
+use super::metrics;
+
+/// Serializes the tests of the wrappers, they assert on the same metric.
+#[cfg(test)]
+pub(crate) static TEST_LOCK: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+/// Why an operation failed.
+pub(crate) struct DbError {
+    /// The `error.type`, the status code when the database returned one.
+    pub(crate) error_type: String,
+    /// The `db.response.status_code`, ie: the SQLSTATE of a SQL database.
+    pub(crate) status_code: Option<String>,
+}
+
+/// The span and measurement of an operation, recorded once it finishes or is dropped.
+pub(crate) struct DbOperation {
+    span: tracing::Span,
+    start: Instant,
+    attributes: Vec<KeyValue>,
+    finished: bool,
+}
+
+impl DbOperation {
+    /// Start the client span of `operation` on the database `system`, ie: `postgresql`.
+    ///
+    /// The span is named `{operation} {namespace}`, or the system without an operation.
+    pub(crate) fn start(
+        system: &str,
+        namespace: Option<&str>,
+        operation: Option<&str>,
+        query_text: Option<&str>,
+    ) -> Self {
+        let name = match (operation, namespace) {
+            (Some(operation), Some(namespace)) => format!("{operation} {namespace}"),
+            (Some(operation), None) => operation.to_string(),
+            (None, _) => system.to_string(),
+        };
+        let span = tracing::info_span!(
+            "db_client_operation",
+            otel.name = name,
+            otel.kind = "client",
+            otel.status_code = Empty,
+            db.system.name = system,
+            db.namespace = namespace,
+            db.operation.name = operation,
+            db.operation.batch.size = Empty,
+            db.query.text = query_text,
+            db.response.status_code = Empty,
+            error.type = Empty,
+        );
+
+        let mut attributes = vec![KeyValue::new("db.system.name", system.to_string())];
+        if let Some(namespace) = namespace {
+            attributes.push(KeyValue::new("db.namespace", namespace.to_string()));
+        }
+        if let Some(operation) = operation {
+            attributes.push(KeyValue::new("db.operation.name", operation.to_string()));
+        }
+        Self {
+            span,
+            start: Instant::now(),
+            attributes,
+            finished: false,
+        }
+    }
+
+    /// The span of the operation, the work it waits for runs in it.
+    pub(crate) fn span(&self) -> &tracing::Span {
+        &self.span
+    }
+
+    /// Record the number of commands of a batch.
+    #[cfg(feature = "redis")]
+    pub(crate) fn record_batch_size(&self, size: usize) {
+        self.span.record("db.operation.batch.size", size);
+    }
+
+    /// Record the outcome and duration of the operation, only the first call counts.
+    pub(crate) fn finish(&mut self, error: Option<DbError>) {
+        if std::mem::replace(&mut self.finished, true) {
+            return;
+        }
+        if let Some(DbError {
+            error_type,
+            status_code,
+        }) = error
+        {
+            self.span.record("otel.status_code", "ERROR");
+            self.span.record("error.type", error_type.as_str());
+            if let Some(status_code) = status_code {
+                self.span
+                    .record("db.response.status_code", status_code.as_str());
+                self.attributes
+                    .push(KeyValue::new("db.response.status_code", status_code));
+            }
+            self.attributes
+                .push(KeyValue::new("error.type", error_type));
+        }
+        metrics::byre_histogram(
+            "db.client.operation.duration",
+            "Duration of database client operations",
+            "s",
+        )
+        .record(self.start.elapsed().as_secs_f64(), &self.attributes);
+    }
+}
+
+impl Drop for DbOperation {
+    fn drop(&mut self) {
+        // An operation that is dropped, ie: before all its rows were read, is not waited for
+        self.finish(None);
+    }
+}
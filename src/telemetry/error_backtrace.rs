@@ -0,0 +1,248 @@
+//! Backtraces attached to `ERROR` events.
+//!
+//! With [`LogSettings::error_backtraces`](super::LogSettings::error_backtraces), every `ERROR`
+//! event carries the backtrace of the code that emitted it, in the `exception.stacktrace` field
+//! of the console logs and the attribute of the same name of the OpenTelemetry logs. Events that
+//! already have an `exception.stacktrace`, such as the ones of the panic hook, are left as is.
+//!
+//! The backtrace is captured whatever `RUST_BACKTRACE` is set to, and capturing one is slow: it
+//! is meant for services where errors are rare.
+
+use std::backtrace::Backtrace;
+use std::fmt;
+
+use opentelemetry::logs::{AnyValue, LogRecord as _, Severity};
+use opentelemetry::{InstrumentationScope, Key};
+use opentelemetry_sdk::error::OTelSdkResult;
+use opentelemetry_sdk::logs::{LogProcessor, SdkLogRecord};
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::fmt::format::Writer;
+use tracing_subscriber::fmt::{FmtContext, FormatEvent, FormatFields};
+use tracing_subscriber::registry::LookupSpan;
+
+/// The field and attribute the backtrace is written to.
+const STACKTRACE: &str = "exception.stacktrace";
+
+/// Whether a backtrace is added to `event`.
+fn wants_backtrace(event: &Event<'_>) -> bool {
+    let metadata = event.metadata();
+    *metadata.level() == Level::ERROR && metadata.fields().field(STACKTRACE).is_none()
+}
+
+/// Console log format that writes the backtrace of `ERROR` events after the event.
+///
+/// With `json`, the backtrace is added as the first field of the JSON object instead.
+pub(crate) struct BacktraceFormat<F> {
+    inner: F,
+    enabled: bool,
+    json: bool,
+}
+
+impl<F> BacktraceFormat<F> {
+    /// Wrap `inner`, which writes JSON objects when `json` is set.
+    pub(crate) fn new(inner: F, enabled: bool, json: bool) -> Self {
+        Self {
+            inner,
+            enabled,
+            json,
+        }
+    }
+}
+
+impl<S, N, F> FormatEvent<S, N> for BacktraceFormat<F>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+    F: FormatEvent<S, N>,
+{
+    fn format_event(
+        &self,
+        ctx: &FmtContext<'_, S, N>,
+        mut writer: Writer<'_>,
+        event: &Event<'_>,
+    ) -> fmt::Result {
+        if !self.enabled || !wants_backtrace(event) {
+            return self.inner.format_event(ctx, writer, event);
+        }
+        let backtrace = Backtrace::force_capture().to_string();
+
+        let mut line = String::new();
+        self.inner
+            .format_event(ctx, Writer::new(&mut line), event)?;
+
+        if !self.json {
+            // The backtrace follows the event line, indented like the pretty format's fields
+            writer.write_str(&line)?;
+            writeln!(writer, "    {STACKTRACE}:")?;
+            for frame in backtrace.lines() {
+                writeln!(writer, "    {frame}")?;
+            }
+            return Ok(());
+        }
+
+        let Some(rest) = line.strip_prefix('{') else {
+            return writer.write_str(&line);
+        };
+        write!(writer, "{{\"{STACKTRACE}\":\"")?;
+        write_json_escaped(&mut writer, &backtrace)?;
+        writer.write_str("\",")?;
+        writer.write_str(rest)
+    }
+}
+
+/// Write `value` as the inside of a JSON string.
+fn write_json_escaped(writer: &mut impl fmt::Write, value: &str) -> fmt::Result {
+    for c in value.chars() {
+        match c {
+            '"' => writer.write_str("\\\"")?,
+            '\\' => writer.write_str("\\\\")?,
+            '\n' => writer.write_str("\\n")?,
+            '\r' => writer.write_str("\\r")?,
+            '\t' => writer.write_str("\\t")?,
+            c if c.is_control() => write!(writer, "\\u{:04x}", c as u32)?,
+            c => writer.write_char(c)?,
+        }
+    }
+    Ok(())
+}
+
+/// Log processor that adds the backtrace to `ERROR` log records.
+///
+/// Processors run on the thread that emitted the event, before the batch processor that
+/// exports the record, so the backtrace is the one of the code that logged it.
+#[cfg_attr(not(feature = "otlp"), allow(dead_code))]
+#[derive(Debug)]
+pub(crate) struct BacktraceProcessor;
+
+impl LogProcessor for BacktraceProcessor {
+    fn emit(&self, record: &mut SdkLogRecord, _instrumentation: &InstrumentationScope) {
+        let is_error = record
+            .severity_number()
+            .is_some_and(|severity| severity >= Severity::Error);
+        let has_stacktrace = record
+            .attributes_iter()
+            .any(|(key, _)| key.as_str() == STACKTRACE);
+        if is_error && !has_stacktrace {
+            record.add_attribute(
+                Key::from_static_str(STACKTRACE),
+                AnyValue::from(Backtrace::force_capture().to_string()),
+            );
+        }
+    }
+
+    fn force_flush(&self) -> OTelSdkResult {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use opentelemetry_appender_tracing::layer::OpenTelemetryTracingBridge;
+    use opentelemetry_sdk::logs::{InMemoryLogExporter, SdkLoggerProvider};
+    use tracing_subscriber::layer::SubscriberExt as _;
+
+    use super::*;
+
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl std::io::Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// The console output of an `INFO`, an `ERROR` and a panic-like `ERROR` event.
+    fn console_output(json: bool) -> String {
+        let buffer = Buffer::default();
+        let writer = buffer.clone();
+        let emit = || {
+            tracing::info!("fine");
+            tracing::error!("broken");
+            tracing::error!("exception.stacktrace" = "from the panic hook", "panicked");
+        };
+
+        if json {
+            let format = tracing_subscriber::fmt::format().json().flatten_event(true);
+            let subscriber = tracing_subscriber::registry().with(
+                tracing_subscriber::fmt::layer()
+                    .json()
+                    .event_format(BacktraceFormat::new(format, true, true))
+                    .with_writer(move || writer.clone()),
+            );
+            tracing::subscriber::with_default(subscriber, emit);
+        } else {
+            let format = tracing_subscriber::fmt::format();
+            let subscriber = tracing_subscriber::registry().with(
+                tracing_subscriber::fmt::layer()
+                    .event_format(BacktraceFormat::new(format, true, false))
+                    .with_ansi(false)
+                    .with_writer(move || writer.clone()),
+            );
+            tracing::subscriber::with_default(subscriber, emit);
+        }
+
+        let output = buffer.0.lock().unwrap().clone();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn test_console_errors_are_followed_by_their_backtrace() {
+        let output = console_output(false);
+
+        assert_eq!(output.matches("    exception.stacktrace:").count(), 1);
+        let (before, after) = output.split_once("    exception.stacktrace:").unwrap();
+        assert!(before.contains("fine") && before.contains("broken"));
+        assert!(after.contains("panicked"));
+    }
+
+    #[test]
+    fn test_json_errors_carry_their_backtrace() {
+        let output = console_output(true);
+        let lines: Vec<_> = output.lines().collect();
+
+        assert_eq!(lines.len(), 3);
+        assert!(!lines[0].contains(STACKTRACE));
+        assert!(lines[1].starts_with("{\"exception.stacktrace\":\""));
+        assert_eq!(lines[2].matches(STACKTRACE).count(), 1);
+    }
+
+    #[test]
+    fn test_json_escaping() {
+        let mut escaped = String::new();
+        write_json_escaped(&mut escaped, "a \"b\"\n\tc\\d\u{1}").unwrap();
+        assert_eq!(escaped, r#"a \"b\"\n\tc\\d\u0001"#);
+    }
+
+    #[test]
+    fn test_error_log_records_carry_their_backtrace() {
+        let exporter = InMemoryLogExporter::default();
+        let provider = SdkLoggerProvider::builder()
+            .with_log_processor(BacktraceProcessor)
+            .with_simple_exporter(exporter.clone())
+            .build();
+        let subscriber =
+            tracing_subscriber::registry().with(OpenTelemetryTracingBridge::new(&provider));
+
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!("fine");
+            tracing::error!("broken");
+        });
+
+        let logs = exporter.get_emitted_logs().unwrap();
+        let has_stacktrace: Vec<_> = logs
+            .iter()
+            .map(|log| {
+                log.record
+                    .attributes_iter()
+                    .any(|(key, _)| key.as_str() == STACKTRACE)
+            })
+            .collect();
+        assert_eq!(has_stacktrace, [false, true]);
+    }
+}
@@ -0,0 +1,368 @@
+//! Turning the exporters off and on, and replacing them, while the service runs.
+//!
+//! Each exporter is wrapped in a [`Switched`] exporter that drops the batches it is handed while
+//! its provider's export is disabled. Spans, logs and metrics are still recorded, and the
+//! console logs are unaffected, so turning the export back on resumes it with the next batch.
+//!
+//! The wrappers also record the batches they are handed, see [`export_metrics`](super::export_metrics).
+//!
+//! The wrapped exporter can be swapped with its [`Replacer`], ie: for a new endpoint, without
+//! rebuilding the provider the subscriber and the instruments hold on to.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock, RwLock};
+use std::time::{Duration, Instant};
+
+use opentelemetry::metrics::Meter;
+
+use opentelemetry_sdk::error::{OTelSdkError, OTelSdkResult};
+use opentelemetry_sdk::logs::{LogBatch, LogExporter};
+use opentelemetry_sdk::metrics::data::ResourceMetrics;
+use opentelemetry_sdk::metrics::exporter::PushMetricExporter;
+use opentelemetry_sdk::metrics::Temporality;
+use opentelemetry_sdk::trace::{SpanData, SpanExporter};
+use opentelemetry_sdk::Resource;
+
+use super::export_metrics::ExportMetrics;
+use super::Provider;
+
+/// Handle for turning the export of traces, logs or metrics off and on at runtime, ie: while the
+/// collector is being migrated.
+///
+/// Obtain one from [`TelemetryProviders::exports`](super::TelemetryProviders::exports). Handles
+/// are cheap to clone and all clones control the same exporters. Every export starts enabled.
+///
+/// With the `otlp` feature, the handle also points the exporters at new endpoints, see
+/// `ExportHandle::reload`.
+///
+/// ```rust,no_run
+/// use byre::telemetry::Provider;
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # let service = byre::ServiceInfo::default();
+/// # let settings = byre::telemetry::TelemetrySettings::default();
+/// let telemetry = byre::telemetry::init(&service, &settings)?;
+/// telemetry.exports().set_enabled(Provider::Tracer, false);
+/// // ... the collector moves ...
+/// telemetry.exports().set_enabled(Provider::Tracer, true);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct ExportHandle {
+    tracer: Arc<AtomicBool>,
+    logger: Arc<AtomicBool>,
+    meter: Arc<AtomicBool>,
+    metrics: Arc<OnceLock<ExportMetrics>>,
+    #[cfg(feature = "otlp")]
+    exporters: Arc<super::otlp::Exporters>,
+}
+
+impl Default for ExportHandle {
+    fn default() -> Self {
+        Self {
+            tracer: Arc::new(AtomicBool::new(true)),
+            logger: Arc::new(AtomicBool::new(true)),
+            meter: Arc::new(AtomicBool::new(true)),
+            metrics: Arc::default(),
+            #[cfg(feature = "otlp")]
+            exporters: Arc::default(),
+        }
+    }
+}
+
+impl ExportHandle {
+    /// Whether the export of `provider` is enabled.
+    pub fn is_enabled(&self, provider: Provider) -> bool {
+        self.switch(provider).load(Ordering::Relaxed)
+    }
+
+    /// Turn the export of `provider` off or on. While it is off, the batches of the provider are
+    /// dropped instead of exported.
+    pub fn set_enabled(&self, provider: Provider, enabled: bool) {
+        self.switch(provider).store(enabled, Ordering::Relaxed);
+    }
+
+    /// Wrap `exporter`, the exporter of `provider`, so that it follows this handle.
+    #[cfg_attr(not(feature = "otlp"), allow(dead_code))]
+    pub(crate) fn switched<E>(&self, provider: Provider, exporter: E) -> Switched<E> {
+        Switched {
+            inner: Arc::new(RwLock::new(Arc::new(exporter))),
+            provider,
+            enabled: self.switch(provider).clone(),
+            metrics: self.metrics.clone(),
+        }
+    }
+
+    /// Record the batches of the exporters with `meter`, see
+    /// [`export_metrics`](super::export_metrics). Only the first meter is used.
+    pub(crate) fn record_metrics(&self, meter: &Meter) {
+        let _ = self.metrics.set(ExportMetrics::new(meter));
+    }
+
+    /// The exporters the providers were built with, replaced by `reload`.
+    #[cfg(feature = "otlp")]
+    pub(crate) fn exporters(&self) -> &super::otlp::Exporters {
+        &self.exporters
+    }
+
+    fn switch(&self, provider: Provider) -> &Arc<AtomicBool> {
+        match provider {
+            Provider::Tracer => &self.tracer,
+            Provider::Logger => &self.logger,
+            Provider::Meter => &self.meter,
+        }
+    }
+}
+
+/// An exporter that drops its batches while its export is disabled, see [`ExportHandle`].
+#[cfg_attr(not(feature = "otlp"), allow(dead_code))]
+#[derive(Debug)]
+pub(crate) struct Switched<E> {
+    inner: Arc<RwLock<Arc<E>>>,
+    provider: Provider,
+    enabled: Arc<AtomicBool>,
+    metrics: Arc<OnceLock<ExportMetrics>>,
+}
+
+#[cfg_attr(not(feature = "otlp"), allow(dead_code))]
+impl<E> Switched<E> {
+    /// Handle for replacing the exporter batches are exported with.
+    pub(crate) fn replacer(&self) -> Replacer<E> {
+        Replacer(self.inner.clone())
+    }
+
+    fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    /// Record a batch of `items` dropped while the export is disabled.
+    fn disabled(&self, items: usize) -> OTelSdkResult {
+        if let Some(metrics) = self.metrics.get() {
+            metrics.disabled(self.provider, items);
+        }
+        Ok(())
+    }
+
+    /// Export a batch of `items` with `export`, and record how it went.
+    async fn export_with<F>(&self, items: usize, export: impl FnOnce(Arc<E>) -> F) -> OTelSdkResult
+    where
+        F: std::future::Future<Output = OTelSdkResult>,
+    {
+        let start = Instant::now();
+        let result = export(self.current()).await;
+        if let Some(metrics) = self.metrics.get() {
+            metrics.exported(self.provider, items, start, &result);
+        }
+        result
+    }
+
+    /// The exporter of the next batch.
+    fn current(&self) -> Arc<E> {
+        self.inner
+            .read()
+            .unwrap_or_else(|err| err.into_inner())
+            .clone()
+    }
+
+    /// Run `f` with the exporter, which must not be exporting.
+    fn with_exclusive(&mut self, f: impl FnOnce(&mut E) -> OTelSdkResult) -> OTelSdkResult {
+        let mut inner = self.inner.write().unwrap_or_else(|err| err.into_inner());
+        match Arc::get_mut(&mut inner) {
+            Some(inner) => f(inner),
+            None => Err(OTelSdkError::InternalFailure(
+                "the exporter is still exporting".to_string(),
+            )),
+        }
+    }
+}
+
+/// Replaces the exporter of a [`Switched`] exporter.
+#[cfg_attr(not(feature = "otlp"), allow(dead_code))]
+#[derive(Debug)]
+pub(crate) struct Replacer<E>(Arc<RwLock<Arc<E>>>);
+
+#[cfg_attr(not(feature = "otlp"), allow(dead_code))]
+impl<E> Replacer<E> {
+    /// Export the next batches with `exporter`. A batch being exported finishes with the
+    /// previous exporter, which is dropped after it.
+    pub(crate) fn replace(&self, exporter: E) {
+        *self.0.write().unwrap_or_else(|err| err.into_inner()) = Arc::new(exporter);
+    }
+}
+
+impl<E: SpanExporter> SpanExporter for Switched<E> {
+    async fn export(&self, batch: Vec<SpanData>) -> OTelSdkResult {
+        let items = batch.len();
+        if !self.is_enabled() {
+            return self.disabled(items);
+        }
+        self.export_with(items, |inner| async move { inner.export(batch).await })
+            .await
+    }
+
+    fn shutdown_with_timeout(&mut self, timeout: Duration) -> OTelSdkResult {
+        self.with_exclusive(|inner| inner.shutdown_with_timeout(timeout))
+    }
+
+    fn force_flush(&mut self) -> OTelSdkResult {
+        self.with_exclusive(|inner| inner.force_flush())
+    }
+
+    fn set_resource(&mut self, resource: &Resource) {
+        let _ = self.with_exclusive(|inner| {
+            inner.set_resource(resource);
+            Ok(())
+        });
+    }
+}
+
+impl<E: LogExporter> LogExporter for Switched<E> {
+    async fn export(&self, batch: LogBatch<'_>) -> OTelSdkResult {
+        let items = batch.iter().count();
+        if !self.is_enabled() {
+            return self.disabled(items);
+        }
+        self.export_with(items, |inner| async move { inner.export(batch).await })
+            .await
+    }
+
+    fn shutdown_with_timeout(&self, timeout: Duration) -> OTelSdkResult {
+        self.current().shutdown_with_timeout(timeout)
+    }
+
+    fn set_resource(&mut self, resource: &Resource) {
+        let _ = self.with_exclusive(|inner| {
+            inner.set_resource(resource);
+            Ok(())
+        });
+    }
+}
+
+impl<E: PushMetricExporter> PushMetricExporter for Switched<E> {
+    async fn export(&self, metrics: &ResourceMetrics) -> OTelSdkResult {
+        let items = metrics
+            .scope_metrics()
+            .map(|scope| scope.metrics().count())
+            .sum();
+        if !self.is_enabled() {
+            return self.disabled(items);
+        }
+        self.export_with(items, |inner| async move { inner.export(metrics).await })
+            .await
+    }
+
+    fn force_flush(&self) -> OTelSdkResult {
+        self.current().force_flush()
+    }
+
+    fn shutdown_with_timeout(&self, timeout: Duration) -> OTelSdkResult {
+        self.current().shutdown_with_timeout(timeout)
+    }
+
+    fn temporality(&self) -> Temporality {
+        self.current().temporality()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::telemetry::testing::{has_metric, meter_provider, metric_value};
+    use opentelemetry::metrics::MeterProvider as _;
+    use opentelemetry::trace::{Tracer as _, TracerProvider as _};
+    use opentelemetry_sdk::trace::{InMemorySpanExporter, SdkTracerProvider};
+
+    #[test]
+    fn test_disabled_exports_are_dropped() {
+        let exports = ExportHandle::default();
+        let exporter = InMemorySpanExporter::default();
+        let provider = SdkTracerProvider::builder()
+            .with_simple_exporter(exports.switched(Provider::Tracer, exporter.clone()))
+            .build();
+        let tracer = provider.tracer("test");
+
+        exports.set_enabled(Provider::Tracer, false);
+        tracer.in_span("dropped", |_| {});
+        assert!(!exports.is_enabled(Provider::Tracer));
+        assert!(exports.is_enabled(Provider::Meter));
+
+        exports.clone().set_enabled(Provider::Tracer, true);
+        tracer.in_span("exported", |_| {});
+
+        let spans = exporter.get_finished_spans().unwrap();
+        let names: Vec<_> = spans.iter().map(|span| span.name.as_ref()).collect();
+        assert_eq!(names, ["exported"]);
+    }
+
+    #[test]
+    fn test_replaced_exporters_export_the_next_batches() {
+        let exports = ExportHandle::default();
+        let first = InMemorySpanExporter::default();
+        let second = InMemorySpanExporter::default();
+        let exporter = exports.switched(Provider::Tracer, first.clone());
+        let replacer = exporter.replacer();
+        let provider = SdkTracerProvider::builder()
+            .with_simple_exporter(exporter)
+            .build();
+        let tracer = provider.tracer("test");
+
+        tracer.in_span("before", |_| {});
+        replacer.replace(second.clone());
+        tracer.in_span("after", |_| {});
+
+        let names = |exporter: &InMemorySpanExporter| {
+            let spans = exporter.get_finished_spans().unwrap();
+            spans
+                .iter()
+                .map(|span| span.name.to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(names(&first), ["before"]);
+        assert_eq!(names(&second), ["after"]);
+        provider.shutdown().unwrap();
+    }
+
+    #[test]
+    fn test_batches_are_recorded() {
+        let (meter_provider, metrics) = meter_provider();
+        let exports = ExportHandle::default();
+        exports.record_metrics(&meter_provider.meter("test"));
+        let provider = SdkTracerProvider::builder()
+            .with_simple_exporter(
+                exports.switched(Provider::Tracer, InMemorySpanExporter::default()),
+            )
+            .build();
+        let tracer = provider.tracer("test");
+
+        tracer.in_span("exported", |_| {});
+        exports.set_enabled(Provider::Tracer, false);
+        tracer.in_span("dropped", |_| {});
+        tracer.in_span("dropped", |_| {});
+
+        let value = |name, attributes: &[(&str, &str)]| {
+            metric_value(&meter_provider, &metrics, name, attributes)
+        };
+        assert_eq!(
+            value(
+                "otel.export.dropped",
+                &[("otel.signal", "traces"), ("reason", "disabled")]
+            ),
+            2.0
+        );
+        assert_eq!(
+            value("otel.export.failures", &[("otel.signal", "traces")]),
+            0.0
+        );
+        assert!(has_metric(
+            &meter_provider,
+            &metrics,
+            "otel.export.duration"
+        ));
+        assert!(has_metric(
+            &meter_provider,
+            &metrics,
+            "otel.export.batch.size"
+        ));
+    }
+}
@@ -0,0 +1,101 @@
+//! Metrics about the export of traces, logs and metrics, to alert when telemetry itself degrades.
+//!
+//! The [`Switched`](super::export_handle::Switched) exporters record every batch they are
+//! handed, once the meter provider exists:
+//!
+//! - `otel.export.batch.size`: items per batch, spans, log records or metrics
+//! - `otel.export.duration`: time taken by the exporter, with `outcome` set to `success` or
+//!   `failure`
+//! - `otel.export.failures`: batches the exporter failed to deliver
+//! - `otel.export.dropped`: items that were not exported, with `reason` set to `failed` or
+//!   `disabled` when the export is turned off
+//!
+//! All of them carry `otel.signal`, `traces`, `logs` or `metrics`. The items the SDK drops
+//! before they reach the exporter, ie: from a full queue, are counted in `otel.export.errors`.
+
+use std::time::Instant;
+
+use opentelemetry::metrics::{Counter, Histogram, Meter};
+use opentelemetry::KeyValue;
+use opentelemetry_sdk::error::OTelSdkResult;
+
+use super::Provider;
+
+/// The instruments the exporters record their batches with.
+#[derive(Debug)]
+pub(crate) struct ExportMetrics {
+    batch_size: Histogram<u64>,
+    duration: Histogram<f64>,
+    failures: Counter<u64>,
+    dropped: Counter<u64>,
+}
+
+impl ExportMetrics {
+    /// Create the instruments with `meter`.
+    pub(crate) fn new(meter: &Meter) -> Self {
+        Self {
+            batch_size: meter
+                .u64_histogram("otel.export.batch.size")
+                .with_description("Number of items in the batches handed to the exporters")
+                .with_unit("{item}")
+                .build(),
+            duration: meter
+                .f64_histogram("otel.export.duration")
+                .with_description("Duration of the exports of batches")
+                .with_unit("ms")
+                .build(),
+            failures: meter
+                .u64_counter("otel.export.failures")
+                .with_description("Number of batches the exporters failed to deliver")
+                .with_unit("{batch}")
+                .build(),
+            dropped: meter
+                .u64_counter("otel.export.dropped")
+                .with_description("Number of items that were not exported")
+                .with_unit("{item}")
+                .build(),
+        }
+    }
+
+    /// Record the export of a batch of `items` by the exporter of `provider`, which started at
+    /// `start` and ended with `result`.
+    pub(crate) fn exported(
+        &self,
+        provider: Provider,
+        items: usize,
+        start: Instant,
+        result: &OTelSdkResult,
+    ) {
+        let signal = KeyValue::new("otel.signal", signal(provider));
+        self.batch_size
+            .record(items as u64, std::slice::from_ref(&signal));
+        let outcome = if result.is_ok() { "success" } else { "failure" };
+        self.duration.record(
+            start.elapsed().as_secs_f64() * 1000.0,
+            &[signal.clone(), KeyValue::new("outcome", outcome)],
+        );
+        if result.is_err() {
+            self.failures.add(1, std::slice::from_ref(&signal));
+            self.dropped
+                .add(items as u64, &[signal, KeyValue::new("reason", "failed")]);
+        }
+    }
+
+    /// Record a batch of `items` dropped because the export of `provider` is disabled.
+    pub(crate) fn disabled(&self, provider: Provider, items: usize) {
+        let signal = KeyValue::new("otel.signal", signal(provider));
+        self.batch_size
+            .record(items as u64, std::slice::from_ref(&signal));
+        self.dropped
+            .add(items as u64, &[signal, KeyValue::new("reason", "disabled")]);
+    }
+}
+
+/// The signal the exporter of `provider` exports.
+fn signal(provider: Provider) -> &'static str {
+    match provider {
+        Provider::Tracer => "traces",
+        Provider::Logger => "logs",
+        Provider::Meter => "metrics",
+    }
+}
@@ -0,0 +1,129 @@
+//! Points the exporters at the endpoints of the config file while the service runs, requires the
+//! `hot-reload` and `otlp` features.
+//!
+//! The exporters are only rebuilt when a setting they are built from changes: the endpoints,
+//! `environment`, `local_only`, `vendor`, `datadog` and `compression`.
+
+use std::path::PathBuf;
+use std::time::Duration;
+
+use doku::Document;
+use serde::Deserialize;
+
+use super::{ExportHandle, TelemetrySettings};
+use crate::config::{self, Config};
+use crate::ServiceInfo;
+
+/// The part of the config the exporters are reloaded from.
+#[derive(Deserialize, Document)]
+struct Section {
+    telemetry: TelemetrySettings,
+}
+
+impl ExportHandle {
+    /// Apply the endpoints of the `[telemetry]` section of the config file at `path` when the
+    /// file changes, checking every `interval` on the current tokio runtime. See
+    /// [`reload`](Self::reload) for what is reloaded.
+    ///
+    /// `settings` are the ones telemetry was initialized with. Values are overridden by the
+    /// environment variables that start with `env_prefix`, like when the config was loaded. A
+    /// file that can't be loaded, or an exporter that can't be built, is logged and the exporters
+    /// are left as they are. The reload stops when the returned task is aborted, or with the
+    /// runtime.
+    pub fn spawn_reload(
+        &self,
+        service_info: &ServiceInfo,
+        settings: &TelemetrySettings,
+        path: impl Into<PathBuf>,
+        env_prefix: impl Into<String>,
+        interval: Duration,
+    ) -> tokio::task::JoinHandle<()> {
+        let path = path.into();
+        let env_prefix = env_prefix.into();
+        let exports = self.clone();
+        let service_info = service_info.clone();
+        let mut loaded = exporter_settings(settings);
+
+        config::spawn_file_watch(path.clone(), interval, move || {
+            let settings = match Config::<Section>::new(Some(&path), Some(&env_prefix)) {
+                Ok(config) => config.config.telemetry,
+                Err(err) => {
+                    tracing::warn!(error = %err, "could not reload the telemetry endpoints");
+                    return;
+                }
+            };
+            let current = exporter_settings(&settings);
+            if current == loaded {
+                return;
+            }
+            if let Err(err) = exports.reload(&service_info, &settings) {
+                tracing::warn!(error = %err, "could not reload the telemetry endpoints");
+                return;
+            }
+            tracing::info!(
+                trace_endpoint = settings.trace.endpoint,
+                log_endpoint = settings.log.endpoint,
+                metric_endpoint = settings.metric.endpoint,
+                "telemetry endpoints reloaded"
+            );
+            loaded = current;
+        })
+    }
+}
+
+/// The settings the exporters are built from, compared to tell whether they changed.
+fn exporter_settings(settings: &TelemetrySettings) -> serde_json::Value {
+    serde_json::json!({
+        "environment": settings.environment,
+        "local_only": settings.local_only,
+        "trace": settings.trace.endpoint,
+        "log": settings.log.endpoint,
+        "metric": settings.metric.endpoint,
+        "vendor": settings.vendor,
+        "datadog": settings.datadog,
+        "compression": settings.compression,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing::Level;
+
+    #[tokio::test]
+    async fn test_invalid_endpoints_leave_the_exporters_as_they_are() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        let config = |endpoint: &str| {
+            format!(
+                "[telemetry.trace]\nendpoint = \"{endpoint}\"\n[telemetry.log]\n\
+                 console_level = \"info\"\notel_level = \"info\"\n[telemetry.metric]\n"
+            )
+        };
+        std::fs::write(&path, config("http://localhost:4317")).unwrap();
+        let settings = Config::<Section>::new(Some(&path), None::<&str>)
+            .unwrap()
+            .config
+            .telemetry;
+        let service_info = ServiceInfo::default();
+        let built = crate::telemetry::build(&service_info, &settings).unwrap();
+        let exports = built.providers.exports().clone();
+        let capture = crate::telemetry::test::capture();
+
+        let reload = exports.spawn_reload(
+            &service_info,
+            &settings,
+            &path,
+            "BYRE_TEST_EXPORTS_",
+            Duration::from_millis(10),
+        );
+        // Modification times can be as coarse as a second
+        tokio::time::sleep(Duration::from_millis(1100)).await;
+        std::fs::write(&path, config("not a uri")).unwrap();
+
+        while !capture.logged(Level::WARN, "could not reload the telemetry endpoints") {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        reload.abort();
+    }
+}
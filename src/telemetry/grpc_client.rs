@@ -0,0 +1,351 @@
+//! Instrumented gRPC client channel, requires the `grpc-client` feature.
+//!
+//! [`grpc_channel`] builds a tonic channel with the timeouts and TLS from
+//! [`GrpcClientSettings`], the client-side counterpart of
+//! [`GrpcTraceContextLayer`](super::GrpcTraceContextLayer). Calls made through the
+//! [`GrpcChannel`] get a client span following the OpenTelemetry RPC semantic conventions, carry
+//! the trace context in their metadata, and are recorded in the `rpc.client.duration` histogram.
+//!
+//! `https` endpoints are verified against the system's root certificates, and the certificates
+//! of the `tls` settings.
+//!
+//! ```no_run
+//! # async fn call() -> Result<(), byre::telemetry::Error> {
+//! let channel = byre::telemetry::grpc_channel("http://inventory:50051").build()?;
+//!
+//! // Generated tonic clients take the channel like a tonic `Channel`:
+//! // let mut client = InventoryClient::new(channel);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use doku::Document;
+use opentelemetry::KeyValue;
+use serde::{Deserialize, Serialize};
+use snafu::ResultExt as _;
+use tonic::body::Body;
+use tonic::transport::{Certificate, Channel, ClientTlsConfig, Endpoint, Identity};
+use tracing::field::Empty;
+use tracing::Instrument as _;
+
+use super::grpc_context::{grpc_header_status, grpc_status, record_grpc_code};
+use super::{
+    grpc_method, metrics, BuildGrpcChannelSnafu, Error, GrpcResponseBody, ReadGrpcTlsFileSnafu,
+};
+use crate::config::ExpandedPath;
+
+/// Settings for the instrumented gRPC client channel.
+#[derive(Clone, Debug, Serialize, Deserialize, Document)]
+pub struct GrpcClientSettings {
+    /// Time allowed for a whole call, in seconds.
+    #[doku(example = "30")]
+    #[serde(default = "default_timeout_secs")]
+    pub timeout_secs: u64,
+
+    /// Time allowed for connecting to the server, in seconds.
+    #[doku(example = "10")]
+    #[serde(default = "default_connect_timeout_secs")]
+    pub connect_timeout_secs: u64,
+
+    /// TLS settings, for servers with a private certificate authority or that require a client
+    /// certificate. `https` endpoints use TLS without them.
+    #[serde(default)]
+    pub tls: Option<GrpcTlsSettings>,
+}
+
+impl Default for GrpcClientSettings {
+    fn default() -> Self {
+        Self {
+            timeout_secs: default_timeout_secs(),
+            connect_timeout_secs: default_connect_timeout_secs(),
+            tls: None,
+        }
+    }
+}
+
+fn default_timeout_secs() -> u64 {
+    30
+}
+
+fn default_connect_timeout_secs() -> u64 {
+    10
+}
+
+/// TLS settings of the gRPC client channel.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, Document)]
+pub struct GrpcTlsSettings {
+    /// PEM file of the certificate authority that signed the server's certificate, trusted on top of the system's root certificates.
+    #[doku(example = "/etc/ssl/private-ca.pem")]
+    #[serde(default)]
+    pub ca_cert_path: Option<ExpandedPath>,
+
+    /// PEM file of the client certificate, for servers that require one. Used with `key_path`.
+    #[doku(example = "/etc/ssl/client.pem")]
+    #[serde(default)]
+    pub cert_path: Option<ExpandedPath>,
+
+    /// PEM file of the private key of `cert_path`.
+    #[doku(example = "/etc/ssl/client.key")]
+    #[serde(default)]
+    pub key_path: Option<ExpandedPath>,
+
+    /// Name the server's certificate is verified against. Omit to use the host of the endpoint.
+    #[doku(example = "inventory.internal")]
+    #[serde(default)]
+    pub domain_name: Option<String>,
+}
+
+/// Start building an instrumented gRPC channel to `endpoint`, ie: `http://inventory:50051`.
+pub fn grpc_channel(endpoint: impl Into<String>) -> GrpcChannelBuilder {
+    GrpcChannelBuilder {
+        endpoint: endpoint.into(),
+        settings: GrpcClientSettings::default(),
+    }
+}
+
+/// Builder for [`GrpcChannel`], created by [`grpc_channel`].
+#[derive(Clone, Debug)]
+pub struct GrpcChannelBuilder {
+    endpoint: String,
+    settings: GrpcClientSettings,
+}
+
+impl GrpcChannelBuilder {
+    /// Use the timeouts and TLS from `settings`.
+    pub fn with_settings(mut self, settings: &GrpcClientSettings) -> Self {
+        self.settings = settings.clone();
+        self
+    }
+
+    /// Build the channel. It connects on the first call, and reconnects when the connection
+    /// is lost. Must be called within a tokio runtime.
+    ///
+    /// # Errors
+    ///
+    /// - `BuildGrpcChannel` if the endpoint is not a valid URI, or the TLS settings are invalid.
+    /// - `ReadGrpcTlsFile` if a certificate or key file of the TLS settings cannot be read.
+    pub fn build(self) -> Result<GrpcChannel, Error> {
+        let endpoint = &self.endpoint;
+        let mut builder = Endpoint::from_shared(endpoint.clone())
+            .context(BuildGrpcChannelSnafu { endpoint })?
+            .timeout(Duration::from_secs(self.settings.timeout_secs))
+            .connect_timeout(Duration::from_secs(self.settings.connect_timeout_secs));
+        let https = builder.uri().scheme_str() == Some("https");
+        if https || self.settings.tls.is_some() {
+            let tls = tls_config(self.settings.tls.as_ref().unwrap_or(&Default::default()))?;
+            builder = builder
+                .tls_config(tls)
+                .context(BuildGrpcChannelSnafu { endpoint })?;
+        }
+
+        let uri = builder.uri();
+        let server = Server {
+            address: uri.host().unwrap_or_default().to_string(),
+            port: uri.port_u16().or(Some(if https { 443 } else { 80 })),
+        };
+        Ok(GrpcChannel {
+            inner: builder.connect_lazy(),
+            server,
+        })
+    }
+}
+
+/// The TLS config of `settings`, on top of the system's root certificates.
+fn tls_config(settings: &GrpcTlsSettings) -> Result<ClientTlsConfig, Error> {
+    let read = |path: &ExpandedPath| {
+        std::fs::read(path).context(ReadGrpcTlsFileSnafu {
+            path: path.as_path(),
+        })
+    };
+
+    let mut tls = ClientTlsConfig::new().with_native_roots();
+    if let Some(path) = &settings.ca_cert_path {
+        tls = tls.ca_certificate(Certificate::from_pem(read(path)?));
+    }
+    if let (Some(cert), Some(key)) = (&settings.cert_path, &settings.key_path) {
+        tls = tls.identity(Identity::from_pem(read(cert)?, read(key)?));
+    }
+    if let Some(domain_name) = &settings.domain_name {
+        tls = tls.domain_name(domain_name);
+    }
+    Ok(tls)
+}
+
+/// The server a channel calls, for the `server.address` and `server.port` attributes.
+#[derive(Clone, Debug)]
+struct Server {
+    address: String,
+    port: Option<u16>,
+}
+
+/// A gRPC channel that traces and measures its calls, use it like a tonic `Channel`.
+///
+/// Each call gets a client span named after the called method (`package.Service/Method`), with
+/// the `rpc.system`, `rpc.service`, `rpc.method`, `server.address`, `server.port` and
+/// `rpc.grpc.status_code` attributes. Calls that end with a status other than `OK` mark the
+/// span as an error. Like on the server, the status is read from the response headers or from
+/// the trailers of the [`GrpcResponseBody`], in which case the span ends once the body is read.
+#[derive(Clone, Debug)]
+pub struct GrpcChannel<S = Channel> {
+    inner: S,
+    server: Server,
+}
+
+impl<S> tower::Service<http::Request<Body>> for GrpcChannel<S>
+where
+    S: tower::Service<http::Request<Body>, Response = http::Response<Body>>
+        + Clone
+        + Send
+        + 'static,
+    S::Future: Send,
+{
+    type Response = http::Response<GrpcResponseBody<Body>>;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut request: http::Request<Body>) -> Self::Future {
+        let path = request.uri().path();
+        let rpc = grpc_method(path);
+        let span = tracing::info_span!(
+            "grpc_client_request",
+            otel.name = rpc.map(|_| path.trim_start_matches('/')),
+            otel.kind = "client",
+            otel.status_code = Empty,
+            rpc.system = "grpc",
+            rpc.service = rpc.map(|(service, _)| service),
+            rpc.method = rpc.map(|(_, method)| method),
+            server.address = self.server.address.as_str(),
+            server.port = self.server.port,
+            rpc.grpc.status_code = Empty,
+        );
+        let mut attributes = vec![
+            KeyValue::new("rpc.system", "grpc"),
+            KeyValue::new("server.address", self.server.address.clone()),
+        ];
+        if let Some((service, method)) = rpc {
+            attributes.push(KeyValue::new("rpc.service", service.to_string()));
+            attributes.push(KeyValue::new("rpc.method", method.to_string()));
+        }
+        span.in_scope(|| super::inject_trace_context_http(request.headers_mut()));
+
+        // The channel that was polled ready handles the call, see `tower::Service`
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        Box::pin(
+            async move {
+                let start = Instant::now();
+                let result = inner.call(request).await;
+                let span = tracing::Span::current();
+                match &result {
+                    Ok(response) => {
+                        let code = grpc_status(response.headers());
+                        attributes.push(KeyValue::new("rpc.grpc.status_code", i64::from(code)));
+                        if let Some(code) = grpc_header_status(response.headers()) {
+                            record_grpc_code(&span, code, is_grpc_client_error);
+                        }
+                    }
+                    Err(_) => {
+                        span.record("otel.status_code", "ERROR");
+                    }
+                }
+
+                metrics::byre_histogram(
+                    "rpc.client.duration",
+                    "Duration of gRPC client calls",
+                    "ms",
+                )
+                .record(start.elapsed().as_secs_f64() * 1000.0, &attributes);
+
+                result.map(|response| {
+                    response.map(|body| GrpcResponseBody::new(body, span, is_grpc_client_error))
+                })
+            }
+            .instrument(span),
+        )
+    }
+}
+
+/// Client spans fail on every status but `OK`.
+fn is_grpc_client_error(code: i32) -> bool {
+    code != 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tower::ServiceExt as _;
+
+    #[tokio::test]
+    async fn test_calls_are_traced_and_measured() {
+        super::super::init_propagator();
+        let capture = super::super::test::capture();
+        let server = tower::service_fn(|request: http::Request<Body>| async move {
+            assert!(request.headers().contains_key("traceparent"));
+            let response = http::Response::builder()
+                .header("grpc-status", "14")
+                .body(Body::empty())
+                .unwrap();
+            Ok::<_, std::convert::Infallible>(response)
+        });
+        let channel = GrpcChannel {
+            inner: server,
+            server: Server {
+                address: "inventory".to_string(),
+                port: Some(50051),
+            },
+        };
+
+        let request = http::Request::post("http://inventory:50051/shop.Inventory/Reserve")
+            .body(Body::empty())
+            .unwrap();
+        channel.oneshot(request).await.unwrap();
+
+        let span = capture.span("shop.Inventory/Reserve").unwrap();
+        assert_eq!(span.span_kind, opentelemetry::trace::SpanKind::Client);
+        assert!(span
+            .attributes
+            .contains(&KeyValue::new("rpc.method", "Reserve")));
+        assert!(matches!(
+            span.status,
+            opentelemetry::trace::Status::Error { .. }
+        ));
+        assert_eq!(
+            capture.metric_value(
+                "rpc.client.duration",
+                &[
+                    ("rpc.service", "shop.Inventory"),
+                    ("rpc.grpc.status_code", "14")
+                ]
+            ),
+            1.0
+        );
+    }
+
+    #[tokio::test]
+    async fn test_https_endpoints_use_tls() {
+        let channel = grpc_channel("https://inventory.internal").build().unwrap();
+        assert_eq!(channel.server.port, Some(443));
+
+        let settings = GrpcClientSettings {
+            tls: Some(GrpcTlsSettings {
+                ca_cert_path: Some("/nonexistent/ca.pem".into()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let err = grpc_channel("http://inventory:50051")
+            .with_settings(&settings)
+            .build()
+            .unwrap_err();
+        assert!(matches!(err, Error::ReadGrpcTlsFile { .. }), "{err}");
+    }
+}
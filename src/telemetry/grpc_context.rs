@@ -0,0 +1,664 @@
+//! gRPC trace context propagation, requires the `grpc` feature.
+//!
+//! The [`TraceContextCarrier`] of tonic's `MetadataMap`, the functions that extract and inject
+//! the trace context of gRPC metadata, the [`TraceContextInterceptor`] of tonic clients, the
+//! [`GrpcTraceContextLayer`] of tonic servers, and [`record_grpc_status`] for their handlers.
+
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Instant;
+
+use opentelemetry::global;
+use opentelemetry::propagation::{Extractor, Injector};
+
+use super::http_context::{extract_trace_context_http, set_request_id_header};
+use super::request_fields::{RequestFields, RequestFieldsFn};
+use super::sampling::{SamplingRule, SamplingRules};
+use super::{propagation, request_id, Error, RequestId, TraceContextCarrier};
+
+// ============================================================================
+// Distributed Tracing Propagation
+// ============================================================================
+
+/// Wrapper for tonic::metadata::MetadataMap to implement Extractor trait.
+/// Used for extracting trace context from incoming gRPC requests.
+pub struct MetadataExtractor<'a>(pub &'a tonic::metadata::MetadataMap);
+
+impl Extractor for MetadataExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|v| v.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        // Only return the keys of the configured propagators that exist in the metadata.
+        self.0
+            .keys()
+            .filter_map(|k| match k {
+                tonic::metadata::KeyRef::Ascii(k) => Some(k.as_str()),
+                tonic::metadata::KeyRef::Binary(_) => None,
+            })
+            .filter(|k| propagation::is_propagated(k))
+            .collect()
+    }
+}
+
+/// Wrapper for tonic::metadata::MetadataMap to implement Injector trait.
+/// Used for injecting trace context into outgoing gRPC requests.
+pub struct MetadataInjector<'a>(pub &'a mut tonic::metadata::MetadataMap);
+
+impl Injector for MetadataInjector<'_> {
+    fn set(&mut self, key: &str, value: String) {
+        if let Ok(key) = tonic::metadata::MetadataKey::from_bytes(key.as_bytes()) {
+            if let Ok(value) = tonic::metadata::MetadataValue::try_from(&value) {
+                self.0.insert(key, value);
+            }
+        }
+    }
+}
+
+impl TraceContextCarrier for tonic::metadata::MetadataMap {
+    fn extract_trace_context(&self) -> opentelemetry::Context {
+        global::get_text_map_propagator(|propagator| propagator.extract(&MetadataExtractor(self)))
+    }
+
+    fn extract_trace_context_with(&self, cx: &opentelemetry::Context) -> opentelemetry::Context {
+        global::get_text_map_propagator(|propagator| {
+            propagator.extract_with_context(cx, &MetadataExtractor(self))
+        })
+    }
+
+    fn inject_trace_context(&mut self) {
+        inject_trace_context(self);
+    }
+
+    fn extract_request_id(&self) -> Option<RequestId> {
+        RequestId::extract(&MetadataExtractor(self))
+    }
+}
+
+/// Extract trace context from incoming gRPC request metadata.
+///
+/// Returns the extracted OpenTelemetry context. Use [`link_distributed_trace`] for a more
+/// convenient way to extract and link the trace context in one call.
+///
+/// # Example
+///
+/// ```
+/// let mut metadata = tonic::metadata::MetadataMap::new();
+/// metadata.insert("traceparent", "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01".parse().unwrap());
+///
+/// let parent_cx = byre::telemetry::extract_trace_context(&metadata);
+/// let _guard = parent_cx.attach();
+/// // Spans created here will be children of the incoming trace
+/// ```
+pub fn extract_trace_context(metadata: &tonic::metadata::MetadataMap) -> opentelemetry::Context {
+    global::get_text_map_propagator(|propagator| propagator.extract(&MetadataExtractor(metadata)))
+}
+
+/// Link the current span to an incoming distributed trace from gRPC metadata.
+///
+/// This is a convenience function that extracts the trace context from the
+/// incoming request metadata and sets it as the parent of the current span.
+/// Call this at the start of your gRPC handler after the `#[tracing::instrument]` span is created.
+///
+/// Returns `Ok(())` if successful, or an error if the span context couldn't be set.
+/// Most callers will want to ignore the error with `let _ = link_distributed_trace(...)`.
+///
+/// # Example
+///
+/// ```
+/// let mut metadata = tonic::metadata::MetadataMap::new();
+/// metadata.insert("traceparent", "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01".parse().unwrap());
+///
+/// let _ = byre::telemetry::link_distributed_trace(&metadata);
+/// // Current span is now part of the distributed trace
+/// ```
+pub fn link_distributed_trace(metadata: &tonic::metadata::MetadataMap) -> Result<(), Error> {
+    use tracing_opentelemetry::OpenTelemetrySpanExt;
+    let parent_cx = extract_trace_context(metadata);
+    tracing::Span::current()
+        .set_parent(parent_cx)
+        .map_err(|e| Error::LinkDistributedTrace {
+            source: Box::new(e),
+        })
+}
+
+/// Inject trace context into outgoing gRPC request metadata.
+///
+/// Call this before making outgoing gRPC calls to propagate the trace context, and the
+/// request id of the current span.
+///
+/// # Example
+///
+/// ```
+/// let mut metadata = tonic::metadata::MetadataMap::new();
+/// byre::telemetry::inject_trace_context(&mut metadata);
+/// // metadata now contains traceparent header (if there's an active span)
+/// ```
+pub fn inject_trace_context(metadata: &mut tonic::metadata::MetadataMap) {
+    use tracing_opentelemetry::OpenTelemetrySpanExt;
+    // Get the OpenTelemetry context from the current tracing span
+    let cx = tracing::Span::current().context();
+    global::get_text_map_propagator(|propagator| {
+        propagator.inject_context(&cx, &mut MetadataInjector(metadata));
+    });
+    request_id::inject(&mut MetadataInjector(metadata));
+}
+
+/// A tonic client interceptor that injects the trace context of the current span into every
+/// outgoing request, so calls don't need [`inject_trace_context`] one by one.
+///
+/// # Example
+///
+/// ```no_run
+/// # async fn connect() -> Result<(), tonic::transport::Error> {
+/// let channel = tonic::transport::Channel::from_static("http://localhost:50051")
+///     .connect()
+///     .await?;
+/// let channel = byre::telemetry::with_trace_context(channel);
+/// // let client = MyServiceClient::new(channel);
+/// // or: MyServiceClient::with_interceptor(channel, byre::telemetry::TraceContextInterceptor);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TraceContextInterceptor;
+
+impl tonic::service::Interceptor for TraceContextInterceptor {
+    fn call(
+        &mut self,
+        mut request: tonic::Request<()>,
+    ) -> Result<tonic::Request<()>, tonic::Status> {
+        inject_trace_context(request.metadata_mut());
+        Ok(request)
+    }
+}
+
+/// Wrap a gRPC channel, or any other client transport, so every request carries the trace
+/// context of the current span.
+///
+/// See [`TraceContextInterceptor`].
+pub fn with_trace_context<T>(
+    channel: T,
+) -> tonic::service::interceptor::InterceptedService<T, TraceContextInterceptor> {
+    tonic::service::interceptor::InterceptedService::new(channel, TraceContextInterceptor)
+}
+
+// ============================================================================
+// Tower Layer for Distributed Trace Context (gRPC/tonic)
+// ============================================================================
+
+/// A Tower layer that extracts distributed trace context from incoming gRPC requests
+/// and creates a parent span for all downstream handlers.
+///
+/// This layer should be added to tonic services to enable distributed tracing.
+/// It extracts the W3C Trace Context headers from incoming requests and creates
+/// a span that becomes the parent of all spans created within the handler.
+///
+/// The span is named after the called method (`package.Service/Method`) and records the
+/// `rpc.system`, `rpc.service`, `rpc.method` and `rpc.grpc.status_code` attributes along with
+/// the handling time in `latency_ms`. Status codes that indicate a server fault (`UNKNOWN`,
+/// `DEADLINE_EXCEEDED`, `UNIMPLEMENTED`, `INTERNAL`, `UNAVAILABLE` and `DATA_LOSS`) mark the
+/// span as an error. The status is read from the response headers, where tonic puts it for
+/// errors returned by a handler, or from the trailers of the [`GrpcResponseBody`], in which
+/// case the span ends once the body is sent.
+///
+/// The [`RequestId`] of the `x-request-id` header, or a new one, is recorded in the
+/// `request.id` field of the span, added to the request extensions and returned in the
+/// `x-request-id` header of the response. The [`RequestFields`] of
+/// [`with_request_fields`](Self::with_request_fields) are attached to the span.
+///
+/// # Example
+///
+/// ```
+/// use byre::telemetry::GrpcTraceContextLayer;
+///
+/// // Create the layer
+/// let layer = GrpcTraceContextLayer::new("my-service");
+///
+/// // Use with tonic Server::builder().layer(layer)
+/// ```
+#[derive(Clone)]
+pub struct GrpcTraceContextLayer {
+    service_name: &'static str,
+    span_details: Option<SpanDetailsFn>,
+    request_fields: Option<RequestFieldsFn>,
+    sampling: SamplingRules,
+}
+
+/// Derives the [`SpanDetails`] of a request span from the request.
+type SpanDetailsFn = Arc<dyn Fn(&http::request::Parts) -> SpanDetails + Send + Sync>;
+
+impl GrpcTraceContextLayer {
+    /// Create a new layer with the given service name.
+    /// The service name is used to identify spans in the trace.
+    pub fn new(service_name: &'static str) -> Self {
+        Self {
+            service_name,
+            span_details: None,
+            request_fields: None,
+            sampling: SamplingRules::default(),
+        }
+    }
+
+    /// Name the request spans, and add attributes to them, from the request.
+    ///
+    /// The name replaces the `package.Service/Method` name of the exported span.
+    ///
+    /// ```
+    /// use byre::telemetry::{GrpcTraceContextLayer, SpanDetails};
+    ///
+    /// let layer = GrpcTraceContextLayer::new("my-service").with_span_details(|request| {
+    ///     let tenant = request
+    ///         .headers
+    ///         .get("x-tenant")
+    ///         .and_then(|value| value.to_str().ok())
+    ///         .unwrap_or("unknown")
+    ///         .to_string();
+    ///     SpanDetails::new(format!("grpc {}", request.uri.path())).with_attribute("tenant", tenant)
+    /// });
+    /// ```
+    pub fn with_span_details<F>(mut self, span_details: F) -> Self
+    where
+        F: Fn(&http::request::Parts) -> SpanDetails + Send + Sync + 'static,
+    {
+        self.span_details = Some(Arc::new(span_details));
+        self
+    }
+
+    /// Attach fields derived from the request to the request span, they are inherited by the
+    /// spans and logs of the handler.
+    ///
+    /// ```
+    /// use byre::telemetry::{GrpcTraceContextLayer, RequestFields};
+    ///
+    /// let layer = GrpcTraceContextLayer::new("my-service").with_request_fields(|request| {
+    ///     match request.headers.get("x-tenant").and_then(|value| value.to_str().ok()) {
+    ///         Some(tenant) => RequestFields::new().with_field("tenant.id", tenant),
+    ///         None => RequestFields::new(),
+    ///     }
+    /// });
+    /// ```
+    pub fn with_request_fields<F>(mut self, request_fields: F) -> Self
+    where
+        F: Fn(&http::request::Parts) -> RequestFields + Send + Sync + 'static,
+    {
+        self.request_fields = Some(Arc::new(request_fields));
+        self
+    }
+
+    /// Sample the calls that start a trace by their `/package.Service/Method` route, see
+    /// [`TraceSettings::sampling`](super::TraceSettings::sampling). Rules with a `method` never
+    /// match gRPC calls.
+    ///
+    /// ```
+    /// use byre::telemetry::GrpcTraceContextLayer;
+    ///
+    /// # let settings = byre::telemetry::TelemetrySettings::default();
+    /// let layer = GrpcTraceContextLayer::new("my-service").with_sampling(&settings.trace.sampling);
+    /// ```
+    pub fn with_sampling(mut self, rules: &[SamplingRule]) -> Self {
+        self.sampling = SamplingRules::new(rules);
+        self
+    }
+}
+
+impl<S> tower::Layer<S> for GrpcTraceContextLayer {
+    type Service = GrpcTraceContextService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        GrpcTraceContextService {
+            inner,
+            service_name: self.service_name,
+            span_details: self.span_details.clone(),
+            request_fields: self.request_fields.clone(),
+            sampling: self.sampling.clone(),
+        }
+    }
+}
+
+/// The name and extra attributes of a request span.
+#[derive(Clone, Debug)]
+pub struct SpanDetails {
+    name: String,
+    attributes: Vec<opentelemetry::KeyValue>,
+}
+
+impl SpanDetails {
+    /// Name the exported span `name`.
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            attributes: Vec::new(),
+        }
+    }
+
+    /// Add an attribute to the span.
+    pub fn with_attribute(
+        mut self,
+        key: impl Into<opentelemetry::Key>,
+        value: impl Into<opentelemetry::Value>,
+    ) -> Self {
+        self.attributes
+            .push(opentelemetry::KeyValue::new(key, value));
+        self
+    }
+}
+
+/// The service that wraps inner services with trace context extraction.
+#[derive(Clone)]
+pub struct GrpcTraceContextService<S> {
+    inner: S,
+    service_name: &'static str,
+    span_details: Option<SpanDetailsFn>,
+    request_fields: Option<RequestFieldsFn>,
+    sampling: SamplingRules,
+}
+
+impl<S, B, ResBody> tower::Service<http::Request<B>> for GrpcTraceContextService<S>
+where
+    S: tower::Service<http::Request<B>, Response = http::Response<ResBody>>
+        + Clone
+        + Send
+        + 'static,
+    S::Future: Send,
+    B: Send + 'static,
+{
+    type Response = http::Response<GrpcResponseBody<ResBody>>;
+    type Error = S::Error;
+    type Future = std::pin::Pin<
+        Box<dyn std::future::Future<Output = Result<Self::Response, Self::Error>> + Send>,
+    >;
+
+    fn poll_ready(
+        &mut self,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: http::Request<B>) -> Self::Future {
+        use tracing::field::Empty;
+        use tracing::Instrument;
+        use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+        // Extract trace context from incoming HTTP/2 headers (gRPC uses HTTP/2)
+        let parent_cx = extract_trace_context_http(request.headers());
+        let request_id = RequestId::from_headers(request.headers());
+
+        let (mut parts, body) = request.into_parts();
+        let details = self
+            .span_details
+            .as_ref()
+            .map(|span_details| span_details(&parts));
+        let fields = self
+            .request_fields
+            .as_ref()
+            .map(|request_fields| request_fields(&parts));
+        parts.extensions.insert(request_id.clone());
+        let request = http::Request::from_parts(parts, body);
+
+        // Create a tracing span and link it to the incoming OpenTelemetry context.
+        // This makes all child spans (from #[tracing::instrument]) part of the distributed trace.
+        let path = request.uri().path();
+        let rpc = grpc_method(path);
+        let span = tracing::info_span!(
+            "grpc_request",
+            service = self.service_name,
+            otel.name = rpc.map(|_| path.trim_start_matches('/')),
+            otel.kind = "server",
+            otel.status_code = Empty,
+            rpc.system = "grpc",
+            rpc.service = rpc.map(|(service, _)| service),
+            rpc.method = rpc.map(|(_, method)| method),
+            rpc.grpc.status_code = Empty,
+            latency_ms = Empty,
+            request.id = Empty,
+        );
+        let parent_cx = self.sampling.parent(parent_cx, path, None);
+        let _ = span.set_parent(parent_cx);
+        request_id.attach(&span);
+        if let Some(fields) = fields {
+            fields.attach(&span);
+        }
+        if let Some(details) = details {
+            span.record("otel.name", details.name.as_str());
+            for attribute in details.attributes {
+                span.set_attribute(attribute.key, attribute.value);
+            }
+        }
+
+        // Clone inner service for use in async block
+        let mut inner = self.inner.clone();
+
+        // Instrument the future with our span so it stays active for the entire request
+        Box::pin(
+            async move {
+                let start = Instant::now();
+                let mut result = inner.call(request).await;
+                let span = tracing::Span::current();
+                span.record("latency_ms", start.elapsed().as_secs_f64() * 1000.0);
+                match &mut result {
+                    Ok(response) => {
+                        set_request_id_header(response.headers_mut(), &request_id);
+                        if let Some(code) = grpc_header_status(response.headers()) {
+                            record_grpc_code(&span, code, is_grpc_server_error);
+                        }
+                    }
+                    Err(_) => {
+                        span.record("otel.status_code", "ERROR");
+                    }
+                }
+                result.map(|response| {
+                    response.map(|body| GrpcResponseBody::new(body, span, is_grpc_server_error))
+                })
+            }
+            .instrument(span),
+        )
+    }
+}
+
+/// Split a gRPC request path, `/package.Service/Method`, into the service and the method.
+pub(crate) fn grpc_method(path: &str) -> Option<(&str, &str)> {
+    let (service, method) = path.strip_prefix('/')?.split_once('/')?;
+    (!service.is_empty() && !method.is_empty() && !method.contains('/'))
+        .then_some((service, method))
+}
+
+/// The gRPC status code in the response headers, `OK` when there is none.
+///
+/// Trailers-only responses, which tonic sends for errors returned by a handler, carry the
+/// status in the headers.
+#[cfg(feature = "grpc-client")]
+pub(crate) fn grpc_status(headers: &http::HeaderMap) -> i32 {
+    grpc_header_status(headers).unwrap_or(0)
+}
+
+/// The gRPC status code in the headers or trailers of a response, if it carries one.
+pub(crate) fn grpc_header_status(headers: &http::HeaderMap) -> Option<i32> {
+    headers
+        .get("grpc-status")
+        .and_then(|value| value.to_str().ok()?.parse::<i32>().ok())
+}
+
+/// Record the status `code` of a call on its span, marking the span as an error when
+/// `is_error` says so.
+pub(crate) fn record_grpc_code(span: &tracing::Span, code: i32, is_error: fn(i32) -> bool) {
+    span.record("rpc.grpc.status_code", code);
+    if is_error(code) {
+        span.record("otel.status_code", "ERROR");
+    }
+}
+
+/// Whether a gRPC status code is an error of the server, rather than of the caller.
+pub(crate) fn is_grpc_server_error(code: i32) -> bool {
+    use tonic::Code;
+    matches!(
+        Code::from_i32(code),
+        Code::Unknown
+            | Code::DeadlineExceeded
+            | Code::Unimplemented
+            | Code::Internal
+            | Code::Unavailable
+            | Code::DataLoss
+    )
+}
+
+/// What the body of a gRPC response records the status of the call on.
+pub(crate) trait CallStatus: Send + Sync {
+    /// The call ended with the status `code`, sent in the trailers.
+    fn record_code(&mut self, code: i32);
+
+    /// The body failed before sending the status.
+    fn record_failure(&mut self);
+}
+
+/// The span of a call, an error when `is_error` says so.
+struct SpanStatus {
+    span: tracing::Span,
+    is_error: fn(i32) -> bool,
+}
+
+impl CallStatus for SpanStatus {
+    fn record_code(&mut self, code: i32) {
+        record_grpc_code(&self.span, code, self.is_error);
+    }
+
+    fn record_failure(&mut self) {
+        self.span.record("otel.status_code", "ERROR");
+    }
+}
+
+pin_project_lite::pin_project! {
+    /// The body of a gRPC response, recording the status sent in its trailers on the span of
+    /// the call, which stays open until the body is dropped.
+    pub struct GrpcResponseBody<B> {
+        #[pin]
+        inner: B,
+        status: Box<dyn CallStatus>,
+    }
+}
+
+impl<B> GrpcResponseBody<B> {
+    /// Wrap the body of the call in `span`, whose status is an error when `is_error` says so.
+    pub(crate) fn new(inner: B, span: tracing::Span, is_error: fn(i32) -> bool) -> Self {
+        Self::with_status(inner, SpanStatus { span, is_error })
+    }
+
+    /// Wrap the body of a call, recording its status on `status`.
+    pub(crate) fn with_status(inner: B, status: impl CallStatus + 'static) -> Self {
+        Self {
+            inner,
+            status: Box::new(status),
+        }
+    }
+
+    /// Unwrap the body, the status of its trailers is no longer recorded.
+    pub fn into_inner(self) -> B {
+        self.inner
+    }
+}
+
+impl<B> std::fmt::Debug for GrpcResponseBody<B> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("GrpcResponseBody").finish_non_exhaustive()
+    }
+}
+
+impl<B: http_body::Body> http_body::Body for GrpcResponseBody<B> {
+    type Data = B::Data;
+    type Error = B::Error;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<http_body::Frame<Self::Data>, Self::Error>>> {
+        let this = self.project();
+        let frame = std::task::ready!(this.inner.poll_frame(cx));
+        match &frame {
+            Some(Ok(frame)) => {
+                if let Some(code) = frame.trailers_ref().and_then(grpc_header_status) {
+                    this.status.record_code(code);
+                }
+            }
+            Some(Err(_)) => {
+                this.status.record_failure();
+            }
+            None => {}
+        }
+        Poll::Ready(frame)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> http_body::SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+/// Record the `status` a handler returns on the current span, ie: the handler's own span.
+///
+/// The status code becomes the span's `rpc.grpc.status_code` attribute. Codes that indicate a
+/// server fault, the same that mark the span of [`GrpcTraceContextLayer`] as an error, set the
+/// span's status to error with the status message as its description.
+///
+/// # Example
+///
+/// ```
+/// #[tracing::instrument]
+/// fn reserve(quantity: u32) -> Result<u32, tonic::Status> {
+///     if quantity == 0 {
+///         let status = tonic::Status::invalid_argument("nothing to reserve");
+///         byre::telemetry::record_grpc_status(&status);
+///         return Err(status);
+///     }
+///     Ok(quantity)
+/// }
+/// # assert!(reserve(0).is_err());
+/// ```
+pub fn record_grpc_status(status: &tonic::Status) {
+    use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+    let code = status.code() as i32;
+    let span = tracing::Span::current();
+    span.set_attribute("rpc.grpc.status_code", i64::from(code));
+    if is_grpc_server_error(code) {
+        span.set_status(opentelemetry::trace::Status::error(
+            status.message().to_string(),
+        ));
+    }
+}
+
+/// Records the outcome of a handler's `Result` with [`record_grpc_status`] as it passes by.
+///
+/// # Example
+///
+/// ```
+/// use byre::telemetry::GrpcStatusExt as _;
+///
+/// #[tracing::instrument]
+/// fn lookup(sku: &str) -> Result<u32, tonic::Status> {
+///     sku.parse::<u32>()
+///         .map_err(|_| tonic::Status::internal("the inventory is corrupted"))
+///         .record_grpc_status()
+/// }
+/// # assert!(lookup("SKU-1").is_err());
+/// ```
+pub trait GrpcStatusExt {
+    /// Call [`record_grpc_status`] with the status of this result, `OK` for a success, then
+    /// return `self` unchanged.
+    fn record_grpc_status(self) -> Self;
+}
+
+impl<T> GrpcStatusExt for Result<T, tonic::Status> {
+    fn record_grpc_status(self) -> Self {
+        match &self {
+            Ok(_) => record_grpc_status(&tonic::Status::ok("")),
+            Err(status) => record_grpc_status(status),
+        }
+        self
+    }
+}
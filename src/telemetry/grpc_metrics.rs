@@ -0,0 +1,362 @@
+//! gRPC server metrics tower layer.
+//!
+//! The metrics companion of [`GrpcTraceContextLayer`](super::GrpcTraceContextLayer): records
+//! the duration, requests and errors of every call, with the same `rpc.*` attributes as the
+//! request spans, once the status of the call is known.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Instant;
+
+use opentelemetry::metrics::{Counter, Histogram, Meter};
+use opentelemetry::KeyValue;
+
+use super::grpc_context::{grpc_header_status, CallStatus};
+use super::{grpc_method, is_grpc_server_error, GrpcResponseBody};
+
+/// A Tower layer that records metrics for the calls of a gRPC server.
+///
+/// For every call the layer records:
+///
+/// - `rpc.server.duration` - a histogram of the time until the status is sent, in milliseconds
+/// - `rpc.server.requests` - a counter of calls
+/// - `rpc.server.errors` - a counter of calls that failed with a server error
+///
+/// All three carry the `rpc.system`, `rpc.service`, `rpc.method` and `rpc.grpc.status_code`
+/// attributes, the service and method are left out for paths that are not gRPC methods. The
+/// path is chosen by the caller, so calls that end `UNIMPLEMENTED`, ie: to a method the server
+/// does not have, are recorded with a service and method of `_OTHER`, which keeps a scan of made
+/// up paths from creating a series per path. Errors
+/// are counted for the same status codes that mark the span of
+/// [`GrpcTraceContextLayer`](super::GrpcTraceContextLayer) as failed, and for requests the
+/// inner service fails. The status is read from the response headers, or from the trailers of
+/// the [`GrpcResponseBody`], in which case the call is measured once the body is sent. A body
+/// dropped before its trailers is measured with the status of the headers, `OK` when there is
+/// none.
+///
+/// # Example
+///
+/// ```
+/// use byre::telemetry::{GrpcMetricsLayer, GrpcTraceContextLayer};
+///
+/// // After `byre::telemetry::init`, the service's meter exports through the configured endpoint
+/// let meter = byre::telemetry::metrics::meter();
+/// let layers = tower::ServiceBuilder::new()
+///     .layer(GrpcTraceContextLayer::new("my-service"))
+///     .layer(GrpcMetricsLayer::new(&meter));
+///
+/// // Use with tonic Server::builder().layer(layers)
+/// ```
+#[derive(Clone)]
+pub struct GrpcMetricsLayer {
+    instruments: GrpcInstruments,
+}
+
+impl std::fmt::Debug for GrpcMetricsLayer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("GrpcMetricsLayer").finish_non_exhaustive()
+    }
+}
+
+impl GrpcMetricsLayer {
+    /// Create a layer that records its metrics with `meter`.
+    pub fn new(meter: &Meter) -> Self {
+        Self {
+            instruments: GrpcInstruments {
+                duration: meter
+                    .f64_histogram("rpc.server.duration")
+                    .with_description("Duration of gRPC server calls")
+                    .with_unit("ms")
+                    .build(),
+                requests: meter
+                    .u64_counter("rpc.server.requests")
+                    .with_description("Number of gRPC server calls")
+                    .with_unit("{request}")
+                    .build(),
+                errors: meter
+                    .u64_counter("rpc.server.errors")
+                    .with_description("Number of gRPC server calls that failed with a server error")
+                    .with_unit("{request}")
+                    .build(),
+            },
+        }
+    }
+}
+
+impl<S> tower::Layer<S> for GrpcMetricsLayer {
+    type Service = GrpcMetricsService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        GrpcMetricsService {
+            inner,
+            instruments: self.instruments.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+struct GrpcInstruments {
+    duration: Histogram<f64>,
+    requests: Counter<u64>,
+    errors: Counter<u64>,
+}
+
+/// The service that records metrics for the calls to the inner service.
+#[derive(Clone)]
+pub struct GrpcMetricsService<S> {
+    inner: S,
+    instruments: GrpcInstruments,
+}
+
+impl<S> std::fmt::Debug for GrpcMetricsService<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("GrpcMetricsService").finish_non_exhaustive()
+    }
+}
+
+impl<S, B, ResBody> tower::Service<http::Request<B>> for GrpcMetricsService<S>
+where
+    S: tower::Service<http::Request<B>, Response = http::Response<ResBody>>
+        + Clone
+        + Send
+        + 'static,
+    S::Future: Send,
+    B: Send + 'static,
+{
+    type Response = http::Response<GrpcResponseBody<ResBody>>;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: http::Request<B>) -> Self::Future {
+        let mut attributes = vec![KeyValue::new("rpc.system", "grpc")];
+        if let Some((service, method)) = grpc_method(request.uri().path()) {
+            attributes.push(KeyValue::new("rpc.service", service.to_string()));
+            attributes.push(KeyValue::new("rpc.method", method.to_string()));
+        }
+
+        let mut inner = self.inner.clone();
+        let instruments = self.instruments.clone();
+        Box::pin(async move {
+            let start = Instant::now();
+            let result = inner.call(request).await;
+            let mut call = CallMeasurements {
+                instruments,
+                attributes,
+                start,
+                code: None,
+                recorded: false,
+            };
+
+            match result {
+                Ok(response) => {
+                    call.code = grpc_header_status(response.headers());
+                    Ok(response.map(|body| GrpcResponseBody::with_status(body, call)))
+                }
+                Err(err) => {
+                    call.record(true);
+                    Err(err)
+                }
+            }
+        })
+    }
+}
+
+/// The value of `rpc.service` and `rpc.method` for calls to a method the server does not have.
+const OTHER_METHOD: &str = "_OTHER";
+
+/// The measurements of a call, recorded when its status is sent in the trailers, or when its
+/// response is dropped.
+struct CallMeasurements {
+    instruments: GrpcInstruments,
+    attributes: Vec<KeyValue>,
+    start: Instant,
+    /// The status of the response headers, if they carry one.
+    code: Option<i32>,
+    recorded: bool,
+}
+
+impl CallMeasurements {
+    /// Record the call once, `failed` counts it as an error.
+    fn record(&mut self, failed: bool) {
+        if std::mem::replace(&mut self.recorded, true) {
+            return;
+        }
+        let elapsed_ms = self.start.elapsed().as_secs_f64() * 1000.0;
+        self.instruments
+            .duration
+            .record(elapsed_ms, &self.attributes);
+        self.instruments.requests.add(1, &self.attributes);
+        if failed {
+            self.instruments.errors.add(1, &self.attributes);
+        }
+    }
+}
+
+impl CallStatus for CallMeasurements {
+    fn record_code(&mut self, code: i32) {
+        if !self.recorded {
+            if tonic::Code::from_i32(code) == tonic::Code::Unimplemented {
+                for attribute in &mut self.attributes {
+                    if matches!(attribute.key.as_str(), "rpc.service" | "rpc.method") {
+                        attribute.value = OTHER_METHOD.into();
+                    }
+                }
+            }
+            self.attributes
+                .push(KeyValue::new("rpc.grpc.status_code", i64::from(code)));
+        }
+        self.record(is_grpc_server_error(code));
+    }
+
+    fn record_failure(&mut self) {
+        self.record(true);
+    }
+}
+
+impl Drop for CallMeasurements {
+    fn drop(&mut self) {
+        if !self.recorded {
+            self.record_code(self.code.unwrap_or(0));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::telemetry::testing::{meter_provider, metric_value};
+    use opentelemetry::metrics::MeterProvider as _;
+    use tower::{Layer as _, ServiceExt as _};
+
+    #[tokio::test]
+    async fn test_calls_are_measured_per_method() {
+        let (provider, exporter) = meter_provider();
+        let layer = GrpcMetricsLayer::new(&provider.meter("test"));
+        let service = layer.layer(tower::service_fn(|request: http::Request<()>| async move {
+            let mut response = http::Response::new(());
+            let status = match request.uri().path() {
+                "/shop.Cart/Checkout" => "14",
+                _ => "0",
+            };
+            response
+                .headers_mut()
+                .insert("grpc-status", status.parse().unwrap());
+            Ok::<_, std::convert::Infallible>(response)
+        }));
+
+        for path in ["/shop.Cart/Checkout", "/shop.Cart/Get", "/shop.Cart/Get"] {
+            let request = http::Request::post(path).body(()).unwrap();
+            service.clone().oneshot(request).await.unwrap();
+        }
+
+        let value = |name, method| {
+            metric_value(
+                &provider,
+                &exporter,
+                name,
+                &[
+                    ("rpc.system", "grpc"),
+                    ("rpc.service", "shop.Cart"),
+                    ("rpc.method", method),
+                ],
+            )
+        };
+        assert_eq!(value("rpc.server.requests", "Get"), 2.0);
+        assert_eq!(value("rpc.server.errors", "Get"), 0.0);
+        assert_eq!(value("rpc.server.duration", "Get"), 2.0);
+        assert_eq!(value("rpc.server.requests", "Checkout"), 1.0);
+        assert_eq!(value("rpc.server.errors", "Checkout"), 1.0);
+    }
+
+    #[tokio::test]
+    async fn test_unimplemented_methods_share_one_series() {
+        let (provider, exporter) = meter_provider();
+        let layer = GrpcMetricsLayer::new(&provider.meter("test"));
+        let service = layer.layer(tower::service_fn(|request: http::Request<()>| async move {
+            let mut response = http::Response::new(());
+            let status = match request.uri().path() {
+                "/shop.Cart/Get" => "0",
+                _ => "12",
+            };
+            response
+                .headers_mut()
+                .insert("grpc-status", status.parse().unwrap());
+            Ok::<_, std::convert::Infallible>(response)
+        }));
+
+        for path in ["/scan.A/X1", "/scan.B/X2", "/shop.Cart/Get"] {
+            let request = http::Request::post(path).body(()).unwrap();
+            service.clone().oneshot(request).await.unwrap();
+        }
+
+        let value = |service, method| {
+            metric_value(
+                &provider,
+                &exporter,
+                "rpc.server.requests",
+                &[("rpc.service", service), ("rpc.method", method)],
+            )
+        };
+        assert_eq!(value("_OTHER", "_OTHER"), 2.0);
+        assert_eq!(value("scan.A", "X1"), 0.0);
+        assert_eq!(value("scan.B", "X2"), 0.0);
+        assert_eq!(value("shop.Cart", "Get"), 1.0);
+    }
+
+    #[tokio::test]
+    async fn test_status_in_the_trailers_is_measured() {
+        /// A streamed response body, ending with its status in the trailers.
+        struct Stream(Option<http::HeaderMap>);
+
+        impl http_body::Body for Stream {
+            type Data = std::io::Cursor<Vec<u8>>;
+            type Error = std::convert::Infallible;
+
+            fn poll_frame(
+                mut self: Pin<&mut Self>,
+                _cx: &mut Context<'_>,
+            ) -> Poll<Option<Result<http_body::Frame<Self::Data>, Self::Error>>> {
+                Poll::Ready(
+                    self.0
+                        .take()
+                        .map(|trailers| Ok(http_body::Frame::trailers(trailers))),
+                )
+            }
+        }
+
+        let (provider, exporter) = meter_provider();
+        let layer = GrpcMetricsLayer::new(&provider.meter("test"));
+        let service = layer.layer(tower::service_fn(|_: http::Request<()>| async {
+            let mut trailers = http::HeaderMap::new();
+            trailers.insert("grpc-status", "14".parse().unwrap());
+            Ok::<_, std::convert::Infallible>(http::Response::new(Stream(Some(trailers))))
+        }));
+
+        let request = http::Request::post("/shop.Cart/Watch").body(()).unwrap();
+        let mut body = service.oneshot(request).await.unwrap().into_body();
+        let value = |name| {
+            metric_value(
+                &provider,
+                &exporter,
+                name,
+                &[("rpc.method", "Watch"), ("rpc.grpc.status_code", "14")],
+            )
+        };
+        // The call is measured once its status is sent
+        assert_eq!(value("rpc.server.requests"), 0.0);
+        while std::future::poll_fn(|cx| http_body::Body::poll_frame(Pin::new(&mut body), cx))
+            .await
+            .is_some()
+        {}
+        drop(body);
+
+        assert_eq!(value("rpc.server.requests"), 1.0);
+        assert_eq!(value("rpc.server.errors"), 1.0);
+        assert_eq!(value("rpc.server.duration"), 1.0);
+    }
+}
@@ -0,0 +1,256 @@
+//! Instrumented HTTP client, requires the `http-client` feature.
+//!
+//! [`http_client`] builds a [`reqwest`] client with the timeouts from [`HttpClientSettings`] and a
+//! `User-Agent` naming the service. Requests sent through [`HttpClient`] get a client span
+//! following the OpenTelemetry HTTP semantic conventions, carry the trace context in their
+//! headers, and are recorded in the `http.client.request.duration` histogram.
+//!
+//! byre builds reqwest without TLS, enable one of reqwest's TLS features (`rustls-tls` or
+//! `native-tls`) in the application's `Cargo.toml` to call `https` URLs.
+//!
+//! ```no_run
+//! # async fn call() -> Result<(), Box<dyn std::error::Error>> {
+//! let service_info = byre::service_info!();
+//! let client = byre::telemetry::http_client(&service_info).build()?;
+//!
+//! let response = client.send(client.get("http://localhost:8080/health")).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::time::Duration;
+
+use doku::Document;
+use serde::{Deserialize, Serialize};
+use snafu::ResultExt as _;
+use tracing::Instrument as _;
+
+use super::{BuildHttpClientSnafu, Error, HttpClientSpan};
+use crate::ServiceInfo;
+
+/// Settings for the instrumented HTTP client.
+#[derive(Clone, Debug, Serialize, Deserialize, Document)]
+pub struct HttpClientSettings {
+    /// Time allowed for a whole request, from connecting until the response body is read, in seconds.
+    #[doku(example = "30")]
+    #[serde(default = "default_timeout_secs")]
+    pub timeout_secs: u64,
+
+    /// Time allowed for connecting to the server, in seconds.
+    #[doku(example = "10")]
+    #[serde(default = "default_connect_timeout_secs")]
+    pub connect_timeout_secs: u64,
+}
+
+impl Default for HttpClientSettings {
+    fn default() -> Self {
+        Self {
+            timeout_secs: default_timeout_secs(),
+            connect_timeout_secs: default_connect_timeout_secs(),
+        }
+    }
+}
+
+fn default_timeout_secs() -> u64 {
+    30
+}
+
+fn default_connect_timeout_secs() -> u64 {
+    10
+}
+
+/// Start building an instrumented HTTP client for the service.
+pub fn http_client(service_info: &ServiceInfo) -> HttpClientBuilder {
+    HttpClientBuilder {
+        user_agent: format!("{}/{}", service_info.name, service_info.version),
+        settings: HttpClientSettings::default(),
+    }
+}
+
+/// Builder for [`HttpClient`], created by [`http_client`].
+#[derive(Clone, Debug)]
+pub struct HttpClientBuilder {
+    user_agent: String,
+    settings: HttpClientSettings,
+}
+
+impl HttpClientBuilder {
+    /// Use the timeouts from `settings`.
+    pub fn with_settings(mut self, settings: &HttpClientSettings) -> Self {
+        self.settings = settings.clone();
+        self
+    }
+
+    /// Build the client.
+    ///
+    /// # Errors
+    ///
+    /// - `BuildHttpClient` if reqwest cannot create the client.
+    pub fn build(self) -> Result<HttpClient, Error> {
+        let client = reqwest::Client::builder()
+            .user_agent(self.user_agent)
+            .timeout(Duration::from_secs(self.settings.timeout_secs))
+            .connect_timeout(Duration::from_secs(self.settings.connect_timeout_secs))
+            .build()
+            .context(BuildHttpClientSnafu)?;
+        Ok(HttpClient { client })
+    }
+}
+
+/// An HTTP client that traces and measures its requests.
+///
+/// Requests are built with the reqwest builders returned by [`get`](Self::get),
+/// [`post`](Self::post) and [`request`](Self::request), then sent with [`send`](Self::send).
+/// Calling `send` on the reqwest builder directly skips the instrumentation.
+#[derive(Clone, Debug)]
+pub struct HttpClient {
+    client: reqwest::Client,
+}
+
+impl HttpClient {
+    /// Start a `GET` request to `url`.
+    pub fn get(&self, url: impl reqwest::IntoUrl) -> reqwest::RequestBuilder {
+        self.client.get(url)
+    }
+
+    /// Start a `POST` request to `url`.
+    pub fn post(&self, url: impl reqwest::IntoUrl) -> reqwest::RequestBuilder {
+        self.client.post(url)
+    }
+
+    /// Start a request to `url`.
+    pub fn request(
+        &self,
+        method: reqwest::Method,
+        url: impl reqwest::IntoUrl,
+    ) -> reqwest::RequestBuilder {
+        self.client.request(method, url)
+    }
+
+    /// Build and send a request.
+    ///
+    /// # Errors
+    ///
+    /// The reqwest error if the request cannot be built or sent.
+    pub async fn send(
+        &self,
+        request: reqwest::RequestBuilder,
+    ) -> Result<reqwest::Response, reqwest::Error> {
+        self.execute(request.build()?).await
+    }
+
+    /// Send a request inside a client span, with the trace context in its headers.
+    ///
+    /// # Errors
+    ///
+    /// The reqwest error if the request cannot be sent.
+    pub async fn execute(
+        &self,
+        mut request: reqwest::Request,
+    ) -> Result<reqwest::Response, reqwest::Error> {
+        let url = request.url();
+        let span = HttpClientSpan::start(
+            request.method(),
+            url.host_str().unwrap_or_default().to_string(),
+            url.port_or_known_default(),
+            redacted_url(url).as_str(),
+        );
+        span.inject_trace_context(request.headers_mut());
+
+        let result = self
+            .client
+            .execute(request)
+            .instrument(span.span().clone())
+            .await;
+        match &result {
+            Ok(response) => span.record_response(response.status()),
+            Err(err) if err.is_timeout() => span.record_error("timeout"),
+            Err(err) if err.is_connect() => span.record_error("connect"),
+            Err(_) => span.record_error("_OTHER"),
+        }
+
+        result
+    }
+}
+
+/// The URL without the credentials it may carry, for the `url.full` attribute.
+fn redacted_url(url: &reqwest::Url) -> reqwest::Url {
+    let mut url = url.clone();
+    // Fails only for URLs that cannot have credentials
+    let _ = url.set_username("");
+    let _ = url.set_password(None);
+    url
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt as _, AsyncWriteExt as _};
+
+    #[tokio::test]
+    async fn test_requests_are_traced_and_measured() {
+        let _serial = super::super::http_client_span::TEST_LOCK.lock().await;
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0; 1024];
+            while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+                let read = stream.read(&mut buf).await.unwrap();
+                request.extend_from_slice(&buf[..read]);
+            }
+            stream
+                .write_all(b"HTTP/1.1 503 Service Unavailable\r\ncontent-length: 0\r\nconnection: close\r\n\r\n")
+                .await
+                .unwrap();
+            String::from_utf8(request).unwrap().to_lowercase()
+        });
+
+        super::super::init_propagator();
+        let capture = super::super::test::capture();
+        let service_info = crate::ServiceInfo {
+            name: "client-test",
+            version: "1.2.3",
+            ..Default::default()
+        };
+        let client = http_client(&service_info).build().unwrap();
+
+        let url = format!("http://user:secret@{addr}/status?verbose=1");
+        let response = client.send(client.get(&url)).await.unwrap();
+        assert_eq!(response.status(), 503);
+
+        let request = server.await.unwrap();
+        assert!(
+            request.contains("user-agent: client-test/1.2.3"),
+            "{request}"
+        );
+        assert!(request.contains("traceparent: 00-"), "{request}");
+
+        let span = capture.span("GET").unwrap();
+        assert_eq!(span.span_kind, opentelemetry::trace::SpanKind::Client);
+        let attribute = |key: &str| {
+            span.attributes
+                .iter()
+                .find(|kv| kv.key.as_str() == key)
+                .map(|kv| kv.value.to_string())
+        };
+        assert_eq!(
+            attribute("url.full"),
+            Some(format!("http://{addr}/status?verbose=1"))
+        );
+        assert_eq!(attribute("error.type").as_deref(), Some("503"));
+        assert!(matches!(
+            span.status,
+            opentelemetry::trace::Status::Error { .. }
+        ));
+
+        assert_eq!(
+            capture.metric_value(
+                "http.client.request.duration",
+                &[("http.response.status_code", "503")]
+            ),
+            1.0
+        );
+    }
+}
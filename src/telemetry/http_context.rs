@@ -0,0 +1,396 @@
+//! HTTP trace context propagation, requires the `http` feature.
+//!
+//! The [`TraceContextCarrier`] of `http::HeaderMap`, the functions that extract and inject the
+//! trace context of HTTP headers, and the [`HttpTraceContextLayer`] of HTTP servers.
+
+use std::sync::Arc;
+
+use opentelemetry::global;
+use opentelemetry::propagation::{Extractor, Injector};
+
+use super::request_fields::{RequestFields, RequestFieldsFn};
+use super::sampling::{SamplingRule, SamplingRules};
+use super::{propagation, request_id, Error, RequestId, TraceContextCarrier, REQUEST_ID_HEADER};
+
+// ============================================================================
+// HTTP Header Propagation (for HTTP proxies and clients)
+// ============================================================================
+
+/// Wrapper for http::HeaderMap to implement Extractor trait.
+/// Used for extracting trace context from incoming HTTP requests.
+pub struct HttpHeaderExtractor<'a>(pub &'a http::HeaderMap);
+
+impl Extractor for HttpHeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|v| v.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        // Only return the keys of the configured propagators that exist in the headers.
+        self.0
+            .keys()
+            .map(http::HeaderName::as_str)
+            .filter(|k| propagation::is_propagated(k))
+            .collect()
+    }
+}
+
+/// Wrapper for http::HeaderMap to implement Injector trait.
+/// Used for injecting trace context into outgoing HTTP requests.
+pub struct HttpHeaderInjector<'a>(pub &'a mut http::HeaderMap);
+
+impl Injector for HttpHeaderInjector<'_> {
+    fn set(&mut self, key: &str, value: String) {
+        if let Ok(key) = http::header::HeaderName::from_bytes(key.as_bytes()) {
+            if let Ok(value) = http::header::HeaderValue::from_str(&value) {
+                self.0.insert(key, value);
+            }
+        }
+    }
+}
+
+impl TraceContextCarrier for http::HeaderMap {
+    fn extract_trace_context(&self) -> opentelemetry::Context {
+        global::get_text_map_propagator(|propagator| propagator.extract(&HttpHeaderExtractor(self)))
+    }
+
+    fn extract_trace_context_with(&self, cx: &opentelemetry::Context) -> opentelemetry::Context {
+        global::get_text_map_propagator(|propagator| {
+            propagator.extract_with_context(cx, &HttpHeaderExtractor(self))
+        })
+    }
+
+    fn inject_trace_context(&mut self) {
+        inject_trace_context_http(self);
+    }
+
+    fn extract_request_id(&self) -> Option<RequestId> {
+        RequestId::extract(&HttpHeaderExtractor(self))
+    }
+}
+
+/// Extract trace context from incoming HTTP request headers.
+///
+/// Returns the extracted OpenTelemetry context. Use [`link_distributed_trace_http`] for a more
+/// convenient way to extract and link the trace context in one call.
+///
+/// # Example
+///
+/// ```
+/// let mut headers = http::HeaderMap::new();
+/// headers.insert("traceparent", "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01".parse().unwrap());
+///
+/// let parent_cx = byre::telemetry::extract_trace_context_http(&headers);
+/// let _guard = parent_cx.attach();
+/// // Spans created here will be children of the incoming trace
+/// ```
+pub fn extract_trace_context_http(headers: &http::HeaderMap) -> opentelemetry::Context {
+    global::get_text_map_propagator(|propagator| propagator.extract(&HttpHeaderExtractor(headers)))
+}
+
+/// Link the current span to an incoming distributed trace from HTTP headers.
+///
+/// This is a convenience function that extracts the trace context from the
+/// incoming request headers and sets it as the parent of the current span.
+/// Call this at the start of your HTTP handler after the `#[tracing::instrument]` span is created.
+///
+/// Returns `Ok(())` if successful, or an error if the span context couldn't be set.
+/// Most callers will want to ignore the error with `let _ = link_distributed_trace_http(...)`.
+///
+/// # Example
+///
+/// ```
+/// let mut headers = http::HeaderMap::new();
+/// headers.insert("traceparent", "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01".parse().unwrap());
+///
+/// let _ = byre::telemetry::link_distributed_trace_http(&headers);
+/// // Current span is now part of the distributed trace
+/// ```
+pub fn link_distributed_trace_http(headers: &http::HeaderMap) -> Result<(), Error> {
+    use tracing_opentelemetry::OpenTelemetrySpanExt;
+    let parent_cx = extract_trace_context_http(headers);
+    tracing::Span::current()
+        .set_parent(parent_cx)
+        .map_err(|e| Error::LinkDistributedTrace {
+            source: Box::new(e),
+        })
+}
+
+/// Inject trace context into outgoing HTTP request headers.
+///
+/// Call this before making outgoing HTTP calls to propagate the trace context, and the
+/// request id of the current span.
+///
+/// # Example
+///
+/// ```
+/// let mut headers = http::HeaderMap::new();
+/// byre::telemetry::inject_trace_context_http(&mut headers);
+/// // headers now contains traceparent header (if there's an active span)
+/// ```
+pub fn inject_trace_context_http(headers: &mut http::HeaderMap) {
+    use tracing_opentelemetry::OpenTelemetrySpanExt;
+    // Get the OpenTelemetry context from the current tracing span
+    let cx = tracing::Span::current().context();
+    global::get_text_map_propagator(|propagator| {
+        propagator.inject_context(&cx, &mut HttpHeaderInjector(headers));
+    });
+    request_id::inject(&mut HttpHeaderInjector(headers));
+}
+
+// ============================================================================
+// Tower Layer for Distributed Trace Context (HTTP servers)
+// ============================================================================
+
+/// Returns the route template a request matched, for the `http.route` attribute.
+pub type HttpRouteFn = fn(&http::Extensions) -> Option<&str>;
+
+/// A Tower layer that extracts distributed trace context from incoming HTTP requests and
+/// creates a server span following the OpenTelemetry HTTP semantic conventions.
+///
+/// The span is named `{method} {route}`, or `{method}` when the route is unknown, and records
+/// `http.request.method`, `http.route`, `url.path`, `network.protocol.version` and
+/// `http.response.status_code`. Responses with a 5xx status mark the span as an error.
+///
+/// The route is the low-cardinality template the request matched, such as `/users/{id}`. The
+/// layer can't know it by itself, provide it with [`with_route`](Self::with_route).
+///
+/// The [`RequestId`] of the `x-request-id` header, or a new one, is recorded in the
+/// `request.id` field of the span, added to the request extensions and returned in the
+/// `x-request-id` header of the response. The [`RequestFields`] of
+/// [`with_request_fields`](Self::with_request_fields) are attached to the span, and the
+/// [`SamplingRule`]s of [`with_sampling`](Self::with_sampling) decide which requests that start
+/// a trace are sampled.
+///
+/// # Example
+///
+/// ```
+/// use byre::telemetry::HttpTraceContextLayer;
+///
+/// let layer = HttpTraceContextLayer::new();
+///
+/// // With axum, the route comes from its `MatchedPath` extension:
+/// // let layer = HttpTraceContextLayer::new().with_route(|extensions| {
+/// //     extensions.get::<axum::extract::MatchedPath>().map(|path| path.as_str())
+/// // });
+/// // Router::new().route("/users/{id}", get(user)).layer(layer)
+/// ```
+#[derive(Clone, Default)]
+pub struct HttpTraceContextLayer {
+    route: Option<HttpRouteFn>,
+    request_fields: Option<RequestFieldsFn>,
+    sampling: SamplingRules,
+}
+
+impl HttpTraceContextLayer {
+    /// Create a new layer without route information.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read the matched route of a request from its extensions.
+    pub fn with_route(mut self, route: HttpRouteFn) -> Self {
+        self.route = Some(route);
+        self
+    }
+
+    /// Attach fields derived from the request to the request span, they are inherited by the
+    /// spans and logs of the handler.
+    ///
+    /// ```
+    /// use byre::telemetry::{HttpTraceContextLayer, RequestFields};
+    ///
+    /// let layer = HttpTraceContextLayer::new().with_request_fields(|request| {
+    ///     match request.headers.get("x-tenant").and_then(|value| value.to_str().ok()) {
+    ///         Some(tenant) => RequestFields::new().with_field("tenant.id", tenant),
+    ///         None => RequestFields::new(),
+    ///     }
+    /// });
+    /// ```
+    pub fn with_request_fields<F>(mut self, request_fields: F) -> Self
+    where
+        F: Fn(&http::request::Parts) -> RequestFields + Send + Sync + 'static,
+    {
+        self.request_fields = Some(Arc::new(request_fields));
+        self
+    }
+
+    /// Sample the requests that start a trace by their route and method, see
+    /// [`TraceSettings::sampling`](super::TraceSettings::sampling).
+    ///
+    /// The rules match the route of [`with_route`](Self::with_route), or the path of requests
+    /// without one.
+    ///
+    /// ```
+    /// use byre::telemetry::{HttpTraceContextLayer, SamplingRule};
+    ///
+    /// # let settings = byre::telemetry::TelemetrySettings::default();
+    /// let layer = HttpTraceContextLayer::new().with_sampling(&settings.trace.sampling);
+    ///
+    /// let assets = [SamplingRule {
+    ///     route: "/assets/*".to_string(),
+    ///     method: None,
+    ///     ratio: 0.01,
+    /// }];
+    /// let layer = HttpTraceContextLayer::new().with_sampling(&assets);
+    /// ```
+    pub fn with_sampling(mut self, rules: &[SamplingRule]) -> Self {
+        self.sampling = SamplingRules::new(rules);
+        self
+    }
+}
+
+impl<S> tower::Layer<S> for HttpTraceContextLayer {
+    type Service = HttpTraceContextService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        HttpTraceContextService {
+            inner,
+            route: self.route,
+            request_fields: self.request_fields.clone(),
+            sampling: self.sampling.clone(),
+        }
+    }
+}
+
+/// The service that wraps inner services with an HTTP server span.
+#[derive(Clone)]
+pub struct HttpTraceContextService<S> {
+    inner: S,
+    route: Option<HttpRouteFn>,
+    request_fields: Option<RequestFieldsFn>,
+    sampling: SamplingRules,
+}
+
+impl<S, B, ResBody> tower::Service<http::Request<B>> for HttpTraceContextService<S>
+where
+    S: tower::Service<http::Request<B>, Response = http::Response<ResBody>>
+        + Clone
+        + Send
+        + 'static,
+    S::Future: Send,
+    B: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = std::pin::Pin<
+        Box<dyn std::future::Future<Output = Result<Self::Response, Self::Error>> + Send>,
+    >;
+
+    fn poll_ready(
+        &mut self,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: http::Request<B>) -> Self::Future {
+        use tracing::field::Empty;
+        use tracing::Instrument;
+        use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+        let parent_cx = extract_trace_context_http(request.headers());
+        let request_id = RequestId::from_headers(request.headers());
+
+        let (mut parts, body) = request.into_parts();
+        let fields = self
+            .request_fields
+            .as_ref()
+            .map(|request_fields| request_fields(&parts));
+        parts.extensions.insert(request_id.clone());
+        let request = http::Request::from_parts(parts, body);
+
+        let method = http_method(request.method());
+        let route = self.route.and_then(|route| route(request.extensions()));
+        let name = match route {
+            Some(route) => format!("{method} {route}"),
+            None => method.to_string(),
+        };
+        let span = tracing::info_span!(
+            "http_request",
+            otel.name = name,
+            otel.kind = "server",
+            otel.status_code = Empty,
+            http.request.method = method,
+            http.route = route,
+            url.path = request.uri().path(),
+            network.protocol.version = http_protocol_version(request.version()),
+            http.response.status_code = Empty,
+            error.type = Empty,
+            request.id = Empty,
+        );
+        let parent_cx = self.sampling.parent(
+            parent_cx,
+            route.unwrap_or(request.uri().path()),
+            Some(method),
+        );
+        let _ = span.set_parent(parent_cx);
+        request_id.attach(&span);
+        if let Some(fields) = fields {
+            fields.attach(&span);
+        }
+
+        // Clone inner service for use in async block
+        let mut inner = self.inner.clone();
+
+        Box::pin(
+            async move {
+                let mut result = inner.call(request).await;
+                let span = tracing::Span::current();
+                match &mut result {
+                    Ok(response) => {
+                        set_request_id_header(response.headers_mut(), &request_id);
+                        let status = response.status();
+                        span.record("http.response.status_code", status.as_u16());
+                        // Only server errors fail a server span, 4xx are the client's
+                        if status.is_server_error() {
+                            span.record("otel.status_code", "ERROR");
+                            span.record("error.type", status.as_str());
+                        }
+                    }
+                    Err(_) => {
+                        span.record("otel.status_code", "ERROR");
+                        span.record("error.type", "_OTHER");
+                    }
+                }
+                result
+            }
+            .instrument(span),
+        )
+    }
+}
+
+/// The request method for spans, `_OTHER` for non-standard methods to bound the cardinality.
+pub(crate) fn http_method(method: &http::Method) -> &'static str {
+    match *method {
+        http::Method::GET => "GET",
+        http::Method::HEAD => "HEAD",
+        http::Method::POST => "POST",
+        http::Method::PUT => "PUT",
+        http::Method::DELETE => "DELETE",
+        http::Method::CONNECT => "CONNECT",
+        http::Method::OPTIONS => "OPTIONS",
+        http::Method::TRACE => "TRACE",
+        http::Method::PATCH => "PATCH",
+        _ => "_OTHER",
+    }
+}
+
+fn http_protocol_version(version: http::Version) -> Option<&'static str> {
+    match version {
+        http::Version::HTTP_09 => Some("0.9"),
+        http::Version::HTTP_10 => Some("1.0"),
+        http::Version::HTTP_11 => Some("1.1"),
+        http::Version::HTTP_2 => Some("2"),
+        http::Version::HTTP_3 => Some("3"),
+        _ => None,
+    }
+}
+
+/// Return the request id in the `x-request-id` header of a response, unless the handler set
+/// one.
+pub(crate) fn set_request_id_header(headers: &mut http::HeaderMap, request_id: &RequestId) {
+    if let Ok(value) = http::HeaderValue::from_str(request_id.as_str()) {
+        headers.entry(REQUEST_ID_HEADER).or_insert(value);
+    }
+}
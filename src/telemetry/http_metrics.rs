@@ -0,0 +1,223 @@
+//! HTTP server metrics tower layer.
+//!
+//! The metrics companion of [`HttpTraceContextLayer`](super::HttpTraceContextLayer): records
+//! the duration and count of every request, with the same attributes as the request spans.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Instant;
+
+use opentelemetry::metrics::{Counter, Histogram, Meter};
+use opentelemetry::KeyValue;
+
+use super::{http_method, HttpRouteFn};
+
+/// A Tower layer that records metrics for the requests of an HTTP server.
+///
+/// For every request the layer records:
+///
+/// - `http.server.request.duration` - a histogram of the handling time in seconds
+/// - `http.server.requests` - a counter of requests
+///
+/// Both carry the `http.request.method`, `http.route` and `http.response.status_code`
+/// attributes, and `error.type` for 5xx responses and for requests the inner service fails.
+///
+/// The URL path is never recorded, since one series per path would grow without bound. The
+/// route template the request matched, such as `/users/{id}`, is recorded when it is provided
+/// with [`with_route`](Self::with_route), and `http.route` is left out otherwise.
+///
+/// # Example
+///
+/// ```
+/// use byre::telemetry::HttpMetricsLayer;
+///
+/// // After `byre::telemetry::init`, the service's meter exports through the configured endpoint
+/// let meter = byre::telemetry::metrics::meter();
+/// let layer = HttpMetricsLayer::new(&meter);
+///
+/// // With axum, the route comes from its `MatchedPath` extension:
+/// // let layer = HttpMetricsLayer::new(&meter).with_route(|extensions| {
+/// //     extensions.get::<axum::extract::MatchedPath>().map(|path| path.as_str())
+/// // });
+/// // Router::new().route("/users/{id}", get(user)).route_layer(layer)
+/// ```
+#[derive(Clone)]
+pub struct HttpMetricsLayer {
+    route: Option<HttpRouteFn>,
+    instruments: HttpInstruments,
+}
+
+impl std::fmt::Debug for HttpMetricsLayer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HttpMetricsLayer")
+            .field("route", &self.route)
+            .finish_non_exhaustive()
+    }
+}
+
+impl HttpMetricsLayer {
+    /// Create a layer without route information that records its metrics with `meter`.
+    pub fn new(meter: &Meter) -> Self {
+        Self {
+            route: None,
+            instruments: HttpInstruments {
+                duration: meter
+                    .f64_histogram("http.server.request.duration")
+                    .with_description("Duration of HTTP server requests")
+                    .with_unit("s")
+                    .build(),
+                requests: meter
+                    .u64_counter("http.server.requests")
+                    .with_description("Number of HTTP server requests")
+                    .with_unit("{request}")
+                    .build(),
+            },
+        }
+    }
+
+    /// Read the matched route of a request from its extensions.
+    pub fn with_route(mut self, route: HttpRouteFn) -> Self {
+        self.route = Some(route);
+        self
+    }
+}
+
+impl<S> tower::Layer<S> for HttpMetricsLayer {
+    type Service = HttpMetricsService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        HttpMetricsService {
+            inner,
+            route: self.route,
+            instruments: self.instruments.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+struct HttpInstruments {
+    duration: Histogram<f64>,
+    requests: Counter<u64>,
+}
+
+/// The service that records metrics for the requests to the inner service.
+#[derive(Clone)]
+pub struct HttpMetricsService<S> {
+    inner: S,
+    route: Option<HttpRouteFn>,
+    instruments: HttpInstruments,
+}
+
+impl<S> std::fmt::Debug for HttpMetricsService<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HttpMetricsService")
+            .field("route", &self.route)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<S, B, ResBody> tower::Service<http::Request<B>> for HttpMetricsService<S>
+where
+    S: tower::Service<http::Request<B>, Response = http::Response<ResBody>>
+        + Clone
+        + Send
+        + 'static,
+    S::Future: Send,
+    B: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: http::Request<B>) -> Self::Future {
+        let mut attributes = vec![KeyValue::new(
+            "http.request.method",
+            http_method(request.method()),
+        )];
+        if let Some(route) = self.route.and_then(|route| route(request.extensions())) {
+            attributes.push(KeyValue::new("http.route", route.to_string()));
+        }
+
+        let mut inner = self.inner.clone();
+        let instruments = self.instruments.clone();
+        Box::pin(async move {
+            let start = Instant::now();
+            let result = inner.call(request).await;
+            let elapsed = start.elapsed().as_secs_f64();
+
+            match &result {
+                Ok(response) => {
+                    let status = response.status();
+                    attributes.push(KeyValue::new(
+                        "http.response.status_code",
+                        i64::from(status.as_u16()),
+                    ));
+                    if status.is_server_error() {
+                        attributes.push(KeyValue::new("error.type", status.as_str().to_string()));
+                    }
+                }
+                Err(_) => attributes.push(KeyValue::new("error.type", "_OTHER")),
+            }
+
+            instruments.duration.record(elapsed, &attributes);
+            instruments.requests.add(1, &attributes);
+
+            result
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::telemetry::testing::{meter_provider, metric_value};
+    use opentelemetry::metrics::MeterProvider as _;
+    use tower::{Layer as _, ServiceExt as _};
+
+    #[tokio::test]
+    async fn test_requests_are_measured_per_route() {
+        let (provider, exporter) = meter_provider();
+        let layer = HttpMetricsLayer::new(&provider.meter("test"))
+            .with_route(|extensions| extensions.get::<&'static str>().copied());
+        let service = layer.layer(tower::service_fn(|request: http::Request<()>| async move {
+            let mut response = http::Response::new(());
+            if request.uri().path() == "/users/13" {
+                *response.status_mut() = http::StatusCode::INTERNAL_SERVER_ERROR;
+            }
+            Ok::<_, std::convert::Infallible>(response)
+        }));
+
+        for path in ["/users/13", "/users/42", "/health"] {
+            let mut request = http::Request::get(path).body(()).unwrap();
+            if path.starts_with("/users/") {
+                request.extensions_mut().insert("/users/{id}");
+            }
+            service.clone().oneshot(request).await.unwrap();
+        }
+
+        let value = |name, attributes| metric_value(&provider, &exporter, name, attributes);
+        let users = [("http.route", "/users/{id}")];
+        assert_eq!(value("http.server.requests", &users), 2.0);
+        assert_eq!(value("http.server.request.duration", &users), 2.0);
+        assert_eq!(
+            value(
+                "http.server.requests",
+                &[("http.route", "/users/{id}"), ("error.type", "500")]
+            ),
+            1.0
+        );
+        assert_eq!(
+            value("http.server.requests", &[("http.request.method", "GET")]),
+            3.0
+        );
+        assert_eq!(
+            value("http.server.requests", &[("http.route", "/health")]),
+            0.0
+        );
+    }
+}
@@ -0,0 +1,174 @@
+//! jemalloc statistics as metrics.
+//!
+//! Only available with the `jemalloc` feature, which also makes jemalloc the global allocator.
+
+use opentelemetry::metrics::Meter;
+use tikv_jemalloc_ctl::{epoch, stats};
+
+/// A snapshot of the jemalloc statistics.
+#[derive(Clone, Copy, Debug)]
+struct JemallocStats {
+    allocated: u64,
+    active: u64,
+    resident: u64,
+    mapped: u64,
+    retained: u64,
+    metadata: u64,
+}
+
+impl JemallocStats {
+    /// Refresh jemalloc's cached statistics and read them.
+    fn read() -> Option<Self> {
+        // jemalloc only updates the statistics when the epoch advances
+        epoch::advance().ok()?;
+        Some(Self {
+            allocated: stats::allocated::read().ok()? as u64,
+            active: stats::active::read().ok()? as u64,
+            resident: stats::resident::read().ok()? as u64,
+            mapped: stats::mapped::read().ok()? as u64,
+            retained: stats::retained::read().ok()? as u64,
+            metadata: stats::metadata::read().ok()? as u64,
+        })
+    }
+
+    /// The fraction of active pages that is not used by allocations.
+    fn fragmentation(&self) -> f64 {
+        if self.active == 0 {
+            return 0.0;
+        }
+        self.active.saturating_sub(self.allocated) as f64 / self.active as f64
+    }
+}
+
+/// Name, description and accessor of a byte-valued jemalloc gauge.
+type ByteGauge = (&'static str, &'static str, fn(&JemallocStats) -> u64);
+
+/// Register observable gauges that publish jemalloc's statistics.
+///
+/// The statistics are read every time the meter provider collects metrics. The following
+/// metrics are recorded, all in bytes except for the fragmentation ratio:
+///
+/// - `jemalloc.allocated` - bytes allocated by the application
+/// - `jemalloc.active` - bytes in active pages, a multiple of the page size
+/// - `jemalloc.resident` - bytes in physically resident pages mapped by the allocator
+/// - `jemalloc.mapped` - bytes in active extents mapped by the allocator
+/// - `jemalloc.retained` - bytes in virtual memory mappings retained for reuse
+/// - `jemalloc.metadata` - bytes dedicated to jemalloc's own metadata
+/// - `jemalloc.fragmentation` - fraction of active bytes not used by allocations
+///
+/// [`init`](super::init) calls this when the `jemalloc` feature is enabled and a metrics
+/// endpoint is configured.
+pub fn register_jemalloc_metrics(meter: &Meter) {
+    let gauges: [ByteGauge; 6] = [
+        (
+            "jemalloc.allocated",
+            "Bytes allocated by the application",
+            |stats| stats.allocated,
+        ),
+        (
+            "jemalloc.active",
+            "Bytes in active pages allocated by the application",
+            |stats| stats.active,
+        ),
+        (
+            "jemalloc.resident",
+            "Bytes in physically resident data pages mapped by the allocator",
+            |stats| stats.resident,
+        ),
+        (
+            "jemalloc.mapped",
+            "Bytes in active extents mapped by the allocator",
+            |stats| stats.mapped,
+        ),
+        (
+            "jemalloc.retained",
+            "Bytes in virtual memory mappings retained for future reuse",
+            |stats| stats.retained,
+        ),
+        (
+            "jemalloc.metadata",
+            "Bytes dedicated to allocator metadata",
+            |stats| stats.metadata,
+        ),
+    ];
+
+    for (name, description, value) in gauges {
+        meter
+            .u64_observable_gauge(name)
+            .with_description(description)
+            .with_unit("By")
+            .with_callback(move |observer| {
+                if let Some(stats) = JemallocStats::read() {
+                    observer.observe(value(&stats), &[]);
+                }
+            })
+            .build();
+    }
+
+    meter
+        .f64_observable_gauge("jemalloc.fragmentation")
+        .with_description("Fraction of active bytes that are not used by allocations")
+        .with_callback(|observer| {
+            if let Some(stats) = JemallocStats::read() {
+                observer.observe(stats.fragmentation(), &[]);
+            }
+        })
+        .build();
+}
+
+/// Read jemalloc's statistics as `name: bytes` lines, `None` if jemalloc cannot report them.
+///
+/// Served by the admin endpoint at `GET /heapstats`.
+#[cfg(feature = "admin")]
+pub(crate) fn heap_stats() -> Option<String> {
+    let stats = JemallocStats::read()?;
+    Some(format!(
+        "allocated: {}\nactive: {}\nresident: {}\nmapped: {}\nretained: {}\nmetadata: {}\nfragmentation: {:.4}\n",
+        stats.allocated,
+        stats.active,
+        stats.resident,
+        stats.mapped,
+        stats.retained,
+        stats.metadata,
+        stats.fragmentation(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::telemetry::testing::{has_metric, meter_provider, metric_value};
+    use opentelemetry::metrics::MeterProvider as _;
+
+    #[test]
+    fn test_register_jemalloc_metrics_reports_allocations() {
+        let (provider, exporter) = meter_provider();
+        register_jemalloc_metrics(&provider.meter("test"));
+
+        let _allocation = vec![0u8; 1 << 20];
+
+        assert!(metric_value(&provider, &exporter, "jemalloc.allocated", &[]) >= (1 << 20) as f64);
+        assert!(has_metric(&provider, &exporter, "jemalloc.resident"));
+        assert!(has_metric(&provider, &exporter, "jemalloc.fragmentation"));
+    }
+
+    #[test]
+    fn test_fragmentation_of_empty_stats_is_zero() {
+        let stats = JemallocStats {
+            allocated: 0,
+            active: 0,
+            resident: 0,
+            mapped: 0,
+            retained: 0,
+            metadata: 0,
+        };
+        assert_eq!(stats.fragmentation(), 0.0);
+
+        let stats = JemallocStats {
+            allocated: 75,
+            active: 100,
+            ..stats
+        };
+        assert_eq!(stats.fragmentation(), 0.25);
+    }
+}
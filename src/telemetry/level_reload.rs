@@ -0,0 +1,143 @@
+//! Applies the log levels of the config file while the service runs, requires the `hot-reload`
+//! feature.
+//!
+//! Only the levels of the `[telemetry.log]` section are reloaded: `console_level`, `otel_level`
+//! and `trace_level`. Changing any other value still needs a restart.
+
+use std::path::PathBuf;
+use std::time::Duration;
+
+use doku::Document;
+use serde::Deserialize;
+
+use super::{LogLevelHandle, LogLevels};
+use crate::config::{self, Config};
+
+/// The part of the config the levels are reloaded from.
+#[derive(Default, Deserialize, Document)]
+struct Section {
+    #[serde(default)]
+    telemetry: TelemetrySection,
+}
+
+#[derive(Default, Deserialize, Document)]
+struct TelemetrySection {
+    #[serde(default)]
+    log: LevelSection,
+}
+
+#[derive(Default, Deserialize, Document)]
+struct LevelSection {
+    #[serde(default)]
+    console_level: String,
+    #[serde(default)]
+    otel_level: String,
+    #[serde(default)]
+    trace_level: String,
+}
+
+impl LogLevelHandle {
+    /// Apply the levels of the `[telemetry.log]` section of the config file at `path` when the
+    /// file changes, checking every `interval` on the current tokio runtime.
+    ///
+    /// Values are overridden by the environment variables that start with `env_prefix`, like
+    /// when the config was loaded. Only the levels that changed in the file are applied, so a
+    /// level set at runtime, ie: with the admin endpoint, stays until the file changes it. A
+    /// file that can't be loaded, or an invalid level, is logged and the levels are left as
+    /// they are. The reload stops when the returned task is aborted, or with the runtime.
+    pub fn spawn_reload(
+        &self,
+        path: impl Into<PathBuf>,
+        env_prefix: impl Into<String>,
+        interval: Duration,
+    ) -> tokio::task::JoinHandle<()> {
+        let path = path.into();
+        let env_prefix = env_prefix.into();
+        let log_levels = self.clone();
+        let mut loaded = self.configured.clone();
+
+        config::spawn_file_watch(path.clone(), interval, move || {
+            let levels = match Config::<Section>::new(Some(&path), Some(&env_prefix)) {
+                Ok(config) => config.config.telemetry.log,
+                Err(err) => {
+                    tracing::warn!(error = %err, "could not reload the log levels");
+                    return;
+                }
+            };
+            let levels = LogLevels {
+                console: levels.console_level,
+                otel: levels.otel_level,
+                trace: levels.trace_level,
+            };
+            if let Err(err) = log_levels.apply_changes(&loaded, &levels) {
+                tracing::warn!(error = %err, "could not reload the log levels");
+                return;
+            }
+            tracing::info!(
+                console_level = levels.console,
+                otel_level = levels.otel,
+                trace_level = levels.trace,
+                "log levels reloaded"
+            );
+            loaded = levels;
+        })
+    }
+
+    /// Set the levels of `levels` that differ from the ones of `previous`.
+    fn apply_changes(&self, previous: &LogLevels, levels: &LogLevels) -> Result<(), super::Error> {
+        if levels.console != previous.console {
+            self.set_console_level(&levels.console)?;
+        }
+        if levels.otel != previous.otel {
+            self.set_otel_level(&levels.otel)?;
+        }
+        if levels.trace != previous.trace {
+            self.set_trace_level(&levels.trace)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::telemetry::LogSettings;
+
+    #[tokio::test]
+    async fn test_changed_levels_are_applied_when_the_file_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(
+            &path,
+            "[telemetry.log]\nconsole_level = \"info\"\notel_level = \"warn\"\n",
+        )
+        .unwrap();
+        let settings = LogSettings {
+            console_level: "info".to_string(),
+            otel_level: "warn".to_string(),
+            endpoint: None,
+            ..Default::default()
+        };
+        let built = crate::telemetry::LogSubscriberBuilder::new(&Default::default(), &settings)
+            .build()
+            .unwrap();
+        let log_levels = built.log_levels.clone();
+        // Set at runtime, the file doesn't change it
+        log_levels.set_otel_level("error").unwrap();
+
+        let reload = log_levels.spawn_reload(&path, "BYRE_TEST_LEVELS_", Duration::from_millis(10));
+        // Modification times can be as coarse as a second
+        tokio::time::sleep(Duration::from_millis(1100)).await;
+        std::fs::write(
+            &path,
+            "[telemetry.log]\nconsole_level = \"debug\"\notel_level = \"warn\"\n",
+        )
+        .unwrap();
+
+        while log_levels.console_level() != "debug" {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(log_levels.otel_level(), "error");
+        reload.abort();
+    }
+}
@@ -0,0 +1,500 @@
+//! Rate limiting of identical log events.
+//!
+//! A flood of identical events, ie: the same error logged for every request while a database is
+//! down, can drown the OTLP pipeline. The [`LogRateLimitLayer`] wraps the log output layers and
+//! only passes a limited number of identical events through per interval. The first event after
+//! an interval in which events were suppressed carries a summary of how many were dropped.
+//!
+//! The warnings and errors of the OpenTelemetry SDK are always rate limited, an unreachable
+//! collector makes every export fail and would otherwise repeat the same error on the console.
+
+use std::any::TypeId;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash as _, Hasher as _};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use doku::Document;
+use serde::{Deserialize, Serialize};
+use tracing::callsite::Identifier;
+use tracing::field::{debug, DebugValue, Field, Value, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::subscriber::Interest;
+use tracing::{Dispatch, Event, Metadata, Subscriber};
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::layer::Context;
+use tracing_subscriber::Layer;
+
+use super::sdk_errors::is_sdk_event;
+
+/// Number of distinct events tracked at once, events beyond it are not rate limited.
+const MAX_TRACKED_EVENTS: usize = 4096;
+
+/// The most fields a summary event can carry, the same limit `tracing`'s macros have.
+const MAX_SUMMARY_FIELDS: usize = 32;
+
+/// Number of identical OpenTelemetry SDK events logged per [`SDK_EVENT_INTERVAL`].
+const SDK_EVENT_BURST: u32 = 1;
+
+/// Interval of the rate limit of the OpenTelemetry SDK events, whatever the settings are.
+const SDK_EVENT_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Settings for rate limiting identical log events.
+///
+/// Events are identical when they come from the same callsite with the same message. Events
+/// without a message are never rate limited.
+#[derive(Clone, Debug, Serialize, Deserialize, Document)]
+pub struct LogRateLimitSettings {
+    /// Number of identical events logged per interval, further events are suppressed until the interval ends.
+    #[doku(example = "10")]
+    pub burst: u32,
+
+    /// Length of the interval in seconds.
+    #[doku(example = "60")]
+    pub interval_secs: u64,
+}
+
+/// Wraps the log output layers, suppressing events that exceed the rate limit.
+///
+/// Only the wrapped layers are affected, spans and span metrics still see every event.
+pub(crate) struct LogRateLimitLayer<L> {
+    inner: L,
+    limiter: Option<Limiter>,
+    sdk_limiter: Limiter,
+}
+
+impl<L> LogRateLimitLayer<L> {
+    /// Wrap `inner`, without settings only the OpenTelemetry SDK events are rate limited.
+    pub(crate) fn new(inner: L, settings: Option<&LogRateLimitSettings>) -> Self {
+        Self {
+            inner,
+            limiter: settings.map(|settings| {
+                Limiter::new(settings.burst, Duration::from_secs(settings.interval_secs))
+            }),
+            sdk_limiter: Limiter::new(SDK_EVENT_BURST, SDK_EVENT_INTERVAL),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Decision {
+    Log,
+    Suppress,
+    /// Log a summary of the events suppressed in the previous interval.
+    Summarize(u64),
+}
+
+struct Window {
+    start: Instant,
+    count: u32,
+    suppressed: u64,
+}
+
+struct Limiter {
+    burst: u32,
+    interval: Duration,
+    windows: Mutex<HashMap<u64, Window>>,
+}
+
+impl Limiter {
+    fn new(burst: u32, interval: Duration) -> Self {
+        Self {
+            burst: burst.max(1),
+            interval,
+            windows: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Identical events share a key.
+    fn key(callsite: &Identifier, message: &str) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        callsite.hash(&mut hasher);
+        message.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    fn check(&self, key: u64, now: Instant) -> Decision {
+        let mut windows = self.windows.lock().unwrap_or_else(|err| err.into_inner());
+        if !windows.contains_key(&key) && windows.len() >= MAX_TRACKED_EVENTS {
+            // Forget the windows that ended without suppressing anything.
+            windows.retain(|_, window| {
+                window.suppressed > 0 || now.duration_since(window.start) < self.interval
+            });
+            if windows.len() >= MAX_TRACKED_EVENTS {
+                return Decision::Log;
+            }
+        }
+
+        let window = windows.entry(key).or_insert(Window {
+            start: now,
+            count: 0,
+            suppressed: 0,
+        });
+
+        if now.duration_since(window.start) >= self.interval {
+            let suppressed = std::mem::take(&mut window.suppressed);
+            window.start = now;
+            window.count = 1;
+            return match suppressed {
+                0 => Decision::Log,
+                suppressed => Decision::Summarize(suppressed),
+            };
+        }
+
+        if window.count < self.burst {
+            window.count += 1;
+            Decision::Log
+        } else {
+            window.suppressed += 1;
+            Decision::Suppress
+        }
+    }
+}
+
+/// Finds the message of an event.
+#[derive(Default)]
+struct MessageVisitor(Option<String>);
+
+impl Visit for MessageVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.0 = Some(value.to_string());
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            self.0 = Some(format!("{value:?}"));
+        }
+    }
+}
+
+/// A recorded field value that can be replayed into a new event.
+enum FieldValue {
+    I64(i64),
+    U64(u64),
+    F64(f64),
+    Bool(bool),
+    Str(String),
+    Debug(DebugValue<Formatted>),
+}
+
+/// A `Debug` formatted value, replayed as is.
+struct Formatted(String);
+
+impl std::fmt::Debug for Formatted {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl FieldValue {
+    fn as_value(&self) -> &dyn Value {
+        match self {
+            FieldValue::I64(value) => value,
+            FieldValue::U64(value) => value,
+            FieldValue::F64(value) => value,
+            FieldValue::Bool(value) => value,
+            FieldValue::Str(value) => value,
+            FieldValue::Debug(value) => value,
+        }
+    }
+}
+
+/// Records every field of an event.
+#[derive(Default)]
+struct FieldsVisitor(Vec<(Field, FieldValue)>);
+
+impl Visit for FieldsVisitor {
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.0.push((field.clone(), FieldValue::I64(value)));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.push((field.clone(), FieldValue::U64(value)));
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.0.push((field.clone(), FieldValue::F64(value)));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0.push((field.clone(), FieldValue::Bool(value)));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0
+            .push((field.clone(), FieldValue::Str(value.to_string())));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        self.0.push((
+            field.clone(),
+            FieldValue::Debug(debug(Formatted(format!("{value:?}")))),
+        ));
+    }
+}
+
+impl<L> LogRateLimitLayer<L> {
+    /// Pass a copy of `event` to the inner layer with the summary appended to its message.
+    fn on_summary<S>(&self, event: &Event<'_>, message: &str, suppressed: u64, ctx: Context<'_, S>)
+    where
+        L: Layer<S>,
+        S: Subscriber,
+    {
+        let mut fields = FieldsVisitor::default();
+        event.record(&mut fields);
+
+        let metadata = event.metadata();
+        let Some(message_field) = metadata.fields().field("message") else {
+            return self.inner.on_event(event, ctx);
+        };
+        // The SDK's events have an empty message, their details are in the other fields.
+        let summary = match message {
+            "" => format!("suppressed {suppressed} duplicates"),
+            message => format!("{message} (suppressed {suppressed} duplicates)"),
+        };
+        let summary = FieldValue::Str(summary);
+
+        // Unused slots repeat the message field without a value, which records nothing.
+        let mut values: [(&Field, Option<&dyn Value>); MAX_SUMMARY_FIELDS] =
+            [(&message_field, None); MAX_SUMMARY_FIELDS];
+        values[0] = (&message_field, Some(summary.as_value()));
+        let others = fields.0.iter().filter(|(field, _)| *field != message_field);
+        for (slot, (field, value)) in values[1..].iter_mut().zip(others) {
+            *slot = (field, Some(value.as_value()));
+        }
+
+        let value_set = metadata.fields().value_set(&values);
+        let summary_event = if event.is_contextual() {
+            Event::new(metadata, &value_set)
+        } else {
+            Event::new_child_of(event.parent().cloned(), metadata, &value_set)
+        };
+        self.inner.on_event(&summary_event, ctx);
+    }
+}
+
+impl<L, S> Layer<S> for LogRateLimitLayer<L>
+where
+    L: Layer<S>,
+    S: Subscriber,
+{
+    fn on_register_dispatch(&self, subscriber: &Dispatch) {
+        self.inner.on_register_dispatch(subscriber);
+    }
+
+    fn on_layer(&mut self, subscriber: &mut S) {
+        self.inner.on_layer(subscriber);
+    }
+
+    fn register_callsite(&self, metadata: &'static Metadata<'static>) -> Interest {
+        self.inner.register_callsite(metadata)
+    }
+
+    fn enabled(&self, metadata: &Metadata<'_>, ctx: Context<'_, S>) -> bool {
+        self.inner.enabled(metadata, ctx)
+    }
+
+    fn max_level_hint(&self) -> Option<LevelFilter> {
+        self.inner.max_level_hint()
+    }
+
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        self.inner.on_new_span(attrs, id, ctx);
+    }
+
+    fn on_record(&self, span: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        self.inner.on_record(span, values, ctx);
+    }
+
+    fn on_follows_from(&self, span: &Id, follows: &Id, ctx: Context<'_, S>) {
+        self.inner.on_follows_from(span, follows, ctx);
+    }
+
+    fn event_enabled(&self, event: &Event<'_>, ctx: Context<'_, S>) -> bool {
+        self.inner.event_enabled(event, ctx)
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let limiter = if is_sdk_event(event.metadata()) {
+            Some(&self.sdk_limiter)
+        } else {
+            self.limiter.as_ref()
+        };
+        let Some(limiter) = limiter else {
+            return self.inner.on_event(event, ctx);
+        };
+
+        let mut message = MessageVisitor::default();
+        event.record(&mut message);
+        let Some(message) = message.0 else {
+            return self.inner.on_event(event, ctx);
+        };
+
+        // Exactly one event is passed on, per-layer filters only allow one per dispatch.
+        let key = Limiter::key(&event.metadata().callsite(), &message);
+        match limiter.check(key, Instant::now()) {
+            Decision::Log => self.inner.on_event(event, ctx),
+            Decision::Suppress => {}
+            Decision::Summarize(suppressed) => self.on_summary(event, &message, suppressed, ctx),
+        }
+    }
+
+    fn on_enter(&self, id: &Id, ctx: Context<'_, S>) {
+        self.inner.on_enter(id, ctx);
+    }
+
+    fn on_exit(&self, id: &Id, ctx: Context<'_, S>) {
+        self.inner.on_exit(id, ctx);
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        self.inner.on_close(id, ctx);
+    }
+
+    fn on_id_change(&self, old: &Id, new: &Id, ctx: Context<'_, S>) {
+        self.inner.on_id_change(old, new, ctx);
+    }
+
+    unsafe fn downcast_raw(&self, id: TypeId) -> Option<*const ()> {
+        if id == TypeId::of::<Self>() {
+            Some(self as *const Self as *const ())
+        } else {
+            // SAFETY: forwarded unchanged, the inner layer upholds the contract.
+            unsafe { self.inner.downcast_raw(id) }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    /// Collects the formatted messages and fields of the events it sees.
+    #[derive(Clone, Default)]
+    struct Collector(Arc<Mutex<Vec<String>>>);
+
+    impl<S: Subscriber> Layer<S> for Collector {
+        fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+            let mut fields = FieldsVisitor::default();
+            event.record(&mut fields);
+            let line = fields
+                .0
+                .iter()
+                .map(|(field, value)| match value {
+                    FieldValue::Str(value) => format!("{}={value}", field.name()),
+                    FieldValue::Debug(value) => format!("{}={value:?}", field.name()),
+                    FieldValue::U64(value) => format!("{}={value}", field.name()),
+                    FieldValue::I64(value) => format!("{}={value}", field.name()),
+                    _ => field.name().to_string(),
+                })
+                .collect::<Vec<_>>()
+                .join(" ");
+            self.0.lock().unwrap().push(line);
+        }
+    }
+
+    #[test]
+    fn test_limiter_suppresses_and_summarizes() {
+        let limiter = Limiter::new(2, Duration::from_secs(60));
+        let start = Instant::now();
+
+        let check = |key, secs| limiter.check(key, start + Duration::from_secs(secs));
+        assert_eq!(check(1, 0), Decision::Log);
+        assert_eq!(check(1, 1), Decision::Log);
+        assert_eq!(check(1, 2), Decision::Suppress);
+        assert_eq!(check(1, 3), Decision::Suppress);
+        assert_eq!(check(2, 3), Decision::Log);
+        assert_eq!(check(1, 61), Decision::Summarize(2));
+        assert_eq!(check(1, 62), Decision::Log);
+        assert_eq!(check(1, 200), Decision::Log);
+    }
+
+    #[test]
+    fn test_layer_passes_summary_with_fields() {
+        let collector = Collector::default();
+        let settings = LogRateLimitSettings {
+            burst: 1,
+            interval_secs: 3600,
+        };
+        let layer = LogRateLimitLayer::new(collector.clone(), Some(&settings));
+
+        let subscriber = tracing_subscriber::registry().with(layer);
+        let dispatch = Dispatch::new(subscriber);
+        let flood = |users: std::ops::Range<u64>| {
+            tracing::dispatcher::with_default(&dispatch, || {
+                for user in users {
+                    tracing::warn!(user, "database unavailable");
+                }
+            })
+        };
+
+        flood(0..3);
+
+        let lines = collector.0.lock().unwrap().clone();
+        assert_eq!(lines, vec!["message=database unavailable user=0"]);
+
+        // End the interval without waiting for it.
+        let layer = dispatch
+            .downcast_ref::<LogRateLimitLayer<Collector>>()
+            .unwrap();
+        for window in layer
+            .limiter
+            .as_ref()
+            .unwrap()
+            .windows
+            .lock()
+            .unwrap()
+            .values_mut()
+        {
+            window.start -= Duration::from_secs(3600);
+        }
+        flood(3..5);
+
+        let lines = collector.0.lock().unwrap().clone();
+        assert_eq!(
+            lines[1..],
+            ["message=database unavailable (suppressed 2 duplicates) user=3"]
+        );
+    }
+
+    #[test]
+    fn test_layer_without_settings_passes_everything() {
+        let collector = Collector::default();
+        let subscriber =
+            tracing_subscriber::registry().with(LogRateLimitLayer::new(collector.clone(), None));
+
+        tracing::subscriber::with_default(subscriber, || {
+            for _ in 0..3 {
+                tracing::warn!("database unavailable");
+            }
+        });
+
+        assert_eq!(collector.0.lock().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_sdk_events_are_always_rate_limited() {
+        let collector = Collector::default();
+        let subscriber =
+            tracing_subscriber::registry().with(LogRateLimitLayer::new(collector.clone(), None));
+
+        tracing::subscriber::with_default(subscriber, || {
+            for _ in 0..3 {
+                tracing::error!(
+                    target: "opentelemetry_sdk",
+                    name = "BatchSpanProcessor.ExportError",
+                    ""
+                );
+            }
+        });
+
+        let lines = collector.0.lock().unwrap().clone();
+        assert_eq!(lines, vec!["message= name=BatchSpanProcessor.ExportError"]);
+    }
+}
@@ -0,0 +1,192 @@
+//! Spans of message queue producers and consumers, following the OpenTelemetry messaging
+//! semantic conventions.
+//!
+//! [`producer_span`] opens the span a producer publishes a message in, named
+//! `publish {destination}`, and injects its trace context in the message's headers.
+//! [`start_consumer_span`] opens the span a consumer processes the message in, named
+//! `process {destination}`, continuing the trace of the producer from the headers.
+//!
+//! ```
+//! use std::collections::HashMap;
+//!
+//! let mut headers: HashMap<String, String> = HashMap::new();
+//! let publish = byre::telemetry::producer_span("kafka", "orders", &mut headers);
+//! // ... send the message with its headers, in `publish.span()` ...
+//! drop(publish);
+//!
+//! let span = byre::telemetry::start_consumer_span("kafka", "orders", &headers);
+//! let _enter = span.enter();
+//! tracing::info!("processing the order");
+//! ```
+
+use opentelemetry::trace::TraceContextExt as _;
+use tracing::field::Empty;
+use tracing_opentelemetry::OpenTelemetrySpanExt as _;
+
+use super::{TraceContextCarrier, TraceContextExt as _};
+
+/// The span of publishing a message, created by [`producer_span`]. The span ends when it is
+/// dropped.
+#[derive(Debug)]
+#[must_use = "the publish span ends when the ProducerSpan is dropped"]
+pub struct ProducerSpan {
+    span: tracing::Span,
+}
+
+impl ProducerSpan {
+    /// The span, to run the publishing in, ie: with `Instrument::instrument`.
+    pub fn span(&self) -> &tracing::Span {
+        &self.span
+    }
+
+    /// Mark the publishing as failed with `error`, its type becomes the span's `error.type`.
+    pub fn record_error<E>(&self, error: &E)
+    where
+        E: std::error::Error + ?Sized,
+    {
+        self.span.record("otel.status_code", "ERROR");
+        self.span
+            .record("otel.status_description", error.to_string());
+        self.span.record("error.type", std::any::type_name::<E>());
+    }
+}
+
+/// Start the span of publishing a message to `destination`, ie: a topic or a queue, of the
+/// messaging `system`, ie: `kafka`, `nats` or `iggy`, and inject its trace context and the
+/// current request id in `headers`.
+///
+/// The span is a producer span, a child of the current span, with the `messaging.system`,
+/// `messaging.destination.name`, `messaging.operation.type` and `messaging.operation.name`
+/// attributes. The consumers' spans started with [`start_consumer_span`] from `headers`
+/// continue its trace.
+pub fn producer_span<C>(system: &str, destination: &str, headers: &mut C) -> ProducerSpan
+where
+    C: TraceContextCarrier,
+{
+    let span = tracing::info_span!(
+        "messaging_publish",
+        otel.name = format!("publish {destination}"),
+        otel.kind = "producer",
+        otel.status_code = Empty,
+        otel.status_description = Empty,
+        messaging.system = system,
+        messaging.destination.name = destination,
+        messaging.operation.type = "send",
+        messaging.operation.name = "publish",
+        error.type = Empty,
+    );
+    span.in_scope(|| headers.inject_trace_context());
+    ProducerSpan { span }
+}
+
+/// Start the span of processing a message received from `destination`, ie: a topic or a queue,
+/// of the messaging `system`, ie: `kafka`, `nats` or `iggy`.
+///
+/// The span is a consumer span with the `messaging.system`, `messaging.destination.name`,
+/// `messaging.operation.type` and `messaging.operation.name` attributes. When `headers` carry
+/// a trace context, the span is its child and links to it, and the request id they carry
+/// becomes the span's. Otherwise the span is a child of the current span.
+///
+/// Consumers processing a batch of messages in one span link each message to it with
+/// [`link_remote_context`](super::TraceContextExt::link_remote_context) instead.
+pub fn start_consumer_span<C>(system: &str, destination: &str, headers: &C) -> tracing::Span
+where
+    C: TraceContextCarrier,
+{
+    let span = tracing::info_span!(
+        "messaging_process",
+        otel.name = format!("process {destination}"),
+        otel.kind = "consumer",
+        otel.status_code = Empty,
+        messaging.system = system,
+        messaging.destination.name = destination,
+        messaging.operation.type = "process",
+        messaging.operation.name = "process",
+        error.type = Empty,
+    );
+    if let Some(remote_cx) = headers.try_extract_trace_context() {
+        span.add_link(remote_cx.span().span_context().clone());
+        let _ = span.set_parent(remote_cx);
+    }
+    if let Some(request_id) = headers.extract_request_id() {
+        request_id.attach(&span);
+    }
+    span
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use opentelemetry::trace::{SpanId, SpanKind, TraceId};
+
+    use super::*;
+
+    #[test]
+    fn test_producer_span_is_injected_in_the_headers() {
+        super::super::init_propagator();
+        let capture = super::super::test::capture();
+
+        let mut headers = HashMap::new();
+        let publish = producer_span("nats", "orders", &mut headers);
+        publish.record_error(&std::io::Error::other("broker unreachable"));
+        drop(publish);
+
+        let span = capture.span("publish orders").unwrap();
+        assert_eq!(span.span_kind, SpanKind::Producer);
+        assert!(matches!(
+            span.status,
+            opentelemetry::trace::Status::Error { .. }
+        ));
+        let traceparent = &headers["traceparent"];
+        assert!(
+            traceparent.contains(&span.span_context.span_id().to_string()),
+            "{traceparent}"
+        );
+        assert!(
+            span.attributes
+                .iter()
+                .any(|kv| kv.key.as_str() == "messaging.operation.type"
+                    && kv.value.as_str() == "send")
+        );
+    }
+
+    #[test]
+    fn test_consumer_span_continues_the_producer_trace() {
+        super::super::init_propagator();
+        let capture = super::super::test::capture();
+        let headers = HashMap::from([(
+            "traceparent".to_string(),
+            "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01".to_string(),
+        )]);
+
+        let span = start_consumer_span("kafka", "orders", &headers);
+        span.in_scope(|| tracing::info!("processing the order"));
+        drop(span);
+
+        let span = capture.span("process orders").unwrap();
+        assert_eq!(span.span_kind, SpanKind::Consumer);
+        assert_eq!(
+            span.span_context.trace_id(),
+            TraceId::from_hex("0af7651916cd43dd8448eb211c80319c").unwrap()
+        );
+        let producer = SpanId::from_hex("b7ad6b7169203331").unwrap();
+        assert_eq!(span.parent_span_id, producer);
+        assert_eq!(span.links.links[0].span_context.span_id(), producer);
+        let attribute = |key: &str| {
+            span.attributes
+                .iter()
+                .find(|kv| kv.key.as_str() == key)
+                .map(|kv| kv.value.to_string())
+        };
+        assert_eq!(attribute("messaging.system").as_deref(), Some("kafka"));
+        assert_eq!(
+            attribute("messaging.destination.name").as_deref(),
+            Some("orders")
+        );
+        assert_eq!(
+            attribute("messaging.operation.type").as_deref(),
+            Some("process")
+        );
+    }
+}
@@ -0,0 +1,310 @@
+//! Metric views configured from the settings.
+//!
+//! Views change how the SDK aggregates the measurements of an instrument, ie: to use
+//! histogram bucket boundaries that suit the latencies of the service, or to keep
+//! high-cardinality attributes such as user ids from reaching the backend. The same view
+//! prefixes application metrics with the service name when that is enabled.
+
+use doku::Document;
+use opentelemetry::Key;
+use opentelemetry_sdk::metrics::{Aggregation, Instrument, InstrumentKind, Stream, StreamBuilder};
+use serde::{Deserialize, Serialize};
+
+use super::{Error, InvalidMetricViewSnafu, BYRE_METER};
+
+/// Changes how the measurements of matching instruments are aggregated.
+///
+/// # Example
+///
+/// ```toml
+/// [[telemetry.metric.views]]
+/// instrument = "span.duration"
+/// buckets = [0.0001, 0.0005, 0.001, 0.005, 0.01, 0.05]
+///
+/// [[telemetry.metric.views]]
+/// instrument = "http.*"
+/// allowed_attributes = ["http.method", "http.status_code"]
+/// cardinality_limit = 500
+/// ```
+#[derive(Clone, Debug, Default, Serialize, Deserialize, Document)]
+pub struct MetricView {
+    /// Name of the instrument to apply the view to. A trailing `*` matches all instruments starting with the prefix.
+    #[doku(example = "span.duration")]
+    pub instrument: String,
+
+    /// Explicit bucket boundaries for histograms, in the unit of the instrument. Must be increasing.
+    #[doku(example = "0.0001")]
+    #[serde(default)]
+    pub buckets: Option<Vec<f64>>,
+
+    /// Attribute keys to keep, all other attributes are dropped. Omit to keep every attribute.
+    #[doku(example = "http.method")]
+    #[serde(default)]
+    pub allowed_attributes: Option<Vec<String>>,
+
+    /// Maximum number of attribute sets per instrument, further sets are folded into an overflow set.
+    #[doku(example = "2000")]
+    #[serde(default)]
+    pub cardinality_limit: Option<usize>,
+}
+
+// Only the OTLP metric exporter registers the views
+#[cfg_attr(not(feature = "otlp"), allow(dead_code))]
+impl MetricView {
+    fn matches(&self, instrument_name: &str) -> bool {
+        match self.instrument.strip_suffix('*') {
+            Some(prefix) => instrument_name.starts_with(prefix),
+            None => instrument_name == self.instrument,
+        }
+    }
+
+    fn apply(&self, mut builder: StreamBuilder, kind: InstrumentKind) -> StreamBuilder {
+        if let (Some(boundaries), InstrumentKind::Histogram) = (&self.buckets, kind) {
+            builder = builder.with_aggregation(Aggregation::ExplicitBucketHistogram {
+                boundaries: boundaries.clone(),
+                record_min_max: true,
+            });
+        }
+
+        if let Some(keys) = &self.allowed_attributes {
+            builder = builder.with_allowed_attribute_keys(keys.iter().cloned().map(Key::from));
+        }
+
+        if let Some(limit) = self.cardinality_limit {
+            builder = builder.with_cardinality_limit(limit);
+        }
+
+        builder
+    }
+
+    fn validate(&self) -> Result<(), Error> {
+        let invalid = |reason: &str| {
+            InvalidMetricViewSnafu {
+                instrument: self.instrument.clone(),
+                reason,
+            }
+            .fail()
+        };
+
+        if self.instrument.is_empty() {
+            return invalid("the instrument name is empty");
+        }
+
+        if let Some(boundaries) = &self.buckets {
+            if boundaries.iter().any(|boundary| !boundary.is_finite()) {
+                return invalid("bucket boundaries must be finite");
+            }
+            if boundaries.windows(2).any(|pair| pair[0] >= pair[1]) {
+                return invalid("bucket boundaries must be increasing");
+            }
+        }
+
+        if self.cardinality_limit == Some(0) {
+            return invalid("the cardinality limit must be greater than 0");
+        }
+
+        Ok(())
+    }
+}
+
+/// Check that all views can be applied by the SDK.
+pub(super) fn validate_views(views: &[MetricView]) -> Result<(), Error> {
+    views.iter().try_for_each(MetricView::validate)
+}
+
+/// Combine the views and the service name prefix into a single SDK view, the first matching
+/// view is used.
+///
+/// A single view is registered so that an instrument matching several views still produces
+/// only one stream. Instruments of the byre meter are never prefixed.
+#[cfg_attr(not(feature = "otlp"), allow(dead_code))]
+pub(super) fn combined_view(
+    views: &[MetricView],
+    prefix: Option<String>,
+) -> Option<impl Fn(&Instrument) -> Option<Stream> + Send + Sync + 'static + use<>> {
+    let prefix = prefix.filter(|prefix| !prefix.is_empty());
+    if views.is_empty() && prefix.is_none() {
+        return None;
+    }
+
+    let views = views.to_vec();
+    Some(move |instrument: &Instrument| {
+        let view = views.iter().find(|view| view.matches(instrument.name()));
+        let prefix = prefix
+            .as_ref()
+            .filter(|_| instrument.scope().name() != BYRE_METER);
+        if view.is_none() && prefix.is_none() {
+            return None;
+        }
+
+        let mut builder = Stream::builder();
+        if let Some(prefix) = prefix {
+            builder = builder.with_name(format!("{prefix}.{}", instrument.name()));
+        }
+        if let Some(view) = view {
+            builder = view.apply(builder, instrument.kind());
+        }
+        builder.build().ok()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::telemetry::testing::{has_metric, metric_value};
+    use opentelemetry::metrics::MeterProvider as _;
+    use opentelemetry::KeyValue;
+    use opentelemetry_sdk::metrics::data::{AggregatedMetrics, MetricData};
+    use opentelemetry_sdk::metrics::{InMemoryMetricExporter, PeriodicReader, SdkMeterProvider};
+
+    fn meter_provider_with_view(view: MetricView) -> (SdkMeterProvider, InMemoryMetricExporter) {
+        let exporter = InMemoryMetricExporter::default();
+        let provider = SdkMeterProvider::builder()
+            .with_reader(PeriodicReader::builder(exporter.clone()).build())
+            .with_view(combined_view(&[view], None).unwrap())
+            .build();
+        (provider, exporter)
+    }
+
+    fn view(instrument: &str, buckets: &[f64]) -> MetricView {
+        MetricView {
+            instrument: instrument.to_string(),
+            buckets: Some(buckets.to_vec()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_combined_view_sets_histogram_buckets() {
+        let (provider, exporter) = meter_provider_with_view(view("request.*", &[0.001, 0.01]));
+
+        let meter = provider.meter("test");
+        meter
+            .f64_histogram("request.duration")
+            .build()
+            .record(0.005, &[]);
+        meter
+            .f64_histogram("other.duration")
+            .build()
+            .record(0.005, &[]);
+        provider.force_flush().unwrap();
+
+        let metrics = exporter.get_finished_metrics().unwrap();
+        let bounds = |name: &str| {
+            metrics
+                .iter()
+                .flat_map(|rm| rm.scope_metrics())
+                .flat_map(|sm| sm.metrics())
+                .find(|m| m.name() == name)
+                .and_then(|m| match m.data() {
+                    AggregatedMetrics::F64(MetricData::Histogram(h)) => h
+                        .data_points()
+                        .next()
+                        .map(|p| p.bounds().collect::<Vec<_>>()),
+                    _ => None,
+                })
+                .unwrap()
+        };
+
+        assert_eq!(bounds("request.duration"), vec![0.001, 0.01]);
+        assert_ne!(bounds("other.duration"), vec![0.001, 0.01]);
+    }
+
+    #[test]
+    fn test_validate_views_rejects_bad_boundaries() {
+        assert!(validate_views(&[view("latency", &[0.001, 0.01, 0.1])]).is_ok());
+        assert!(validate_views(&[view("latency", &[0.01, 0.001])]).is_err());
+        assert!(validate_views(&[view("latency", &[0.01, f64::NAN])]).is_err());
+        assert!(validate_views(&[view("", &[0.01])]).is_err());
+
+        let unlimited = MetricView {
+            instrument: "latency".to_string(),
+            cardinality_limit: Some(0),
+            ..Default::default()
+        };
+        assert!(validate_views(&[unlimited]).is_err());
+    }
+
+    #[test]
+    fn test_combined_view_drops_attributes_not_allowed() {
+        let (provider, exporter) = meter_provider_with_view(MetricView {
+            instrument: "requests".to_string(),
+            allowed_attributes: Some(vec!["route".to_string()]),
+            ..Default::default()
+        });
+
+        let counter = provider.meter("test").u64_counter("requests").build();
+        for user in ["alice", "bob"] {
+            counter.add(
+                1,
+                &[
+                    KeyValue::new("route", "/users"),
+                    KeyValue::new("user", user),
+                ],
+            );
+        }
+
+        assert_eq!(
+            metric_value(&provider, &exporter, "requests", &[("route", "/users")]),
+            2.0
+        );
+        assert_eq!(
+            metric_value(&provider, &exporter, "requests", &[("user", "alice")]),
+            0.0
+        );
+    }
+
+    #[test]
+    fn test_combined_view_limits_cardinality() {
+        let (provider, exporter) = meter_provider_with_view(MetricView {
+            instrument: "requests".to_string(),
+            cardinality_limit: Some(2),
+            ..Default::default()
+        });
+
+        let counter = provider.meter("test").u64_counter("requests").build();
+        for user in ["alice", "bob", "carol", "dave"] {
+            counter.add(1, &[KeyValue::new("user", user)]);
+        }
+
+        assert_eq!(
+            metric_value(
+                &provider,
+                &exporter,
+                "requests",
+                &[("otel.metric.overflow", "true")]
+            ),
+            2.0
+        );
+    }
+
+    #[test]
+    fn test_combined_view_without_views_is_none() {
+        assert!(combined_view(&[], None).is_none());
+        assert!(combined_view(&[], Some(String::new())).is_none());
+    }
+
+    #[test]
+    fn test_combined_view_prefixes_application_metrics() {
+        let exporter = InMemoryMetricExporter::default();
+        let provider = SdkMeterProvider::builder()
+            .with_reader(PeriodicReader::builder(exporter.clone()).build())
+            .with_view(combined_view(&[], Some("my_service".to_string())).unwrap())
+            .build();
+
+        provider
+            .meter("app")
+            .u64_counter("requests")
+            .build()
+            .add(1, &[]);
+        provider
+            .meter(BYRE_METER)
+            .u64_counter("span.calls")
+            .build()
+            .add(1, &[]);
+
+        assert!(has_metric(&provider, &exporter, "my_service.requests"));
+        assert!(!has_metric(&provider, &exporter, "requests"));
+        assert!(has_metric(&provider, &exporter, "span.calls"));
+    }
+}
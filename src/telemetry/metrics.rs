@@ -0,0 +1,419 @@
+//! # Metric Helpers
+//!
+//! A small registry of instruments bound to the global meter provider and the service name,
+//! so applications don't need a struct holding every instrument they record.
+//!
+//! Instruments are created on first use and cached by name, later calls with the same name
+//! return the same instrument. [`init`](super::init) binds the registry to the service, use the
+//! helpers after it has been called: instruments created earlier are replaced once it runs.
+//!
+//! ```
+//! use opentelemetry::KeyValue;
+//!
+//! byre::counter!("requests", "Number of requests processed")
+//!     .add(1, &[KeyValue::new("status", "ok")]);
+//! byre::up_down_counter!("active_operations").add(1, &[]);
+//! byre::histogram!("operation.duration", "Duration of operations", "s").record(0.25, &[]);
+//! byre::gauge!("queue.depth").record(12.0, &[]);
+//! ```
+
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::sync::{LazyLock, RwLock};
+use std::time::Instant;
+
+use opentelemetry::metrics::{Counter, Gauge, Histogram, Meter, UpDownCounter};
+use opentelemetry::{global, InstrumentationScope};
+
+use crate::ServiceInfo;
+
+/// Scope name used for instruments created before [`init`](super::init).
+const UNBOUND_SCOPE: &str = "unknown_service";
+
+static REGISTRY: LazyLock<RwLock<Registry>> =
+    LazyLock::new(|| RwLock::new(Registry::new(global::meter(UNBOUND_SCOPE))));
+
+/// The instruments byre records itself, ie: the durations of its clients' requests. Their scope
+/// is [`BYRE_METER`](super::BYRE_METER), so they keep their semantic convention names when the
+/// service's metrics are prefixed.
+static BYRE_REGISTRY: LazyLock<RwLock<Registry>> =
+    LazyLock::new(|| RwLock::new(Registry::new(global::meter(super::BYRE_METER))));
+
+/// Instruments keyed by their type and name.
+struct Registry {
+    meter: Meter,
+    instruments: HashMap<(TypeId, &'static str), Box<dyn Any + Send + Sync>>,
+}
+
+impl Registry {
+    fn new(meter: Meter) -> Self {
+        Self {
+            meter,
+            instruments: HashMap::new(),
+        }
+    }
+
+    fn get<T: Clone + 'static>(&self, name: &'static str) -> Option<T> {
+        self.instruments
+            .get(&(TypeId::of::<T>(), name))
+            .and_then(|instrument| instrument.downcast_ref::<T>())
+            .cloned()
+    }
+
+    fn get_or_build<T: Clone + Send + Sync + 'static>(
+        &mut self,
+        name: &'static str,
+        build: impl FnOnce(&Meter) -> T,
+    ) -> T {
+        if let Some(instrument) = self.get(name) {
+            return instrument;
+        }
+        let instrument = build(&self.meter);
+        self.instruments
+            .insert((TypeId::of::<T>(), name), Box::new(instrument.clone()));
+        instrument
+    }
+}
+
+/// Bind the registry to the service and the current global meter provider.
+pub(crate) fn bind(service_info: &ServiceInfo) {
+    let scope = InstrumentationScope::builder(service_info.name_in_metrics.clone())
+        .with_version(service_info.version)
+        .build();
+    let meter = global::meter_provider().meter_with_scope(scope);
+
+    let mut registry = REGISTRY.write().unwrap_or_else(|err| err.into_inner());
+    *registry = Registry::new(meter);
+    let mut byre_registry = BYRE_REGISTRY.write().unwrap_or_else(|err| err.into_inner());
+    *byre_registry = Registry::new(global::meter(super::BYRE_METER));
+}
+
+fn instrument<T: Clone + Send + Sync + 'static>(
+    name: &'static str,
+    build: impl FnOnce(&Meter) -> T,
+) -> T {
+    instrument_in(&REGISTRY, name, build)
+}
+
+fn instrument_in<T: Clone + Send + Sync + 'static>(
+    registry: &RwLock<Registry>,
+    name: &'static str,
+    build: impl FnOnce(&Meter) -> T,
+) -> T {
+    if let Some(instrument) = registry
+        .read()
+        .unwrap_or_else(|err| err.into_inner())
+        .get(name)
+    {
+        return instrument;
+    }
+    registry
+        .write()
+        .unwrap_or_else(|err| err.into_inner())
+        .get_or_build(name, build)
+}
+
+/// A counter of `u64` values that byre records itself, not prefixed with the service name.
+pub(crate) fn byre_counter(
+    name: &'static str,
+    description: &'static str,
+    unit: &'static str,
+) -> Counter<u64> {
+    instrument_in(&BYRE_REGISTRY, name, |meter| {
+        meter
+            .u64_counter(name)
+            .with_description(description)
+            .with_unit(unit)
+            .build()
+    })
+}
+
+/// A histogram of `f64` values that byre records itself, not prefixed with the service name.
+#[cfg(any(
+    feature = "http",
+    feature = "grpc-client",
+    feature = "sqlx",
+    feature = "redis"
+))]
+pub(crate) fn byre_histogram(
+    name: &'static str,
+    description: &'static str,
+    unit: &'static str,
+) -> Histogram<f64> {
+    instrument_in(&BYRE_REGISTRY, name, |meter| {
+        meter
+            .f64_histogram(name)
+            .with_description(description)
+            .with_unit(unit)
+            .build()
+    })
+}
+
+/// The meter the helpers create their instruments with, named after the service.
+pub fn meter() -> Meter {
+    REGISTRY
+        .read()
+        .unwrap_or_else(|err| err.into_inner())
+        .meter
+        .clone()
+}
+
+/// A counter of `u64` values, see [`counter!`](crate::counter).
+pub fn counter(name: &'static str, description: &'static str, unit: &'static str) -> Counter<u64> {
+    instrument(name, |meter| {
+        meter
+            .u64_counter(name)
+            .with_description(description)
+            .with_unit(unit)
+            .build()
+    })
+}
+
+/// A counter of `i64` values that can go down, see [`up_down_counter!`](crate::up_down_counter).
+pub fn up_down_counter(
+    name: &'static str,
+    description: &'static str,
+    unit: &'static str,
+) -> UpDownCounter<i64> {
+    instrument(name, |meter| {
+        meter
+            .i64_up_down_counter(name)
+            .with_description(description)
+            .with_unit(unit)
+            .build()
+    })
+}
+
+/// A histogram of `f64` values, see [`histogram!`](crate::histogram).
+pub fn histogram(
+    name: &'static str,
+    description: &'static str,
+    unit: &'static str,
+) -> Histogram<f64> {
+    instrument(name, |meter| {
+        meter
+            .f64_histogram(name)
+            .with_description(description)
+            .with_unit(unit)
+            .build()
+    })
+}
+
+/// A gauge of `f64` values, see [`gauge!`](crate::gauge).
+pub fn gauge(name: &'static str, description: &'static str, unit: &'static str) -> Gauge<f64> {
+    instrument(name, |meter| {
+        meter
+            .f64_gauge(name)
+            .with_description(description)
+            .with_unit(unit)
+            .build()
+    })
+}
+
+/// Records a call, its duration and whether it failed when dropped.
+///
+/// This is what [`timed`](crate::timed) expands to, it can also be used directly to time a block
+/// of code. A call also counts as failed when the timer is dropped while panicking.
+///
+/// ```
+/// use byre::telemetry::metrics::{counter, histogram, CallTimer};
+///
+/// let mut timer = CallTimer::new(
+///     counter("load_user.calls", "", ""),
+///     counter("load_user.errors", "", ""),
+///     histogram("load_user.duration", "", "s"),
+/// );
+/// let result: Result<(), std::io::Error> = Ok(());
+/// timer.record_result(&result);
+/// ```
+pub struct CallTimer {
+    start: Instant,
+    failed: bool,
+    calls: Counter<u64>,
+    errors: Counter<u64>,
+    duration: Histogram<f64>,
+}
+
+impl std::fmt::Debug for CallTimer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CallTimer")
+            .field("start", &self.start)
+            .field("failed", &self.failed)
+            .finish_non_exhaustive()
+    }
+}
+
+impl CallTimer {
+    /// Start timing a call, the duration is recorded in seconds.
+    pub fn new(calls: Counter<u64>, errors: Counter<u64>, duration: Histogram<f64>) -> Self {
+        Self {
+            start: Instant::now(),
+            failed: false,
+            calls,
+            errors,
+            duration,
+        }
+    }
+
+    /// Mark the call as failed if `result` is an error.
+    pub fn record_result<T, E>(&mut self, result: &Result<T, E>) {
+        self.failed = result.is_err();
+    }
+}
+
+impl Drop for CallTimer {
+    fn drop(&mut self) {
+        self.calls.add(1, &[]);
+        if self.failed || std::thread::panicking() {
+            self.errors.add(1, &[]);
+        }
+        self.duration
+            .record(self.start.elapsed().as_secs_f64(), &[]);
+    }
+}
+
+/// Returns the `u64` counter called `name` from the metric registry, creating it on first use.
+///
+/// The description and unit are optional and only used when the counter is created.
+///
+/// ```
+/// byre::counter!("requests").add(1, &[]);
+/// byre::counter!("bytes.sent", "Bytes written to clients", "By").add(512, &[]);
+/// ```
+#[macro_export]
+macro_rules! counter {
+    ($name:literal) => {
+        $crate::counter!($name, "")
+    };
+    ($name:literal, $description:literal) => {
+        $crate::counter!($name, $description, "")
+    };
+    ($name:literal, $description:literal, $unit:literal) => {
+        $crate::telemetry::metrics::counter($name, $description, $unit)
+    };
+}
+
+/// Returns the `i64` up/down counter called `name` from the metric registry, creating it on
+/// first use.
+///
+/// The description and unit are optional and only used when the counter is created.
+///
+/// ```
+/// let active = byre::up_down_counter!("connections.active");
+/// active.add(1, &[]);
+/// active.add(-1, &[]);
+/// ```
+#[macro_export]
+macro_rules! up_down_counter {
+    ($name:literal) => {
+        $crate::up_down_counter!($name, "")
+    };
+    ($name:literal, $description:literal) => {
+        $crate::up_down_counter!($name, $description, "")
+    };
+    ($name:literal, $description:literal, $unit:literal) => {
+        $crate::telemetry::metrics::up_down_counter($name, $description, $unit)
+    };
+}
+
+/// Returns the `f64` histogram called `name` from the metric registry, creating it on first use.
+///
+/// The description and unit are optional and only used when the histogram is created.
+///
+/// ```
+/// byre::histogram!("request.duration", "Time to handle a request", "s").record(0.012, &[]);
+/// ```
+#[macro_export]
+macro_rules! histogram {
+    ($name:literal) => {
+        $crate::histogram!($name, "")
+    };
+    ($name:literal, $description:literal) => {
+        $crate::histogram!($name, $description, "")
+    };
+    ($name:literal, $description:literal, $unit:literal) => {
+        $crate::telemetry::metrics::histogram($name, $description, $unit)
+    };
+}
+
+/// Returns the `f64` gauge called `name` from the metric registry, creating it on first use.
+///
+/// The description and unit are optional and only used when the gauge is created.
+///
+/// ```
+/// byre::gauge!("cache.hit_ratio").record(0.93, &[]);
+/// ```
+#[macro_export]
+macro_rules! gauge {
+    ($name:literal) => {
+        $crate::gauge!($name, "")
+    };
+    ($name:literal, $description:literal) => {
+        $crate::gauge!($name, $description, "")
+    };
+    ($name:literal, $description:literal, $unit:literal) => {
+        $crate::telemetry::metrics::gauge($name, $description, $unit)
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::telemetry::testing::{meter_provider, metric_value};
+    use opentelemetry::metrics::MeterProvider as _;
+
+    #[test]
+    fn test_registry_reuses_instruments_by_name() {
+        let (provider, exporter) = meter_provider();
+        let mut registry = Registry::new(provider.meter("test"));
+
+        let mut builds = 0;
+        for _ in 0..2 {
+            registry
+                .get_or_build("requests", |meter| {
+                    builds += 1;
+                    meter.u64_counter("requests").build()
+                })
+                .add(1, &[]);
+        }
+
+        assert_eq!(builds, 1);
+        assert_eq!(metric_value(&provider, &exporter, "requests", &[]), 2.0);
+    }
+
+    #[test]
+    fn test_registry_keys_instruments_by_type() {
+        let (provider, _exporter) = meter_provider();
+        let mut registry = Registry::new(provider.meter("test"));
+
+        registry.get_or_build("shared", |meter| meter.u64_counter("shared").build());
+
+        assert!(registry.get::<Counter<u64>>("shared").is_some());
+        assert!(registry.get::<Histogram<f64>>("shared").is_none());
+    }
+
+    #[test]
+    fn test_call_timer_records_calls_errors_and_duration() {
+        let (provider, exporter) = meter_provider();
+        let meter = provider.meter("test");
+        let timer = || {
+            CallTimer::new(
+                meter.u64_counter("f.calls").build(),
+                meter.u64_counter("f.errors").build(),
+                meter.f64_histogram("f.duration").build(),
+            )
+        };
+
+        drop(timer());
+        timer().record_result(&Err::<(), _>("failed"));
+        timer().record_result(&Ok::<_, ()>(()));
+        let _ = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let _timer = timer();
+            panic!("failed");
+        }));
+
+        assert_eq!(metric_value(&provider, &exporter, "f.calls", &[]), 4.0);
+        assert_eq!(metric_value(&provider, &exporter, "f.errors", &[]), 2.0);
+        assert_eq!(metric_value(&provider, &exporter, "f.duration", &[]), 4.0);
+    }
+}
@@ -0,0 +1,170 @@
+//! On-demand snapshots of the exported metrics.
+//!
+//! Only available with the `admin` feature, which serves them at `GET /metrics`.
+
+use std::fmt::Write as _;
+use std::sync::{Arc, Weak};
+use std::time::Duration;
+
+use opentelemetry::KeyValue;
+use opentelemetry_sdk::error::OTelSdkResult;
+use opentelemetry_sdk::metrics::data::{AggregatedMetrics, MetricData, ResourceMetrics};
+use opentelemetry_sdk::metrics::reader::MetricReader;
+use opentelemetry_sdk::metrics::{InstrumentKind, ManualReader, Pipeline, Temporality};
+
+/// Reads the current value of every metric of the meter provider when asked to.
+///
+/// The values are cumulative since the process started, whatever temporality the exporter uses.
+#[derive(Clone, Debug, Default)]
+pub struct MetricsSnapshot {
+    reader: Arc<ManualReader>,
+}
+
+impl MetricsSnapshot {
+    /// The reader to register with the meter provider, it shares its state with the snapshot.
+    pub(crate) fn reader(&self) -> SnapshotReader {
+        SnapshotReader(self.reader.clone())
+    }
+
+    /// Collect every metric and render one `name{attributes} value` line per data point.
+    ///
+    /// Histograms are rendered as their `_count` and `_sum`.
+    ///
+    /// # Errors
+    ///
+    /// - `OTelSdkError` if the meter provider was shut down.
+    pub fn render(&self) -> Result<String, opentelemetry_sdk::error::OTelSdkError> {
+        let mut metrics = ResourceMetrics::default();
+        self.reader.collect(&mut metrics)?;
+
+        let mut out = String::new();
+        for scope in metrics.scope_metrics() {
+            for metric in scope.metrics() {
+                let name = metric.name();
+                match metric.data() {
+                    AggregatedMetrics::F64(data) => render_data(&mut out, name, data),
+                    AggregatedMetrics::U64(data) => render_data(&mut out, name, data),
+                    AggregatedMetrics::I64(data) => render_data(&mut out, name, data),
+                }
+            }
+        }
+        Ok(out)
+    }
+}
+
+fn render_data<T: Copy + std::fmt::Display>(out: &mut String, name: &str, data: &MetricData<T>) {
+    match data {
+        MetricData::Gauge(gauge) => {
+            for point in gauge.data_points() {
+                render_line(out, name, point.attributes(), point.value());
+            }
+        }
+        MetricData::Sum(sum) => {
+            for point in sum.data_points() {
+                render_line(out, name, point.attributes(), point.value());
+            }
+        }
+        MetricData::Histogram(histogram) => {
+            for point in histogram.data_points() {
+                render_line(
+                    out,
+                    &format!("{name}_count"),
+                    point.attributes(),
+                    point.count(),
+                );
+                render_line(out, &format!("{name}_sum"), point.attributes(), point.sum());
+            }
+        }
+        MetricData::ExponentialHistogram(histogram) => {
+            for point in histogram.data_points() {
+                render_line(
+                    out,
+                    &format!("{name}_count"),
+                    point.attributes(),
+                    point.count(),
+                );
+                render_line(out, &format!("{name}_sum"), point.attributes(), point.sum());
+            }
+        }
+    }
+}
+
+fn render_line<'a>(
+    out: &mut String,
+    name: &str,
+    attributes: impl Iterator<Item = &'a KeyValue>,
+    value: impl std::fmt::Display,
+) {
+    let attributes: Vec<_> = attributes
+        .map(|kv| format!("{}=\"{}\"", kv.key, kv.value.as_str().replace('"', "\\\"")))
+        .collect();
+    // Writing to a String cannot fail
+    let _ = if attributes.is_empty() {
+        writeln!(out, "{name} {value}")
+    } else {
+        writeln!(out, "{name}{{{}}} {value}", attributes.join(","))
+    };
+}
+
+/// Registers the shared [`ManualReader`] of a [`MetricsSnapshot`] with a meter provider.
+#[derive(Debug)]
+pub(crate) struct SnapshotReader(Arc<ManualReader>);
+
+impl MetricReader for SnapshotReader {
+    fn register_pipeline(&self, pipeline: Weak<Pipeline>) {
+        self.0.register_pipeline(pipeline);
+    }
+
+    fn collect(&self, rm: &mut ResourceMetrics) -> OTelSdkResult {
+        self.0.collect(rm)
+    }
+
+    fn force_flush(&self) -> OTelSdkResult {
+        self.0.force_flush()
+    }
+
+    fn shutdown_with_timeout(&self, timeout: Duration) -> OTelSdkResult {
+        self.0.shutdown_with_timeout(timeout)
+    }
+
+    fn temporality(&self, kind: InstrumentKind) -> Temporality {
+        self.0.temporality(kind)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opentelemetry::metrics::MeterProvider as _;
+    use opentelemetry_sdk::metrics::SdkMeterProvider;
+
+    #[test]
+    fn test_snapshot_renders_every_data_point() {
+        let snapshot = MetricsSnapshot::default();
+        let provider = SdkMeterProvider::builder()
+            .with_reader(snapshot.reader())
+            .build();
+        let meter = provider.meter("test");
+
+        let requests = meter.u64_counter("requests").build();
+        requests.add(2, &[KeyValue::new("route", "/users")]);
+        requests.add(1, &[KeyValue::new("route", "/users")]);
+        meter.f64_histogram("duration").build().record(0.5, &[]);
+
+        let rendered = snapshot.render().unwrap();
+        assert!(
+            rendered.contains("requests{route=\"/users\"} 3\n"),
+            "{rendered}"
+        );
+        assert!(rendered.contains("duration_count 1\n"), "{rendered}");
+        assert!(rendered.contains("duration_sum 0.5\n"), "{rendered}");
+
+        // The values are cumulative
+        requests.add(1, &[KeyValue::new("route", "/users")]);
+        let rendered = snapshot.render().unwrap();
+        assert!(
+            rendered.contains("requests{route=\"/users\"} 4\n"),
+            "{rendered}"
+        );
+    }
+}
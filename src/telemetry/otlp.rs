@@ -0,0 +1,275 @@
+//! The OTLP exporters of traces, metrics and logs, requires the `otlp` feature.
+//!
+//! Each signal gets a provider when it has an endpoint, see [`ExportConfig::endpoint`]. The
+//! exporters send over gRPC, to `http(s)://` endpoints or to Unix domain sockets.
+//!
+//! The exporters are registered with the [`ExportHandle`] of the providers, which replaces them
+//! when the endpoints change, see [`ExportHandle::reload`].
+
+use std::sync::OnceLock;
+
+use opentelemetry_appender_tracing::layer::OpenTelemetryTracingBridge;
+use opentelemetry_otlp::{
+    ExporterBuildError, LogExporter, MetricExporter, SpanExporter, WithExportConfig,
+    WithTonicConfig, OTEL_EXPORTER_OTLP_TIMEOUT_DEFAULT,
+};
+use opentelemetry_sdk::logs::{LogExporter as _, SdkLoggerProvider};
+use opentelemetry_sdk::metrics::{MeterProviderBuilder, PeriodicReader, SdkMeterProvider};
+use opentelemetry_sdk::trace::{SdkTracerProvider, SpanExporter as _};
+use snafu::ResultExt as _;
+
+use super::export_handle::Replacer;
+use super::vendor::ExportConfig;
+use super::{
+    error_backtrace, metric_views, request_fields, request_id, Compression, Error, ExportHandle,
+    InitLogSnafu, InitMetricSnafu, InitTraceSnafu, LogSettings, MetricSettings, OtelLogLayer,
+    Provider, TelemetrySettings, TraceSettings,
+};
+use crate::ServiceInfo;
+
+/// The exporters of the providers, registered when the providers are built.
+#[derive(Debug, Default)]
+pub(crate) struct Exporters {
+    tracer: OnceLock<Replacer<SpanExporter>>,
+    logger: OnceLock<Replacer<LogExporter>>,
+    meter: OnceLock<Replacer<MetricExporter>>,
+}
+
+impl ExportHandle {
+    /// Point the exporters at the endpoints of `settings`, ie: after the `[telemetry]` section of
+    /// the config changed. Requires the `otlp` feature.
+    ///
+    /// The exporters of traces, logs and metrics are rebuilt with the endpoints, vendor preset
+    /// and compression of `settings`, then replace the current ones: the next batch is sent to
+    /// the new endpoint, and the providers, the subscriber and the instruments are kept. Only
+    /// the signals that are exported are reloaded; a signal that had no endpoint when telemetry
+    /// was initialized, or that has none in `settings`, needs a restart.
+    ///
+    /// # Errors
+    ///
+    /// - `InitLog`, `InitTrace` or `InitMetric` if an exporter cannot be built, ie: the endpoint
+    ///   is not a valid URI. No exporter is replaced then.
+    /// - `InvalidExportHeader` if a vendor preset setting cannot be sent as an export header.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let service = byre::ServiceInfo::default();
+    /// let mut settings = byre::telemetry::TelemetrySettings::default();
+    /// let telemetry = byre::telemetry::init(&service, &settings)?;
+    /// // ... the collector moves ...
+    /// settings.trace.endpoint = Some("http://collector.internal:4317".to_string());
+    /// telemetry.exports().reload(&service, &settings)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn reload(
+        &self,
+        service_info: &ServiceInfo,
+        settings: &TelemetrySettings,
+    ) -> Result<(), Error> {
+        let export = ExportConfig::from_settings(service_info, settings)?;
+        let exporters = self.exporters();
+        let resource = export.resource();
+
+        // Every exporter is built before any is replaced, a failure leaves them all as they are
+        let tracer = match (
+            exporters.tracer.get(),
+            export.endpoint(&settings.trace.endpoint),
+        ) {
+            (Some(replacer), Some(endpoint)) => {
+                let mut exporter = span_exporter(endpoint, &export)?;
+                exporter.set_resource(&resource);
+                Some((replacer, exporter))
+            }
+            _ => None,
+        };
+        let logger = match (
+            exporters.logger.get(),
+            export.endpoint(&settings.log.endpoint),
+        ) {
+            (Some(replacer), Some(endpoint)) => {
+                let mut exporter = log_exporter(endpoint, &export)?;
+                exporter.set_resource(&resource);
+                Some((replacer, exporter))
+            }
+            _ => None,
+        };
+        let meter = match (
+            exporters.meter.get(),
+            export.endpoint(&settings.metric.endpoint),
+        ) {
+            (Some(replacer), Some(endpoint)) => {
+                Some((replacer, metric_exporter(endpoint, &export)?))
+            }
+            _ => None,
+        };
+
+        if let Some((replacer, exporter)) = tracer {
+            replacer.replace(exporter);
+        }
+        if let Some((replacer, exporter)) = logger {
+            replacer.replace(exporter);
+        }
+        if let Some((replacer, exporter)) = meter {
+            replacer.replace(exporter);
+        }
+        Ok(())
+    }
+}
+
+impl From<Compression> for opentelemetry_otlp::Compression {
+    fn from(compression: Compression) -> Self {
+        match compression {
+            Compression::Gzip => Self::Gzip,
+            Compression::Zstd => Self::Zstd,
+        }
+    }
+}
+
+pub(super) fn init_traces(
+    settings: &TraceSettings,
+    export: &ExportConfig,
+) -> Result<Option<SdkTracerProvider>, Error> {
+    match export.endpoint(&settings.endpoint) {
+        Some(endpoint) => {
+            let exporter = export
+                .exports()
+                .switched(Provider::Tracer, span_exporter(endpoint, export)?);
+            let _ = export.exports().exporters().tracer.set(exporter.replacer());
+
+            let mut builder = SdkTracerProvider::builder()
+                .with_resource(export.resource())
+                .with_span_limits(settings.limits.into());
+            if !export.static_fields().is_empty() {
+                builder = builder.with_span_processor(export.static_fields().clone());
+            }
+            Ok(Some(builder.with_batch_exporter(exporter).build()))
+        }
+        None => Ok(None),
+    }
+}
+
+pub(super) fn init_metrics(
+    service_info: &ServiceInfo,
+    setting: &MetricSettings,
+    export: &ExportConfig,
+    builder: MeterProviderBuilder,
+) -> Result<Option<SdkMeterProvider>, Error> {
+    match export.endpoint(&setting.endpoint) {
+        Some(endpoint) => {
+            let exporter = export
+                .exports()
+                .switched(Provider::Meter, metric_exporter(endpoint, export)?);
+            let _ = export.exports().exporters().meter.set(exporter.replacer());
+            let reader = PeriodicReader::builder(exporter).build();
+
+            let mut builder = builder.with_reader(reader).with_resource(export.resource());
+            let prefix = setting
+                .prefix_service_name
+                .then(|| service_info.name_in_metrics.clone());
+            if let Some(view) = metric_views::combined_view(&setting.views, prefix) {
+                builder = builder.with_view(view);
+            }
+
+            Ok(Some(builder.build()))
+        }
+
+        None => Ok(None),
+    }
+}
+
+pub(super) fn init_otel_logs(
+    settings: &LogSettings,
+    export: &ExportConfig,
+) -> Result<(Option<SdkLoggerProvider>, Option<OtelLogLayer>), Error> {
+    match export.endpoint(&settings.endpoint) {
+        None => Ok((None, None)),
+
+        Some(endpoint) => {
+            let builder = init_otel_logs_builder(export, endpoint, settings.error_backtraces)?;
+
+            let logger_provider = builder.build();
+
+            // Create a new OpenTelemetryTracingBridge using the above LoggerProvider.
+            // The filter is added by `LogSubscriberBuilder` so that it can be reloaded.
+            let otel_layer = OpenTelemetryTracingBridge::new(&logger_provider);
+
+            Ok((Some(logger_provider), Some(otel_layer)))
+        }
+    }
+}
+
+pub(super) fn init_otel_logs_builder(
+    export: &ExportConfig,
+    endpoint: &str,
+    error_backtraces: bool,
+) -> Result<opentelemetry_sdk::logs::LoggerProviderBuilder, Error> {
+    // Processors run in order, the request id and fields are added before the record is
+    // batched, and win over the static fields
+    let mut builder = SdkLoggerProvider::builder()
+        .with_log_processor(request_id::RequestIdProcessor)
+        .with_log_processor(request_fields::RequestFieldsProcessor);
+    if !export.static_fields().is_empty() {
+        builder = builder.with_log_processor(export.static_fields().clone());
+    }
+    if error_backtraces {
+        // Processors run in order, the backtrace is added before the record is batched
+        builder = builder.with_log_processor(error_backtrace::BacktraceProcessor);
+    }
+    let exporter = export
+        .exports()
+        .switched(Provider::Logger, log_exporter(endpoint, export)?);
+    let _ = export.exports().exporters().logger.set(exporter.replacer());
+    let builder = builder
+        .with_resource(export.resource())
+        .with_batch_exporter(exporter);
+    Ok(builder)
+}
+
+fn span_exporter(endpoint: &str, export: &ExportConfig) -> Result<SpanExporter, Error> {
+    tonic_exporter(SpanExporter::builder().with_tonic(), endpoint, export)
+        .and_then(|builder| builder.build())
+        .context(InitTraceSnafu)
+}
+
+fn metric_exporter(endpoint: &str, export: &ExportConfig) -> Result<MetricExporter, Error> {
+    tonic_exporter(MetricExporter::builder().with_tonic(), endpoint, export)
+        .and_then(|builder| builder.with_temporality(export.temporality()).build())
+        .context(InitMetricSnafu)
+}
+
+fn log_exporter(endpoint: &str, export: &ExportConfig) -> Result<LogExporter, Error> {
+    tonic_exporter(LogExporter::builder().with_tonic(), endpoint, export)
+        .and_then(|builder| builder.build())
+        .context(InitLogSnafu)
+}
+
+/// Points an OTLP exporter at `endpoint`, with the headers and compression of `export`.
+///
+/// `unix:///path/to.sock` endpoints connect to a Unix domain socket.
+fn tonic_exporter<B>(
+    builder: B,
+    endpoint: &str,
+    export: &ExportConfig,
+) -> Result<B, ExporterBuildError>
+where
+    B: WithExportConfig + WithTonicConfig,
+{
+    let builder = if endpoint.starts_with("unix:") {
+        // The exporter only parses URIs with an authority, tonic's endpoint understands sockets
+        let channel = tonic::transport::Endpoint::from_shared(endpoint.to_string())
+            .map_err(|err| ExporterBuildError::InvalidUri(endpoint.to_string(), err.to_string()))?
+            .timeout(OTEL_EXPORTER_OTLP_TIMEOUT_DEFAULT)
+            .connect_lazy();
+        builder.with_channel(channel)
+    } else {
+        builder.with_endpoint(endpoint)
+    };
+    let builder = builder.with_metadata(export.metadata());
+    Ok(match export.compression() {
+        Some(compression) => builder.with_compression(compression),
+        None => builder,
+    })
+}
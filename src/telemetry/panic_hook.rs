@@ -0,0 +1,106 @@
+//! Panic hook that reports panics through telemetry.
+//!
+//! The hook runs before the previously installed hook (usually the default one that prints
+//! to stderr), so the panic is recorded and flushed to the collector even if the process
+//! aborts right after.
+
+use std::any::Any;
+use std::backtrace::Backtrace;
+use std::panic::PanicHookInfo;
+
+use opentelemetry::metrics::Meter;
+
+/// Install a panic hook that records panics as error events and counts them.
+///
+/// On every panic the hook:
+///
+/// - emits an `ERROR` event with target `panic`, carrying `exception.type`, `exception.message`
+///   and `exception.stacktrace` fields
+/// - increments the `process.panics` counter
+/// - calls the previously installed panic hook
+///
+/// [`init`](super::init) installs the hook when
+/// [`TelemetrySettings::panic_hook`](super::TelemetrySettings::panic_hook) is enabled, and
+/// flushes the telemetry providers before the previous hook runs.
+///
+/// # Example
+///
+/// ```
+/// let meter = opentelemetry::global::meter("my_service");
+/// byre::telemetry::install_panic_hook(&meter);
+/// ```
+pub fn install_panic_hook(meter: &Meter) {
+    install(meter, || {});
+}
+
+/// Install the hook, calling `flush` after the panic has been recorded.
+pub(super) fn install(meter: &Meter, flush: impl Fn() + Send + Sync + 'static) {
+    let panics = meter
+        .u64_counter("process.panics")
+        .with_description("Number of panics in the process")
+        .build();
+    let previous = std::panic::take_hook();
+
+    std::panic::set_hook(Box::new(move |info: &PanicHookInfo<'_>| {
+        let thread = std::thread::current();
+        let location = info.location().map(ToString::to_string).unwrap_or_default();
+
+        tracing::error!(
+            target: "panic",
+            thread = thread.name().unwrap_or("<unnamed>"),
+            location,
+            "exception.type" = "panic",
+            "exception.message" = %panic_message(info.payload()),
+            "exception.stacktrace" = %Backtrace::force_capture(),
+            "thread panicked"
+        );
+        panics.add(1, &[]);
+        flush();
+
+        previous(info);
+    }));
+}
+
+/// The message passed to `panic!`, panics with other payloads don't have one.
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message
+    } else {
+        "Box<dyn Any>"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::telemetry::testing::{meter_provider, metric_value};
+    use opentelemetry::metrics::MeterProvider as _;
+
+    #[test]
+    fn test_panic_message_from_payload() {
+        assert_eq!(panic_message(&"static message"), "static message");
+        assert_eq!(panic_message(&"owned".to_string()), "owned");
+        assert_eq!(panic_message(&42), "Box<dyn Any>");
+    }
+
+    #[test]
+    fn test_panic_hook_counts_and_flushes() {
+        let (provider, exporter) = meter_provider();
+        let flushes = Arc::new(AtomicUsize::new(0));
+        let flushed = flushes.clone();
+        install(&provider.meter("test"), move || {
+            flushed.fetch_add(1, Ordering::SeqCst);
+        });
+
+        let _ = std::panic::catch_unwind(|| panic!("expected panic"));
+
+        // Other tests may panic while the hook is installed.
+        assert!(metric_value(&provider, &exporter, "process.panics", &[]) >= 1.0);
+        assert!(flushes.load(Ordering::SeqCst) >= 1);
+    }
+}
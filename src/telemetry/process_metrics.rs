@@ -0,0 +1,194 @@
+//! Process liveness and build metrics.
+//!
+//! Lets alerts tell whether a service is alive and exporting, and dashboards which build is
+//! deployed where, without any application code.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+use opentelemetry::metrics::Meter;
+use opentelemetry::KeyValue;
+
+use crate::container::ContainerLimits;
+use crate::ServiceInfo;
+
+/// Register observable instruments that show the process is alive and exporting metrics.
+///
+/// - `process.uptime` - time since the instruments were registered, in seconds
+/// - `process.heartbeat` - number of times metrics have been collected
+///
+/// Both are sampled when the meter provider collects metrics. An alert on `process.heartbeat`
+/// not increasing catches a service that is down as well as one that stopped exporting.
+///
+/// [`init`](super::init) calls this when metrics are exported.
+///
+/// # Example
+///
+/// ```
+/// let meter = opentelemetry::global::meter("my_service");
+/// byre::telemetry::register_process_metrics(&meter);
+/// ```
+pub fn register_process_metrics(meter: &Meter) {
+    let start = Instant::now();
+    meter
+        .f64_observable_gauge("process.uptime")
+        .with_description("Time since the process started reporting metrics")
+        .with_unit("s")
+        .with_callback(move |observer| {
+            observer.observe(start.elapsed().as_secs_f64(), &[]);
+        })
+        .build();
+
+    let heartbeats = Arc::new(AtomicU64::new(0));
+    meter
+        .u64_observable_counter("process.heartbeat")
+        .with_description("Number of times the process has collected metrics")
+        .with_callback(move |observer| {
+            let count = heartbeats.fetch_add(1, Ordering::Relaxed) + 1;
+            observer.observe(count, &[]);
+        })
+        .build();
+}
+
+/// Register the `service.build_info` gauge, which is always 1 and labelled with the build of the
+/// service.
+///
+/// The labels are `version`, `git_sha`, `git_dirty`, `build_timestamp` and `rustc_version` from
+/// the [`ServiceInfo`], the build metadata that is unknown is left out. Counting the series by `version`
+/// shows which versions are deployed across a fleet, and joining on it labels other metrics.
+///
+/// [`init`](super::init) calls this when metrics are exported.
+///
+/// # Example
+///
+/// ```
+/// let meter = opentelemetry::global::meter("my_service");
+/// byre::telemetry::register_build_info(&meter, &byre::service_info!());
+/// ```
+pub fn register_build_info(meter: &Meter, service_info: &ServiceInfo) {
+    let mut labels = vec![KeyValue::new("version", service_info.version)];
+    if !service_info.git_sha.is_empty() {
+        labels.push(KeyValue::new("git_sha", service_info.git_sha));
+        labels.push(KeyValue::new(
+            "git_dirty",
+            service_info.git_dirty.to_string(),
+        ));
+    }
+    if !service_info.build_timestamp.is_empty() {
+        labels.push(KeyValue::new(
+            "build_timestamp",
+            service_info.build_timestamp,
+        ));
+    }
+    if !service_info.rustc_version.is_empty() {
+        labels.push(KeyValue::new("rustc_version", service_info.rustc_version));
+    }
+
+    meter
+        .u64_observable_gauge("service.build_info")
+        .with_description("Build of the running service, always 1")
+        .with_callback(move |observer| observer.observe(1, &labels))
+        .build();
+}
+
+/// Register gauges of the resource limits of the container, the limits that are not set are
+/// left out.
+///
+/// - `container.cpu.limit` - CPUs of the quota, ie: `1.5`
+/// - `container.memory.limit` - memory limit in bytes
+///
+/// [`init`](super::init) calls this with [`ContainerLimits::detect`] when metrics are exported.
+///
+/// # Example
+///
+/// ```
+/// let meter = opentelemetry::global::meter("my_service");
+/// let limits = byre::container::ContainerLimits::detect();
+/// byre::telemetry::register_container_limits(&meter, &limits);
+/// ```
+pub fn register_container_limits(meter: &Meter, limits: &ContainerLimits) {
+    if let Some(cpus) = limits.cpus {
+        meter
+            .f64_observable_gauge("container.cpu.limit")
+            .with_description("CPUs the container may use, from its cgroup quota")
+            .with_unit("{cpu}")
+            .with_callback(move |observer| observer.observe(cpus, &[]))
+            .build();
+    }
+    if let Some(bytes) = limits.memory_bytes {
+        meter
+            .u64_observable_gauge("container.memory.limit")
+            .with_description("Memory the container may use, from its cgroup limit")
+            .with_unit("By")
+            .with_callback(move |observer| observer.observe(bytes, &[]))
+            .build();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::telemetry::testing::{has_metric, meter_provider, metric_value};
+    use opentelemetry::metrics::MeterProvider as _;
+
+    #[test]
+    fn test_heartbeat_counts_collections() {
+        let (provider, exporter) = meter_provider();
+        register_process_metrics(&provider.meter("test"));
+
+        assert_eq!(
+            metric_value(&provider, &exporter, "process.heartbeat", &[]),
+            1.0
+        );
+        assert_eq!(
+            metric_value(&provider, &exporter, "process.heartbeat", &[]),
+            2.0
+        );
+        assert!(has_metric(&provider, &exporter, "process.uptime"));
+    }
+
+    #[test]
+    fn test_container_limits_that_are_set_are_published() {
+        let (provider, exporter) = meter_provider();
+        let limits = ContainerLimits {
+            cpus: None,
+            memory_bytes: Some(536_870_912),
+        };
+        register_container_limits(&provider.meter("test"), &limits);
+
+        assert_eq!(
+            metric_value(&provider, &exporter, "container.memory.limit", &[]),
+            536_870_912.0
+        );
+        assert!(!has_metric(&provider, &exporter, "container.cpu.limit"));
+    }
+
+    #[test]
+    fn test_build_info_is_labelled_with_the_build() {
+        let (provider, exporter) = meter_provider();
+        let service_info = ServiceInfo {
+            version: "1.2.3",
+            git_sha: "0123abcd",
+            git_dirty: true,
+            build_timestamp: "2026-10-17T09:04:05Z",
+            ..Default::default()
+        };
+        register_build_info(&provider.meter("test"), &service_info);
+
+        assert_eq!(
+            metric_value(
+                &provider,
+                &exporter,
+                "service.build_info",
+                &[
+                    ("version", "1.2.3"),
+                    ("git_sha", "0123abcd"),
+                    ("git_dirty", "true"),
+                    ("build_timestamp", "2026-10-17T09:04:05Z")
+                ]
+            ),
+            1.0
+        );
+    }
+}
@@ -0,0 +1,155 @@
+//! Development profiling output, requires the `profiling` feature.
+//!
+//! Writes the spans of a run to a file, either as folded stacks for a flamegraph or as a
+//! `chrome://tracing` JSON trace. No collector is needed, which makes it handy for profiling
+//! async code paths on a development machine. The file is complete once the
+//! [`TelemetryProviders`](super::TelemetryProviders) are dropped.
+
+use std::fs::File;
+use std::io::BufWriter;
+
+use doku::Document;
+use serde::{Deserialize, Serialize};
+use snafu::ResultExt as _;
+
+use super::{Error, InitProfileSnafu, RegistryLayer};
+use crate::config::ExpandedPath;
+
+/// Settings for writing a profile of the run to a file.
+#[derive(Clone, Debug, Serialize, Deserialize, Document)]
+pub struct ProfileSettings {
+    /// The format of the profile, `flame` or `chrome`.
+    #[doku(as = "String", example = "chrome")]
+    pub format: ProfileFormat,
+
+    /// File the profile is written to, it is replaced if it exists.
+    #[doku(example = "trace.json")]
+    pub path: ExpandedPath,
+}
+
+/// The file format of a profile.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, Document)]
+#[serde(rename_all = "lowercase")]
+pub enum ProfileFormat {
+    /// Folded stacks, turn them into a flamegraph with `inferno-flamegraph`.
+    Flame,
+    /// JSON trace events, open them in `chrome://tracing` or <https://ui.perfetto.dev>.
+    Chrome,
+}
+
+/// Writes out the profile when dropped.
+pub(crate) enum ProfileGuard {
+    Flame(tracing_flame::FlushGuard<BufWriter<File>>),
+    Chrome(tracing_chrome::FlushGuard),
+}
+
+impl std::fmt::Debug for ProfileGuard {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Flame(_) => f.write_str("ProfileGuard::Flame"),
+            Self::Chrome(_) => f.write_str("ProfileGuard::Chrome"),
+        }
+    }
+}
+
+impl ProfileGuard {
+    /// Write out the rest of the profile and close the file.
+    pub(crate) fn finish(self) {
+        match self {
+            Self::Flame(guard) => {
+                if let Err(err) = guard.flush() {
+                    eprintln!("Error writing the flamegraph profile: {err}");
+                }
+            }
+            // The closing bracket of the JSON is written when the guard is dropped
+            Self::Chrome(guard) => drop(guard),
+        }
+    }
+}
+
+/// Create the profile file and the layer that writes to it.
+///
+/// # Errors
+///
+/// - `InitProfile` if the file cannot be created.
+pub(crate) fn layer(settings: &ProfileSettings) -> Result<(RegistryLayer, ProfileGuard), Error> {
+    let file = File::create(&settings.path).with_context(|_| InitProfileSnafu {
+        path: settings.path.clone(),
+    })?;
+
+    Ok(match settings.format {
+        ProfileFormat::Flame => {
+            // Tasks move between the runtime's worker threads, merge their stacks
+            let layer =
+                tracing_flame::FlameLayer::new(BufWriter::new(file)).with_threads_collapsed(true);
+            let guard = layer.flush_on_drop();
+            (Box::new(layer), ProfileGuard::Flame(guard))
+        }
+        ProfileFormat::Chrome => {
+            let (layer, guard) = tracing_chrome::ChromeLayerBuilder::new()
+                .writer(file)
+                .include_args(true)
+                // Async spans are entered and exited on different threads
+                .trace_style(tracing_chrome::TraceStyle::Async)
+                .build();
+            (Box::new(layer), ProfileGuard::Chrome(guard))
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing_subscriber::layer::SubscriberExt as _;
+
+    fn profile(format: ProfileFormat, path: std::path::PathBuf) -> String {
+        let (layer, guard) = layer(&ProfileSettings {
+            format,
+            path: path.clone().into(),
+        })
+        .unwrap();
+        let subscriber = tracing_subscriber::registry().with(layer);
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info_span!("outer").in_scope(|| {
+                tracing::info_span!("inner").in_scope(|| {});
+            });
+        });
+        guard.finish();
+        std::fs::read_to_string(path).unwrap()
+    }
+
+    #[test]
+    fn test_flame_profile_has_folded_stacks() {
+        let dir = tempfile::tempdir().unwrap();
+        let output = profile(ProfileFormat::Flame, dir.path().join("profile.folded"));
+
+        // The inner span is folded under the outer one
+        assert!(
+            output
+                .lines()
+                .any(|line| line.contains("::outer:") && line.contains("::inner:")),
+            "{output}"
+        );
+    }
+
+    #[test]
+    fn test_chrome_profile_is_json() {
+        let dir = tempfile::tempdir().unwrap();
+        let output = profile(ProfileFormat::Chrome, dir.path().join("trace.json"));
+
+        assert!(output.trim_start().starts_with('['), "{output}");
+        assert!(output.contains("\"name\":\"outer\""), "{output}");
+    }
+
+    #[test]
+    fn test_unwritable_path_is_an_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let settings = ProfileSettings {
+            format: ProfileFormat::Chrome,
+            path: dir.path().join("missing").join("trace.json").into(),
+        };
+
+        let result = layer(&settings);
+        assert!(matches!(result, Err(Error::InitProfile { .. })));
+    }
+}
@@ -0,0 +1,356 @@
+//! Trace context formats, selected by [`TraceSettings::propagators`](super::TraceSettings::propagators).
+//!
+//! W3C Trace Context is the default. Services behind load balancers that start traces in a
+//! format of their own add it, so they join those traces instead of starting new ones.
+
+use doku::Document;
+use opentelemetry::propagation::{
+    text_map_propagator::FieldIter, Extractor, Injector, TextMapCompositePropagator,
+    TextMapPropagator,
+};
+use opentelemetry::trace::{
+    SpanContext, SpanId, TraceContextExt as _, TraceFlags, TraceId, TraceState,
+};
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use serde::{Deserialize, Serialize};
+
+/// A trace context format, read from incoming requests and written to outgoing ones.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, Document)]
+#[serde(rename_all = "lowercase")]
+pub enum Propagator {
+    /// W3C Trace Context, the `traceparent` and `tracestate` headers.
+    TraceContext,
+    /// AWS X-Ray, the `X-Amzn-Trace-Id` header that AWS load balancers add to the requests.
+    Xray,
+    /// Google Cloud Trace, the `X-Cloud-Trace-Context` header that GCP load balancers and Cloud Run add to the
+    /// requests.
+    CloudTrace,
+}
+
+impl Propagator {
+    fn build(self) -> Box<dyn TextMapPropagator + Send + Sync> {
+        match self {
+            Propagator::TraceContext => Box::new(TraceContextPropagator::new()),
+            Propagator::Xray => Box::new(XrayPropagator::new()),
+            Propagator::CloudTrace => Box::new(CloudTracePropagator::new()),
+        }
+    }
+}
+
+/// W3C Trace Context only.
+pub(crate) fn default_propagators() -> Vec<Propagator> {
+    vec![Propagator::TraceContext]
+}
+
+/// A propagator of every format of `propagators`, extracted in order.
+pub(crate) fn composite(propagators: &[Propagator]) -> TextMapCompositePropagator {
+    TextMapCompositePropagator::new(propagators.iter().map(|p| p.build()).collect())
+}
+
+/// Whether `key` is a header of the global propagator.
+#[cfg(feature = "http")]
+pub(crate) fn is_propagated(key: &str) -> bool {
+    opentelemetry::global::get_text_map_propagator(|propagator| {
+        propagator.fields().any(|field| field == key)
+    })
+}
+
+/// Header of the AWS X-Ray trace context.
+const XRAY_HEADER: &str = "x-amzn-trace-id";
+
+/// Propagates the trace context in the `X-Amzn-Trace-Id` header, ie:
+/// `Root=1-5759e988-bd862e3fe1be46a994272793;Parent=53995c3f42cd8ad8;Sampled=1`.
+///
+/// The load balancer starting a trace only sets `Root`. Its requests continue that trace with
+/// a parent derived from the trace id, which no exported span has. A `Sampled` flag that is
+/// missing or `?` leaves the decision to the service, and the request is sampled.
+#[derive(Debug)]
+struct XrayPropagator {
+    fields: [String; 1],
+}
+
+impl XrayPropagator {
+    fn new() -> Self {
+        Self {
+            fields: [XRAY_HEADER.to_string()],
+        }
+    }
+}
+
+impl TextMapPropagator for XrayPropagator {
+    fn inject_context(&self, cx: &opentelemetry::Context, injector: &mut dyn Injector) {
+        let span = cx.span();
+        let span_context = span.span_context();
+        if !span_context.is_valid() {
+            return;
+        }
+        let trace_id = span_context.trace_id().to_string();
+        let sampled = if span_context.is_sampled() { 1 } else { 0 };
+        injector.set(
+            XRAY_HEADER,
+            format!(
+                "Root=1-{}-{};Parent={};Sampled={sampled}",
+                &trace_id[..8],
+                &trace_id[8..],
+                span_context.span_id()
+            ),
+        );
+    }
+
+    fn extract_with_context(
+        &self,
+        cx: &opentelemetry::Context,
+        extractor: &dyn Extractor,
+    ) -> opentelemetry::Context {
+        match extractor.get(XRAY_HEADER).and_then(parse_xray_header) {
+            Some(span_context) => cx.with_remote_span_context(span_context),
+            None => cx.clone(),
+        }
+    }
+
+    fn fields(&self) -> FieldIter<'_> {
+        FieldIter::new(&self.fields)
+    }
+}
+
+/// Parse an `X-Amzn-Trace-Id` header, `None` without a valid `Root`.
+fn parse_xray_header(header: &str) -> Option<SpanContext> {
+    let (mut root, mut parent, mut sampled) = (None, None, None);
+    for field in header.split(';') {
+        match field.trim().split_once('=') {
+            Some(("Root", value)) => root = Some(value),
+            Some(("Parent", value)) => parent = Some(value),
+            Some(("Sampled", value)) => sampled = Some(value),
+            // `Self`, `Lineage` and the fields added by the application
+            _ => {}
+        }
+    }
+
+    // `1-{8 hex digits of epoch seconds}-{24 random hex digits}`
+    let mut parts = root?.split('-');
+    let (version, time, random) = (parts.next()?, parts.next()?, parts.next()?);
+    if version != "1" || time.len() != 8 || random.len() != 24 || parts.next().is_some() {
+        return None;
+    }
+    let trace_id = TraceId::from_hex(&format!("{time}{random}")).ok()?;
+    let span_id = match parent {
+        Some(parent) if parent.len() == 16 => Some(SpanId::from_hex(parent).ok()?),
+        Some(_) => return None,
+        None => None,
+    };
+    remote_span_context(trace_id, span_id, sampled != Some("0"))
+}
+
+/// Header of the Google Cloud Trace context.
+const CLOUD_TRACE_HEADER: &str = "x-cloud-trace-context";
+
+/// Propagates the trace context in the `X-Cloud-Trace-Context` header, ie:
+/// `105445aa7843bc8bf206b12000100000/1;o=1`, with the span id in decimal.
+///
+/// Like the X-Ray header, a header without a span id continues the trace with a parent derived
+/// from the trace id, and one without `o=0` is sampled.
+#[derive(Debug)]
+struct CloudTracePropagator {
+    fields: [String; 1],
+}
+
+impl CloudTracePropagator {
+    fn new() -> Self {
+        Self {
+            fields: [CLOUD_TRACE_HEADER.to_string()],
+        }
+    }
+}
+
+impl TextMapPropagator for CloudTracePropagator {
+    fn inject_context(&self, cx: &opentelemetry::Context, injector: &mut dyn Injector) {
+        let span = cx.span();
+        let span_context = span.span_context();
+        if !span_context.is_valid() {
+            return;
+        }
+        let span_id = u64::from_be_bytes(span_context.span_id().to_bytes());
+        let sampled = if span_context.is_sampled() { 1 } else { 0 };
+        injector.set(
+            CLOUD_TRACE_HEADER,
+            format!("{}/{span_id};o={sampled}", span_context.trace_id()),
+        );
+    }
+
+    fn extract_with_context(
+        &self,
+        cx: &opentelemetry::Context,
+        extractor: &dyn Extractor,
+    ) -> opentelemetry::Context {
+        match extractor
+            .get(CLOUD_TRACE_HEADER)
+            .and_then(parse_cloud_trace_header)
+        {
+            Some(span_context) => cx.with_remote_span_context(span_context),
+            None => cx.clone(),
+        }
+    }
+
+    fn fields(&self) -> FieldIter<'_> {
+        FieldIter::new(&self.fields)
+    }
+}
+
+/// Parse an `X-Cloud-Trace-Context` header, `None` without a valid trace id.
+fn parse_cloud_trace_header(header: &str) -> Option<SpanContext> {
+    let (ids, options) = header.trim().split_once(';').unwrap_or((header.trim(), ""));
+    let (trace_id, span_id) = ids.split_once('/').unwrap_or((ids, ""));
+    if trace_id.len() != 32 {
+        return None;
+    }
+    let trace_id = TraceId::from_hex(trace_id).ok()?;
+    let span_id = match span_id.parse::<u64>() {
+        Ok(0) => None,
+        Ok(span_id) => Some(SpanId::from_bytes(span_id.to_be_bytes())),
+        Err(_) if span_id.is_empty() => None,
+        Err(_) => return None,
+    };
+    remote_span_context(trace_id, span_id, options.trim() != "o=0")
+}
+
+/// The remote parent of a request continuing the trace `trace_id`.
+///
+/// Without a `span_id`, ie: when the load balancer that started the trace didn't record a span,
+/// the parent's id is the lower half of the trace id.
+fn remote_span_context(
+    trace_id: TraceId,
+    span_id: Option<SpanId>,
+    sampled: bool,
+) -> Option<SpanContext> {
+    let span_id = span_id.unwrap_or_else(|| {
+        SpanId::from_bytes((u128::from_be_bytes(trace_id.to_bytes()) as u64).to_be_bytes())
+    });
+    let flags = if sampled {
+        TraceFlags::SAMPLED
+    } else {
+        TraceFlags::default()
+    };
+    let span_context = SpanContext::new(trace_id, span_id, flags, true, TraceState::default());
+    span_context.is_valid().then_some(span_context)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    #[test]
+    fn test_xray_header_roundtrip() {
+        let header = "Root=1-5759e988-bd862e3fe1be46a994272793;Parent=53995c3f42cd8ad8;Sampled=1";
+        let carrier = HashMap::from([(XRAY_HEADER.to_string(), header.to_string())]);
+
+        let cx = XrayPropagator::new().extract(&carrier);
+        let span = cx.span();
+        let span_context = span.span_context();
+        assert_eq!(
+            span_context.trace_id().to_string(),
+            "5759e988bd862e3fe1be46a994272793"
+        );
+        assert_eq!(span_context.span_id().to_string(), "53995c3f42cd8ad8");
+        assert!(span_context.is_sampled());
+        assert!(span_context.is_remote());
+
+        let mut injected = HashMap::new();
+        XrayPropagator::new().inject_context(&cx, &mut injected);
+        assert_eq!(injected, carrier);
+    }
+
+    #[test]
+    fn test_xray_header_started_by_a_load_balancer() {
+        let span_context = parse_xray_header("Root=1-67891233-abcdef012345678912345678").unwrap();
+        assert_eq!(
+            span_context.trace_id().to_string(),
+            "67891233abcdef012345678912345678"
+        );
+        assert_eq!(span_context.span_id().to_string(), "2345678912345678");
+        assert!(span_context.is_sampled());
+
+        let span_context =
+            parse_xray_header("Self=1-67891234-12456789abcdef0123456789;Root=1-67891233-abcdef012345678912345678;Sampled=0")
+                .unwrap();
+        assert!(!span_context.is_sampled());
+    }
+
+    #[test]
+    fn test_invalid_xray_headers_are_ignored() {
+        assert!(parse_xray_header("").is_none());
+        assert!(parse_xray_header("Parent=53995c3f42cd8ad8;Sampled=1").is_none());
+        assert!(parse_xray_header("Root=2-5759e988-bd862e3fe1be46a994272793").is_none());
+        assert!(parse_xray_header("Root=1-5759e988-bd862e3fe1be46a99427").is_none());
+        assert!(
+            parse_xray_header("Root=1-5759e988-bd862e3fe1be46a994272793;Parent=53995c").is_none()
+        );
+        assert!(parse_xray_header("Root=1-00000000-000000000000000000000000").is_none());
+    }
+
+    #[test]
+    fn test_cloud_trace_header_roundtrip() {
+        let header = "105445aa7843bc8bf206b12000100000/1;o=1";
+        let carrier = HashMap::from([(CLOUD_TRACE_HEADER.to_string(), header.to_string())]);
+
+        let cx = CloudTracePropagator::new().extract(&carrier);
+        let span = cx.span();
+        let span_context = span.span_context();
+        assert_eq!(
+            span_context.trace_id().to_string(),
+            "105445aa7843bc8bf206b12000100000"
+        );
+        assert_eq!(span_context.span_id().to_string(), "0000000000000001");
+        assert!(span_context.is_sampled());
+
+        let mut injected = HashMap::new();
+        CloudTracePropagator::new().inject_context(&cx, &mut injected);
+        assert_eq!(injected, carrier);
+    }
+
+    #[test]
+    fn test_cloud_trace_headers() {
+        let span_context = parse_cloud_trace_header("105445aa7843bc8bf206b12000100000").unwrap();
+        assert_eq!(span_context.span_id().to_string(), "f206b12000100000");
+        assert!(span_context.is_sampled());
+
+        let span_context =
+            parse_cloud_trace_header("105445aa7843bc8bf206b12000100000/18446744073709551615;o=0")
+                .unwrap();
+        assert_eq!(span_context.span_id().to_string(), "ffffffffffffffff");
+        assert!(!span_context.is_sampled());
+
+        assert!(parse_cloud_trace_header("").is_none());
+        assert!(parse_cloud_trace_header("105445aa7843bc8bf206b120001/1;o=1").is_none());
+        assert!(parse_cloud_trace_header("105445aa7843bc8bf206b12000100000/abc;o=1").is_none());
+    }
+
+    #[test]
+    fn test_composite_reads_every_format() {
+        let propagator = composite(&[
+            Propagator::TraceContext,
+            Propagator::Xray,
+            Propagator::CloudTrace,
+        ]);
+        let mut fields: Vec<_> = propagator.fields().collect();
+        fields.sort_unstable();
+        assert_eq!(
+            fields,
+            ["traceparent", "tracestate", XRAY_HEADER, CLOUD_TRACE_HEADER]
+        );
+
+        let carrier = HashMap::from([(
+            XRAY_HEADER.to_string(),
+            "Root=1-5759e988-bd862e3fe1be46a994272793;Parent=53995c3f42cd8ad8".to_string(),
+        )]);
+        let cx = propagator.extract(&carrier);
+        assert!(cx.span().span_context().is_valid());
+
+        let mut injected = HashMap::new();
+        propagator.inject_context(&cx, &mut injected);
+        assert!(injected.contains_key("traceparent"));
+        assert!(injected.contains_key(XRAY_HEADER));
+        assert!(injected.contains_key(CLOUD_TRACE_HEADER));
+    }
+}
@@ -0,0 +1,143 @@
+//! Recording errors on the current span and in metrics.
+
+use opentelemetry::trace::Status;
+use opentelemetry::KeyValue;
+use tracing_opentelemetry::OpenTelemetrySpanExt as _;
+
+/// Record `error` on the current span and count it.
+///
+/// The current span's status is set to error, and an `exception` event carrying the
+/// `exception.type` and `exception.message` fields is added to it. byre's `error.recorded`
+/// counter is incremented with the `exception.type` attribute.
+///
+/// # Example
+///
+/// ```
+/// #[tracing::instrument]
+/// fn read_config(path: &str) -> Option<String> {
+///     match std::fs::read_to_string(path) {
+///         Ok(config) => Some(config),
+///         Err(err) => {
+///             byre::telemetry::record_error(&err);
+///             None
+///         }
+///     }
+/// }
+/// # read_config("/does/not/exist");
+/// ```
+pub fn record_error<E>(error: &E)
+where
+    E: std::error::Error + ?Sized,
+{
+    let error_type = std::any::type_name::<E>();
+    let message = error.to_string();
+
+    let span = tracing::Span::current();
+    span.set_status(Status::error(message.clone()));
+    span.add_event(
+        "exception",
+        vec![
+            KeyValue::new("exception.type", error_type),
+            KeyValue::new("exception.message", message),
+        ],
+    );
+
+    super::metrics::byre_counter(
+        "error.recorded",
+        "Number of errors recorded with record_error",
+        "",
+    )
+    .add(1, &[KeyValue::new("exception.type", error_type)]);
+}
+
+/// Records the error of a `Result` with [`record_error`] as it passes by.
+///
+/// # Example
+///
+/// ```
+/// use byre::telemetry::RecordErrorExt as _;
+///
+/// #[tracing::instrument]
+/// fn parse_port(value: &str) -> Result<u16, std::num::ParseIntError> {
+///     value.parse::<u16>().record_err()
+/// }
+/// # assert!(parse_port("http").is_err());
+/// ```
+pub trait RecordErrorExt {
+    /// Call [`record_error`] if this is an error, then return `self` unchanged.
+    fn record_err(self) -> Self;
+}
+
+impl<T, E> RecordErrorExt for Result<T, E>
+where
+    E: std::error::Error,
+{
+    fn record_err(self) -> Self {
+        if let Err(error) = &self {
+            record_error(error);
+        }
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opentelemetry::trace::{Status, TracerProvider as _};
+    use opentelemetry_sdk::trace::{InMemorySpanExporter, SdkTracerProvider};
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[test]
+    fn test_record_err_marks_span_as_failed() {
+        let exporter = InMemorySpanExporter::default();
+        let provider = SdkTracerProvider::builder()
+            .with_simple_exporter(exporter.clone())
+            .build();
+        let subscriber = tracing_subscriber::registry()
+            .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("test")));
+
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info_span!("failed").in_scope(|| {
+                let _ = "not a number".parse::<u16>().record_err();
+            });
+            tracing::info_span!("succeeded").in_scope(|| {
+                let _ = "8080".parse::<u16>().record_err();
+            });
+        });
+
+        let spans = exporter.get_finished_spans().unwrap();
+        let span = |name: &str| spans.iter().find(|span| span.name == name).unwrap();
+
+        let failed = span("failed");
+        assert!(matches!(failed.status, Status::Error { .. }));
+        let event = failed.events.iter().next().unwrap();
+        assert_eq!(event.name, "exception");
+        assert!(event
+            .attributes
+            .iter()
+            .any(|kv| kv.key.as_str() == "exception.type"
+                && kv.value.as_str() == "core::num::error::ParseIntError"));
+
+        let succeeded = span("succeeded");
+        assert_eq!(succeeded.status, Status::Unset);
+        assert!(succeeded.events.is_empty());
+    }
+
+    #[test]
+    fn test_recorded_errors_are_counted_by_byre() {
+        use crate::telemetry::testing::{metric_value, prefixed_global_meter_provider};
+
+        let (provider, exporter) = prefixed_global_meter_provider("inventory");
+        let _ = "not a number".parse::<u16>().record_err();
+
+        assert_eq!(
+            metric_value(
+                &provider,
+                &exporter,
+                "error.recorded",
+                &[("exception.type", "core::num::error::ParseIntError")]
+            ),
+            1.0
+        );
+    }
+}
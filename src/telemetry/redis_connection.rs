@@ -0,0 +1,215 @@
+//! Instrumented Redis connection, requires the `redis` feature.
+//!
+//! [`InstrumentedConnection`] wraps an async [`redis`] connection, ie: a multiplexed connection
+//! or a connection manager, and is used in its place. Each command and pipeline gets a client
+//! span following the OpenTelemetry database semantic conventions, with `db.system.name` set to
+//! `redis`, and is recorded in the `db.client.operation.duration` histogram. Failed commands set
+//! the span's status to error, and their `error.type` to the error code Redis returned, ie:
+//! `WRONGTYPE`.
+//!
+//! ```no_run
+//! # async fn call() -> redis::RedisResult<()> {
+//! # let connection: redis::aio::MultiplexedConnection = unimplemented!();
+//! use redis::AsyncCommands as _;
+//!
+//! let mut redis = byre::telemetry::InstrumentedConnection::new(connection);
+//! let _: () = redis.set("greeting", "hello").await?;
+//! let greeting: String = redis.get("greeting").await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The arguments of the commands are not recorded, they usually hold the application's data.
+
+use redis::aio::ConnectionLike;
+use redis::{Cmd, Pipeline, RedisError, RedisFuture, Value};
+use tracing::Instrument as _;
+
+use super::db_client::{DbError, DbOperation};
+
+/// A Redis connection that traces and measures its commands.
+#[derive(Clone, Debug)]
+pub struct InstrumentedConnection<C> {
+    inner: C,
+}
+
+impl<C: ConnectionLike> InstrumentedConnection<C> {
+    /// Instrument the commands sent on `connection`.
+    pub fn new(connection: C) -> Self {
+        Self { inner: connection }
+    }
+
+    /// The wrapped connection.
+    pub fn get_ref(&self) -> &C {
+        &self.inner
+    }
+
+    /// Unwrap the connection, its commands are no longer instrumented.
+    pub fn into_inner(self) -> C {
+        self.inner
+    }
+
+    /// Start the span and the measurement of running `operation`.
+    fn start(&self, operation: Option<&str>) -> DbOperation {
+        let namespace = self.inner.get_db().to_string();
+        DbOperation::start("redis", Some(&namespace), operation, None)
+    }
+}
+
+impl<C: ConnectionLike + Send> ConnectionLike for InstrumentedConnection<C> {
+    fn req_packed_command<'a>(&'a mut self, cmd: &'a Cmd) -> RedisFuture<'a, Value> {
+        let mut operation = self.start(command_name(cmd).as_deref());
+        Box::pin(async move {
+            let result = self
+                .inner
+                .req_packed_command(cmd)
+                .instrument(operation.span().clone())
+                .await;
+            operation.finish(result.as_ref().err().map(db_error));
+            result
+        })
+    }
+
+    fn req_packed_commands<'a>(
+        &'a mut self,
+        pipeline: &'a Pipeline,
+        offset: usize,
+        count: usize,
+    ) -> RedisFuture<'a, Vec<Value>> {
+        let name = if pipeline.is_transaction() {
+            "MULTI"
+        } else {
+            "PIPELINE"
+        };
+        let mut operation = self.start(Some(name));
+        operation.record_batch_size(pipeline.len());
+        Box::pin(async move {
+            let result = self
+                .inner
+                .req_packed_commands(pipeline, offset, count)
+                .instrument(operation.span().clone())
+                .await;
+            operation.finish(result.as_ref().err().map(db_error));
+            result
+        })
+    }
+
+    fn get_db(&self) -> i64 {
+        self.inner.get_db()
+    }
+}
+
+/// The name of the command, ie: `GET`.
+fn command_name(cmd: &Cmd) -> Option<String> {
+    match cmd.args_iter().next()? {
+        redis::Arg::Simple(name) => Some(String::from_utf8_lossy(name).to_ascii_uppercase()),
+        _ => None,
+    }
+}
+
+/// The error code Redis returned, or the kind of the error when it didn't return one.
+fn db_error(error: &RedisError) -> DbError {
+    let status_code = error.code().map(str::to_string);
+    let error_type = status_code.clone().unwrap_or_else(|| {
+        if error.is_timeout() {
+            "timeout"
+        } else if error.is_connection_dropped() {
+            "connection_dropped"
+        } else if error.is_io_error() {
+            "io"
+        } else {
+            "_OTHER"
+        }
+        .to_string()
+    });
+    DbError {
+        error_type,
+        status_code,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use redis::AsyncCommands as _;
+
+    use super::*;
+
+    /// A connection answering `OK` to every command but `INCRBY`, which fails.
+    struct FakeConnection;
+
+    impl ConnectionLike for FakeConnection {
+        fn req_packed_command<'a>(&'a mut self, cmd: &'a Cmd) -> RedisFuture<'a, Value> {
+            let name = command_name(cmd);
+            Box::pin(async move {
+                match name.as_deref() {
+                    Some("INCRBY") => Err(RedisError::from((
+                        redis::ErrorKind::Server(redis::ServerErrorKind::ResponseError),
+                        "value is not an integer",
+                    ))),
+                    _ => Ok(Value::Okay),
+                }
+            })
+        }
+
+        fn req_packed_commands<'a>(
+            &'a mut self,
+            _pipeline: &'a Pipeline,
+            _offset: usize,
+            count: usize,
+        ) -> RedisFuture<'a, Vec<Value>> {
+            Box::pin(async move { Ok(vec![Value::Okay; count]) })
+        }
+
+        fn get_db(&self) -> i64 {
+            2
+        }
+    }
+
+    #[tokio::test]
+    async fn test_commands_are_traced_and_measured() {
+        let _serial = super::super::db_client::TEST_LOCK.lock().await;
+        let capture = super::super::test::capture();
+        let mut redis = InstrumentedConnection::new(FakeConnection);
+
+        let _: () = redis.set("greeting", "hello").await.unwrap();
+        let err = redis.incr::<_, _, i64>("greeting", 1).await.unwrap_err();
+        let _: () = redis::pipe()
+            .set("a", 1)
+            .set("b", 2)
+            .query_async(&mut redis)
+            .await
+            .unwrap();
+
+        let attribute = |span: &opentelemetry_sdk::trace::SpanData, key: &str| {
+            span.attributes
+                .iter()
+                .find(|kv| kv.key.as_str() == key)
+                .map(|kv| kv.value.to_string())
+        };
+        let set = capture.span("SET 2").unwrap();
+        assert_eq!(set.span_kind, opentelemetry::trace::SpanKind::Client);
+        assert_eq!(attribute(&set, "db.system.name").as_deref(), Some("redis"));
+        assert_eq!(attribute(&set, "db.namespace").as_deref(), Some("2"));
+
+        let incr = capture.span("INCRBY 2").unwrap();
+        assert!(matches!(
+            incr.status,
+            opentelemetry::trace::Status::Error { .. }
+        ));
+        assert_eq!(attribute(&incr, "error.type").as_deref(), err.code());
+
+        let pipeline = capture.span("PIPELINE 2").unwrap();
+        assert_eq!(
+            attribute(&pipeline, "db.operation.batch.size").as_deref(),
+            Some("2")
+        );
+
+        assert_eq!(
+            capture.metric_value(
+                "db.client.operation.duration",
+                &[("db.system.name", "redis"), ("db.operation.name", "SET")]
+            ),
+            1.0
+        );
+    }
+}
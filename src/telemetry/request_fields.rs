@@ -0,0 +1,200 @@
+//! Per-request fields, ie: the tenant or user a request is made for, inherited by the spans and
+//! events of the request.
+
+use std::cell::RefCell;
+#[cfg(feature = "http")]
+use std::sync::Arc;
+
+use opentelemetry::logs::{AnyValue, LogRecord as _};
+use opentelemetry::{InstrumentationScope, Key, KeyValue};
+use opentelemetry_sdk::error::OTelSdkResult;
+use opentelemetry_sdk::logs::{LogProcessor, SdkLogRecord};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::layer::Context;
+use tracing_subscriber::registry::{LookupSpan, SpanRef};
+use tracing_subscriber::{Layer, Registry};
+
+/// Derives the [`RequestFields`] of a request from the request.
+#[cfg(feature = "http")]
+pub(crate) type RequestFieldsFn = Arc<dyn Fn(&http::request::Parts) -> RequestFields + Send + Sync>;
+
+/// Fields of a request, ie: the tenant or user it is made for, inherited by the spans and events
+/// of the request.
+///
+/// `HttpTraceContextLayer` and `GrpcTraceContextLayer` derive them from each request with
+/// `with_request_fields`, handlers add the fields they learn later with
+/// [`RequestFields::attach`]. The fields are:
+///
+/// - attributes of the span they are attached to
+/// - attributes of the OpenTelemetry logs emitted within the span, or one of its children
+/// - printed at the start of the console logs emitted within the span
+///
+/// ```
+/// use byre::telemetry::RequestFields;
+///
+/// #[tracing::instrument(skip_all)]
+/// fn authenticated(user_id: &str) {
+///     RequestFields::new()
+///         .with_field("user.id", user_id)
+///         .attach(&tracing::Span::current());
+///     tracing::info!("logged with user.id");
+/// }
+/// # authenticated("42");
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RequestFields(Vec<KeyValue>);
+
+impl RequestFields {
+    /// No fields.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the field `key`, replacing a field of the same name.
+    pub fn with_field(mut self, key: impl Into<Key>, value: impl Into<String>) -> Self {
+        let field = KeyValue::new(key, value.into());
+        self.0.retain(|set| set.key != field.key);
+        self.0.push(field);
+        self
+    }
+
+    /// Whether there are no fields.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Add the fields to `span`, its children and the logs emitted within them.
+    ///
+    /// They are added to the fields already attached to `span`, replacing those of the same
+    /// name.
+    pub fn attach(&self, span: &tracing::Span) {
+        use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+        if self.is_empty() {
+            return;
+        }
+        for field in &self.0 {
+            span.set_attribute(field.key.clone(), field.value.clone());
+        }
+        span.with_subscriber(|(id, dispatch)| {
+            let span = dispatch
+                .downcast_ref::<Registry>()
+                .and_then(|registry| registry.span(id));
+            if let Some(span) = span {
+                let mut extensions = span.extensions_mut();
+                match extensions.get_mut::<RequestFields>() {
+                    Some(fields) => {
+                        for field in &self.0 {
+                            fields.0.retain(|set| set.key != field.key);
+                            fields.0.push(field.clone());
+                        }
+                    }
+                    None => extensions.insert(self.clone()),
+                }
+            }
+        });
+    }
+
+    /// The name and value of each field.
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (&str, String)> {
+        self.0
+            .iter()
+            .map(|field| (field.key.as_str(), field.value.as_str().into_owned()))
+    }
+}
+
+/// The fields attached to `span` and its parents, a span's fields win over its parents'.
+pub(crate) fn collect<'a, R: LookupSpan<'a>>(span: SpanRef<'a, R>) -> RequestFields {
+    let mut collected = RequestFields::new();
+    for span in span.scope() {
+        let extensions = span.extensions();
+        if let Some(fields) = extensions.get::<RequestFields>() {
+            for field in &fields.0 {
+                if !collected.0.iter().any(|set| set.key == field.key) {
+                    collected.0.push(field.clone());
+                }
+            }
+        }
+    }
+    collected
+}
+
+thread_local! {
+    /// The request fields of the event the layers are handling on this thread.
+    static EVENT_FIELDS: RefCell<RequestFields> = RefCell::default();
+}
+
+/// Layer that hands the request fields of each event over to the [`RequestFieldsProcessor`].
+///
+/// The current span can't be looked up while the subscriber handles an event, this layer must
+/// come before the OpenTelemetry log layer, which emits the log record of the event.
+pub(crate) struct RequestFieldsLayer;
+
+impl<S> Layer<S> for RequestFieldsLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let fields = ctx.event_span(event).map(collect).unwrap_or_default();
+        EVENT_FIELDS.with(|current| *current.borrow_mut() = fields);
+    }
+}
+
+/// Log processor that adds the request fields of the event to log records, see
+/// [`RequestFieldsLayer`].
+#[cfg_attr(not(feature = "otlp"), allow(dead_code))]
+#[derive(Debug)]
+pub(crate) struct RequestFieldsProcessor;
+
+impl LogProcessor for RequestFieldsProcessor {
+    fn emit(&self, record: &mut SdkLogRecord, _instrumentation: &InstrumentationScope) {
+        let fields = EVENT_FIELDS.with(|current| current.take());
+        for field in fields.0 {
+            let is_set = record.attributes_iter().any(|(key, _)| *key == field.key);
+            if !is_set {
+                record.add_attribute(field.key, AnyValue::from(field.value.to_string()));
+            }
+        }
+    }
+
+    fn force_flush(&self) -> OTelSdkResult {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_children_and_their_logs_inherit_the_fields() {
+        let capture = crate::telemetry::test::capture();
+
+        let span = tracing::info_span!("request");
+        RequestFields::new()
+            .with_field("tenant.id", "acme")
+            .with_field("user.id", "1")
+            .attach(&span);
+        span.in_scope(|| {
+            tracing::info_span!("child").in_scope(|| {
+                RequestFields::new()
+                    .with_field("user.id", "2")
+                    .attach(&tracing::Span::current());
+                tracing::info!("handled");
+            });
+            tracing::info!("responded");
+        });
+
+        let logs = capture.logs();
+        assert!(logs[0]
+            .attributes
+            .contains(&KeyValue::new("tenant.id", "acme")));
+        assert!(logs[0].attributes.contains(&KeyValue::new("user.id", "2")));
+        assert!(logs[1].attributes.contains(&KeyValue::new("user.id", "1")));
+        drop(span);
+        let span = capture.span("request").unwrap();
+        assert!(span
+            .attributes
+            .contains(&KeyValue::new("tenant.id", "acme")));
+    }
+}
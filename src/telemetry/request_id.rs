@@ -0,0 +1,241 @@
+//! Request ids, the `x-request-id` a request is known by across services and in their logs.
+
+use std::cell::RefCell;
+use std::fmt;
+use std::sync::Arc;
+
+use opentelemetry::logs::{AnyValue, LogRecord as _};
+use opentelemetry::propagation::{Extractor, Injector};
+use opentelemetry::{InstrumentationScope, Key};
+use opentelemetry_sdk::error::OTelSdkResult;
+use opentelemetry_sdk::logs::{LogProcessor, SdkLogRecord};
+use opentelemetry_sdk::trace::{IdGenerator as _, RandomIdGenerator};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::layer::Context;
+use tracing_subscriber::registry::{LookupSpan, SpanRef};
+use tracing_subscriber::{Layer, Registry};
+
+/// The header, or metadata key, the request id is carried in.
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// The span field, and log attribute, the request id is recorded in.
+pub(crate) const REQUEST_ID_FIELD: &str = "request.id";
+
+/// Longer ids are replaced, they would bloat every log of the request.
+const MAX_LEN: usize = 128;
+
+/// The id of a request, the `x-request-id` it is known by across services and in their logs.
+///
+/// `HttpTraceContextLayer` and `GrpcTraceContextLayer` take the id of the `x-request-id`
+/// header of a request, or generate one when it is missing or invalid. The id is:
+///
+/// - recorded in the `request.id` field of the request span, which console logs print with the
+///   span
+/// - added as the `request.id` attribute of the OpenTelemetry logs emitted within the span, or
+///   one of its children
+/// - injected next to `traceparent` by the [`TraceContextCarrier`](super::TraceContextCarrier)
+///   carriers, so the services called while handling the request get the same id
+/// - in the extensions of the request, for handlers, and in the `x-request-id` header of the
+///   response
+///
+/// ```
+/// #[tracing::instrument]
+/// fn error_message() -> String {
+///     match byre::telemetry::current_request_id() {
+///         Some(request_id) => format!("Something went wrong, request: {request_id}"),
+///         None => "Something went wrong".to_string(),
+///     }
+/// }
+/// # error_message();
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct RequestId(Arc<str>);
+
+impl RequestId {
+    /// A new random id, formatted like a version 4 UUID.
+    pub fn generate() -> Self {
+        let mut bytes = RandomIdGenerator::default().new_trace_id().to_bytes();
+        // The version and variant bits of a random UUID
+        bytes[6] = (bytes[6] & 0x0f) | 0x40;
+        bytes[8] = (bytes[8] & 0x3f) | 0x80;
+        let id = u128::from_be_bytes(bytes);
+        Self(
+            format!(
+                "{:08x}-{:04x}-{:04x}-{:04x}-{:012x}",
+                id >> 96,
+                (id >> 80) & 0xffff,
+                (id >> 64) & 0xffff,
+                (id >> 48) & 0xffff,
+                id & 0xffff_ffff_ffff
+            )
+            .into(),
+        )
+    }
+
+    /// The id `value` carried by a request, `None` if it is empty, longer than 128 characters,
+    /// or has characters other than visible ASCII.
+    pub fn parse(value: &str) -> Option<Self> {
+        let valid = !value.is_empty()
+            && value.len() <= MAX_LEN
+            && value.bytes().all(|byte| byte.is_ascii_graphic());
+        valid.then(|| Self(value.into()))
+    }
+
+    /// The id.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Make the id the request id of `span`, its children and the logs emitted within them.
+    ///
+    /// The id is recorded in the `request.id` field of the span when it declares one.
+    pub fn attach(&self, span: &tracing::Span) {
+        span.record(REQUEST_ID_FIELD, self.as_str());
+        span.with_subscriber(|(id, dispatch)| {
+            let span = dispatch
+                .downcast_ref::<Registry>()
+                .and_then(|registry| registry.span(id));
+            if let Some(span) = span {
+                span.extensions_mut().replace(self.clone());
+            }
+        });
+    }
+
+    /// The id carried by the `x-request-id` of `extractor`, when it is valid.
+    pub(crate) fn extract(extractor: &dyn Extractor) -> Option<Self> {
+        extractor.get(REQUEST_ID_HEADER).and_then(Self::parse)
+    }
+
+    /// The id carried by the `x-request-id` header of `headers`, or a new one.
+    #[cfg(feature = "http")]
+    pub(crate) fn from_headers(headers: &http::HeaderMap) -> Self {
+        Self::extract(&super::HttpHeaderExtractor(headers)).unwrap_or_else(Self::generate)
+    }
+}
+
+impl fmt::Display for RequestId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// The request id of the current span, inherited from the closest of its parents that has one,
+/// `None` outside of a request.
+pub fn current_request_id() -> Option<RequestId> {
+    tracing::Span::current()
+        .with_subscriber(|(id, dispatch)| {
+            let registry = dispatch.downcast_ref::<Registry>()?;
+            find(registry.span(id)?)
+        })
+        .flatten()
+}
+
+/// The request id of `span`, or of the closest of its parents that has one.
+fn find<'a, R: LookupSpan<'a>>(span: SpanRef<'a, R>) -> Option<RequestId> {
+    span.scope()
+        .find_map(|span| span.extensions().get::<RequestId>().cloned())
+}
+
+/// Set the `x-request-id` of `injector` to the request id of the current span, if any.
+pub(crate) fn inject(injector: &mut dyn Injector) {
+    if let Some(request_id) = current_request_id() {
+        injector.set(REQUEST_ID_HEADER, request_id.to_string());
+    }
+}
+
+thread_local! {
+    /// The request id of the event the layers are handling on this thread.
+    static EVENT_REQUEST_ID: RefCell<Option<RequestId>> = const { RefCell::new(None) };
+}
+
+/// Layer that hands the request id of each event over to the [`RequestIdProcessor`].
+///
+/// The current span can't be looked up while the subscriber handles an event, this layer must
+/// come before the OpenTelemetry log layer, which emits the log record of the event.
+pub(crate) struct RequestIdLayer;
+
+impl<S> Layer<S> for RequestIdLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let request_id = ctx.event_span(event).and_then(find);
+        EVENT_REQUEST_ID.with(|current| *current.borrow_mut() = request_id);
+    }
+}
+
+/// Log processor that adds the request id of the event to log records, see
+/// [`RequestIdLayer`].
+///
+/// Processors run on the thread that emitted the event, before the record is exported.
+#[cfg_attr(not(feature = "otlp"), allow(dead_code))]
+#[derive(Debug)]
+pub(crate) struct RequestIdProcessor;
+
+impl LogProcessor for RequestIdProcessor {
+    fn emit(&self, record: &mut SdkLogRecord, _instrumentation: &InstrumentationScope) {
+        let Some(request_id) = EVENT_REQUEST_ID.with(|current| current.borrow_mut().take()) else {
+            return;
+        };
+        let has_request_id = record
+            .attributes_iter()
+            .any(|(key, _)| key.as_str() == REQUEST_ID_FIELD);
+        if !has_request_id {
+            record.add_attribute(
+                Key::from_static_str(REQUEST_ID_FIELD),
+                AnyValue::from(request_id.to_string()),
+            );
+        }
+    }
+
+    fn force_flush(&self) -> OTelSdkResult {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generated_ids_look_like_uuids() {
+        let id = RequestId::generate();
+        assert_ne!(id, RequestId::generate());
+        let groups: Vec<_> = id.as_str().split('-').map(str::len).collect();
+        assert_eq!(groups, [8, 4, 4, 4, 12]);
+        assert_eq!(id.as_str().as_bytes()[14], b'4');
+
+        assert_eq!(RequestId::parse("req-42").unwrap().as_str(), "req-42");
+        assert_eq!(RequestId::parse(""), None);
+        assert_eq!(RequestId::parse("two words"), None);
+        assert_eq!(RequestId::parse(&"a".repeat(129)), None);
+    }
+
+    #[test]
+    fn test_children_and_their_logs_carry_the_request_id() {
+        let capture = crate::telemetry::test::capture();
+        assert_eq!(current_request_id(), None);
+
+        let span = tracing::info_span!("request", request.id = tracing::field::Empty);
+        RequestId::parse("req-42").unwrap().attach(&span);
+        let injected = span.in_scope(|| {
+            tracing::info_span!("child").in_scope(|| {
+                tracing::info!("handled");
+                let mut headers = std::collections::HashMap::new();
+                inject(&mut headers);
+                headers
+            })
+        });
+
+        assert_eq!(injected[REQUEST_ID_HEADER], "req-42");
+        let logs = capture.logs();
+        assert!(logs[0]
+            .attributes
+            .contains(&opentelemetry::KeyValue::new(REQUEST_ID_FIELD, "req-42")));
+        drop(span);
+        let span = capture.span("request").unwrap();
+        assert!(span
+            .attributes
+            .contains(&opentelemetry::KeyValue::new(REQUEST_ID_FIELD, "req-42")));
+    }
+}
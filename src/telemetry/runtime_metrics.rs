@@ -0,0 +1,206 @@
+//! Tokio runtime metrics.
+//!
+//! Samples [`tokio::runtime::RuntimeMetrics`] whenever the meter provider collects metrics.
+//! Metrics that tokio only provides when built with `--cfg tokio_unstable` are recorded when
+//! that cfg is set.
+
+use opentelemetry::metrics::Meter;
+use opentelemetry::KeyValue;
+
+/// Register observable instruments that sample the metrics of a tokio runtime.
+///
+/// The runtime is sampled every time the meter provider collects metrics, there is no
+/// background task. The following metrics are recorded:
+///
+/// - `tokio.workers` - number of worker threads
+/// - `tokio.tasks.alive` - number of alive tasks
+/// - `tokio.global_queue.depth` - number of tasks in the global queue
+/// - `tokio.worker.busy_duration` - time each worker has spent busy, in seconds
+/// - `tokio.worker.park_count` - number of times each worker has parked
+///
+/// With `--cfg tokio_unstable` the following are recorded as well:
+///
+/// - `tokio.blocking_threads` - number of threads in the blocking pool
+/// - `tokio.blocking_threads.idle` - number of idle threads in the blocking pool
+/// - `tokio.blocking_queue.depth` - number of tasks waiting for a blocking thread
+/// - `tokio.worker.poll_count` - number of tasks each worker has polled
+/// - `tokio.worker.local_queue.depth` - number of tasks in each worker's local queue
+///
+/// Per-worker metrics carry a `tokio.worker` attribute with the worker index.
+///
+/// [`init`](super::init) calls this for the current runtime when
+/// [`MetricSettings::runtime_metrics`](super::MetricSettings::runtime_metrics) is enabled.
+///
+/// # Example
+///
+/// ```
+/// # #[tokio::main]
+/// # async fn main() {
+/// let meter = opentelemetry::global::meter("my_service");
+/// byre::telemetry::register_runtime_metrics(&meter, tokio::runtime::Handle::current());
+/// # }
+/// ```
+pub fn register_runtime_metrics(meter: &Meter, runtime: tokio::runtime::Handle) {
+    let handle = runtime.clone();
+    meter
+        .u64_observable_gauge("tokio.workers")
+        .with_description("Number of worker threads used by the runtime")
+        .with_callback(move |observer| {
+            observer.observe(handle.metrics().num_workers() as u64, &[]);
+        })
+        .build();
+
+    let handle = runtime.clone();
+    meter
+        .u64_observable_gauge("tokio.tasks.alive")
+        .with_description("Number of alive tasks in the runtime")
+        .with_callback(move |observer| {
+            observer.observe(handle.metrics().num_alive_tasks() as u64, &[]);
+        })
+        .build();
+
+    let handle = runtime.clone();
+    meter
+        .u64_observable_gauge("tokio.global_queue.depth")
+        .with_description("Number of tasks in the runtime's global queue")
+        .with_callback(move |observer| {
+            observer.observe(handle.metrics().global_queue_depth() as u64, &[]);
+        })
+        .build();
+
+    #[cfg(target_has_atomic = "64")]
+    register_worker_metrics(meter, runtime.clone());
+
+    #[cfg(tokio_unstable)]
+    register_unstable_runtime_metrics(meter, runtime);
+}
+
+/// Per-worker counters, tokio only provides them on platforms with 64 bit atomics.
+#[cfg(target_has_atomic = "64")]
+fn register_worker_metrics(meter: &Meter, runtime: tokio::runtime::Handle) {
+    let handle = runtime.clone();
+    meter
+        .f64_observable_counter("tokio.worker.busy_duration")
+        .with_description("Time a worker thread has spent executing tasks")
+        .with_unit("s")
+        .with_callback(move |observer| {
+            let metrics = handle.metrics();
+            for worker in 0..metrics.num_workers() {
+                observer.observe(
+                    metrics.worker_total_busy_duration(worker).as_secs_f64(),
+                    &[worker_attribute(worker)],
+                );
+            }
+        })
+        .build();
+
+    let handle = runtime;
+    meter
+        .u64_observable_counter("tokio.worker.park_count")
+        .with_description("Number of times a worker thread has parked")
+        .with_callback(move |observer| {
+            let metrics = handle.metrics();
+            for worker in 0..metrics.num_workers() {
+                observer.observe(
+                    metrics.worker_park_count(worker),
+                    &[worker_attribute(worker)],
+                );
+            }
+        })
+        .build();
+}
+
+#[cfg(tokio_unstable)]
+fn register_unstable_runtime_metrics(meter: &Meter, runtime: tokio::runtime::Handle) {
+    let handle = runtime.clone();
+    meter
+        .u64_observable_gauge("tokio.blocking_threads")
+        .with_description("Number of threads in the runtime's blocking pool")
+        .with_callback(move |observer| {
+            observer.observe(handle.metrics().num_blocking_threads() as u64, &[]);
+        })
+        .build();
+
+    let handle = runtime.clone();
+    meter
+        .u64_observable_gauge("tokio.blocking_threads.idle")
+        .with_description("Number of idle threads in the runtime's blocking pool")
+        .with_callback(move |observer| {
+            observer.observe(handle.metrics().num_idle_blocking_threads() as u64, &[]);
+        })
+        .build();
+
+    let handle = runtime.clone();
+    meter
+        .u64_observable_gauge("tokio.blocking_queue.depth")
+        .with_description("Number of tasks waiting for a blocking thread")
+        .with_callback(move |observer| {
+            observer.observe(handle.metrics().blocking_queue_depth() as u64, &[]);
+        })
+        .build();
+
+    let handle = runtime.clone();
+    meter
+        .u64_observable_counter("tokio.worker.poll_count")
+        .with_description("Number of tasks a worker thread has polled")
+        .with_callback(move |observer| {
+            let metrics = handle.metrics();
+            for worker in 0..metrics.num_workers() {
+                observer.observe(
+                    metrics.worker_poll_count(worker),
+                    &[worker_attribute(worker)],
+                );
+            }
+        })
+        .build();
+
+    let handle = runtime;
+    meter
+        .u64_observable_gauge("tokio.worker.local_queue.depth")
+        .with_description("Number of tasks in a worker thread's local queue")
+        .with_callback(move |observer| {
+            let metrics = handle.metrics();
+            for worker in 0..metrics.num_workers() {
+                observer.observe(
+                    metrics.worker_local_queue_depth(worker) as u64,
+                    &[worker_attribute(worker)],
+                );
+            }
+        })
+        .build();
+}
+
+fn worker_attribute(worker: usize) -> KeyValue {
+    KeyValue::new("tokio.worker", worker as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::telemetry::testing::{has_metric, meter_provider, metric_value};
+    use opentelemetry::metrics::MeterProvider as _;
+
+    #[test]
+    fn test_register_runtime_metrics_samples_runtime() {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(2)
+            .build()
+            .unwrap();
+        let (provider, exporter) = meter_provider();
+
+        register_runtime_metrics(&provider.meter("test"), runtime.handle().clone());
+
+        assert_eq!(
+            metric_value(&provider, &exporter, "tokio.workers", &[]),
+            2.0
+        );
+        assert!(has_metric(&provider, &exporter, "tokio.tasks.alive"));
+        assert!(has_metric(&provider, &exporter, "tokio.global_queue.depth"));
+        assert!(has_metric(&provider, &exporter, "tokio.worker.park_count"));
+        assert!(has_metric(
+            &provider,
+            &exporter,
+            "tokio.worker.busy_duration"
+        ));
+    }
+}
@@ -0,0 +1,167 @@
+//! Per-route sampling of the request spans of the HTTP and gRPC server layers.
+//!
+//! The [`SamplingRule`]s of [`TraceSettings::sampling`](super::TraceSettings::sampling) keep a
+//! ratio of the requests to a route, ie: every `/checkout` but 1% of `/assets/*`. The decision
+//! is made before the request span is created: a dropped request gets an unsampled parent, so
+//! neither its span nor the spans of its handler are exported, and the calls it makes carry the
+//! decision downstream.
+//!
+//! Requests that continue a trace keep the decision of their caller, rules only apply to the
+//! requests that start one. This relies on the tracer provider's default sampler, which follows
+//! the parent.
+
+use std::sync::Arc;
+
+use doku::Document;
+use opentelemetry::trace::{SpanContext, TraceContextExt as _, TraceFlags, TraceState};
+use opentelemetry_sdk::trace::{IdGenerator as _, RandomIdGenerator};
+use serde::{Deserialize, Serialize};
+
+/// Sample a ratio of the requests matching a route, and method.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, Document)]
+pub struct SamplingRule {
+    /// Route the rule applies to, where `*` matches anything, ie: `/assets/*`. Matched against the route template when
+    /// the layer knows it, the path otherwise. gRPC routes are `/package.Service/Method`.
+    #[doku(example = "/assets/*")]
+    pub route: String,
+
+    /// HTTP method the rule applies to, ie: `GET`. Omit to apply it to every method.
+    #[doku(example = "GET")]
+    #[serde(default)]
+    pub method: Option<String>,
+
+    /// Fraction of the matching requests that are sampled, from 0.0 for none to 1.0 for all.
+    #[doku(example = "0.01")]
+    pub ratio: f64,
+}
+
+/// The rules given to a server layer, the first rule matching a request decides.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct SamplingRules(Arc<[SamplingRule]>);
+
+#[cfg_attr(not(feature = "http"), allow(dead_code))]
+impl SamplingRules {
+    pub(crate) fn new(rules: &[SamplingRule]) -> Self {
+        Self(rules.into())
+    }
+
+    /// The ratio of the first rule matching `route` and `method`, `None` when none does.
+    fn ratio(&self, route: &str, method: Option<&str>) -> Option<f64> {
+        self.0
+            .iter()
+            .find(|rule| {
+                matches_route(&rule.route, route)
+                    && rule.method.as_deref().is_none_or(|expected| {
+                        method.is_some_and(|method| method.eq_ignore_ascii_case(expected))
+                    })
+            })
+            .map(|rule| rule.ratio)
+    }
+
+    /// The parent of the span of a request to `route`, `parent` unless the request starts a
+    /// trace that the rules drop.
+    pub(crate) fn parent(
+        &self,
+        parent: opentelemetry::Context,
+        route: &str,
+        method: Option<&str>,
+    ) -> opentelemetry::Context {
+        if parent.span().span_context().is_valid() {
+            return parent;
+        }
+        let Some(ratio) = self.ratio(route, method) else {
+            return parent;
+        };
+        let ids = RandomIdGenerator::default();
+        let trace_id = ids.new_trace_id();
+        if is_sampled(u128::from_be_bytes(trace_id.to_bytes()) as u64, ratio) {
+            return parent;
+        }
+        // The parent only carries the decision, the dropped spans never reference it
+        let unsampled = SpanContext::new(
+            trace_id,
+            ids.new_span_id(),
+            TraceFlags::default(),
+            true,
+            TraceState::default(),
+        );
+        parent.with_remote_span_context(unsampled)
+    }
+}
+
+/// Whether the trace with the random bits `random` is kept at `ratio`.
+fn is_sampled(random: u64, ratio: f64) -> bool {
+    if ratio >= 1.0 {
+        return true;
+    }
+    if ratio <= 0.0 || ratio.is_nan() {
+        return false;
+    }
+    (random as f64) < ratio * u64::MAX as f64
+}
+
+/// Whether `route` matches `pattern`, where `*` matches any number of characters.
+fn matches_route(pattern: &str, route: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = route.strip_prefix(first) else {
+        return false;
+    };
+    let mut parts = parts.peekable();
+    while let Some(part) = parts.next() {
+        if parts.peek().is_none() {
+            // The last part must end the route
+            return rest.ends_with(part);
+        }
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+    rest.is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_routes_match_patterns() {
+        assert!(matches_route("/checkout", "/checkout"));
+        assert!(!matches_route("/checkout", "/checkout/cart"));
+        assert!(matches_route("/assets/*", "/assets/css/site.css"));
+        assert!(!matches_route("/assets/*", "/api/assets"));
+        assert!(matches_route("/users/*/avatar", "/users/42/avatar"));
+        assert!(!matches_route("/users/*/avatar", "/users/42/name"));
+        assert!(matches_route("*", "/anything"));
+        assert!(matches_route("/inventory.Stock/*", "/inventory.Stock/Get"));
+    }
+
+    #[test]
+    fn test_the_first_matching_rule_decides() {
+        let rule = |route: &str, method: Option<&str>, ratio| SamplingRule {
+            route: route.to_string(),
+            method: method.map(str::to_string),
+            ratio,
+        };
+        let rules = SamplingRules::new(&[
+            rule("/assets/*", Some("GET"), 0.01),
+            rule("/assets/*", None, 0.5),
+            rule("/checkout", None, 1.0),
+        ]);
+
+        assert_eq!(rules.ratio("/assets/site.css", Some("get")), Some(0.01));
+        assert_eq!(rules.ratio("/assets/site.css", Some("POST")), Some(0.5));
+        assert_eq!(rules.ratio("/assets/site.css", None), Some(0.5));
+        assert_eq!(rules.ratio("/checkout", Some("POST")), Some(1.0));
+        assert_eq!(rules.ratio("/orders", Some("GET")), None);
+    }
+
+    #[test]
+    fn test_ratio_bounds() {
+        assert!(is_sampled(u64::MAX, 1.0));
+        assert!(!is_sampled(0, 0.0));
+        assert!(is_sampled(u64::MAX / 4, 0.5));
+        assert!(!is_sampled(u64::MAX / 4 * 3, 0.5));
+    }
+}
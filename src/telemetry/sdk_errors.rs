@@ -0,0 +1,118 @@
+//! Metrics for the errors of the OpenTelemetry SDK itself.
+//!
+//! The SDK reports its own failures, ie: a batch the collector rejected or spans dropped from a
+//! full queue, as `tracing` events from the `opentelemetry*` targets. They reach the console
+//! like any other event, the [`SdkErrorLayer`] also counts them so an exporter that silently
+//! stopped delivering shows up on a dashboard.
+
+use opentelemetry::metrics::{Counter, Meter};
+use opentelemetry::KeyValue;
+use tracing::field::{Field, Visit};
+use tracing::{Event, Level, Metadata, Subscriber};
+use tracing_subscriber::filter::Targets;
+use tracing_subscriber::layer::Context;
+use tracing_subscriber::Layer;
+
+/// The target prefix of the events of the OpenTelemetry crates.
+pub(crate) const SDK_TARGET: &str = "opentelemetry";
+
+/// Whether the event comes from one of the OpenTelemetry crates.
+pub(crate) fn is_sdk_event(metadata: &Metadata<'_>) -> bool {
+    metadata.target().starts_with(SDK_TARGET)
+}
+
+/// Counts the warnings and errors of the OpenTelemetry SDK in `otel.export.errors`.
+///
+/// The counter carries the `error.type` attribute with the name the SDK gives the event, such
+/// as `BatchSpanProcessor.ExportError` or `BatchLogProcessor.LogsDropped`.
+pub(crate) struct SdkErrorLayer {
+    errors: Counter<u64>,
+}
+
+impl SdkErrorLayer {
+    /// Create a layer that records its counter with `meter`.
+    pub(crate) fn new(meter: &Meter) -> Self {
+        Self {
+            errors: meter
+                .u64_counter("otel.export.errors")
+                .with_description("Number of errors and warnings of the OpenTelemetry SDK")
+                .with_unit("{error}")
+                .build(),
+        }
+    }
+
+    /// The filter that only lets the SDK's warnings and errors reach the layer.
+    pub(crate) fn filter() -> Targets {
+        Targets::new().with_target(SDK_TARGET, Level::WARN)
+    }
+}
+
+/// Finds the name the SDK gives an event.
+#[derive(Default)]
+struct NameVisitor(Option<String>);
+
+impl Visit for NameVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "name" {
+            self.0 = Some(value.to_string());
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "name" {
+            self.0 = Some(format!("{value:?}"));
+        }
+    }
+}
+
+impl<S: Subscriber> Layer<S> for SdkErrorLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let mut name = NameVisitor::default();
+        event.record(&mut name);
+        let error_type = name.0.unwrap_or_else(|| "_OTHER".to_string());
+        self.errors
+            .add(1, &[KeyValue::new("error.type", error_type)]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::telemetry::testing::{meter_provider, metric_value};
+    use opentelemetry::metrics::MeterProvider as _;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[test]
+    fn test_sdk_warnings_and_errors_are_counted() {
+        let (provider, exporter) = meter_provider();
+        let layer =
+            SdkErrorLayer::new(&provider.meter("test")).with_filter(SdkErrorLayer::filter());
+        let subscriber = tracing_subscriber::registry().with(layer);
+
+        tracing::subscriber::with_default(subscriber, || {
+            for _ in 0..2 {
+                tracing::error!(
+                    target: "opentelemetry_sdk",
+                    name = "BatchSpanProcessor.ExportError",
+                    error = "connection refused",
+                    ""
+                );
+            }
+            tracing::warn!(target: "opentelemetry_sdk", name = "BatchLogProcessor.LogsDropped", "");
+            tracing::debug!(target: "opentelemetry_sdk", name = "BatchSpanProcessor.Flush", "");
+            tracing::error!(name = "BatchSpanProcessor.ExportError", "not from the SDK");
+        });
+
+        let value = |error_type| {
+            metric_value(
+                &provider,
+                &exporter,
+                "otel.export.errors",
+                &[("error.type", error_type)],
+            )
+        };
+        assert_eq!(value("BatchSpanProcessor.ExportError"), 2.0);
+        assert_eq!(value("BatchLogProcessor.LogsDropped"), 1.0);
+        assert_eq!(value("BatchSpanProcessor.Flush"), 0.0);
+    }
+}
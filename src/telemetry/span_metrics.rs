@@ -0,0 +1,325 @@
+//! Span-to-metrics (RED) layer.
+//!
+//! Derives request rate, error and duration metrics from closed spans so that handlers
+//! instrumented with `#[tracing::instrument]` get RED dashboards without hand-written
+//! histograms.
+
+use std::time::Instant;
+
+use doku::Document;
+use opentelemetry::metrics::{Counter, Histogram, Meter};
+use opentelemetry::KeyValue;
+use serde::{Deserialize, Serialize};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Level, Metadata, Subscriber};
+use tracing_subscriber::layer::Context;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::Layer;
+
+/// Settings for deriving metrics from spans.
+///
+/// Spans are selected by name or by target. Nothing is recorded when both lists are empty.
+#[derive(Debug, Default, Serialize, Deserialize, Document)]
+pub struct SpanMetricSettings {
+    /// Names of the spans to record metrics for, ie: the names of `#[instrument]`ed functions.
+    #[doku(example = "handle_request")]
+    #[serde(default)]
+    pub span_names: Vec<String>,
+
+    /// Targets (module paths) to record span metrics for. Submodules of a target are included.
+    #[doku(example = "yourcrate::handlers")]
+    #[serde(default)]
+    pub targets: Vec<String>,
+}
+
+/// A tracing layer that records request, error and duration metrics for selected spans.
+///
+/// For every selected span that closes, the layer records:
+///
+/// - `span.calls` - a counter of closed spans
+/// - `span.errors` - a counter of closed spans that failed
+/// - `span.duration` - a histogram of the span's lifetime in seconds
+///
+/// All three carry the `span.name` and `span.target` attributes.
+///
+/// A span counts as failed when an `ERROR` level event is emitted directly inside it, when it
+/// records an `error` field, or when it records `otel.status_code = "ERROR"`.
+///
+/// [`init`](super::init) installs this layer when [`SpanMetricSettings`] selects any spans
+/// and a metrics endpoint is configured.
+///
+/// # Example
+///
+/// ```
+/// use byre::telemetry::SpanMetricsLayer;
+/// use tracing_subscriber::layer::SubscriberExt;
+///
+/// let meter = opentelemetry::global::meter("my_service");
+/// let layer = SpanMetricsLayer::new(&meter)
+///     .with_span_name("handle_request")
+///     .with_target("my_service::handlers");
+///
+/// let subscriber = tracing_subscriber::registry().with(layer);
+/// ```
+#[derive(Clone)]
+pub struct SpanMetricsLayer {
+    span_names: Vec<String>,
+    targets: Vec<String>,
+    calls: Counter<u64>,
+    errors: Counter<u64>,
+    duration: Histogram<f64>,
+}
+
+impl std::fmt::Debug for SpanMetricsLayer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SpanMetricsLayer")
+            .field("span_names", &self.span_names)
+            .field("targets", &self.targets)
+            .finish_non_exhaustive()
+    }
+}
+
+impl SpanMetricsLayer {
+    /// Create a layer that records its instruments with `meter`.
+    ///
+    /// No spans are selected until [`with_span_name`](Self::with_span_name) or
+    /// [`with_target`](Self::with_target) is called.
+    pub fn new(meter: &Meter) -> Self {
+        Self {
+            span_names: Vec::new(),
+            targets: Vec::new(),
+            calls: meter
+                .u64_counter("span.calls")
+                .with_description("Number of closed spans")
+                .build(),
+            errors: meter
+                .u64_counter("span.errors")
+                .with_description("Number of closed spans that recorded an error")
+                .build(),
+            duration: meter
+                .f64_histogram("span.duration")
+                .with_description("Time between the creation and closing of a span")
+                .with_unit("s")
+                .build(),
+        }
+    }
+
+    /// Create a layer from settings, returning `None` if the settings select no spans.
+    pub fn from_settings(meter: &Meter, settings: &SpanMetricSettings) -> Option<Self> {
+        if settings.span_names.is_empty() && settings.targets.is_empty() {
+            return None;
+        }
+
+        let layer = settings
+            .span_names
+            .iter()
+            .fold(Self::new(meter), |layer, name| layer.with_span_name(name));
+        Some(
+            settings
+                .targets
+                .iter()
+                .fold(layer, |layer, target| layer.with_target(target)),
+        )
+    }
+
+    /// Record metrics for spans with this name.
+    pub fn with_span_name(mut self, name: impl Into<String>) -> Self {
+        self.span_names.push(name.into());
+        self
+    }
+
+    /// Record metrics for spans with this target, or a target in one of its submodules.
+    pub fn with_target(mut self, target: impl Into<String>) -> Self {
+        self.targets.push(target.into());
+        self
+    }
+
+    fn is_selected(&self, metadata: &Metadata<'_>) -> bool {
+        let target = metadata.target();
+        self.span_names.iter().any(|name| name == metadata.name())
+            || self.targets.iter().any(|selected| {
+                target
+                    .strip_prefix(selected.as_str())
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with("::"))
+            })
+    }
+}
+
+/// Per-span state stored in the span's extensions while it is open.
+struct SpanTiming {
+    start: Instant,
+    error: bool,
+}
+
+/// Looks for fields that mark a span as failed.
+struct ErrorVisitor<'a>(&'a mut bool);
+
+impl Visit for ErrorVisitor<'_> {
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        if field.name() == "error" {
+            *self.0 = value;
+        }
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "otel.status_code" {
+            *self.0 = value.eq_ignore_ascii_case("error");
+        } else if field.name() == "error" {
+            *self.0 = true;
+        }
+    }
+
+    fn record_error(&mut self, field: &Field, _value: &(dyn std::error::Error + 'static)) {
+        if field.name() == "error" {
+            *self.0 = true;
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, _value: &dyn std::fmt::Debug) {
+        if field.name() == "error" {
+            *self.0 = true;
+        }
+    }
+}
+
+impl<S> Layer<S> for SpanMetricsLayer
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        if !self.is_selected(attrs.metadata()) {
+            return;
+        }
+
+        let mut timing = SpanTiming {
+            start: Instant::now(),
+            error: false,
+        };
+        attrs.record(&mut ErrorVisitor(&mut timing.error));
+
+        if let Some(span) = ctx.span(id) {
+            span.extensions_mut().insert(timing);
+        }
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            if let Some(timing) = span.extensions_mut().get_mut::<SpanTiming>() {
+                values.record(&mut ErrorVisitor(&mut timing.error));
+            }
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        if *event.metadata().level() != Level::ERROR {
+            return;
+        }
+        if let Some(span) = ctx.event_span(event) {
+            if let Some(timing) = span.extensions_mut().get_mut::<SpanTiming>() {
+                timing.error = true;
+            }
+        }
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(&id) else {
+            return;
+        };
+        let Some(timing) = span.extensions_mut().remove::<SpanTiming>() else {
+            return;
+        };
+
+        let metadata = span.metadata();
+        let attributes = [
+            KeyValue::new("span.name", metadata.name()),
+            KeyValue::new("span.target", metadata.target()),
+        ];
+
+        self.calls.add(1, &attributes);
+        if timing.error {
+            self.errors.add(1, &attributes);
+        }
+        self.duration
+            .record(timing.start.elapsed().as_secs_f64(), &attributes);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::telemetry::testing::{meter_provider, metric_value};
+    use opentelemetry::metrics::MeterProvider as _;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[test]
+    fn test_span_metrics_layer_records_selected_spans() {
+        let (provider, exporter) = meter_provider();
+        let layer = SpanMetricsLayer::new(&provider.meter("test")).with_span_name("selected");
+
+        let subscriber = tracing_subscriber::registry().with(layer);
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info_span!("selected").in_scope(|| {});
+            tracing::info_span!("selected").in_scope(|| {
+                tracing::error!("failed");
+            });
+            tracing::info_span!("ignored").in_scope(|| {});
+        });
+
+        let value = |name, span| metric_value(&provider, &exporter, name, &[("span.name", span)]);
+        assert_eq!(value("span.calls", "selected"), 2.0);
+        assert_eq!(value("span.errors", "selected"), 1.0);
+        assert_eq!(value("span.duration", "selected"), 2.0);
+        assert_eq!(value("span.calls", "ignored"), 0.0);
+    }
+
+    #[test]
+    fn test_span_metrics_layer_error_field_marks_failure() {
+        let (provider, exporter) = meter_provider();
+        let layer = SpanMetricsLayer::new(&provider.meter("test")).with_span_name("request");
+
+        let subscriber = tracing_subscriber::registry().with(layer);
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("request", otel.status_code = tracing::field::Empty);
+            span.record("otel.status_code", "ERROR");
+            drop(span);
+            tracing::info_span!("request", error = false).in_scope(|| {});
+        });
+
+        let value = |name| metric_value(&provider, &exporter, name, &[("span.name", "request")]);
+        assert_eq!(value("span.calls"), 2.0);
+        assert_eq!(value("span.errors"), 1.0);
+    }
+
+    #[test]
+    fn test_span_metrics_layer_matches_target_prefix() {
+        let (provider, exporter) = meter_provider();
+        let layer = SpanMetricsLayer::new(&provider.meter("test")).with_target("app::handlers");
+
+        let subscriber = tracing_subscriber::registry().with(layer);
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info_span!(target: "app::handlers", "exact").in_scope(|| {});
+            tracing::info_span!(target: "app::handlers::users", "submodule").in_scope(|| {});
+            tracing::info_span!(target: "app::handlers_extra", "sibling").in_scope(|| {});
+        });
+
+        let value = |span| metric_value(&provider, &exporter, "span.calls", &[("span.name", span)]);
+        assert_eq!(value("exact"), 1.0);
+        assert_eq!(value("submodule"), 1.0);
+        assert_eq!(value("sibling"), 0.0);
+    }
+
+    #[test]
+    fn test_span_metrics_layer_from_empty_settings_is_none() {
+        let (provider, _exporter) = meter_provider();
+        let meter = provider.meter("test");
+
+        assert!(SpanMetricsLayer::from_settings(&meter, &SpanMetricSettings::default()).is_none());
+
+        let settings = SpanMetricSettings {
+            span_names: vec!["handle_request".to_string()],
+            targets: vec![],
+        };
+        assert!(SpanMetricsLayer::from_settings(&meter, &settings).is_some());
+    }
+}
@@ -0,0 +1,289 @@
+//! Instrumented sqlx pool, requires the `sqlx` feature.
+//!
+//! [`InstrumentedPool`] wraps an [`sqlx::Pool`] and is used in its place as the executor of the
+//! queries. Each query gets a client span following the OpenTelemetry database semantic
+//! conventions, and is recorded in the `db.client.operation.duration` histogram. Failed queries
+//! set the span's status to error, and their `error.type` to the SQLSTATE code the database
+//! returned, or the kind of the sqlx error.
+//!
+//! byre builds sqlx without a driver or runtime, enable them in the application's `Cargo.toml`.
+//!
+//! ```no_run
+//! # async fn query() -> Result<(), sqlx::Error> {
+//! # let pool: sqlx::Pool<sqlx::Sqlite> = unimplemented!();
+//! let db = byre::telemetry::InstrumentedPool::new(pool).with_namespace("inventory");
+//!
+//! let rows = sqlx::query("SELECT id FROM items WHERE price < ?")
+//!     .bind(10)
+//!     .fetch_all(&db)
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The query text is recorded in `db.query.text` as it is written, bind its values as parameters
+//! rather than formatting them into the query.
+
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures_core::future::BoxFuture;
+use futures_core::stream::{BoxStream, Stream};
+use sqlx::{Database, Describe, Either, Execute, Executor};
+use tracing::Instrument as _;
+
+use super::db_client::{DbError, DbOperation};
+
+/// An sqlx pool that traces and measures its queries.
+///
+/// Queries run on `&InstrumentedPool` are instrumented, [`pool`](Self::pool) gives the wrapped
+/// pool for the rest, ie: starting a transaction. Queries run on a connection or a transaction
+/// acquired from it are not instrumented.
+#[derive(Debug)]
+pub struct InstrumentedPool<DB: Database> {
+    pool: sqlx::Pool<DB>,
+    namespace: Option<String>,
+}
+
+impl<DB: Database> Clone for InstrumentedPool<DB> {
+    fn clone(&self) -> Self {
+        Self {
+            pool: self.pool.clone(),
+            namespace: self.namespace.clone(),
+        }
+    }
+}
+
+impl<DB: Database> InstrumentedPool<DB> {
+    /// Instrument the queries run on `pool`.
+    pub fn new(pool: sqlx::Pool<DB>) -> Self {
+        Self {
+            pool,
+            namespace: None,
+        }
+    }
+
+    /// Name of the database the pool connects to, recorded as `db.namespace`.
+    pub fn with_namespace(mut self, namespace: impl Into<String>) -> Self {
+        self.namespace = Some(namespace.into());
+        self
+    }
+
+    /// The wrapped pool.
+    pub fn pool(&self) -> &sqlx::Pool<DB> {
+        &self.pool
+    }
+
+    /// Start the span and the measurement of running `sql`.
+    fn start(&self, sql: &str) -> DbOperation {
+        DbOperation::start(
+            &DB::NAME.to_lowercase(),
+            self.namespace.as_deref(),
+            operation_name(sql).as_deref(),
+            Some(sql),
+        )
+    }
+}
+
+impl<'p, DB: Database> Executor<'p> for &'p InstrumentedPool<DB>
+where
+    for<'c> &'c mut DB::Connection: Executor<'c, Database = DB>,
+{
+    type Database = DB;
+
+    fn fetch_many<'e, 'q: 'e, E>(
+        self,
+        query: E,
+    ) -> BoxStream<'e, Result<Either<DB::QueryResult, DB::Row>, sqlx::Error>>
+    where
+        'p: 'e,
+        E: 'q + Execute<'q, DB>,
+    {
+        let operation = self.start(query.sql());
+        let inner = operation.span().in_scope(|| self.pool.fetch_many(query));
+        Box::pin(QueryStream { inner, operation })
+    }
+
+    fn fetch_optional<'e, 'q: 'e, E>(
+        self,
+        query: E,
+    ) -> BoxFuture<'e, Result<Option<DB::Row>, sqlx::Error>>
+    where
+        'p: 'e,
+        E: 'q + Execute<'q, DB>,
+    {
+        let mut operation = self.start(query.sql());
+        let inner = self.pool.fetch_optional(query);
+        Box::pin(async move {
+            let result = inner.instrument(operation.span().clone()).await;
+            operation.finish(result.as_ref().err().map(db_error));
+            result
+        })
+    }
+
+    fn prepare_with<'e, 'q: 'e>(
+        self,
+        sql: &'q str,
+        parameters: &'e [DB::TypeInfo],
+    ) -> BoxFuture<'e, Result<DB::Statement<'q>, sqlx::Error>>
+    where
+        'p: 'e,
+    {
+        self.pool.prepare_with(sql, parameters)
+    }
+
+    fn describe<'e, 'q: 'e>(self, sql: &'q str) -> BoxFuture<'e, Result<Describe<DB>, sqlx::Error>>
+    where
+        'p: 'e,
+    {
+        self.pool.describe(sql)
+    }
+}
+
+/// The results of a query, finishing the operation with the first error or the last result.
+struct QueryStream<'e, T> {
+    inner: BoxStream<'e, Result<T, sqlx::Error>>,
+    operation: DbOperation,
+}
+
+impl<T> Stream for QueryStream<'_, T> {
+    type Item = Result<T, sqlx::Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        let poll = this
+            .operation
+            .span()
+            .in_scope(|| this.inner.as_mut().poll_next(cx));
+        match &poll {
+            Poll::Ready(Some(Err(error))) => this.operation.finish(Some(db_error(error))),
+            Poll::Ready(None) => this.operation.finish(None),
+            _ => {}
+        }
+        poll
+    }
+}
+
+/// The first keyword of `sql`, ie: `SELECT`, `None` if it doesn't start with one.
+fn operation_name(sql: &str) -> Option<String> {
+    let keyword = sql.split_whitespace().next()?;
+    keyword
+        .chars()
+        .all(|c| c.is_ascii_alphabetic())
+        .then(|| keyword.to_ascii_uppercase())
+}
+
+/// The SQLSTATE code of `error`, or its kind when the database didn't give it one.
+fn db_error(error: &sqlx::Error) -> DbError {
+    let status_code = error
+        .as_database_error()
+        .and_then(|error| error.code())
+        .map(|code| code.into_owned());
+    let error_type = status_code
+        .clone()
+        .unwrap_or_else(|| error_kind(error).to_string());
+    DbError {
+        error_type,
+        status_code,
+    }
+}
+
+/// The `error.type` of an sqlx error the database didn't give a code to.
+fn error_kind(error: &sqlx::Error) -> &'static str {
+    match error {
+        sqlx::Error::Database(_) => "database",
+        sqlx::Error::Io(_) => "io",
+        sqlx::Error::Tls(_) => "tls",
+        sqlx::Error::Protocol(_) => "protocol",
+        sqlx::Error::RowNotFound => "row_not_found",
+        sqlx::Error::ColumnDecode { .. } | sqlx::Error::Decode(_) => "decode",
+        sqlx::Error::PoolTimedOut => "pool_timed_out",
+        sqlx::Error::PoolClosed => "pool_closed",
+        _ => "_OTHER",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_operation_names() {
+        assert_eq!(
+            operation_name("select id from items").as_deref(),
+            Some("SELECT")
+        );
+        assert_eq!(
+            operation_name("\n  INSERT INTO items VALUES (?)").as_deref(),
+            Some("INSERT")
+        );
+        assert_eq!(operation_name("(SELECT 1)"), None);
+        assert_eq!(operation_name(""), None);
+    }
+
+    #[tokio::test]
+    async fn test_queries_are_traced_and_measured() {
+        let _serial = super::super::db_client::TEST_LOCK.lock().await;
+        let capture = super::super::test::capture();
+        let pool = sqlx::sqlite::SqlitePoolOptions::new()
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        let db = InstrumentedPool::new(pool).with_namespace("inventory");
+
+        sqlx::query("CREATE TABLE items (id INTEGER PRIMARY KEY)")
+            .execute(&db)
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO items (id) VALUES (?)")
+            .bind(1)
+            .execute(&db)
+            .await
+            .unwrap();
+        let rows = sqlx::query("SELECT id FROM items")
+            .fetch_all(&db)
+            .await
+            .unwrap();
+        assert_eq!(rows.len(), 1);
+        let result = sqlx::query("INSERT INTO items (id) VALUES (?)")
+            .bind(1)
+            .fetch_optional(&db)
+            .await;
+        assert!(result.is_err_and(|err| err.as_database_error().is_some()));
+        // The SQLite worker holds the spans of the queries it ran until it is shut down
+        db.pool().close().await;
+
+        let span = capture.span("SELECT inventory").unwrap();
+        assert_eq!(span.span_kind, opentelemetry::trace::SpanKind::Client);
+        let attribute = |span: &opentelemetry_sdk::trace::SpanData, key: &str| {
+            span.attributes
+                .iter()
+                .find(|kv| kv.key.as_str() == key)
+                .map(|kv| kv.value.to_string())
+        };
+        assert_eq!(
+            attribute(&span, "db.system.name").as_deref(),
+            Some("sqlite")
+        );
+        assert_eq!(
+            attribute(&span, "db.query.text").as_deref(),
+            Some("SELECT id FROM items")
+        );
+
+        let failed = capture
+            .spans_named("INSERT inventory")
+            .into_iter()
+            .find(|span| matches!(span.status, opentelemetry::trace::Status::Error { .. }))
+            .unwrap();
+        // SQLITE_CONSTRAINT_PRIMARYKEY
+        assert_eq!(attribute(&failed, "error.type").as_deref(), Some("1555"));
+
+        assert_eq!(
+            capture.metric_value(
+                "db.client.operation.duration",
+                &[("db.operation.name", "INSERT"), ("error.type", "1555")]
+            ),
+            1.0
+        );
+    }
+}
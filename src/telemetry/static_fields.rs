@@ -0,0 +1,152 @@
+//! The static fields of [`LogSettings::fields`](super::LogSettings::fields), ie: the team,
+//! shard or datacenter of the service, added to every log record and span.
+//!
+//! The OpenTelemetry logs get them from [`StaticFields`] as a log processor, the spans as a span
+//! processor, and the console logs from the [`CorrelatedFormat`](super::vendor::CorrelatedFormat)
+//! of the console layer.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use opentelemetry::logs::{AnyValue, LogRecord as _};
+use opentelemetry::trace::Span as _;
+use opentelemetry::{Context, InstrumentationScope, KeyValue};
+use opentelemetry_sdk::error::OTelSdkResult;
+use opentelemetry_sdk::logs::{LogProcessor, SdkLogRecord};
+use opentelemetry_sdk::trace::{Span, SpanData, SpanProcessor};
+
+/// Fields added to every log record and span, a field the record or span sets itself wins.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct StaticFields(Arc<[KeyValue]>);
+
+impl StaticFields {
+    pub(crate) fn new(fields: &BTreeMap<String, String>) -> Self {
+        Self(
+            fields
+                .iter()
+                .map(|(key, value)| KeyValue::new(key.clone(), value.clone()))
+                .collect(),
+        )
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// The name and value of each field.
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (&str, String)> {
+        self.0
+            .iter()
+            .map(|field| (field.key.as_str(), field.value.as_str().into_owned()))
+    }
+}
+
+impl LogProcessor for StaticFields {
+    fn emit(&self, record: &mut SdkLogRecord, _instrumentation: &InstrumentationScope) {
+        for field in self.0.iter() {
+            let is_set = record.attributes_iter().any(|(key, _)| *key == field.key);
+            if !is_set {
+                record.add_attribute(
+                    field.key.clone(),
+                    AnyValue::from(field.value.as_str().into_owned()),
+                );
+            }
+        }
+    }
+
+    fn force_flush(&self) -> OTelSdkResult {
+        Ok(())
+    }
+}
+
+impl SpanProcessor for StaticFields {
+    fn on_start(&self, span: &mut Span, _cx: &Context) {
+        // The attributes of the span when it starts, its fields that have a value
+        let attributes = span
+            .exported_data()
+            .map(|data| data.attributes)
+            .unwrap_or_default();
+        for field in self.0.iter() {
+            if !attributes.iter().any(|set| set.key == field.key) {
+                span.set_attribute(field.clone());
+            }
+        }
+    }
+
+    fn on_end(&self, _span: SpanData) {}
+
+    fn force_flush(&self) -> OTelSdkResult {
+        Ok(())
+    }
+
+    fn shutdown_with_timeout(&self, _timeout: Duration) -> OTelSdkResult {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opentelemetry::logs::{Logger as _, LoggerProvider as _};
+    use opentelemetry::trace::{Tracer as _, TracerProvider as _};
+    use opentelemetry_sdk::logs::{InMemoryLogExporter, SdkLoggerProvider};
+    use opentelemetry_sdk::trace::{InMemorySpanExporter, SdkTracerProvider};
+
+    fn fields() -> BTreeMap<String, String> {
+        BTreeMap::from([
+            ("team".to_string(), "payments".to_string()),
+            ("shard".to_string(), "7".to_string()),
+        ])
+    }
+
+    #[test]
+    fn test_logs_carry_the_fields_they_do_not_set() {
+        let exporter = InMemoryLogExporter::default();
+        let provider = SdkLoggerProvider::builder()
+            .with_log_processor(StaticFields::new(&fields()))
+            .with_simple_exporter(exporter.clone())
+            .build();
+
+        let logger = provider.logger("test");
+        let mut record = logger.create_log_record();
+        record.add_attribute("shard", "3");
+        logger.emit(record);
+
+        let logs = exporter.get_emitted_logs().unwrap();
+        let attributes: Vec<_> = logs[0]
+            .record
+            .attributes_iter()
+            .map(|(key, value)| (key.to_string(), value.clone()))
+            .collect();
+        assert_eq!(
+            attributes,
+            [
+                ("shard".to_string(), AnyValue::from("3")),
+                ("team".to_string(), AnyValue::from("payments".to_string())),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_spans_carry_the_fields_they_do_not_set() {
+        let exporter = InMemorySpanExporter::default();
+        let provider = SdkTracerProvider::builder()
+            .with_span_processor(StaticFields::new(&fields()))
+            .with_simple_exporter(exporter.clone())
+            .build();
+
+        let tracer = provider.tracer("test");
+        tracer
+            .span_builder("work")
+            .with_attributes([KeyValue::new("shard", "3")])
+            .start(&tracer)
+            .end();
+
+        let spans = exporter.get_finished_spans().unwrap();
+        let attributes = &spans[0].attributes;
+        assert!(attributes.contains(&KeyValue::new("team", "payments")));
+        assert!(attributes.contains(&KeyValue::new("shard", "3")));
+        assert!(!attributes.contains(&KeyValue::new("shard", "7")));
+    }
+}
@@ -0,0 +1,160 @@
+//! Host system metrics.
+//!
+//! Only available with the `system-metrics` feature. Intended for single-binary deployments
+//! that do not run a separate node exporter.
+
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use doku::Document;
+use opentelemetry::metrics::Meter;
+use opentelemetry::KeyValue;
+use serde::{Deserialize, Serialize};
+use sysinfo::{Disks, Networks, System};
+
+/// Settings for host system metrics.
+#[derive(Debug, Default, Serialize, Deserialize, Document)]
+pub struct SystemMetricSettings {
+    /// Export network IO and load average of the host, and disk usage of `disk_paths`.
+    #[doku(example = "true")]
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Paths to report filesystem usage for, each is reported for the disk it is mounted on.
+    #[doku(example = "/var/db")]
+    #[serde(default)]
+    pub disk_paths: Vec<PathBuf>,
+}
+
+/// Register observable instruments that sample the host system.
+///
+/// The host is sampled every time the meter provider collects metrics. The following
+/// metrics are recorded:
+///
+/// - `system.filesystem.usage` - bytes used and free on the disk of each configured path,
+///   with `system.filesystem.mountpoint`, `path` and `system.filesystem.state` attributes
+/// - `system.network.io` - bytes received and transmitted per network interface, with
+///   `network.interface.name` and `network.io.direction` attributes
+/// - `system.cpu.load_average.1m`, `system.cpu.load_average.5m` and
+///   `system.cpu.load_average.15m` - the load average of the host
+///
+/// [`init`](super::init) calls this when [`SystemMetricSettings::enabled`] is set and a
+/// metrics endpoint is configured.
+pub fn register_system_metrics(meter: &Meter, settings: &SystemMetricSettings) {
+    if !settings.disk_paths.is_empty() {
+        let disks = Mutex::new(Disks::new_with_refreshed_list());
+        let paths = settings.disk_paths.clone();
+        meter
+            .u64_observable_gauge("system.filesystem.usage")
+            .with_description("Filesystem bytes used and free on the disks of the configured paths")
+            .with_unit("By")
+            .with_callback(move |observer| {
+                let mut disks = disks.lock().unwrap_or_else(|e| e.into_inner());
+                disks.refresh(true);
+                for path in &paths {
+                    let Some((mount_point, total, available)) = disk_usage(&disks, path) else {
+                        continue;
+                    };
+                    let attributes = |state: &'static str| {
+                        [
+                            KeyValue::new("system.filesystem.mountpoint", mount_point.clone()),
+                            KeyValue::new("path", path.display().to_string()),
+                            KeyValue::new("system.filesystem.state", state),
+                        ]
+                    };
+                    observer.observe(total.saturating_sub(available), &attributes("used"));
+                    observer.observe(available, &attributes("free"));
+                }
+            })
+            .build();
+    }
+
+    let networks = Mutex::new(Networks::new_with_refreshed_list());
+    meter
+        .u64_observable_counter("system.network.io")
+        .with_description("Bytes received and transmitted per network interface")
+        .with_unit("By")
+        .with_callback(move |observer| {
+            let mut networks = networks.lock().unwrap_or_else(|e| e.into_inner());
+            networks.refresh(true);
+            for (interface, data) in networks.list() {
+                for (direction, bytes) in [
+                    ("receive", data.total_received()),
+                    ("transmit", data.total_transmitted()),
+                ] {
+                    observer.observe(
+                        bytes,
+                        &[
+                            KeyValue::new("network.interface.name", interface.clone()),
+                            KeyValue::new("network.io.direction", direction),
+                        ],
+                    );
+                }
+            }
+        })
+        .build();
+
+    for minutes in [1, 5, 15] {
+        meter
+            .f64_observable_gauge(format!("system.cpu.load_average.{minutes}m"))
+            .with_description("Load average of the host")
+            .with_callback(move |observer| {
+                let load = System::load_average();
+                let value = match minutes {
+                    1 => load.one,
+                    5 => load.five,
+                    _ => load.fifteen,
+                };
+                observer.observe(value, &[]);
+            })
+            .build();
+    }
+}
+
+/// The mount point, total and available bytes of the disk that `path` is stored on.
+fn disk_usage(disks: &Disks, path: &Path) -> Option<(String, u64, u64)> {
+    let path = path.canonicalize().ok()?;
+    disks
+        .list()
+        .iter()
+        .filter(|disk| path.starts_with(disk.mount_point()))
+        .max_by_key(|disk| disk.mount_point().as_os_str().len())
+        .map(|disk| {
+            (
+                disk.mount_point().display().to_string(),
+                disk.total_space(),
+                disk.available_space(),
+            )
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::telemetry::testing::{has_metric, meter_provider};
+    use opentelemetry::metrics::MeterProvider as _;
+
+    #[test]
+    fn test_register_system_metrics_reports_load_average() {
+        let (provider, exporter) = meter_provider();
+        let settings = SystemMetricSettings {
+            enabled: true,
+            disk_paths: vec![],
+        };
+
+        register_system_metrics(&provider.meter("test"), &settings);
+
+        assert!(has_metric(
+            &provider,
+            &exporter,
+            "system.cpu.load_average.1m"
+        ));
+        assert!(!has_metric(&provider, &exporter, "system.filesystem.usage"));
+    }
+
+    #[test]
+    fn test_disk_usage_of_missing_path_is_none() {
+        let disks = Disks::new_with_refreshed_list();
+        assert!(disk_usage(&disks, Path::new("/definitely/not/a/real/path")).is_none());
+    }
+}
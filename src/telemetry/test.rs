@@ -0,0 +1,360 @@
+//! In-memory telemetry for tests, requires the `test-util` feature.
+//!
+//! [`capture`] records spans, logs and metrics in memory so a test can assert on the telemetry
+//! its code produces without running a collector.
+//!
+//! ```
+//! let capture = byre::telemetry::test::capture();
+//!
+//! tracing::info_span!("handle_request").in_scope(|| tracing::info!("handled"));
+//! byre::counter!("requests").add(1, &[]);
+//!
+//! assert!(capture.span("handle_request").is_some());
+//! capture.assert_logged(tracing::Level::INFO, "handled");
+//! assert_eq!(capture.metric_value("requests", &[]), 1.0);
+//! ```
+
+use opentelemetry::logs::{AnyValue, Severity};
+use opentelemetry::metrics::MeterProvider as _;
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry::{global, KeyValue};
+use opentelemetry_appender_tracing::layer::OpenTelemetryTracingBridge;
+use opentelemetry_sdk::logs::{InMemoryLogExporter, SdkLoggerProvider};
+use opentelemetry_sdk::metrics::data::{AggregatedMetrics, MetricData, ResourceMetrics};
+use opentelemetry_sdk::metrics::{InMemoryMetricExporter, PeriodicReader, SdkMeterProvider};
+use opentelemetry_sdk::trace::{InMemorySpanExporter, SdkTracerProvider, SpanData};
+use tracing::subscriber::DefaultGuard;
+use tracing::Level;
+use tracing_opentelemetry::OpenTelemetryLayer;
+use tracing_subscriber::prelude::*;
+
+use super::metrics;
+use crate::ServiceInfo;
+
+/// Scope of the tracer spans are captured with.
+const CAPTURE_SCOPE: &str = "byre-test";
+
+/// Start capturing telemetry in memory, until the returned [`Capture`] is dropped.
+///
+/// Spans and logs of every level are captured on the current thread only, so tests running in
+/// parallel don't see each other's. Metrics go through the global meter provider, which is
+/// shared by the whole process: give instruments names that are unique to the test, or run
+/// the tests that assert on the same metric serially.
+pub fn capture() -> Capture {
+    let spans = InMemorySpanExporter::default();
+    let tracer_provider = SdkTracerProvider::builder()
+        .with_simple_exporter(spans.clone())
+        .build();
+
+    let logs = InMemoryLogExporter::default();
+    let logger_provider = SdkLoggerProvider::builder()
+        .with_log_processor(super::request_id::RequestIdProcessor)
+        .with_log_processor(super::request_fields::RequestFieldsProcessor)
+        .with_simple_exporter(logs.clone())
+        .build();
+
+    let metrics = InMemoryMetricExporter::default();
+    let meter_provider = SdkMeterProvider::builder()
+        .with_reader(PeriodicReader::builder(metrics.clone()).build())
+        .build();
+    global::set_meter_provider(meter_provider.clone());
+    metrics::bind(&ServiceInfo::default());
+
+    let subscriber = tracing_subscriber::registry()
+        .with(OpenTelemetryLayer::new(
+            tracer_provider.tracer(CAPTURE_SCOPE),
+        ))
+        .with(super::request_id::RequestIdLayer)
+        .with(super::request_fields::RequestFieldsLayer)
+        .with(OpenTelemetryTracingBridge::new(&logger_provider));
+    let guard = tracing::subscriber::set_default(subscriber);
+
+    Capture {
+        spans,
+        logs,
+        metrics,
+        tracer_provider,
+        logger_provider,
+        meter_provider,
+        _guard: guard,
+    }
+}
+
+/// Telemetry recorded since [`capture`] was called.
+#[must_use = "telemetry is only captured until the Capture is dropped"]
+pub struct Capture {
+    spans: InMemorySpanExporter,
+    logs: InMemoryLogExporter,
+    metrics: InMemoryMetricExporter,
+    tracer_provider: SdkTracerProvider,
+    logger_provider: SdkLoggerProvider,
+    meter_provider: SdkMeterProvider,
+    _guard: DefaultGuard,
+}
+
+impl std::fmt::Debug for Capture {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Capture").finish_non_exhaustive()
+    }
+}
+
+/// A log event that was captured.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CapturedLog {
+    /// The log message.
+    pub body: String,
+    /// The severity of the event.
+    pub severity: Option<Severity>,
+    /// The target of the event, usually the module path.
+    pub target: Option<String>,
+    /// The fields of the event, other than the message.
+    pub attributes: Vec<KeyValue>,
+}
+
+impl Capture {
+    /// Every span that has ended, in the order they ended.
+    pub fn spans(&self) -> Vec<SpanData> {
+        let _ = self.tracer_provider.force_flush();
+        self.spans.get_finished_spans().unwrap_or_default()
+    }
+
+    /// The first ended span called `name`.
+    pub fn span(&self, name: &str) -> Option<SpanData> {
+        self.spans().into_iter().find(|span| span.name == name)
+    }
+
+    /// Every ended span called `name`, in the order they ended.
+    pub fn spans_named(&self, name: &str) -> Vec<SpanData> {
+        self.spans()
+            .into_iter()
+            .filter(|span| span.name == name)
+            .collect()
+    }
+
+    /// Every log event, in the order they were emitted.
+    pub fn logs(&self) -> Vec<CapturedLog> {
+        let _ = self.logger_provider.force_flush();
+        let emitted = self.logs.get_emitted_logs().unwrap_or_default();
+        emitted
+            .into_iter()
+            .map(|log| CapturedLog {
+                body: log.record.body().map(any_value_string).unwrap_or_default(),
+                severity: log.record.severity_number(),
+                target: log.record.target().map(|target| target.to_string()),
+                attributes: log
+                    .record
+                    .attributes_iter()
+                    .map(|(key, value)| KeyValue::new(key.clone(), any_value_string(value)))
+                    .collect(),
+            })
+            .collect()
+    }
+
+    /// Whether an event of `level` whose message contains `text` was logged.
+    pub fn logged(&self, level: Level, text: &str) -> bool {
+        let severity = severity(level);
+        self.logs()
+            .iter()
+            .any(|log| log.severity == Some(severity) && log.body.contains(text))
+    }
+
+    /// Assert that an event of `level` whose message contains `text` was logged.
+    ///
+    /// # Panics
+    ///
+    /// If no such event was logged, the message lists the events that were.
+    #[track_caller]
+    pub fn assert_logged(&self, level: Level, text: &str) {
+        if self.logged(level, text) {
+            return;
+        }
+        let logged: Vec<_> = self
+            .logs()
+            .iter()
+            .map(|log| format!("{:?} {}", log.severity, log.body))
+            .collect();
+        panic!("no {level} event containing {text:?} was logged, got {logged:#?}");
+    }
+
+    /// Export the metrics recorded so far and return them.
+    pub fn metrics(&self) -> Vec<ResourceMetrics> {
+        let _ = self.meter_provider.force_flush();
+        self.metrics.get_finished_metrics().unwrap_or_default()
+    }
+
+    /// The latest value of metric `name`, summed over the data points whose attributes contain
+    /// all of `attributes`. Histograms report their number of recordings. Zero if the metric
+    /// was not recorded.
+    pub fn metric_value(&self, name: &str, attributes: &[(&str, &str)]) -> f64 {
+        self.metrics()
+            .last()
+            .map_or(0.0, |metrics| sum_metric(metrics, name, attributes))
+    }
+
+    /// The meter provider metrics are captured from, for code that takes a meter rather than
+    /// using the global one.
+    pub fn meter_provider(&self) -> &SdkMeterProvider {
+        &self.meter_provider
+    }
+
+    /// A meter of the capturing meter provider.
+    pub fn meter(&self, name: &'static str) -> opentelemetry::metrics::Meter {
+        self.meter_provider.meter(name)
+    }
+}
+
+/// The severity the tracing bridge gives the events of `level`.
+fn severity(level: Level) -> Severity {
+    match level {
+        Level::TRACE => Severity::Trace,
+        Level::DEBUG => Severity::Debug,
+        Level::INFO => Severity::Info,
+        Level::WARN => Severity::Warn,
+        Level::ERROR => Severity::Error,
+    }
+}
+
+fn any_value_string(value: &AnyValue) -> String {
+    match value {
+        AnyValue::String(value) => value.to_string(),
+        AnyValue::Int(value) => value.to_string(),
+        AnyValue::Double(value) => value.to_string(),
+        AnyValue::Boolean(value) => value.to_string(),
+        other => format!("{other:?}"),
+    }
+}
+
+/// Sum the data points of metric `name` whose attributes contain all of `attributes`. Works for
+/// sums and gauges, histograms count their recordings.
+pub(crate) fn sum_metric(
+    resource_metrics: &ResourceMetrics,
+    name: &str,
+    attributes: &[(&str, &str)],
+) -> f64 {
+    let matches = |point_attributes: &mut dyn Iterator<Item = &KeyValue>| {
+        let point_attributes: Vec<_> = point_attributes.collect();
+        attributes.iter().all(|(key, value)| {
+            point_attributes
+                .iter()
+                .any(|kv| kv.key.as_str() == *key && kv.value.as_str() == *value)
+        })
+    };
+
+    let mut total = 0.0;
+    for scope in resource_metrics.scope_metrics() {
+        for metric in scope.metrics().filter(|m| m.name() == name) {
+            total += match metric.data() {
+                AggregatedMetrics::U64(MetricData::Sum(sum)) => sum
+                    .data_points()
+                    .filter(|p| matches(&mut p.attributes()))
+                    .map(|p| p.value() as f64)
+                    .sum(),
+                AggregatedMetrics::U64(MetricData::Gauge(gauge)) => gauge
+                    .data_points()
+                    .filter(|p| matches(&mut p.attributes()))
+                    .map(|p| p.value() as f64)
+                    .sum(),
+                AggregatedMetrics::I64(MetricData::Sum(sum)) => sum
+                    .data_points()
+                    .filter(|p| matches(&mut p.attributes()))
+                    .map(|p| p.value() as f64)
+                    .sum(),
+                AggregatedMetrics::I64(MetricData::Gauge(gauge)) => gauge
+                    .data_points()
+                    .filter(|p| matches(&mut p.attributes()))
+                    .map(|p| p.value() as f64)
+                    .sum(),
+                AggregatedMetrics::F64(MetricData::Sum(sum)) => sum
+                    .data_points()
+                    .filter(|p| matches(&mut p.attributes()))
+                    .map(|p| p.value())
+                    .sum(),
+                AggregatedMetrics::F64(MetricData::Gauge(gauge)) => gauge
+                    .data_points()
+                    .filter(|p| matches(&mut p.attributes()))
+                    .map(|p| p.value())
+                    .sum(),
+                AggregatedMetrics::F64(MetricData::Histogram(histogram)) => histogram
+                    .data_points()
+                    .filter(|p| matches(&mut p.attributes()))
+                    .map(|p| p.count() as f64)
+                    .sum(),
+                _ => 0.0,
+            };
+        }
+    }
+    total
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_capture_records_spans_and_logs() {
+        let capture = capture();
+
+        tracing::info_span!("outer").in_scope(|| {
+            tracing::debug!(user = "alice", "signed in");
+        });
+
+        let span = capture.span("outer").unwrap();
+        assert_eq!(span.name, "outer");
+        assert!(capture.span("missing").is_none());
+
+        let logs = capture.logs();
+        assert_eq!(logs.len(), 1);
+        assert_eq!(logs[0].body, "signed in");
+        assert_eq!(logs[0].severity, Some(Severity::Debug));
+        assert!(logs[0].attributes.contains(&KeyValue::new("user", "alice")));
+    }
+
+    #[test]
+    fn test_capture_query_helpers() {
+        let capture = capture();
+
+        for table in ["users", "orders"] {
+            tracing::info_span!("db_query", table).in_scope(|| {
+                tracing::warn!(table, "slow query on {table}");
+            });
+        }
+
+        assert_eq!(capture.spans_named("db_query").len(), 2);
+        assert!(capture.spans_named("missing").is_empty());
+        capture.assert_logged(Level::WARN, "slow query on orders");
+        assert!(!capture.logged(Level::ERROR, "slow query"));
+
+        let missing = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            capture.assert_logged(Level::INFO, "slow query");
+        }));
+        assert!(missing.is_err());
+    }
+
+    #[test]
+    fn test_capture_is_per_thread() {
+        let capture = capture();
+        std::thread::spawn(|| tracing::info!("elsewhere"))
+            .join()
+            .unwrap();
+        assert!(capture.logs().is_empty());
+    }
+
+    #[test]
+    fn test_capture_records_metrics() {
+        let capture = capture();
+        capture
+            .meter("test")
+            .u64_counter("captured_requests")
+            .build()
+            .add(2, &[KeyValue::new("status", "ok")]);
+
+        assert_eq!(
+            capture.metric_value("captured_requests", &[("status", "ok")]),
+            2.0
+        );
+        assert_eq!(
+            capture.metric_value("captured_requests", &[("status", "error")]),
+            0.0
+        );
+    }
+}
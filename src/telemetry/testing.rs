@@ -0,0 +1,62 @@
+//! Helpers for asserting on metrics in unit tests.
+
+use opentelemetry_sdk::metrics::{InMemoryMetricExporter, PeriodicReader, SdkMeterProvider};
+
+/// A meter provider that exports to memory when flushed.
+pub(crate) fn meter_provider() -> (SdkMeterProvider, InMemoryMetricExporter) {
+    let exporter = InMemoryMetricExporter::default();
+    let provider = SdkMeterProvider::builder()
+        .with_reader(PeriodicReader::builder(exporter.clone()).build())
+        .build();
+    (provider, exporter)
+}
+
+/// A meter provider like [`meter_provider`], prefixing the application's metrics with `prefix`
+/// as `metric.prefix_service_name` does. It becomes the global meter provider the
+/// [`metrics`](super::metrics) registry is bound to, to check byre's own instruments.
+pub(crate) fn prefixed_global_meter_provider(
+    prefix: &str,
+) -> (SdkMeterProvider, InMemoryMetricExporter) {
+    let exporter = InMemoryMetricExporter::default();
+    let provider = SdkMeterProvider::builder()
+        .with_reader(PeriodicReader::builder(exporter.clone()).build())
+        .with_view(super::metric_views::combined_view(&[], Some(prefix.to_string())).unwrap())
+        .build();
+    opentelemetry::global::set_meter_provider(provider.clone());
+    super::metrics::bind(&crate::ServiceInfo::default());
+    (provider, exporter)
+}
+
+/// Flush `provider` and return the latest value of every data point of metric `name` whose
+/// attributes contain all of `attributes`, summed. Works for sums and gauges.
+pub(crate) fn metric_value(
+    provider: &SdkMeterProvider,
+    exporter: &InMemoryMetricExporter,
+    name: &str,
+    attributes: &[(&str, &str)],
+) -> f64 {
+    provider.force_flush().unwrap();
+    exporter
+        .get_finished_metrics()
+        .unwrap()
+        .last()
+        .map_or(0.0, |metrics| {
+            super::test::sum_metric(metrics, name, attributes)
+        })
+}
+
+/// Whether a metric called `name` was exported by the last flush of `provider`.
+pub(crate) fn has_metric(
+    provider: &SdkMeterProvider,
+    exporter: &InMemoryMetricExporter,
+    name: &str,
+) -> bool {
+    provider.force_flush().unwrap();
+    let finished = exporter.get_finished_metrics().unwrap();
+    let found = finished
+        .iter()
+        .flat_map(|resource_metrics| resource_metrics.scope_metrics())
+        .flat_map(|scope| scope.metrics())
+        .any(|metric| metric.name() == name);
+    found
+}
@@ -0,0 +1,75 @@
+//! [tokio-console](https://github.com/tokio-rs/console) server, requires the `tokio-console`
+//! feature.
+//!
+//! Tokio only emits the task instrumentation the console needs when the application is built
+//! with `RUSTFLAGS="--cfg tokio_unstable"`, otherwise the console shows no tasks.
+
+use std::net::{SocketAddr, ToSocketAddrs as _};
+
+use doku::Document;
+use serde::{Deserialize, Serialize};
+use snafu::{OptionExt as _, ResultExt as _};
+use tracing_subscriber::Registry;
+
+use super::{
+    Error, InvalidTokioConsoleAddressSnafu, RegistryLayer, ResolveTokioConsoleAddressSnafu,
+};
+
+/// Settings for the tokio-console server.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, Document)]
+pub struct TokioConsoleSettings {
+    /// Address for the tokio-console server to listen on, connect with `tokio-console http://<listen>`.
+    /// Omit to disable the server. It has no authentication, only bind it to a private address.
+    #[doku(example = "127.0.0.1:6669")]
+    pub listen: Option<String>,
+}
+
+/// Start the tokio-console server on a background thread, returns `None` if it is disabled.
+///
+/// # Errors
+///
+/// - `ResolveTokioConsoleAddress` if the listen address cannot be resolved.
+/// - `InvalidTokioConsoleAddress` if the listen address does not resolve to any address.
+pub(crate) fn layer(settings: &TokioConsoleSettings) -> Result<Option<RegistryLayer>, Error> {
+    let Some(listen) = &settings.listen else {
+        return Ok(None);
+    };
+    let addr = resolve(listen)?;
+
+    let layer = console_subscriber::ConsoleLayer::builder()
+        .with_default_env()
+        .server_addr(addr)
+        .spawn::<Registry>();
+
+    Ok(Some(Box::new(layer)))
+}
+
+fn resolve(listen: &str) -> Result<SocketAddr, Error> {
+    listen
+        .to_socket_addrs()
+        .with_context(|_| ResolveTokioConsoleAddressSnafu { listen })?
+        .next()
+        .context(InvalidTokioConsoleAddressSnafu { listen })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_disabled_without_listen_address() {
+        let layer = layer(&TokioConsoleSettings::default()).unwrap();
+        assert!(layer.is_none());
+    }
+
+    #[test]
+    fn test_resolve_listen_address() {
+        assert_eq!(
+            resolve("127.0.0.1:6669").unwrap(),
+            SocketAddr::from(([127, 0, 0, 1], 6669))
+        );
+
+        let err = resolve("no port").unwrap_err();
+        assert!(matches!(err, Error::ResolveTokioConsoleAddress { .. }));
+    }
+}
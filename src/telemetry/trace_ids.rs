@@ -0,0 +1,60 @@
+//! The ids of the current trace and span, for error responses and support tickets.
+
+use opentelemetry::trace::{SpanContext, TraceContextExt as _};
+use tracing_opentelemetry::OpenTelemetrySpanExt as _;
+
+/// The id of the trace the current span belongs to, as 32 lowercase hex digits, `None` outside
+/// of a span that is traced.
+///
+/// Handy to show as a reference code in the errors the service returns, which then leads
+/// straight to the trace of the request.
+///
+/// # Example
+///
+/// ```
+/// #[tracing::instrument]
+/// fn error_message() -> String {
+///     match byre::telemetry::current_trace_id() {
+///         Some(trace_id) => format!("Something went wrong, reference: {trace_id}"),
+///         None => "Something went wrong".to_string(),
+///     }
+/// }
+/// # error_message();
+/// ```
+pub fn current_trace_id() -> Option<String> {
+    current_span_context().map(|context| context.trace_id().to_string())
+}
+
+/// The id of the current span, as 16 lowercase hex digits, `None` outside of a span that is
+/// traced.
+pub fn current_span_id() -> Option<String> {
+    current_span_context().map(|context| context.span_id().to_string())
+}
+
+/// The OpenTelemetry context of the current span, when it is valid.
+fn current_span_context() -> Option<SpanContext> {
+    let context = tracing::Span::current().context();
+    let span_context = context.span().span_context().clone();
+    span_context.is_valid().then_some(span_context)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ids_of_the_current_span() {
+        let capture = crate::telemetry::test::capture();
+        assert_eq!(current_trace_id(), None);
+        assert_eq!(current_span_id(), None);
+
+        let (trace_id, span_id) = tracing::info_span!("handle_request")
+            .in_scope(|| (current_trace_id().unwrap(), current_span_id().unwrap()));
+
+        let span = capture.span("handle_request").unwrap();
+        assert_eq!(trace_id, span.span_context.trace_id().to_string());
+        assert_eq!(span_id, span.span_context.span_id().to_string());
+        assert_eq!(trace_id.len(), 32);
+        assert_eq!(span_id.len(), 16);
+    }
+}
@@ -0,0 +1,833 @@
+//! Presets that configure the exporters for an observability vendor.
+//!
+//! A preset fills in what the vendor expects: the endpoint the exporters send to when none is
+//! configured, the headers, the resource attributes and the fields that correlate logs with
+//! traces. Without a preset, the exporters send to the local collector outside of the `dev`
+//! [`Environment`]. Endpoints set in [`TelemetrySettings`](super::TelemetrySettings) always win, and the
+//! `OTEL_RESOURCE_ATTRIBUTES` and `OTEL_SERVICE_NAME` environment variables override the
+//! resource attributes.
+
+use std::fmt;
+
+use doku::Document;
+use opentelemetry::KeyValue;
+use opentelemetry_sdk::metrics::Temporality;
+use opentelemetry_sdk::resource::TelemetryResourceDetector;
+use opentelemetry_sdk::Resource;
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "otlp")]
+use snafu::ResultExt as _;
+#[cfg(feature = "otlp")]
+use tonic::metadata::{MetadataMap, MetadataValue};
+use tracing::{Event, Subscriber};
+use tracing_opentelemetry::OtelData;
+use tracing_subscriber::fmt::format::Writer;
+use tracing_subscriber::fmt::{FmtContext, FormatEvent, FormatFields};
+use tracing_subscriber::registry::LookupSpan;
+
+use super::export_handle::ExportHandle;
+use super::request_fields;
+use super::static_fields::StaticFields;
+#[cfg(feature = "otlp")]
+use super::Compression;
+#[cfg(feature = "otlp")]
+use super::InvalidExportHeaderSnafu;
+use super::{Error, TelemetrySettings};
+use crate::{Environment, ServiceInfo};
+
+/// Resource attribute Datadog reads the `env` tag from.
+const DEPLOYMENT_ENVIRONMENT_NAME: &str = "deployment.environment.name";
+
+/// Resource attribute of the region the service runs in.
+const CLOUD_REGION: &str = "cloud.region";
+
+/// Resource attribute of the git commit the service was built from.
+const VCS_REF_HEAD_REVISION: &str = "vcs.ref.head.revision";
+
+/// OTLP gRPC endpoint of a collector running next to the service.
+const LOCAL_COLLECTOR_ENDPOINT: &str = "http://localhost:4317";
+
+/// Header carrying the Datadog API key.
+#[cfg(feature = "otlp")]
+const DATADOG_API_KEY_HEADER: &str = "dd-api-key";
+
+/// Environment variable with `key=value,...` resource attributes, see the OpenTelemetry
+/// [environment variable specification](https://opentelemetry.io/docs/specs/otel/configuration/sdk-environment-variables/).
+const OTEL_RESOURCE_ATTRIBUTES: &str = "OTEL_RESOURCE_ATTRIBUTES";
+
+/// Environment variable with the `service.name` resource attribute.
+const OTEL_SERVICE_NAME: &str = "OTEL_SERVICE_NAME";
+
+/// Observability vendors with a preset.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, Document)]
+#[serde(rename_all = "lowercase")]
+pub enum Vendor {
+    /// Datadog, through the OTLP receiver of the Datadog Agent. See [`DatadogSettings`].
+    Datadog,
+}
+
+/// Settings for the Datadog preset, used when `vendor = "datadog"`.
+#[derive(Clone, Serialize, Deserialize, Document)]
+pub struct DatadogSettings {
+    /// OTLP gRPC endpoint of the Datadog Agent. Used for traces, logs and metrics that don't set an endpoint.
+    #[doku(example = "http://localhost:4317")]
+    #[serde(default = "default_datadog_endpoint")]
+    pub endpoint: String,
+
+    /// Environment the service runs in, reported as the `env` tag.
+    #[doku(example = "production")]
+    #[serde(default)]
+    pub env: Option<String>,
+
+    /// API key sent in the `dd-api-key` header. Only needed when exporting to an OTLP intake instead of an Agent.
+    #[serde(default)]
+    pub api_key: Option<String>,
+}
+
+impl Default for DatadogSettings {
+    fn default() -> Self {
+        Self {
+            endpoint: default_datadog_endpoint(),
+            env: None,
+            api_key: None,
+        }
+    }
+}
+
+// The API key is a secret, keep it out of logs.
+impl fmt::Debug for DatadogSettings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DatadogSettings")
+            .field("endpoint", &self.endpoint)
+            .field("env", &self.env)
+            .field("api_key", &self.api_key.as_ref().map(|_| "<redacted>"))
+            .finish()
+    }
+}
+
+fn default_datadog_endpoint() -> String {
+    LOCAL_COLLECTOR_ENDPOINT.to_string()
+}
+
+/// What the exporters are configured with, after applying the vendor preset.
+///
+/// Without the `otlp` feature, only the console logs read it.
+#[derive(Clone, Debug)]
+#[cfg_attr(not(feature = "otlp"), allow(dead_code))]
+pub(crate) struct ExportConfig {
+    resource: Resource,
+    #[cfg(feature = "otlp")]
+    metadata: MetadataMap,
+    endpoint: Option<String>,
+    temporality: Temporality,
+    datadog_log_correlation: bool,
+    #[cfg(feature = "otlp")]
+    compression: Option<Compression>,
+    environment: Option<Environment>,
+    fields: StaticFields,
+    local_only: bool,
+    exports: ExportHandle,
+}
+
+#[cfg_attr(not(feature = "otlp"), allow(dead_code))]
+impl ExportConfig {
+    /// The configuration without a vendor preset, in the environment of `service_info`.
+    pub(crate) fn new(service_info: &ServiceInfo) -> Self {
+        Self::for_environment(service_info, service_info.environment)
+    }
+
+    /// The configuration without a vendor preset, an unknown `environment` is `dev`.
+    fn for_environment(service_info: &ServiceInfo, environment: Option<Environment>) -> Self {
+        let effective = environment.unwrap_or_default();
+        Self {
+            resource: resource(
+                std::iter::once(service_name(service_info))
+                    .chain(deployment_attributes(service_info, effective.as_str()))
+                    .chain(build_attributes(service_info))
+                    .collect(),
+            ),
+            #[cfg(feature = "otlp")]
+            metadata: MetadataMap::new(),
+            // Nothing is exported while developing unless an endpoint is configured
+            endpoint: (!effective.is_dev()).then(|| LOCAL_COLLECTOR_ENDPOINT.to_string()),
+            temporality: Temporality::default(),
+            datadog_log_correlation: false,
+            #[cfg(feature = "otlp")]
+            compression: None,
+            environment,
+            fields: StaticFields::default(),
+            local_only: false,
+            exports: ExportHandle::default(),
+        }
+    }
+
+    /// The configuration for the vendor selected in `settings`.
+    ///
+    /// # Errors
+    ///
+    /// - `InvalidExportHeader` if the Datadog API key cannot be sent as a header.
+    pub(crate) fn from_settings(
+        service_info: &ServiceInfo,
+        settings: &TelemetrySettings,
+    ) -> Result<Self, Error> {
+        let environment = settings.environment.or(service_info.environment);
+        let config = match settings.vendor {
+            None => Self::for_environment(service_info, environment),
+            Some(Vendor::Datadog) => Self::datadog(service_info, &settings.datadog, environment)?,
+        };
+        Ok(Self {
+            #[cfg(feature = "otlp")]
+            compression: settings.compression,
+            fields: StaticFields::new(&settings.log.fields),
+            local_only: settings.local_only,
+            ..config
+        })
+    }
+
+    fn datadog(
+        service_info: &ServiceInfo,
+        settings: &DatadogSettings,
+        environment: Option<Environment>,
+    ) -> Result<Self, Error> {
+        // Unified service tagging: service, env and version
+        let mut attributes = vec![
+            service_name(service_info),
+            KeyValue::new(
+                opentelemetry_semantic_conventions::resource::SERVICE_VERSION,
+                service_info.version,
+            ),
+        ];
+        // The Datadog `env` tag wins over the environment
+        let env = settings
+            .env
+            .as_deref()
+            .unwrap_or(environment.unwrap_or_default().as_str());
+        attributes.extend(deployment_attributes(service_info, env));
+        attributes.extend(build_attributes(service_info));
+
+        // Without the OTLP exporters there is nothing to send the API key with
+        #[cfg(feature = "otlp")]
+        let mut metadata = MetadataMap::new();
+        #[cfg(feature = "otlp")]
+        if let Some(api_key) = &settings.api_key {
+            let value = MetadataValue::try_from(api_key.as_str()).with_context(|_| {
+                InvalidExportHeaderSnafu {
+                    header: DATADOG_API_KEY_HEADER,
+                }
+            })?;
+            metadata.insert(DATADOG_API_KEY_HEADER, value);
+        }
+
+        Ok(Self {
+            resource: resource(attributes),
+            #[cfg(feature = "otlp")]
+            metadata,
+            endpoint: Some(settings.endpoint.clone()),
+            // Datadog stores metrics as deltas
+            temporality: Temporality::Delta,
+            datadog_log_correlation: true,
+            #[cfg(feature = "otlp")]
+            compression: None,
+            environment,
+            fields: StaticFields::default(),
+            local_only: false,
+            exports: ExportHandle::default(),
+        })
+    }
+
+    /// The endpoint to export to, `configured` wins over the preset's endpoint. An empty
+    /// `configured` endpoint disables the export, and so does `local_only`.
+    pub(crate) fn endpoint<'a>(&'a self, configured: &'a Option<String>) -> Option<&'a str> {
+        if self.local_only {
+            return None;
+        }
+        configured
+            .as_deref()
+            .or(self.endpoint.as_deref())
+            .filter(|endpoint| !endpoint.is_empty())
+    }
+
+    pub(crate) fn resource(&self) -> Resource {
+        self.resource.clone()
+    }
+
+    /// Headers sent with every export request.
+    #[cfg(feature = "otlp")]
+    pub(crate) fn metadata(&self) -> MetadataMap {
+        self.metadata.clone()
+    }
+
+    pub(crate) fn temporality(&self) -> Temporality {
+        self.temporality
+    }
+
+    /// How the exports are compressed, `None` to send them uncompressed.
+    #[cfg(feature = "otlp")]
+    pub(crate) fn compression(&self) -> Option<opentelemetry_otlp::Compression> {
+        self.compression.map(Into::into)
+    }
+
+    /// The environment the service runs in, after applying the `environment` setting, `None`
+    /// when it is unknown.
+    pub(crate) fn environment(&self) -> Option<Environment> {
+        self.environment
+    }
+
+    /// The switches of the exporters.
+    pub(crate) fn exports(&self) -> &ExportHandle {
+        &self.exports
+    }
+
+    /// The static fields added to every log record and span.
+    pub(crate) fn static_fields(&self) -> &StaticFields {
+        &self.fields
+    }
+
+    /// The console log format, with the static fields, the fields the vendor correlates logs and
+    /// traces by, and the W3C trace and span ids when `trace_ids` is set. `json` tells that
+    /// `inner` writes JSON objects, which the fields and ids are added to.
+    pub(crate) fn console_format<F>(
+        &self,
+        inner: F,
+        trace_ids: bool,
+        json: bool,
+    ) -> CorrelatedFormat<F> {
+        CorrelatedFormat {
+            inner,
+            trace_ids,
+            datadog: self.datadog_log_correlation,
+            json,
+            fields: self.fields.clone(),
+        }
+    }
+}
+
+fn service_name(service_info: &ServiceInfo) -> KeyValue {
+    KeyValue::new(
+        opentelemetry_semantic_conventions::resource::SERVICE_NAME,
+        service_info.name_in_metrics.clone(),
+    )
+}
+
+/// The resource attributes of where the service is deployed: the environment, and the region
+/// when it is known.
+fn deployment_attributes(service_info: &ServiceInfo, environment: &str) -> Vec<KeyValue> {
+    let mut attributes = vec![KeyValue::new(
+        DEPLOYMENT_ENVIRONMENT_NAME,
+        environment.to_string(),
+    )];
+    if !service_info.region.is_empty() {
+        attributes.push(KeyValue::new(CLOUD_REGION, service_info.region.clone()));
+    }
+    attributes
+}
+
+/// The resource attributes of the build metadata of the service that is known.
+fn build_attributes(service_info: &ServiceInfo) -> Vec<KeyValue> {
+    let mut attributes = Vec::new();
+    if !service_info.git_sha.is_empty() {
+        attributes.push(KeyValue::new(VCS_REF_HEAD_REVISION, service_info.git_sha));
+        attributes.push(KeyValue::new("build.git_dirty", service_info.git_dirty));
+    }
+    if !service_info.build_timestamp.is_empty() {
+        attributes.push(KeyValue::new(
+            "build.timestamp",
+            service_info.build_timestamp,
+        ));
+    }
+    if !service_info.rustc_version.is_empty() {
+        attributes.push(KeyValue::new(
+            "build.rustc_version",
+            service_info.rustc_version,
+        ));
+    }
+    attributes
+}
+
+/// Console log format that prefixes events with the static fields, and events in a span with
+/// the [`RequestFields`](super::RequestFields) of the span and the ids of its trace.
+///
+/// The request fields, then the static fields, come first, as `name="value"` like the fields of
+/// the event. With
+/// `trace_ids`, the ids are written as `trace_id` and `span_id` in the hex form trace backends
+/// search by. With `datadog`, they are written as `dd.trace_id` and `dd.span_id`, so the Datadog
+/// Agent can link the log lines it collects to the trace. With `json`, they are added as the first
+/// fields of the JSON object instead.
+pub(crate) struct CorrelatedFormat<F> {
+    inner: F,
+    trace_ids: bool,
+    datadog: bool,
+    json: bool,
+    fields: StaticFields,
+}
+
+impl<S, N, F> FormatEvent<S, N> for CorrelatedFormat<F>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+    F: FormatEvent<S, N>,
+{
+    fn format_event(
+        &self,
+        ctx: &FmtContext<'_, S, N>,
+        mut writer: Writer<'_>,
+        event: &Event<'_>,
+    ) -> fmt::Result {
+        let span = ctx.event_scope().and_then(|mut scope| scope.next());
+        let ids = span
+            .as_ref()
+            .filter(|_| self.trace_ids || self.datadog)
+            .and_then(|span| {
+                let extensions = span.extensions();
+                let data = extensions.get::<OtelData>()?;
+                Some((data.trace_id()?, data.span_id()?))
+            });
+        let request_fields = span.map(request_fields::collect).unwrap_or_default();
+        if ids.is_none() && request_fields.is_empty() && self.fields.is_empty() {
+            return self.inner.format_event(ctx, writer, event);
+        }
+
+        // A request field wins over the static field of the same name
+        let static_fields = self
+            .fields
+            .iter()
+            .filter(|(name, _)| !request_fields.iter().any(|(set, _)| set == *name));
+        let fields: Vec<_> = request_fields.iter().chain(static_fields).collect();
+
+        let mut ids_fields = Vec::new();
+        if let Some((trace_id, span_id)) = ids {
+            if self.trace_ids {
+                ids_fields.push(("trace_id", trace_id.to_string()));
+                ids_fields.push(("span_id", span_id.to_string()));
+            }
+            if self.datadog {
+                ids_fields.push(("dd.trace_id", datadog_trace_id(trace_id).to_string()));
+                let span_id = u64::from_be_bytes(span_id.to_bytes());
+                ids_fields.push(("dd.span_id", span_id.to_string()));
+            }
+        }
+
+        if !self.json {
+            for (name, value) in &fields {
+                write!(writer, "{name}={value:?} ")?;
+            }
+            for (name, value) in ids_fields {
+                write!(writer, "{name}={value} ")?;
+            }
+            return self.inner.format_event(ctx, writer, event);
+        }
+
+        let mut line = String::new();
+        self.inner
+            .format_event(ctx, Writer::new(&mut line), event)?;
+        let Some(rest) = line.strip_prefix('{') else {
+            return writer.write_str(&line);
+        };
+        writer.write_char('{')?;
+        for (name, value) in fields {
+            let name = serde_json::Value::from(name);
+            let value = serde_json::Value::from(value);
+            write!(writer, "{name}:{value},")?;
+        }
+        // The ids are hex or decimal, they never need escaping
+        for (name, value) in ids_fields {
+            write!(writer, "\"{name}\":\"{value}\",")?;
+        }
+        writer.write_str(rest)
+    }
+}
+
+/// Datadog identifies traces in logs by the lower 64 bits of the trace id, in decimal.
+fn datadog_trace_id(trace_id: opentelemetry::TraceId) -> u64 {
+    let bytes = trace_id.to_bytes();
+    let mut low = [0; 8];
+    low.copy_from_slice(&bytes[8..]);
+    u64::from_be_bytes(low)
+}
+
+/// The resource with byre's `attributes`, overridden by the `OTEL_RESOURCE_ATTRIBUTES` and
+/// `OTEL_SERVICE_NAME` environment variables.
+fn resource(attributes: Vec<KeyValue>) -> Resource {
+    let resource_attributes = std::env::var(OTEL_RESOURCE_ATTRIBUTES).ok();
+    let service_name = std::env::var(OTEL_SERVICE_NAME).ok();
+    resource_with_overrides(
+        attributes,
+        resource_attributes.as_deref(),
+        service_name.as_deref(),
+    )
+}
+
+fn resource_with_overrides(
+    attributes: Vec<KeyValue>,
+    resource_attributes: Option<&str>,
+    service_name: Option<&str>,
+) -> Resource {
+    let mut builder = Resource::builder_empty()
+        .with_detector(Box::new(TelemetryResourceDetector))
+        .with_attributes(attributes);
+    if let Some(resource_attributes) = resource_attributes {
+        builder = builder.with_attributes(parse_resource_attributes(resource_attributes));
+    }
+    // OTEL_SERVICE_NAME wins over a service.name in OTEL_RESOURCE_ATTRIBUTES
+    if let Some(service_name) = service_name.filter(|name| !name.is_empty()) {
+        builder = builder.with_service_name(service_name.to_string());
+    }
+    builder.build()
+}
+
+/// Parses `key=value,...` resource attributes, entries without a `=` are skipped.
+fn parse_resource_attributes(value: &str) -> Vec<KeyValue> {
+    value
+        .split(',')
+        .filter_map(|entry| {
+            let (key, value) = entry.split_once('=')?;
+            let key = key.trim();
+            (!key.is_empty()).then(|| KeyValue::new(key.to_string(), value.trim().to_string()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_datadog_preset() {
+        let service_info = crate::ServiceInfo::default();
+        let settings = TelemetrySettings {
+            vendor: Some(Vendor::Datadog),
+            datadog: DatadogSettings {
+                env: Some("staging".to_string()),
+                api_key: Some("secret".to_string()),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let config = ExportConfig::from_settings(&service_info, &settings).unwrap();
+
+        assert_eq!(config.endpoint(&None), Some("http://localhost:4317"));
+        let configured = Some("http://collector:4317".to_string());
+        assert_eq!(config.endpoint(&configured), Some("http://collector:4317"));
+        #[cfg(feature = "otlp")]
+        assert_eq!(
+            config.metadata().get(DATADOG_API_KEY_HEADER).unwrap(),
+            "secret"
+        );
+        assert_eq!(config.temporality(), Temporality::Delta);
+
+        let resource = config.resource();
+        let attribute = |key: &'static str| resource.get(&opentelemetry::Key::new(key));
+        assert_eq!(
+            attribute(DEPLOYMENT_ENVIRONMENT_NAME),
+            Some("staging".into())
+        );
+        assert_eq!(
+            attribute(opentelemetry_semantic_conventions::resource::SERVICE_VERSION),
+            Some(service_info.version.into())
+        );
+        assert!(!format!("{settings:?}").contains("secret"));
+    }
+
+    #[test]
+    fn test_resource_carries_the_environment_and_region() {
+        let service_info = crate::ServiceInfo {
+            environment: Some(Environment::Staging),
+            region: "eu-west-1".to_string(),
+            ..Default::default()
+        };
+        let settings = TelemetrySettings {
+            environment: Some(Environment::Prod),
+            ..Default::default()
+        };
+        let config = ExportConfig::from_settings(&service_info, &settings).unwrap();
+
+        let resource = config.resource();
+        let attribute = |key: &'static str| resource.get(&opentelemetry::Key::new(key));
+        assert_eq!(attribute(DEPLOYMENT_ENVIRONMENT_NAME), Some("prod".into()));
+        assert_eq!(attribute(CLOUD_REGION), Some("eu-west-1".into()));
+
+        let resource = ExportConfig::new(&crate::ServiceInfo::default()).resource();
+        assert_eq!(
+            resource.get(&opentelemetry::Key::new(DEPLOYMENT_ENVIRONMENT_NAME)),
+            Some("dev".into())
+        );
+        assert_eq!(resource.get(&opentelemetry::Key::new(CLOUD_REGION)), None);
+    }
+
+    #[test]
+    fn test_resource_carries_the_build_metadata() {
+        let service_info = crate::ServiceInfo {
+            git_sha: "0123abcd",
+            git_dirty: true,
+            rustc_version: "1.85.0",
+            ..Default::default()
+        };
+        let config = ExportConfig::new(&service_info);
+
+        let resource = config.resource();
+        let attribute = |key: &'static str| resource.get(&opentelemetry::Key::new(key));
+        assert_eq!(attribute(VCS_REF_HEAD_REVISION), Some("0123abcd".into()));
+        assert_eq!(attribute("build.git_dirty"), Some(true.into()));
+        assert_eq!(attribute("build.rustc_version"), Some("1.85.0".into()));
+        assert_eq!(attribute("build.timestamp"), None);
+    }
+
+    #[test]
+    fn test_without_vendor_keeps_configured_endpoints() {
+        let service_info = crate::ServiceInfo::default();
+        let config =
+            ExportConfig::from_settings(&service_info, &TelemetrySettings::default()).unwrap();
+
+        assert_eq!(config.endpoint(&None), None);
+        #[cfg(feature = "otlp")]
+        {
+            assert!(config.metadata().is_empty());
+            assert_eq!(config.compression(), None);
+        }
+    }
+
+    #[cfg(feature = "otlp")]
+    #[test]
+    fn test_compression_applies_with_a_vendor() {
+        let settings = TelemetrySettings {
+            vendor: Some(Vendor::Datadog),
+            compression: Some(Compression::Zstd),
+            ..Default::default()
+        };
+        let config =
+            ExportConfig::from_settings(&crate::ServiceInfo::default(), &settings).unwrap();
+
+        assert_eq!(
+            config.compression(),
+            Some(opentelemetry_otlp::Compression::Zstd)
+        );
+    }
+
+    #[test]
+    fn test_environment_overrides_resource_attributes() {
+        use opentelemetry_semantic_conventions::resource::{SERVICE_NAME, SERVICE_VERSION};
+
+        let attributes = vec![
+            KeyValue::new(SERVICE_NAME, "from-cargo"),
+            KeyValue::new(SERVICE_VERSION, "1.0.0"),
+        ];
+        let attribute =
+            |resource: &Resource, key: &'static str| resource.get(&opentelemetry::Key::new(key));
+
+        let resource = resource_with_overrides(
+            attributes.clone(),
+            Some("service.name=from-attributes, k8s.pod.name = web-1,invalid,"),
+            None,
+        );
+        assert_eq!(
+            attribute(&resource, SERVICE_NAME),
+            Some("from-attributes".into())
+        );
+        assert_eq!(attribute(&resource, "k8s.pod.name"), Some("web-1".into()));
+        assert_eq!(attribute(&resource, SERVICE_VERSION), Some("1.0.0".into()));
+        assert_eq!(
+            attribute(&resource, "telemetry.sdk.language"),
+            Some("rust".into())
+        );
+
+        let resource = resource_with_overrides(
+            attributes.clone(),
+            Some("service.name=from-attributes"),
+            Some("from-service-name"),
+        );
+        assert_eq!(
+            attribute(&resource, SERVICE_NAME),
+            Some("from-service-name".into())
+        );
+
+        let resource = resource_with_overrides(attributes, None, Some(""));
+        assert_eq!(
+            attribute(&resource, SERVICE_NAME),
+            Some("from-cargo".into())
+        );
+    }
+
+    /// The console lines written with `config`'s format for an event outside a span, then one
+    /// inside a span, then one inside a span with request fields, as JSON objects with `json`.
+    fn console_lines(config: &ExportConfig, trace_ids: bool, json: bool) -> Vec<String> {
+        use std::sync::{Arc, Mutex};
+
+        use opentelemetry::trace::TracerProvider as _;
+        use tracing_subscriber::layer::SubscriberExt as _;
+
+        #[derive(Clone, Default)]
+        struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+        impl std::io::Write for Buffer {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.0.lock().unwrap().write(buf)
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let provider = opentelemetry_sdk::trace::SdkTracerProvider::builder().build();
+        let buffer = Buffer::default();
+        let writer = buffer.clone();
+        let registry = tracing_subscriber::registry()
+            .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("test")));
+        let emit = || {
+            tracing::info!("outside");
+            tracing::info_span!("request").in_scope(|| tracing::info!("inside"));
+            let span = tracing::info_span!("tenant");
+            super::super::RequestFields::new()
+                .with_field("tenant.id", "acme")
+                .with_field("team", "search")
+                .attach(&span);
+            span.in_scope(|| tracing::info!("for a tenant"));
+        };
+
+        if json {
+            let format = tracing_subscriber::fmt::format().json().flatten_event(true);
+            let subscriber = registry.with(
+                tracing_subscriber::fmt::layer()
+                    .json()
+                    .event_format(config.console_format(format, trace_ids, true))
+                    .with_writer(move || writer.clone()),
+            );
+            tracing::subscriber::with_default(subscriber, emit);
+        } else {
+            let format = tracing_subscriber::fmt::format();
+            let subscriber = registry.with(
+                tracing_subscriber::fmt::layer()
+                    .event_format(config.console_format(format, trace_ids, false))
+                    .with_ansi(false)
+                    .with_writer(move || writer.clone()),
+            );
+            tracing::subscriber::with_default(subscriber, emit);
+        }
+
+        let output = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        output.lines().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn test_datadog_console_logs_carry_trace_ids() {
+        let service_info = crate::ServiceInfo::default();
+        let settings = TelemetrySettings {
+            vendor: Some(Vendor::Datadog),
+            ..Default::default()
+        };
+        let config = ExportConfig::from_settings(&service_info, &settings).unwrap();
+
+        let lines = console_lines(&config, false, false);
+        assert!(!lines[0].contains("dd.trace_id"), "{lines:?}");
+        assert!(lines[1].starts_with("dd.trace_id="), "{lines:?}");
+        assert!(lines[1].contains(" dd.span_id="), "{lines:?}");
+    }
+
+    #[test]
+    fn test_console_logs_carry_trace_ids_when_enabled() {
+        let config = ExportConfig::new(&crate::ServiceInfo::default());
+
+        let lines = console_lines(&config, true, false);
+        assert!(!lines[0].contains("trace_id"), "{lines:?}");
+        let (trace_id, rest) = lines[1]
+            .strip_prefix("trace_id=")
+            .and_then(|line| line.split_once(" span_id="))
+            .unwrap();
+        assert_eq!(trace_id.len(), 32, "{lines:?}");
+        assert_eq!(rest.split(' ').next().unwrap().len(), 16, "{lines:?}");
+
+        let lines = console_lines(&config, false, false);
+        assert!(!lines[1].contains("trace_id"), "{lines:?}");
+    }
+
+    #[test]
+    fn test_json_console_logs_carry_trace_ids_as_fields() {
+        let settings = TelemetrySettings {
+            vendor: Some(Vendor::Datadog),
+            ..Default::default()
+        };
+        let config =
+            ExportConfig::from_settings(&crate::ServiceInfo::default(), &settings).unwrap();
+
+        let lines = console_lines(&config, true, true);
+        assert!(lines[0].starts_with("{\"timestamp\":"), "{lines:?}");
+        assert!(lines[1].starts_with("{\"trace_id\":\""), "{lines:?}");
+        assert!(lines[1].contains(",\"dd.span_id\":\""), "{lines:?}");
+        assert!(lines[1].contains("\"message\":\"inside\""), "{lines:?}");
+        assert!(lines[1].ends_with('}'), "{lines:?}");
+    }
+
+    #[test]
+    fn test_console_logs_carry_the_static_fields() {
+        let mut settings = TelemetrySettings::default();
+        settings.log.fields = [("team".to_string(), "pay \"ments\"".to_string())].into();
+        let config =
+            ExportConfig::from_settings(&crate::ServiceInfo::default(), &settings).unwrap();
+
+        let lines = console_lines(&config, true, false);
+        assert!(
+            lines[0].starts_with("team=\"pay \\\"ments\\\"\" "),
+            "{lines:?}"
+        );
+        assert!(
+            lines[1].starts_with("team=\"pay \\\"ments\\\"\" trace_id="),
+            "{lines:?}"
+        );
+
+        let lines = console_lines(&config, false, true);
+        assert!(
+            lines[0].starts_with("{\"team\":\"pay \\\"ments\\\"\",\"timestamp\":"),
+            "{lines:?}"
+        );
+        let line: serde_json::Value = serde_json::from_str(&lines[1]).unwrap();
+        assert_eq!(line["team"], "pay \"ments\"");
+    }
+
+    #[test]
+    fn test_console_logs_carry_the_request_fields() {
+        let mut settings = TelemetrySettings::default();
+        settings.log.fields = [("team".to_string(), "payments".to_string())].into();
+        let config =
+            ExportConfig::from_settings(&crate::ServiceInfo::default(), &settings).unwrap();
+
+        // A request field wins over the static field of the same name
+        let lines = console_lines(&config, false, false);
+        assert!(
+            lines[2].starts_with("tenant.id=\"acme\" team=\"search\" "),
+            "{lines:?}"
+        );
+
+        let lines = console_lines(&config, false, true);
+        let line: serde_json::Value = serde_json::from_str(&lines[2]).unwrap();
+        assert_eq!(line["tenant.id"], "acme");
+        assert_eq!(line["team"], "search");
+    }
+
+    #[test]
+    fn test_environment_picks_the_default_endpoint() {
+        let mut service_info = crate::ServiceInfo::default();
+        assert_eq!(ExportConfig::new(&service_info).endpoint(&None), None);
+
+        service_info.environment = Some(Environment::Staging);
+        let config = ExportConfig::new(&service_info);
+        assert_eq!(config.endpoint(&None), Some(LOCAL_COLLECTOR_ENDPOINT));
+        assert_eq!(config.endpoint(&Some(String::new())), None);
+        assert_eq!(config.environment(), Some(Environment::Staging));
+
+        // The setting wins over the detected environment
+        let settings = TelemetrySettings {
+            environment: Some(Environment::Dev),
+            ..Default::default()
+        };
+        let config = ExportConfig::from_settings(&service_info, &settings).unwrap();
+        assert_eq!(config.endpoint(&None), None);
+        assert_eq!(config.environment(), Some(Environment::Dev));
+    }
+
+    #[test]
+    fn test_datadog_trace_id_uses_the_lower_64_bits() {
+        let trace_id =
+            opentelemetry::TraceId::from_hex("0af7651916cd43dd8448eb211c80319c").unwrap();
+        assert_eq!(datadog_trace_id(trace_id), 0x8448eb211c80319c);
+    }
+}
@@ -0,0 +1,91 @@
+//! Integration tests for `byre::App`.
+//!
+//! Running an app installs the global subscriber, so these tests live in their own process.
+
+use doku::Document;
+use serde::Deserialize;
+
+#[derive(Document, Deserialize)]
+pub struct TestSettings {
+    /// Port to listen on
+    #[doku(example = "8080")]
+    pub listen_port: u16,
+
+    /// Telemetry settings from byre
+    pub telemetry: byre::telemetry::TelemetrySettings,
+
+    /// Runtime settings from byre
+    pub runtime: byre::runtime::RuntimeSettings,
+
+    /// Open file limit settings from byre
+    pub file_limit: byre::limits::FileLimitSettings,
+}
+
+impl byre::app::AppSettings for TestSettings {
+    fn telemetry(&self) -> &byre::telemetry::TelemetrySettings {
+        &self.telemetry
+    }
+
+    fn runtime(&self) -> Option<&byre::runtime::RuntimeSettings> {
+        Some(&self.runtime)
+    }
+
+    fn file_limit(&self) -> Option<&byre::limits::FileLimitSettings> {
+        Some(&self.file_limit)
+    }
+
+    fn listen_addresses(&self) -> Vec<(&str, &str)> {
+        vec![("http", "0.0.0.0:9090")]
+    }
+}
+
+#[test]
+fn test_app_runs_main_with_config() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let config_path = temp_dir.path().join("app.toml");
+    let config_content = r#"
+listen_port = 9090
+
+[telemetry.trace]
+
+[telemetry.log]
+console_level = "off"
+otel_level = "off"
+
+[telemetry.metric]
+
+[runtime]
+worker_threads = 2
+thread_name_prefix = "test-app"
+
+# A target below the current limit leaves it unchanged
+[file_limit]
+raise = true
+target = 1
+"#;
+    std::fs::write(&config_path, config_content).unwrap();
+
+    let mut ran = false;
+    let main_ran = &mut ran;
+    let result = byre::App::<TestSettings>::new(byre::service_info!(), "BYRE_TEST_APP_")
+        .try_run_from(
+            ["test_app", "--config", config_path.to_str().unwrap()],
+            |ctx| async move {
+                assert_eq!(ctx.config.listen_port, 9090);
+                assert_eq!(ctx.service_info.name, "byre");
+                assert!(ctx.log_levels().is_some());
+                assert!(!ctx.shutdown().is_requested());
+
+                // The main function runs inside the runtime built from the settings
+                let worker =
+                    tokio::task::spawn(async { std::thread::current().name().map(str::to_string) })
+                        .await?;
+                assert!(worker.unwrap().starts_with("test-app-"));
+                *main_ran = true;
+                Ok(())
+            },
+        );
+
+    result.unwrap();
+    assert!(ran);
+}